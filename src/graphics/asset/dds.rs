@@ -0,0 +1,132 @@
+use ddsfile::{Caps2, D3D10ResourceDimension, D3DFormat, Dds, DxgiFormat, MiscFlag};
+
+use crate::graphics::core::{
+    resource::{TextureDesc, TextureType},
+    types::Format,
+};
+
+use super::{resolve_format, AssetError, TextureAsset, TextureLoadSettings};
+
+pub(super) fn load(
+    bytes: &[u8],
+    settings: &TextureLoadSettings,
+) -> Result<TextureAsset, AssetError> {
+    let dds = Dds::read(bytes).map_err(|err| AssetError::Decode(err.to_string()))?;
+
+    let format = if let Some(format) = dds.get_dxgi_format() {
+        map_dxgi_format(format)
+            .ok_or_else(|| AssetError::UnsupportedFormat(format!("{:?}", format)))?
+    } else if let Some(format) = dds.get_d3d_format() {
+        map_d3d_format(format)
+            .ok_or_else(|| AssetError::UnsupportedFormat(format!("{:?}", format)))?
+    } else {
+        return Err(AssetError::UnsupportedFormat(
+            "unknown pixel format".to_string(),
+        ));
+    };
+
+    let is_cube = dds
+        .header10
+        .as_ref()
+        .map(|h| h.misc_flag.contains(MiscFlag::TEXTURECUBE))
+        .unwrap_or(dds.header.caps2.contains(Caps2::CUBEMAP));
+
+    let is_1d = dds
+        .header10
+        .as_ref()
+        .is_some_and(|h| h.resource_dimension == D3D10ResourceDimension::Texture1D);
+
+    let depth = dds.get_depth().max(1);
+
+    let (ty, depth) = if depth > 1 {
+        (TextureType::D3, depth)
+    } else if is_cube {
+        // DX10 headers count whole cubes, legacy headers already report 6 faces.
+        let faces = if dds.header10.is_some() {
+            dds.get_num_array_layers() * 6
+        } else {
+            dds.get_num_array_layers()
+        };
+
+        (TextureType::Cube, faces)
+    } else if is_1d {
+        (TextureType::D1, dds.get_num_array_layers())
+    } else {
+        (TextureType::D2, dds.get_num_array_layers())
+    };
+
+    let depth = u16::try_from(depth)
+        .map_err(|_| AssetError::InvalidData("texture has more than 65535 layers or slices"))?;
+
+    let desc = TextureDesc {
+        name: settings.name.clone(),
+        ty,
+        width: dds.get_width(),
+        height: dds.get_height().max(1),
+        depth,
+        mip_levels: dds.get_num_mipmap_levels().max(1),
+        format: resolve_format(format, settings.srgb),
+        usage: settings.usage,
+    };
+
+    let size = desc.packed_size();
+    if dds.data.len() < size {
+        return Err(AssetError::InvalidData(
+            "dds payload is smaller than its header",
+        ));
+    }
+
+    let mut data = dds.data;
+    data.truncate(size);
+
+    Ok(TextureAsset { desc, data })
+}
+
+fn map_dxgi_format(format: DxgiFormat) -> Option<Format> {
+    match format {
+        DxgiFormat::R8_UNorm => Some(Format::R8Unorm),
+        DxgiFormat::R8G8_UNorm => Some(Format::Rg8Unorm),
+        DxgiFormat::R8G8B8A8_UNorm => Some(Format::Rgba8Unorm),
+        DxgiFormat::R8G8B8A8_UNorm_sRGB => Some(Format::Rgba8UnormSrgb),
+        DxgiFormat::B8G8R8A8_UNorm => Some(Format::Bgra8Unorm),
+        DxgiFormat::B8G8R8A8_UNorm_sRGB => Some(Format::Bgra8UnormSrgb),
+        DxgiFormat::R16G16B16A16_UNorm => Some(Format::Rgba16Unorm),
+        DxgiFormat::R16G16B16A16_Float => Some(Format::Rgba16Float),
+        DxgiFormat::R32_Float => Some(Format::R32),
+        DxgiFormat::R32G32_Float => Some(Format::Rg32),
+        DxgiFormat::R32G32B32_Float => Some(Format::Rgb32),
+        DxgiFormat::R32G32B32A32_Float => Some(Format::Rgba32),
+        DxgiFormat::BC1_UNorm => Some(Format::Bc1Unorm),
+        DxgiFormat::BC1_UNorm_sRGB => Some(Format::Bc1UnormSrgb),
+        DxgiFormat::BC2_UNorm => Some(Format::Bc2Unorm),
+        DxgiFormat::BC2_UNorm_sRGB => Some(Format::Bc2UnormSrgb),
+        DxgiFormat::BC3_UNorm => Some(Format::Bc3Unorm),
+        DxgiFormat::BC3_UNorm_sRGB => Some(Format::Bc3UnormSrgb),
+        DxgiFormat::BC4_UNorm => Some(Format::Bc4Unorm),
+        DxgiFormat::BC4_SNorm => Some(Format::Bc4Snorm),
+        DxgiFormat::BC5_UNorm => Some(Format::Bc5Unorm),
+        DxgiFormat::BC5_SNorm => Some(Format::Bc5Snorm),
+        DxgiFormat::BC6H_UF16 => Some(Format::Bc6hUfloat),
+        DxgiFormat::BC6H_SF16 => Some(Format::Bc6hSfloat),
+        DxgiFormat::BC7_UNorm => Some(Format::Bc7Unorm),
+        DxgiFormat::BC7_UNorm_sRGB => Some(Format::Bc7UnormSrgb),
+        _ => None,
+    }
+}
+
+fn map_d3d_format(format: D3DFormat) -> Option<Format> {
+    match format {
+        D3DFormat::L8 => Some(Format::R8Unorm),
+        D3DFormat::A8B8G8R8 => Some(Format::Rgba8Unorm),
+        D3DFormat::A8R8G8B8 => Some(Format::Bgra8Unorm),
+        D3DFormat::A16B16G16R16 => Some(Format::Rgba16Unorm),
+        D3DFormat::A16B16G16R16F => Some(Format::Rgba16Float),
+        D3DFormat::R32F => Some(Format::R32),
+        D3DFormat::G32R32F => Some(Format::Rg32),
+        D3DFormat::A32B32G32R32F => Some(Format::Rgba32),
+        D3DFormat::DXT1 => Some(Format::Bc1Unorm),
+        D3DFormat::DXT3 => Some(Format::Bc2Unorm),
+        D3DFormat::DXT5 => Some(Format::Bc3Unorm),
+        _ => None,
+    }
+}
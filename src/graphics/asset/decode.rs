@@ -0,0 +1,118 @@
+use image::{ColorType, DynamicImage, ImageFormat};
+
+use crate::graphics::core::{
+    resource::{TextureDesc, TextureType},
+    types::Format,
+};
+
+use super::{
+    resolve_format, AssetError, SrgbMode, TextureAsset, TextureFileFormat, TextureLoadSettings,
+};
+
+const PNG_GAMMA_LINEAR: u32 = 100_000;
+
+pub(super) fn load(
+    bytes: &[u8],
+    format: TextureFileFormat,
+    settings: &TextureLoadSettings,
+) -> Result<TextureAsset, AssetError> {
+    let image_format = match format {
+        TextureFileFormat::Png => ImageFormat::Png,
+        TextureFileFormat::Jpeg => ImageFormat::Jpeg,
+        TextureFileFormat::Hdr => ImageFormat::Hdr,
+        _ => unreachable!(),
+    };
+
+    let image = image::load_from_memory_with_format(bytes, image_format)
+        .map_err(|err| AssetError::Decode(err.to_string()))?;
+
+    let (width, height) = (image.width(), image.height());
+
+    let (format, data) = if format == TextureFileFormat::Hdr || is_float(image.color()) {
+        (
+            Format::Rgba32,
+            bytemuck::cast_slice(&image.to_rgba32f().into_raw()).to_vec(),
+        )
+    } else {
+        let srgb = match settings.srgb {
+            SrgbMode::Auto => format != TextureFileFormat::Png || is_png_srgb(bytes),
+            SrgbMode::Srgb => true,
+            SrgbMode::Linear => false,
+        };
+
+        // There is no sRGB 16-bit format, so sRGB-encoded 16-bit images are narrowed
+        // to 8 bits to keep hardware decoding.
+        if is_16bit(image.color()) && !srgb {
+            (
+                Format::Rgba16Unorm,
+                bytemuck::cast_slice(&image.to_rgba16().into_raw()).to_vec(),
+            )
+        } else {
+            (
+                resolve_format(
+                    Format::Rgba8Unorm,
+                    if srgb {
+                        SrgbMode::Srgb
+                    } else {
+                        SrgbMode::Linear
+                    },
+                ),
+                DynamicImage::into_rgba8(image).into_raw(),
+            )
+        }
+    };
+
+    Ok(TextureAsset {
+        desc: TextureDesc {
+            name: settings.name.clone(),
+            ty: TextureType::D2,
+            width,
+            height,
+            depth: 1,
+            mip_levels: 1,
+            format,
+            usage: settings.usage,
+        },
+        data,
+    })
+}
+
+fn is_float(color: ColorType) -> bool {
+    matches!(color, ColorType::Rgb32F | ColorType::Rgba32F)
+}
+
+fn is_16bit(color: ColorType) -> bool {
+    matches!(
+        color,
+        ColorType::L16 | ColorType::La16 | ColorType::Rgb16 | ColorType::Rgba16
+    )
+}
+
+/// PNGs are treated as sRGB unless they carry a linear `gAMA` chunk without an
+/// `sRGB` or `iCCP` chunk overriding it.
+fn is_png_srgb(bytes: &[u8]) -> bool {
+    let mut pos = 8;
+    let mut linear_gamma = false;
+
+    while pos + 8 <= bytes.len() {
+        let len = u32::from_be_bytes([bytes[pos], bytes[pos + 1], bytes[pos + 2], bytes[pos + 3]])
+            as usize;
+        let ty = &bytes[(pos + 4)..(pos + 8)];
+        let data = bytes.get((pos + 8)..(pos + 8 + len));
+
+        match ty {
+            b"sRGB" | b"iCCP" => return true,
+            b"gAMA" => {
+                linear_gamma = data
+                    .and_then(|d| d.try_into().ok())
+                    .is_some_and(|d: [u8; 4]| u32::from_be_bytes(d) == PNG_GAMMA_LINEAR);
+            }
+            b"IDAT" | b"IEND" => break,
+            _ => {}
+        }
+
+        pos += len + 12;
+    }
+
+    !linear_gamma
+}
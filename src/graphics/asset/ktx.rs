@@ -0,0 +1,114 @@
+use ktx2::Reader;
+
+use crate::graphics::core::{
+    resource::{TextureDesc, TextureType},
+    types::Format,
+};
+
+use super::{resolve_format, AssetError, TextureAsset, TextureLoadSettings};
+
+pub(super) fn load(
+    bytes: &[u8],
+    settings: &TextureLoadSettings,
+) -> Result<TextureAsset, AssetError> {
+    let reader = Reader::new(bytes).map_err(|err| AssetError::Decode(err.to_string()))?;
+    let header = reader.header();
+
+    if let Some(scheme) = header.supercompression_scheme {
+        return Err(AssetError::UnsupportedFormat(format!(
+            "supercompression {:?}",
+            scheme
+        )));
+    }
+
+    let format = header
+        .format
+        .ok_or_else(|| AssetError::UnsupportedFormat("undefined vk format".to_string()))?;
+    let format = map_vk_format(format)
+        .ok_or_else(|| AssetError::UnsupportedFormat(format!("{:?}", format)))?;
+
+    let faces = header.face_count.max(1);
+    let layers = header.layer_count.max(1) * faces;
+
+    let (ty, depth) = if header.pixel_depth > 0 {
+        (TextureType::D3, header.pixel_depth)
+    } else if faces == 6 {
+        (TextureType::Cube, layers)
+    } else if header.pixel_height == 0 {
+        (TextureType::D1, layers)
+    } else {
+        (TextureType::D2, layers)
+    };
+
+    let depth = u16::try_from(depth)
+        .map_err(|_| AssetError::InvalidData("texture has more than 65535 layers or slices"))?;
+
+    let desc = TextureDesc {
+        name: settings.name.clone(),
+        ty,
+        width: header.pixel_width,
+        height: header.pixel_height.max(1),
+        depth,
+        mip_levels: header.level_count.max(1),
+        format: resolve_format(format, settings.srgb),
+        usage: settings.usage,
+    };
+
+    // KTX2 stores each level with all of its layers and faces together, while
+    // subresource order keeps all levels of one layer together.
+    let levels = reader.levels().map(|l| l.data).collect::<Vec<_>>();
+    let layouts = desc.subresource_layouts();
+    let mut data = Vec::with_capacity(desc.packed_size());
+
+    for layout in layouts {
+        let level = levels
+            .get(layout.mip as usize)
+            .ok_or(AssetError::InvalidData("missing ktx2 level"))?;
+        let start = layout.layer as usize * layout.size;
+
+        let src = level
+            .get(start..(start + layout.size))
+            .ok_or(AssetError::InvalidData(
+                "ktx2 level is smaller than its header",
+            ))?;
+        data.extend_from_slice(src);
+    }
+
+    Ok(TextureAsset { desc, data })
+}
+
+fn map_vk_format(format: ktx2::Format) -> Option<Format> {
+    match format {
+        ktx2::Format::R8_UNORM => Some(Format::R8Unorm),
+        ktx2::Format::R8G8_UNORM => Some(Format::Rg8Unorm),
+        ktx2::Format::R8G8B8A8_UNORM => Some(Format::Rgba8Unorm),
+        ktx2::Format::R8G8B8A8_SRGB => Some(Format::Rgba8UnormSrgb),
+        ktx2::Format::B8G8R8A8_UNORM => Some(Format::Bgra8Unorm),
+        ktx2::Format::B8G8R8A8_SRGB => Some(Format::Bgra8UnormSrgb),
+        ktx2::Format::R16G16B16A16_UNORM => Some(Format::Rgba16Unorm),
+        ktx2::Format::R16G16B16A16_SFLOAT => Some(Format::Rgba16Float),
+        ktx2::Format::R32_SFLOAT => Some(Format::R32),
+        ktx2::Format::R32G32_SFLOAT => Some(Format::Rg32),
+        ktx2::Format::R32G32B32_SFLOAT => Some(Format::Rgb32),
+        ktx2::Format::R32G32B32A32_SFLOAT => Some(Format::Rgba32),
+        ktx2::Format::BC1_RGB_UNORM_BLOCK | ktx2::Format::BC1_RGBA_UNORM_BLOCK => {
+            Some(Format::Bc1Unorm)
+        }
+        ktx2::Format::BC1_RGB_SRGB_BLOCK | ktx2::Format::BC1_RGBA_SRGB_BLOCK => {
+            Some(Format::Bc1UnormSrgb)
+        }
+        ktx2::Format::BC2_UNORM_BLOCK => Some(Format::Bc2Unorm),
+        ktx2::Format::BC2_SRGB_BLOCK => Some(Format::Bc2UnormSrgb),
+        ktx2::Format::BC3_UNORM_BLOCK => Some(Format::Bc3Unorm),
+        ktx2::Format::BC3_SRGB_BLOCK => Some(Format::Bc3UnormSrgb),
+        ktx2::Format::BC4_UNORM_BLOCK => Some(Format::Bc4Unorm),
+        ktx2::Format::BC4_SNORM_BLOCK => Some(Format::Bc4Snorm),
+        ktx2::Format::BC5_UNORM_BLOCK => Some(Format::Bc5Unorm),
+        ktx2::Format::BC5_SNORM_BLOCK => Some(Format::Bc5Snorm),
+        ktx2::Format::BC6H_UFLOAT_BLOCK => Some(Format::Bc6hUfloat),
+        ktx2::Format::BC6H_SFLOAT_BLOCK => Some(Format::Bc6hSfloat),
+        ktx2::Format::BC7_UNORM_BLOCK => Some(Format::Bc7Unorm),
+        ktx2::Format::BC7_SRGB_BLOCK => Some(Format::Bc7UnormSrgb),
+        _ => None,
+    }
+}
@@ -0,0 +1,298 @@
+use std::{borrow::Cow, path::Path, sync::Arc};
+
+use super::{
+    context::RenderContext,
+    core::{
+        handle::RenderHandle,
+        mips,
+        resource::{SubresourceLayout, Texture, TextureDesc, TextureUsages},
+        types::Format,
+    },
+};
+
+mod dds;
+mod decode;
+mod ktx;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextureFileFormat {
+    Png,
+    Jpeg,
+    Hdr,
+    Dds,
+    Ktx2,
+}
+
+impl TextureFileFormat {
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext.to_ascii_lowercase().as_str() {
+            "png" => Some(Self::Png),
+            "jpg" | "jpeg" => Some(Self::Jpeg),
+            "hdr" => Some(Self::Hdr),
+            "dds" => Some(Self::Dds),
+            "ktx2" => Some(Self::Ktx2),
+            _ => None,
+        }
+    }
+
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(Self::Png)
+        } else if bytes.starts_with(&[0xff, 0xd8, 0xff]) {
+            Some(Self::Jpeg)
+        } else if bytes.starts_with(b"#?RADIANCE") || bytes.starts_with(b"#?RGBE") {
+            Some(Self::Hdr)
+        } else if bytes.starts_with(b"DDS ") {
+            Some(Self::Dds)
+        } else if bytes.starts_with(b"\xabKTX 20\xbb\r\n\x1a\n") {
+            Some(Self::Ktx2)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SrgbMode {
+    /// Use the color space stored in the file, or sRGB for 8-bit images without metadata.
+    #[default]
+    Auto,
+    Srgb,
+    Linear,
+}
+
+#[derive(Clone, Debug)]
+pub struct TextureLoadSettings {
+    pub name: Option<Cow<'static, str>>,
+    pub usage: TextureUsages,
+    pub srgb: SrgbMode,
+    pub generate_mips: bool,
+}
+
+impl Default for TextureLoadSettings {
+    fn default() -> Self {
+        Self {
+            name: None,
+            usage: TextureUsages::Resource,
+            srgb: SrgbMode::Auto,
+            generate_mips: false,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum AssetError {
+    Io(std::io::Error),
+    UnknownFileFormat,
+    UnsupportedFormat(String),
+    Decode(String),
+    InvalidData(&'static str),
+}
+
+impl std::fmt::Display for AssetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AssetError::Io(err) => write!(f, "io error: {}", err),
+            AssetError::UnknownFileFormat => write!(f, "unknown texture file format"),
+            AssetError::UnsupportedFormat(format) => {
+                write!(f, "unsupported texture format: {}", format)
+            }
+            AssetError::Decode(err) => write!(f, "failed to decode texture: {}", err),
+            AssetError::InvalidData(err) => write!(f, "invalid texture data: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for AssetError {}
+
+impl From<std::io::Error> for AssetError {
+    fn from(value: std::io::Error) -> Self {
+        AssetError::Io(value)
+    }
+}
+
+/// Decoded texture with every subresource tightly packed in subresource order,
+/// the layout `RenderContext::bind_texture` expects for init data.
+#[derive(Clone, Debug)]
+pub struct TextureAsset {
+    pub desc: TextureDesc,
+    pub data: Vec<u8>,
+}
+
+impl TextureAsset {
+    pub fn subresources(&self) -> impl Iterator<Item = (SubresourceLayout, &[u8])> + '_ {
+        self.desc
+            .subresource_layouts()
+            .into_iter()
+            .map(|l| (l, &self.data[l.offset..(l.offset + l.size)]))
+    }
+
+    pub fn subresource(&self, mip: u32, layer: u32) -> Option<&[u8]> {
+        self.subresources()
+            .find(|(l, _)| l.mip == mip && l.layer == layer)
+            .map(|(_, data)| data)
+    }
+
+    pub fn bind<C: RenderContext>(&self, ctx: &Arc<C>, handle: RenderHandle<Texture>) {
+        ctx.bind_texture(handle, self.desc.clone(), Some(&self.data));
+    }
+}
+
+pub fn load_texture(
+    path: impl AsRef<Path>,
+    settings: &TextureLoadSettings,
+) -> Result<TextureAsset, AssetError> {
+    let path = path.as_ref();
+    let bytes = std::fs::read(path)?;
+
+    let format = TextureFileFormat::detect(&bytes)
+        .or_else(|| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .and_then(TextureFileFormat::from_extension)
+        })
+        .ok_or(AssetError::UnknownFileFormat)?;
+
+    let mut settings = settings.clone();
+    if settings.name.is_none() {
+        settings.name = path
+            .file_name()
+            .map(|name| Cow::Owned(name.to_string_lossy().into_owned()));
+    }
+
+    load_texture_from_memory(&bytes, Some(format), &settings)
+}
+
+pub fn load_texture_from_memory(
+    bytes: &[u8],
+    format: Option<TextureFileFormat>,
+    settings: &TextureLoadSettings,
+) -> Result<TextureAsset, AssetError> {
+    let format = format
+        .or_else(|| TextureFileFormat::detect(bytes))
+        .ok_or(AssetError::UnknownFileFormat)?;
+
+    let mut asset = match format {
+        TextureFileFormat::Png | TextureFileFormat::Jpeg | TextureFileFormat::Hdr => {
+            decode::load(bytes, format, settings)?
+        }
+        TextureFileFormat::Dds => dds::load(bytes, settings)?,
+        TextureFileFormat::Ktx2 => ktx::load(bytes, settings)?,
+    };
+
+    if settings.generate_mips && asset.desc.mip_levels == 1 {
+        if let Some((desc, data)) = mips::generate_mip_chain(&asset.desc, &asset.data) {
+            asset.desc = desc;
+            asset.data = data;
+        }
+    }
+
+    Ok(asset)
+}
+
+fn resolve_format(format: Format, mode: SrgbMode) -> Format {
+    match mode {
+        SrgbMode::Auto => format,
+        SrgbMode::Srgb => format.to_srgb(),
+        SrgbMode::Linear => format.to_linear(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::core::resource::TextureType;
+
+    fn encode_png(width: u32, height: u32, pixels: Vec<u8>) -> Vec<u8> {
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        image::RgbaImage::from_raw(width, height, pixels)
+            .unwrap()
+            .write_to(&mut bytes, image::ImageFormat::Png)
+            .unwrap();
+
+        bytes.into_inner()
+    }
+
+    #[test]
+    fn png_defaults_to_srgb() {
+        let bytes = encode_png(2, 2, vec![255; 16]);
+        assert_eq!(
+            TextureFileFormat::detect(&bytes),
+            Some(TextureFileFormat::Png)
+        );
+
+        let asset =
+            load_texture_from_memory(&bytes, None, &TextureLoadSettings::default()).unwrap();
+        assert_eq!(asset.desc.format, Format::Rgba8UnormSrgb);
+        assert_eq!(asset.data.len(), 16);
+
+        let asset = load_texture_from_memory(
+            &bytes,
+            None,
+            &TextureLoadSettings {
+                srgb: SrgbMode::Linear,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(asset.desc.format, Format::Rgba8Unorm);
+    }
+
+    #[test]
+    fn generate_mips_averages_in_linear_space() {
+        let pixels = [[0, 0, 0, 255], [255, 255, 255, 255]]
+            .into_iter()
+            .cycle()
+            .take(16)
+            .flatten()
+            .collect::<Vec<u8>>();
+        let bytes = encode_png(4, 4, pixels);
+
+        let asset = load_texture_from_memory(
+            &bytes,
+            None,
+            &TextureLoadSettings {
+                generate_mips: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(asset.desc.mip_levels, 3);
+        assert_eq!(asset.data.len(), (16 + 4 + 1) * 4);
+        assert_eq!(asset.subresource(2, 0).unwrap(), &[188, 188, 188, 255]);
+    }
+
+    #[test]
+    fn dds_cubemap_is_stored_in_subresource_order() {
+        let mut dds = ddsfile::Dds::new_dxgi(ddsfile::NewDxgiParams {
+            height: 4,
+            width: 4,
+            depth: None,
+            format: ddsfile::DxgiFormat::BC1_UNorm_sRGB,
+            mipmap_levels: Some(3),
+            array_layers: Some(6),
+            caps2: None,
+            is_cubemap: true,
+            resource_dimension: ddsfile::D3D10ResourceDimension::Texture2D,
+            alpha_mode: ddsfile::AlphaMode::Unknown,
+        })
+        .unwrap();
+
+        for (i, byte) in dds.data.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+
+        let mut bytes = Vec::new();
+        dds.write(&mut bytes).unwrap();
+
+        let asset =
+            load_texture_from_memory(&bytes, None, &TextureLoadSettings::default()).unwrap();
+        assert_eq!(asset.desc.ty, TextureType::Cube);
+        assert_eq!(asset.desc.depth, 6);
+        assert_eq!(asset.desc.format, Format::Bc1UnormSrgb);
+        assert_eq!(asset.desc.subresource_count(), 18);
+        assert_eq!(asset.data.len(), 6 * 3 * 8);
+        assert_eq!(asset.subresource(0, 1).unwrap()[0], 24);
+    }
+}
@@ -1,9 +1,18 @@
 use std::sync::Arc;
 
 use super::core::{
+    capture::Capture,
     handle::RenderHandle,
-    resource::{Buffer, BufferDesc, Sampler, SamplerDesc, Texture, TextureDesc, TextureViewDesc},
-    shader::{ComputePipeline, RenderPipeline},
+    profiler::GpuTiming,
+    residency::ResidencyPriority,
+    resource::{
+        Buffer, BufferDesc, BufferView, BufferViewDesc, BufferViewError, Sampler, SamplerDesc,
+        Texture, TextureDesc, TextureViewDesc,
+    },
+    shader::{
+        ComputePipeline, ComputePipelineDesc, PipelineError, RenderPipeline, RenderPipelineDesc,
+        ShaderError, ShaderModule, ShaderModuleDesc,
+    },
 };
 
 pub trait RenderContext {
@@ -16,6 +25,14 @@ pub trait RenderContext {
     );
     fn unbind_buffer(&self, handle: RenderHandle<Buffer>);
 
+    fn bind_buffer_view(
+        &self,
+        handle: RenderHandle<BufferView>,
+        buffer: RenderHandle<Buffer>,
+        desc: BufferViewDesc,
+    ) -> Result<(), BufferViewError>;
+    fn unbind_buffer_view(&self, handle: RenderHandle<BufferView>);
+
     fn bind_texture(
         self: &Arc<Self>,
         handle: RenderHandle<Texture>,
@@ -24,6 +41,8 @@ pub trait RenderContext {
     );
     fn unbind_texture(&self, handle: RenderHandle<Texture>);
 
+    fn set_texture_priority(&self, handle: RenderHandle<Texture>, priority: ResidencyPriority);
+
     fn bind_texture_view(
         &self,
         handle: RenderHandle<Texture>,
@@ -37,9 +56,43 @@ pub trait RenderContext {
     fn unbind_sampler(&self, handle: RenderHandle<Sampler>);
 
     // Shader
-    fn bind_compute_pipeline(&self, handle: RenderHandle<ComputePipeline>, desc: ());
+    /// Fails if the device does not accept the form of `desc.source`, see
+    /// [`RenderDeviceInfo::shader_languages`](super::core::backend::RenderDeviceInfo).
+    fn bind_shader_module(
+        &self,
+        handle: RenderHandle<ShaderModule>,
+        desc: ShaderModuleDesc,
+    ) -> Result<(), ShaderError>;
+    fn unbind_shader_module(&self, handle: RenderHandle<ShaderModule>);
+
+    /// Fails if the shader entry is missing from its bound module or is not a
+    /// compute shader, or the backend rejects the pipeline.
+    fn bind_compute_pipeline(
+        &self,
+        handle: RenderHandle<ComputePipeline>,
+        desc: ComputePipelineDesc,
+    ) -> Result<(), PipelineError>;
     fn unbind_compute_pipeline(&self, handle: RenderHandle<ComputePipeline>);
 
-    fn bind_render_pipeline(&self, handle: RenderHandle<RenderPipeline>, desc: ());
+    /// Fails if a shader entry is missing from its bound module or runs at
+    /// another stage, or the backend rejects the pipeline.
+    fn bind_render_pipeline(
+        &self,
+        handle: RenderHandle<RenderPipeline>,
+        desc: RenderPipelineDesc,
+    ) -> Result<(), PipelineError>;
     fn unbind_render_pipeline(&self, handle: RenderHandle<RenderPipeline>);
+
+    // Profiling
+    /// Scopes of the latest frame read back from the graphics queue.
+    fn gpu_timings(&self) -> Vec<GpuTiming>;
+
+    // Capture
+    /// Starts recording the next `frames` frames, ended by graphics commits,
+    /// along with the resources they can reference. Only the mock backend
+    /// records captures yet, DX12 logs a warning and never finishes one, but
+    /// captures replay on either backend.
+    fn begin_capture(&self, frames: u32);
+    /// The capture started by [`RenderContext::begin_capture`], once all of its frames ran.
+    fn take_capture(&self) -> Option<Capture>;
 }
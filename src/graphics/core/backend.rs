@@ -1,6 +1,12 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
-use super::{commands::CommandDevice, resource::ResourceDevice};
+use parking_lot::Mutex;
+
+use super::{
+    commands::{CommandBufferType, CommandDevice, DeviceTag, FenceValue, SyncPoint},
+    resource::ResourceDevice,
+    shader::ShaderLanguages,
+};
 
 pub type RenderDeviceId = usize;
 
@@ -18,6 +24,8 @@ pub struct RenderDeviceInfo {
     pub is_cross_adapter_texture_supported: bool,
     pub is_uma: bool,
     pub ty: DeviceType,
+    /// Forms shader modules can be created from on this device.
+    pub shader_languages: ShaderLanguages,
 }
 
 pub trait Api: Sized {
@@ -31,13 +39,68 @@ pub trait Api: Sized {
 pub struct RenderDeviceGroup<D: CommandDevice + ResourceDevice> {
     pub primary: Arc<D>,
     pub secondaries: Vec<Arc<D>>,
+
+    /// One timeline for every queue of every device.
+    timelines: Arc<SharedTimelines<D::SharedFence>>,
+}
+
+type SharedTimelines<F> = HashMap<(DeviceTag, CommandBufferType), SharedTimeline<F>>;
+
+/// Shared fence signalled by a single queue, so its value only grows.
+#[derive(Debug)]
+struct SharedTimeline<F> {
+    /// Created by the signalling device and opened by the others, indexed like
+    /// [`RenderDeviceGroup::device`].
+    fences: Vec<F>,
+    /// Last signalled value, locked until the signal is queued.
+    value: Mutex<FenceValue>,
 }
 
 impl<D: CommandDevice + ResourceDevice> RenderDeviceGroup<D> {
     pub fn new(primary: Arc<D>, secondaries: Vec<Arc<D>>) -> Self {
+        let devices = std::iter::once(&primary)
+            .chain(&secondaries)
+            .collect::<Vec<_>>();
+
+        let mut timelines = HashMap::new();
+        for (index, owner) in devices.iter().enumerate() {
+            for ty in [
+                CommandBufferType::Graphics,
+                CommandBufferType::Compute,
+                CommandBufferType::Transfer,
+            ] {
+                let fence = owner.create_shared_fence();
+                let mut fences = devices
+                    .iter()
+                    .enumerate()
+                    .filter(|(other, _)| *other != index)
+                    .map(|(_, device)| device.open_shared_fence(&fence, owner))
+                    .collect::<Vec<_>>();
+                fences.insert(index, fence);
+
+                timelines.insert(
+                    (owner.tag(), ty),
+                    SharedTimeline {
+                        fences,
+                        value: Mutex::new(0),
+                    },
+                );
+            }
+        }
+
         Self {
             primary,
             secondaries,
+            timelines: Arc::new(timelines),
+        }
+    }
+
+    /// Device at `index`, the primary is 0 and the secondaries follow in order.
+    pub fn device(&self, index: usize) -> &Arc<D> {
+        if index == 0 {
+            &self.primary
+        } else {
+            &self.secondaries[index - 1]
         }
     }
 
@@ -48,4 +111,34 @@ impl<D: CommandDevice + ResourceDevice> RenderDeviceGroup<D> {
             func(device);
         }
     }
+
+    /// Signals the shared fence of queue `ty` on device `index` once the work
+    /// committed to it so far is done. The sync point names that queue, any
+    /// device of the group can wait on it.
+    pub fn signal_shared(&self, index: usize, ty: CommandBufferType) -> SyncPoint {
+        let device = self.device(index);
+        let timeline = &self.timelines[&(device.tag(), ty)];
+
+        let mut value = timeline.value.lock();
+        *value += 1;
+        device.signal_shared(ty, &timeline.fences[index], *value);
+
+        SyncPoint::new(device.tag(), ty, *value)
+    }
+
+    /// Makes work committed to `ty` on device `index` after this call wait on the
+    /// GPU until the queue of `point` reaches it.
+    pub fn wait_shared(&self, index: usize, ty: CommandBufferType, point: SyncPoint) {
+        let timeline = self
+            .timelines
+            .get(&(point.device(), point.ty()))
+            .expect("sync point of a device outside the group");
+        debug_assert!(
+            point.value() <= *timeline.value.lock(),
+            "waiting for a sync point that was never signaled"
+        );
+
+        self.device(index)
+            .wait_shared(ty, &timeline.fences[index], point.value());
+    }
 }
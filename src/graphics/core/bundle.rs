@@ -0,0 +1,160 @@
+use std::{
+    borrow::Cow,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Weak,
+    },
+};
+
+use super::{
+    handle::RenderHandle,
+    resource::{Buffer, BufferView},
+    shader::RenderPipeline,
+    types::Format,
+};
+
+/// Passes a bundle can execute in, described by their attachment formats.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BundleDesc {
+    pub name: Option<Cow<'static, str>>,
+    pub color_formats: Vec<Format>,
+    pub depth_format: Option<Format>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BundleError {
+    /// A resource the bundle was recorded with has been unbound since.
+    Invalidated,
+    /// The attachment formats of the pass differ from the ones of the bundle.
+    IncompatiblePass,
+    /// Bundles can not execute other bundles.
+    Nested,
+}
+
+impl fmt::Display for BundleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BundleError::Invalidated => {
+                write!(f, "bundle references a resource that was unbound")
+            }
+            BundleError::IncompatiblePass => {
+                write!(f, "bundle was recorded for different attachment formats")
+            }
+            BundleError::Nested => write!(f, "bundles can not execute other bundles"),
+        }
+    }
+}
+
+impl std::error::Error for BundleError {}
+
+pub fn validate_pass(
+    desc: &BundleDesc,
+    color_formats: &[Format],
+    depth_format: Option<Format>,
+) -> Result<(), BundleError> {
+    if desc.color_formats != color_formats || desc.depth_format != depth_format {
+        return Err(BundleError::IncompatiblePass);
+    }
+
+    Ok(())
+}
+
+/// Handles a bundle was recorded through, including the buffers behind its
+/// views. The bundle keeps none of them alive, unbinding any invalidates it.
+#[derive(Clone, Debug, Default)]
+pub struct BundleReferences {
+    pub buffers: Vec<RenderHandle<Buffer>>,
+    pub buffer_views: Vec<RenderHandle<BufferView>>,
+    pub render_pipelines: Vec<RenderHandle<RenderPipeline>>,
+}
+
+impl BundleReferences {
+    pub fn use_buffer(&mut self, handle: RenderHandle<Buffer>) {
+        if !self.buffers.contains(&handle) {
+            self.buffers.push(handle);
+        }
+    }
+
+    pub fn use_buffer_view(&mut self, handle: RenderHandle<BufferView>) {
+        if !self.buffer_views.contains(&handle) {
+            self.buffer_views.push(handle);
+        }
+    }
+
+    pub fn use_render_pipeline(&mut self, handle: RenderHandle<RenderPipeline>) {
+        if !self.render_pipelines.contains(&handle) {
+            self.render_pipelines.push(handle);
+        }
+    }
+}
+
+/// Validity flag of a recorded bundle, cleared by the [`BundleTracker`] of its device.
+#[derive(Debug)]
+pub struct BundleValidity(Arc<AtomicBool>);
+
+impl BundleValidity {
+    pub fn is_valid(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// References of every live bundle of a device, so unbinding a handle can
+/// invalidate the bundles recorded with it.
+#[derive(Debug, Default)]
+pub struct BundleTracker {
+    bundles: Vec<(BundleReferences, Weak<AtomicBool>)>,
+}
+
+impl BundleTracker {
+    pub fn register(&mut self, references: BundleReferences) -> BundleValidity {
+        let valid = Arc::new(AtomicBool::new(true));
+        self.bundles.push((references, Arc::downgrade(&valid)));
+
+        BundleValidity(valid)
+    }
+
+    /// Invalidates the bundles whose references match, forgetting dropped bundles on the way.
+    pub fn invalidate(&mut self, referenced: impl Fn(&BundleReferences) -> bool) {
+        self.bundles.retain(|(references, valid)| {
+            let Some(valid) = valid.upgrade() else {
+                return false;
+            };
+
+            if referenced(references) {
+                valid.store(false, Ordering::Relaxed);
+                return false;
+            }
+
+            true
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unbinding_invalidates_only_referencing_bundles() {
+        let (used, unused) = (RenderHandle::new(0, 0), RenderHandle::new(1, 0));
+        let mut tracker = BundleTracker::default();
+
+        let mut references = BundleReferences::default();
+        references.use_buffer_view(used);
+        references.use_buffer_view(used);
+        assert_eq!(references.buffer_views.len(), 1);
+
+        let bundle = tracker.register(references);
+        let dropped = tracker.register(BundleReferences::default());
+        drop(dropped);
+
+        tracker.invalidate(|r| r.buffer_views.contains(&unused));
+        assert!(bundle.is_valid());
+        assert_eq!(tracker.bundles.len(), 1);
+
+        tracker.invalidate(|r| r.buffer_views.contains(&used));
+        assert!(!bundle.is_valid());
+        assert!(tracker.bundles.is_empty());
+    }
+}
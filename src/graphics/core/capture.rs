@@ -0,0 +1,766 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+    sync::Arc,
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
+
+use crate::graphics::context::RenderContext;
+
+use super::{
+    commands::{
+        ColorAttachment, CommandBuffer, CommandBufferType, CommandDevice, ComputeEncoder,
+        DebugEncoder, DepthStencilAttachment, DynComputeEncoder, DynRenderEncoder,
+        DynTransferEncoder, LoadOp, RenderPassDesc, StoreOp,
+    },
+    handle::RenderHandle,
+    resource::{Buffer, BufferDesc, BufferView, BufferViewDesc, Texture, TextureDesc},
+    shader::{
+        ComputePipeline, ComputePipelineDesc, RenderPipeline, RenderPipelineDesc, ShaderModule,
+        ShaderModuleDesc,
+    },
+    stream::{CommandStream, StreamCommand, StreamRef},
+};
+
+const CAPTURE_MAGIC: [u8; 8] = *b"ASPXCAP\0";
+/// Bumped whenever a captured type changes its serialized layout.
+const CAPTURE_VERSION: u32 = 5;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CapturedBuffer {
+    pub handle: RenderHandle<Buffer>,
+    pub desc: BufferDesc,
+    pub contents: Vec<u8>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CapturedBufferView {
+    pub handle: RenderHandle<BufferView>,
+    pub buffer: RenderHandle<Buffer>,
+    pub desc: BufferViewDesc,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CapturedTexture {
+    pub handle: RenderHandle<Texture>,
+    pub desc: TextureDesc,
+    pub contents: Vec<u8>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CapturedShaderModule {
+    pub handle: RenderHandle<ShaderModule>,
+    pub desc: ShaderModuleDesc,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CapturedRenderPipeline {
+    pub handle: RenderHandle<RenderPipeline>,
+    pub desc: RenderPipelineDesc,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CapturedComputePipeline {
+    pub handle: RenderHandle<ComputePipeline>,
+    pub desc: ComputePipelineDesc,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CapturedSubmission {
+    pub ty: CommandBufferType,
+    pub stream: CommandStream,
+}
+
+/// Position of a command in a [`Capture`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CaptureCursor {
+    pub frame: usize,
+    pub submission: usize,
+    pub command: usize,
+}
+
+/// Frames recorded on a device, along with every resource they could touch.
+///
+/// Resources are stored with the contents they had when the capture started,
+/// or when they were bound during it. Replaying binds them on another device
+/// and records the captured streams again, so any backend can reproduce the
+/// frames and have its resources inspected afterwards.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Capture {
+    pub buffers: Vec<CapturedBuffer>,
+    pub buffer_views: Vec<CapturedBufferView>,
+    pub textures: Vec<CapturedTexture>,
+    pub shader_modules: Vec<CapturedShaderModule>,
+    pub render_pipelines: Vec<CapturedRenderPipeline>,
+    pub compute_pipelines: Vec<CapturedComputePipeline>,
+    /// Submissions of every frame in push order.
+    pub frames: Vec<Vec<CapturedSubmission>>,
+}
+
+#[derive(Debug)]
+pub enum CaptureError {
+    Io(io::Error),
+    /// The file does not start with the capture magic.
+    NotACapture,
+    UnsupportedVersion {
+        found: u32,
+    },
+    Malformed(String),
+}
+
+impl fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaptureError::Io(err) => write!(f, "capture io failed: {}", err),
+            CaptureError::NotACapture => write!(f, "file is not a frame capture"),
+            CaptureError::UnsupportedVersion { found } => write!(
+                f,
+                "capture version {} is not supported, expected {}",
+                found, CAPTURE_VERSION
+            ),
+            CaptureError::Malformed(reason) => write!(f, "capture is malformed: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for CaptureError {}
+
+impl From<io::Error> for CaptureError {
+    fn from(err: io::Error) -> Self {
+        CaptureError::Io(err)
+    }
+}
+
+impl From<bincode::Error> for CaptureError {
+    fn from(err: bincode::Error) -> Self {
+        match *err {
+            bincode::ErrorKind::Io(err) => CaptureError::Io(err),
+            err => CaptureError::Malformed(err.to_string()),
+        }
+    }
+}
+
+impl Capture {
+    pub fn write_to(&self, mut writer: impl Write) -> Result<(), CaptureError> {
+        writer.write_all(&CAPTURE_MAGIC)?;
+        writer.write_all(&CAPTURE_VERSION.to_le_bytes())?;
+        bincode::serialize_into(&mut writer, self)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    pub fn read_from(mut reader: impl Read) -> Result<Self, CaptureError> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if magic != CAPTURE_MAGIC {
+            return Err(CaptureError::NotACapture);
+        }
+
+        let mut version = [0; 4];
+        reader.read_exact(&mut version)?;
+        let found = u32::from_le_bytes(version);
+        if found != CAPTURE_VERSION {
+            return Err(CaptureError::UnsupportedVersion { found });
+        }
+
+        Ok(bincode::deserialize_from(reader)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), CaptureError> {
+        self.write_to(BufWriter::new(File::create(path)?))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, CaptureError> {
+        Self::read_from(BufReader::new(File::open(path)?))
+    }
+
+    /// Every captured command with its position, to step through a replay.
+    pub fn commands(&self) -> impl Iterator<Item = (CaptureCursor, &StreamCommand)> {
+        self.frames
+            .iter()
+            .enumerate()
+            .flat_map(|(frame, submissions)| {
+                submissions
+                    .iter()
+                    .enumerate()
+                    .flat_map(move |(submission, captured)| {
+                        captured
+                            .stream
+                            .commands()
+                            .iter()
+                            .enumerate()
+                            .map(move |(command, c)| {
+                                let cursor = CaptureCursor {
+                                    frame,
+                                    submission,
+                                    command,
+                                };
+
+                                (cursor, c)
+                            })
+                    })
+            })
+    }
+
+    /// Binds the captured resources on `device` with their initial contents.
+    pub fn bind<D: RenderContext>(&self, device: &Arc<D>) {
+        for buffer in &self.buffers {
+            device.bind_buffer(buffer.handle, buffer.desc.clone(), Some(&buffer.contents));
+        }
+
+        for view in &self.buffer_views {
+            if let Err(err) = device.bind_buffer_view(view.handle, view.buffer, view.desc) {
+                error!("Failed to bind captured buffer view: {}", err);
+            }
+        }
+
+        for texture in &self.textures {
+            device.bind_texture(
+                texture.handle,
+                texture.desc.clone(),
+                Some(&texture.contents),
+            );
+        }
+
+        for module in &self.shader_modules {
+            if let Err(err) = device.bind_shader_module(module.handle, module.desc.clone()) {
+                error!("Failed to bind captured shader module: {}", err);
+            }
+        }
+
+        for pipeline in &self.render_pipelines {
+            if let Err(err) = device.bind_render_pipeline(pipeline.handle, pipeline.desc.clone()) {
+                error!("Failed to bind captured render pipeline: {}", err);
+            }
+        }
+
+        for pipeline in &self.compute_pipelines {
+            if let Err(err) = device.bind_compute_pipeline(pipeline.handle, pipeline.desc.clone()) {
+                error!("Failed to bind captured compute pipeline: {}", err);
+            }
+        }
+    }
+
+    /// Binds the captured resources on a fresh `device` and replays every frame.
+    pub fn replay<D: RenderContext + CommandDevice>(&self, device: &Arc<D>) {
+        self.replay_until(device, None);
+    }
+
+    /// Like [`Capture::replay`], but stops after the command at `last`. Passes
+    /// left open are ended and the partial submission still runs, so the state
+    /// of the resources at that command can be inspected on `device`.
+    pub fn replay_until<D: RenderContext + CommandDevice>(
+        &self,
+        device: &Arc<D>,
+        last: Option<CaptureCursor>,
+    ) {
+        self.bind(device);
+
+        for (frame, submissions) in self.frames.iter().enumerate() {
+            for (submission, captured) in submissions.iter().enumerate() {
+                let commands = captured.stream.commands();
+                let end = match last {
+                    Some(last) if (frame, submission) > (last.frame, last.submission) => return,
+                    Some(last) if (frame, submission) == (last.frame, last.submission) => {
+                        (last.command + 1).min(commands.len())
+                    }
+                    _ => commands.len(),
+                };
+
+                let mut cmd = device.create_command_buffer(captured.ty);
+                replay_stream(&mut cmd, &commands[..end]);
+                device.push_cmd_buffer(cmd);
+
+                device.wait_cpu(device.commit(captured.ty));
+            }
+        }
+    }
+}
+
+/// Capture in progress on a device. Backends report bound resources and
+/// submitted streams, and end a frame on every graphics commit.
+#[derive(Debug)]
+pub struct CaptureRecorder {
+    capture: Capture,
+    frames: u32,
+}
+
+impl CaptureRecorder {
+    pub fn new(frames: u32) -> Self {
+        Self {
+            capture: Capture {
+                frames: vec![vec![]],
+                ..Default::default()
+            },
+            frames,
+        }
+    }
+
+    pub fn buffer(&mut self, handle: RenderHandle<Buffer>, desc: BufferDesc, contents: Vec<u8>) {
+        self.capture.buffers.push(CapturedBuffer {
+            handle,
+            desc,
+            contents,
+        });
+    }
+
+    pub fn buffer_view(
+        &mut self,
+        handle: RenderHandle<BufferView>,
+        buffer: RenderHandle<Buffer>,
+        desc: BufferViewDesc,
+    ) {
+        self.capture.buffer_views.push(CapturedBufferView {
+            handle,
+            buffer,
+            desc,
+        });
+    }
+
+    pub fn texture(&mut self, handle: RenderHandle<Texture>, desc: TextureDesc, contents: Vec<u8>) {
+        self.capture.textures.push(CapturedTexture {
+            handle,
+            desc,
+            contents,
+        });
+    }
+
+    pub fn shader_module(&mut self, handle: RenderHandle<ShaderModule>, desc: ShaderModuleDesc) {
+        self.capture
+            .shader_modules
+            .push(CapturedShaderModule { handle, desc });
+    }
+
+    pub fn render_pipeline(
+        &mut self,
+        handle: RenderHandle<RenderPipeline>,
+        desc: RenderPipelineDesc,
+    ) {
+        self.capture
+            .render_pipelines
+            .push(CapturedRenderPipeline { handle, desc });
+    }
+
+    pub fn compute_pipeline(
+        &mut self,
+        handle: RenderHandle<ComputePipeline>,
+        desc: ComputePipelineDesc,
+    ) {
+        self.capture
+            .compute_pipelines
+            .push(CapturedComputePipeline { handle, desc });
+    }
+
+    pub fn submit(&mut self, ty: CommandBufferType, stream: CommandStream) {
+        self.capture
+            .frames
+            .last_mut()
+            .expect("unreachable")
+            .push(CapturedSubmission { ty, stream });
+    }
+
+    /// Ends the current frame and returns the capture once all of its frames are recorded.
+    pub fn end_frame(&mut self) -> Option<Capture> {
+        if self.capture.frames.len() as u32 >= self.frames {
+            return Some(std::mem::take(&mut self.capture));
+        }
+
+        self.capture.frames.push(vec![]);
+        None
+    }
+}
+
+fn skipped(command: &StreamCommand) {
+    warn!(
+        "Skipping {} during replay, it references a resource that is not bound",
+        command
+    );
+}
+
+fn report<E: fmt::Display>(command: &StreamCommand, result: Result<(), E>) {
+    if let Err(err) = result {
+        error!("Replaying {} failed: {}", command, err);
+    }
+}
+
+fn is_compute(command: &StreamCommand) -> bool {
+    matches!(
+        command,
+        StreamCommand::SetPipeline(StreamRef::ComputePipeline(_))
+            | StreamCommand::SetBufferView { .. }
+            | StreamCommand::SetConstants { .. }
+            | StreamCommand::Dispatch(_)
+            | StreamCommand::DispatchIndirect { .. }
+    )
+}
+
+fn is_debug(command: &StreamCommand) -> bool {
+    matches!(
+        command,
+        StreamCommand::PushDebugGroup(_) | StreamCommand::PopDebugGroup | StreamCommand::Marker(_)
+    )
+}
+
+/// Records `commands` into `cmd`. Barriers are left to the backend, which
+/// derives them from the replayed work like it did during the capture.
+fn replay_stream<C: CommandBuffer>(cmd: &mut C, commands: &[StreamCommand]) {
+    let mut commands = commands.iter().peekable();
+
+    while let Some(command) = commands.next() {
+        match command {
+            StreamCommand::BeginRenderPass {
+                colors,
+                depth,
+                views,
+            } => {
+                let pass = RenderPassDesc {
+                    colors: colors
+                        .iter()
+                        .filter_map(|(texture, load)| {
+                            Some(ColorAttachment {
+                                texture: texture.texture()?,
+                                load: *load,
+                                store: StoreOp::Store,
+                            })
+                        })
+                        .collect(),
+                    depth_stencil: depth.and_then(|(texture, load)| {
+                        Some(DepthStencilAttachment {
+                            texture: texture.texture()?,
+                            depth_load: load,
+                            depth_store: StoreOp::Store,
+                            stencil_load: LoadOp::Load,
+                            stencil_store: StoreOp::Store,
+                        })
+                    }),
+                    buffer_views: views.iter().filter_map(|v| v.buffer_view()).collect(),
+                };
+
+                let encoder = cmd.render_encoder(&pass);
+                while let Some(command) =
+                    commands.next_if(|c| !matches!(c, StreamCommand::EndRenderPass))
+                {
+                    replay_render(&encoder, command);
+                }
+                commands.next();
+            }
+            command if is_compute(command) => {
+                let encoder = cmd.compute_encoder();
+                replay_compute(&encoder, command);
+
+                while let Some(command) = commands.next_if(|c| is_compute(c) || is_debug(c)) {
+                    replay_compute(&encoder, command);
+                }
+            }
+            StreamCommand::GenerateMips(texture) => match texture.texture() {
+                Some(texture) => cmd.generate_mips(texture),
+                None => skipped(command),
+            },
+            StreamCommand::Release { resource, queue } => match *resource {
+                StreamRef::Buffer(buffer) => cmd.release_buffer(buffer, *queue),
+                StreamRef::Texture(texture) => cmd.release_texture(texture, *queue),
+                _ => skipped(command),
+            },
+            StreamCommand::Barrier { .. } => {}
+            command => replay_transfer(&cmd.transfer_encoder(), command),
+        }
+    }
+}
+
+fn replay_debug<E: DebugEncoder>(encoder: &E, command: &StreamCommand) -> bool {
+    match command {
+        StreamCommand::PushDebugGroup(name) => encoder.push_debug_group(name),
+        StreamCommand::PopDebugGroup => encoder.pop_debug_group(),
+        StreamCommand::Marker(name) => encoder.insert_marker(name),
+        _ => return false,
+    }
+
+    true
+}
+
+fn replay_render<E: DynRenderEncoder>(encoder: &E, command: &StreamCommand) {
+    match command {
+        StreamCommand::SetViewport(viewport) => encoder.set_viewport(*viewport),
+        StreamCommand::SetScissor(scissor) => encoder.set_scissor(*scissor),
+        StreamCommand::SetStencilReference(reference) => encoder.set_stencil_reference(*reference),
+        StreamCommand::SetBlendConstants(constants) => encoder.set_blend_constants(*constants),
+        StreamCommand::SetPipeline(pipeline) => match pipeline.render_pipeline() {
+            Some(pipeline) => DynRenderEncoder::set_pipeline(encoder, pipeline),
+            None => skipped(command),
+        },
+        StreamCommand::SetVertexBuffer { slot, view } => match view.buffer_view() {
+            Some(view) => DynRenderEncoder::set_vertex_buffer(encoder, *slot, view),
+            None => skipped(command),
+        },
+        StreamCommand::SetIndexBuffer(view) => match view.buffer_view() {
+            Some(view) => DynRenderEncoder::set_index_buffer(encoder, view),
+            None => skipped(command),
+        },
+        StreamCommand::Draw {
+            vertices,
+            instances,
+            base_vertex: None,
+        } => encoder.draw_instanced(vertices.clone(), instances.clone()),
+        StreamCommand::Draw {
+            vertices,
+            instances,
+            base_vertex: Some(base_vertex),
+        } => encoder.draw_indexed_instanced(vertices.clone(), *base_vertex, instances.clone()),
+        StreamCommand::DrawIndirect {
+            args,
+            offset,
+            count,
+            max_draws,
+            indexed,
+        } => {
+            let Some(args) = args.buffer() else {
+                return skipped(command);
+            };
+
+            let result = match (count, indexed) {
+                (None, false) => {
+                    DynRenderEncoder::draw_indirect(encoder, args, *offset, *max_draws)
+                }
+                (None, true) => {
+                    DynRenderEncoder::draw_indexed_indirect(encoder, args, *offset, *max_draws)
+                }
+                (Some((count, count_offset)), indexed) => {
+                    let Some(count) = count.buffer() else {
+                        return skipped(command);
+                    };
+
+                    match indexed {
+                        false => DynRenderEncoder::draw_indirect_count(
+                            encoder,
+                            args,
+                            *offset,
+                            count,
+                            *count_offset,
+                            *max_draws,
+                        ),
+                        true => DynRenderEncoder::draw_indexed_indirect_count(
+                            encoder,
+                            args,
+                            *offset,
+                            count,
+                            *count_offset,
+                            *max_draws,
+                        ),
+                    }
+                }
+            };
+            report(command, result);
+        }
+        StreamCommand::ExecuteBundle(_) => {
+            warn!(
+                "Skipping {} during replay, bundles are not captured",
+                command
+            )
+        }
+        command if replay_debug(encoder, command) => {}
+        command => warn!(
+            "Skipping {} during replay, not valid in a render pass",
+            command
+        ),
+    }
+}
+
+fn replay_compute<E: DynComputeEncoder>(encoder: &E, command: &StreamCommand) {
+    match command {
+        StreamCommand::SetPipeline(pipeline) => match pipeline.compute_pipeline() {
+            Some(pipeline) => DynComputeEncoder::set_pipeline(encoder, pipeline),
+            None => skipped(command),
+        },
+        StreamCommand::SetBufferView { slot, view } => match view.buffer_view() {
+            Some(view) => DynComputeEncoder::set_buffer_view(encoder, *slot, view),
+            None => skipped(command),
+        },
+        StreamCommand::SetConstants { slot, constants } => {
+            ComputeEncoder::set_constants(encoder, *slot, constants)
+        }
+        StreamCommand::Dispatch([x, y, z]) => encoder.dispatch(*x, *y, *z),
+        StreamCommand::DispatchIndirect { args, offset } => match args.buffer() {
+            Some(args) => report(
+                command,
+                DynComputeEncoder::dispatch_indirect(encoder, args, *offset),
+            ),
+            None => skipped(command),
+        },
+        command => {
+            replay_debug(encoder, command);
+        }
+    }
+}
+
+fn replay_transfer<E: DynTransferEncoder>(encoder: &E, command: &StreamCommand) {
+    let result = match command {
+        StreamCommand::CopyBuffer { dst, src } => {
+            let (Some(dst), Some(src)) = (dst.buffer(), src.buffer()) else {
+                return skipped(command);
+            };
+            DynTransferEncoder::copy_buffer_to_buffer(encoder, dst, src)
+        }
+        StreamCommand::CopyTexture { dst, src } => {
+            let (Some(dst), Some(src)) = (dst.texture(), src.texture()) else {
+                return skipped(command);
+            };
+            DynTransferEncoder::copy_texture_to_texture(encoder, dst, src)
+        }
+        StreamCommand::UploadTexture { dst, src, data } => {
+            let (Some(dst), Some(src)) = (dst.texture(), src.buffer()) else {
+                return skipped(command);
+            };
+            DynTransferEncoder::upload_to_texture(encoder, dst, src, data)
+        }
+        StreamCommand::CopyBufferRegion {
+            dst,
+            dst_offset,
+            src,
+            src_offset,
+            size,
+        } => {
+            let (Some(dst), Some(src)) = (dst.buffer(), src.buffer()) else {
+                return skipped(command);
+            };
+            DynTransferEncoder::copy_buffer_region(
+                encoder,
+                dst,
+                *dst_offset,
+                src,
+                *src_offset,
+                *size,
+            )
+        }
+        StreamCommand::CopyBufferToTexture {
+            dst,
+            region,
+            src,
+            layout,
+        } => {
+            let (Some(dst), Some(src)) = (dst.texture(), src.buffer()) else {
+                return skipped(command);
+            };
+            DynTransferEncoder::copy_buffer_to_texture(encoder, dst, *region, src, *layout)
+        }
+        StreamCommand::CopyTextureToBuffer {
+            dst,
+            layout,
+            src,
+            region,
+        } => {
+            let (Some(dst), Some(src)) = (dst.buffer(), src.texture()) else {
+                return skipped(command);
+            };
+            DynTransferEncoder::copy_texture_to_buffer(encoder, dst, *layout, src, *region)
+        }
+        StreamCommand::CopyTextureRegion {
+            dst,
+            dst_region,
+            src,
+            src_region,
+        } => {
+            let (Some(dst), Some(src)) = (dst.texture(), src.texture()) else {
+                return skipped(command);
+            };
+            DynTransferEncoder::copy_texture_region(encoder, dst, *dst_region, src, *src_region)
+        }
+        StreamCommand::FillBuffer { dst, range, value } => {
+            let Some(dst) = dst.buffer() else {
+                return skipped(command);
+            };
+            DynTransferEncoder::fill_buffer(encoder, dst, range.clone(), *value)
+        }
+        StreamCommand::ClearTexture { dst, range, value } => {
+            let Some(dst) = dst.texture() else {
+                return skipped(command);
+            };
+            DynTransferEncoder::clear_texture(encoder, dst, range.clone(), *value)
+        }
+        command if replay_debug(encoder, command) => Ok(()),
+        command => {
+            warn!(
+                "Skipping {} during replay, not valid outside a pass",
+                command
+            );
+            Ok(())
+        }
+    };
+
+    report(command, result);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::core::{
+        shader::{PipelineLayout, PrimitiveTopology, ShaderEntry},
+        types::Format,
+    };
+
+    #[test]
+    fn captures_round_trip_through_files_and_reject_foreign_data() {
+        let mut recorder = CaptureRecorder::new(2);
+        recorder.render_pipeline(
+            RenderHandle::new(0, 0),
+            RenderPipelineDesc {
+                name: Some("opaque".into()),
+                layout: PipelineLayout::default(),
+                vertex: ShaderEntry {
+                    module: RenderHandle::new(0, 0),
+                    name: "vs_main".into(),
+                },
+                pixel: None,
+                vertex_attributes: vec![],
+                topology: PrimitiveTopology::TriangleList,
+                color_formats: vec![Format::Rgba8Unorm],
+                depth_format: None,
+            },
+        );
+
+        let mut stream = CommandStream::default();
+        stream.push(StreamCommand::Dispatch([1, 2, 3]));
+        recorder.submit(CommandBufferType::Compute, stream.clone());
+        assert!(recorder.end_frame().is_none());
+        recorder.submit(CommandBufferType::Graphics, stream);
+
+        let capture = recorder.end_frame().unwrap();
+        let mut file = vec![];
+        capture.write_to(&mut file).unwrap();
+
+        let loaded = Capture::read_from(file.as_slice()).unwrap();
+        assert_eq!(loaded.render_pipelines, capture.render_pipelines);
+        assert_eq!(
+            loaded
+                .commands()
+                .map(|(cursor, _)| cursor)
+                .collect::<Vec<_>>(),
+            [
+                CaptureCursor {
+                    frame: 0,
+                    submission: 0,
+                    command: 0
+                },
+                CaptureCursor {
+                    frame: 1,
+                    submission: 0,
+                    command: 0
+                }
+            ]
+        );
+
+        file[8] = 0;
+        assert!(matches!(
+            Capture::read_from(file.as_slice()),
+            Err(CaptureError::UnsupportedVersion { found: 0 })
+        ));
+        assert!(matches!(
+            Capture::read_from(&b"RIFF0000"[..]),
+            Err(CaptureError::NotACapture)
+        ));
+    }
+}
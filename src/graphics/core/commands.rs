@@ -1,75 +1,848 @@
+use std::{
+    cmp::Ordering,
+    fmt,
+    ops::{Deref, Range},
+    sync::atomic::{self, AtomicU32},
+};
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
 use super::{
+    bundle::{BundleDesc, BundleError},
+    future::GpuFuture,
     handle::RenderHandle,
-    resource::{Buffer, Texture},
+    indirect::IndirectError,
+    profiler::GpuScope,
+    query::QueryError,
+    resource::{Buffer, BufferView, SubresourceRange, Texture},
+    shader::{ComputePipeline, RenderPipeline},
+    stream::CommandStream,
+    transfer::{BufferTextureLayout, ClearValue, TextureRegion, TransferError},
     types::ResourceState,
 };
 
-pub type SyncPoint = u64;
+/// Raw value of a queue or shared fence.
+pub type FenceValue = u64;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum CommandBufferType {
     Graphics,
     Compute,
     Transfer,
 }
 
+/// Tag of a created device, unique for the process.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DeviceTag(u32);
+
+impl DeviceTag {
+    pub fn unique() -> Self {
+        static NEXT: AtomicU32 = AtomicU32::new(0);
+
+        Self(NEXT.fetch_add(1, atomic::Ordering::Relaxed))
+    }
+}
+
+/// Point on the timeline of one queue of one device.
+///
+/// Waits find the queue from the sync point itself, using it on another device
+/// panics. Sync points of different queues are unordered, see [`Self::try_cmp`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SyncPoint {
+    device: DeviceTag,
+    ty: CommandBufferType,
+    value: FenceValue,
+}
+
+impl SyncPoint {
+    pub fn new(device: DeviceTag, ty: CommandBufferType, value: FenceValue) -> Self {
+        Self { device, ty, value }
+    }
+
+    pub fn device(self) -> DeviceTag {
+        self.device
+    }
+
+    pub fn ty(self) -> CommandBufferType {
+        self.ty
+    }
+
+    pub fn value(self) -> FenceValue {
+        self.value
+    }
+
+    /// Fence value of the sync point, which must come from `device`.
+    pub fn value_on(self, device: DeviceTag) -> FenceValue {
+        assert_eq!(
+            self.device, device,
+            "sync point of {:?} used on another device",
+            self.device
+        );
+
+        self.value
+    }
+
+    /// Orders two sync points of the same queue.
+    pub fn try_cmp(self, other: Self) -> Result<Ordering, SyncPointError> {
+        if self.device != other.device || self.ty != other.ty {
+            return Err(SyncPointError::DifferentTimelines);
+        }
+
+        Ok(self.value.cmp(&other.value))
+    }
+}
+
+impl PartialOrd for SyncPoint {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.try_cmp(*other).ok()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncPointError {
+    /// The sync points belong to different devices or queues.
+    DifferentTimelines,
+}
+
+impl fmt::Display for SyncPointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyncPointError::DifferentTimelines => {
+                write!(f, "sync points are on different timelines")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SyncPointError {}
+
+#[derive(Debug)]
+pub struct BufferBarrier<'a, B> {
+    pub buffer: &'a B,
+    pub state: ResourceState,
+}
+
+#[derive(Debug)]
+pub struct TextureBarrier<'a, T> {
+    pub texture: &'a T,
+    pub state: ResourceState,
+    /// Subresources to transition, `None` for the whole texture.
+    pub range: Option<SubresourceRange>,
+    /// Targets the cross adapter copy of a shared texture instead of the local one.
+    pub cross_adapter: bool,
+}
+
+impl<'a, T> TextureBarrier<'a, T> {
+    pub fn new(texture: &'a T, state: ResourceState) -> Self {
+        Self {
+            texture,
+            state,
+            range: None,
+            cross_adapter: false,
+        }
+    }
+}
+
+/// Resources touched by a pass or copy, gathered before it is recorded so the
+/// transitions can be issued as a single batch.
+#[derive(Debug)]
+pub struct ResourceUsages<'a, B, T> {
+    pub buffers: Vec<BufferBarrier<'a, B>>,
+    pub textures: Vec<TextureBarrier<'a, T>>,
+}
+
+impl<'a, B, T> Default for ResourceUsages<'a, B, T> {
+    fn default() -> Self {
+        Self {
+            buffers: vec![],
+            textures: vec![],
+        }
+    }
+}
+
+impl<'a, B, T> ResourceUsages<'a, B, T> {
+    pub fn use_buffer(&mut self, buffer: &'a B, state: ResourceState) -> &mut Self {
+        self.buffers.push(BufferBarrier { buffer, state });
+        self
+    }
+
+    pub fn use_texture(&mut self, texture: &'a T, state: ResourceState) -> &mut Self {
+        self.textures.push(TextureBarrier::new(texture, state));
+        self
+    }
+
+    pub fn use_subresources(
+        &mut self,
+        texture: &'a T,
+        state: ResourceState,
+        range: SubresourceRange,
+    ) -> &mut Self {
+        self.textures.push(TextureBarrier {
+            range: Some(range),
+            ..TextureBarrier::new(texture, state)
+        });
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffers.is_empty() && self.textures.is_empty()
+    }
+}
+
 pub trait CommandBuffer {
-    type BufferBarrier<'a>;
-    type TextureBarrier<'a>;
+    type Buffer;
+    type Texture;
+    type QuerySet;
 
-    type RenderEncoder<'a>: RenderEncoder
+    type RenderEncoder<'a>: DynRenderEncoder
     where
         Self: 'a;
 
-    type ComputeEncoder<'a>: ComputeEncoder
+    type ComputeEncoder<'a>: DynComputeEncoder
     where
         Self: 'a;
 
-    type TransferEncoder<'a>: TransferEncoder
+    type TransferEncoder<'a>: DynTransferEncoder
     where
         Self: 'a;
 
-    fn render_encoder(&mut self) -> Self::RenderEncoder<'_>;
+    /// Begins a render pass, the pass ends when the encoder is dropped.
+    fn render_encoder(&mut self, pass: &RenderPassDesc) -> Self::RenderEncoder<'_>;
+    /// Available on graphics and compute buffers.
     fn compute_encoder(&mut self) -> Self::ComputeEncoder<'_>;
     fn transfer_encoder(&mut self) -> Self::TransferEncoder<'_>;
 
-    fn set_buffer_barriers(&self, barriers: &[Self::BufferBarrier<'_>]);
-    fn set_texture_barriers(&self, barriers: &[Self::TextureBarrier<'_>]);
+    /// Transitions every listed resource, skipping the ones already in the requested state.
+    fn set_barriers(
+        &self,
+        buffers: &[BufferBarrier<'_, Self::Buffer>],
+        textures: &[TextureBarrier<'_, Self::Texture>],
+    );
+
+    fn set_buffer_barriers(&self, barriers: &[BufferBarrier<'_, Self::Buffer>]) {
+        self.set_barriers(barriers, &[]);
+    }
+
+    fn set_texture_barriers(&self, barriers: &[TextureBarrier<'_, Self::Texture>]) {
+        self.set_barriers(&[], barriers);
+    }
+
+    fn use_resources(&self, usages: &ResourceUsages<'_, Self::Buffer, Self::Texture>) {
+        if !usages.is_empty() {
+            self.set_barriers(&usages.buffers, &usages.textures);
+        }
+    }
+
+    /// Fills every mip below the top level of each layer. Not available on transfer buffers.
+    fn generate_mips(&mut self, texture: RenderHandle<Texture>);
+
+    /// Hands a resource to the `to` queue, leaving it in a state `to` supports.
+    /// The first use on `to` acquires it. Resources left in `Common` move
+    /// between queues without a release.
+    fn release_buffer(&self, buffer: RenderHandle<Buffer>, to: CommandBufferType);
+    fn release_texture(&self, texture: RenderHandle<Texture>, to: CommandBufferType);
+
+    /// Writes the queue clock into a timestamp query once preceding work has finished.
+    fn write_timestamp(&self, set: &Self::QuerySet, index: u32) -> Result<(), QueryError>;
+
+    /// Starts counting into a pipeline statistics query.
+    fn begin_query(&self, set: &Self::QuerySet, index: u32) -> Result<(), QueryError>;
+    fn end_query(&self, set: &Self::QuerySet, index: u32) -> Result<(), QueryError>;
+
+    /// Writes the results of `queries` to `dst` at `offset`, which must be 8 byte aligned.
+    fn resolve_queries(
+        &self,
+        set: &Self::QuerySet,
+        queries: Range<u32>,
+        dst: &Self::Buffer,
+        offset: usize,
+    ) -> Result<(), QueryError>;
+
+    /// Opens a profiling scope, timed on graphics buffers and ignored on others.
+    /// Scopes nest and must be closed before the buffer is submitted.
+    fn begin_scope(&self, name: &'static str);
+    fn end_scope(&self);
+
+    fn scope(&mut self, name: &'static str) -> GpuScope<'_, Self>
+    where
+        Self: Sized,
+    {
+        GpuScope::new(self, name)
+    }
+
+    /// Work recorded so far in backend-neutral form, `None` unless the backend
+    /// mirrors its commands and [`DebugFlags::CommandStream`] is set.
+    ///
+    /// [`DebugFlags::CommandStream`]: crate::graphics::DebugFlags::CommandStream
+    fn command_stream(&self) -> Option<CommandStream> {
+        None
+    }
 }
 
 pub trait CommandDevice {
     type CommandBuffer: CommandBuffer;
+    type SharedFence: fmt::Debug;
+    type Bundle;
+    type BundleEncoder: DynRenderEncoder;
+
+    /// Device the sync points returned by this device belong to.
+    fn tag(&self) -> DeviceTag;
 
     fn create_command_buffer(&self, ty: CommandBufferType) -> Self::CommandBuffer;
+    /// Always returns an empty buffer, ignoring stashed ones. Worker threads record
+    /// with these, the queue only locks its pool while handing them out.
+    fn allocate_command_buffer(&self, ty: CommandBufferType) -> Self::CommandBuffer;
     fn stash_cmd_buffer(&self, cmd_buffer: Self::CommandBuffer);
     fn push_cmd_buffer(&self, cmd_buffer: Self::CommandBuffer);
     fn commit(&self, ty: CommandBufferType) -> SyncPoint;
-    fn wait_cpu(&self, ty: CommandBufferType, time: SyncPoint);
+    fn wait_cpu(&self, time: SyncPoint);
+
+    /// Resolves once the queue of `time` reaches it.
+    fn future(&self, time: SyncPoint) -> GpuFuture;
+
+    /// Commits the pending buffers of `ty` and returns a future for their completion.
+    fn commit_future(&self, ty: CommandBufferType) -> GpuFuture {
+        self.future(self.commit(ty))
+    }
+
+    /// Makes work committed to `waiting` after this call wait on the GPU until
+    /// the queue of `time` reaches it.
+    fn wait_gpu(&self, waiting: CommandBufferType, time: SyncPoint);
+
+    /// Commits the pending buffers of `ty` behind GPU waits on other queues.
+    fn commit_with_waits(&self, ty: CommandBufferType, waits: &[SyncPoint]) -> SyncPoint {
+        for &time in waits {
+            self.wait_gpu(ty, time);
+        }
+
+        self.commit(ty)
+    }
+
+    /// Reserves `count` ordered slots for buffers of `ty` recorded on worker threads.
+    fn reserve_slots(
+        &self,
+        ty: CommandBufferType,
+        count: usize,
+    ) -> CommandSlots<Self::CommandBuffer> {
+        CommandSlots::new(ty, count)
+    }
+
+    /// Pushes the buffers of `slots` in slot order, then commits them together
+    /// with the buffers pushed before.
+    fn commit_slots(&self, slots: CommandSlots<Self::CommandBuffer>) -> SyncPoint {
+        let ty = slots.ty();
+        for cmd_buffer in slots.into_ordered() {
+            self.push_cmd_buffer(cmd_buffer);
+        }
+
+        self.commit(ty)
+    }
+
+    /// Starts recording a bundle for passes with the attachment formats of `desc`.
+    /// Bundles inherit the viewport and scissor of the pass they execute in, setting
+    /// them on the bundle encoder is ignored with a warning. The resources they use
+    /// must already be in the right state.
+    fn bundle_encoder(&self, desc: BundleDesc) -> Self::BundleEncoder;
+    fn finish_bundle(&self, encoder: Self::BundleEncoder) -> Self::Bundle;
+
+    /// Creates a fence that other devices can open with [`CommandDevice::open_shared_fence`].
+    fn create_shared_fence(&self) -> Self::SharedFence;
+    /// Opens a fence created by `owner` on this device.
+    fn open_shared_fence(&self, fence: &Self::SharedFence, owner: &Self) -> Self::SharedFence;
+
+    /// Sets `fence` to `value` once the work committed to `ty` so far is done.
+    fn signal_shared(&self, ty: CommandBufferType, fence: &Self::SharedFence, value: FenceValue);
+    /// Makes work committed to `ty` after this call wait on the GPU until
+    /// `fence` reaches `value`.
+    fn wait_shared(&self, ty: CommandBufferType, fence: &Self::SharedFence, value: FenceValue);
+}
+
+/// Command buffers recorded in parallel, submitted in slot order no matter
+/// which thread finishes first.
+#[derive(Debug)]
+pub struct CommandSlots<C> {
+    ty: CommandBufferType,
+    slots: Vec<Mutex<Option<C>>>,
+}
+
+impl<C> CommandSlots<C> {
+    pub fn new(ty: CommandBufferType, count: usize) -> Self {
+        Self {
+            ty,
+            slots: (0..count).map(|_| Mutex::new(None)).collect(),
+        }
+    }
+
+    pub fn ty(&self) -> CommandBufferType {
+        self.ty
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Hands the recorded buffer of slot `index` back, callable from any thread.
+    pub fn fill(&self, index: usize, cmd_buffer: C) {
+        let previous = self.slots[index].lock().replace(cmd_buffer);
+        debug_assert!(previous.is_none(), "slot {} filled twice", index);
+    }
+
+    /// Buffers in slot order, slots that were never filled are skipped.
+    pub fn into_ordered(self) -> Vec<C> {
+        self.slots
+            .into_iter()
+            .filter_map(|slot| slot.into_inner())
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum LoadOp<T> {
+    Load,
+    Clear(T),
+    /// Previous contents are undefined.
+    DontCare,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StoreOp {
+    Store,
+    Discard,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColorAttachment {
+    pub texture: RenderHandle<Texture>,
+    pub load: LoadOp<[f32; 4]>,
+    pub store: StoreOp,
+}
+
+impl ColorAttachment {
+    pub fn load(texture: RenderHandle<Texture>) -> Self {
+        Self {
+            texture,
+            load: LoadOp::Load,
+            store: StoreOp::Store,
+        }
+    }
+
+    pub fn clear(texture: RenderHandle<Texture>, color: [f32; 4]) -> Self {
+        Self {
+            texture,
+            load: LoadOp::Clear(color),
+            store: StoreOp::Store,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DepthStencilAttachment {
+    pub texture: RenderHandle<Texture>,
+    pub depth_load: LoadOp<f32>,
+    pub depth_store: StoreOp,
+    pub stencil_load: LoadOp<u8>,
+    pub stencil_store: StoreOp,
+}
+
+impl DepthStencilAttachment {
+    pub fn clear(texture: RenderHandle<Texture>, depth: f32) -> Self {
+        Self {
+            texture,
+            depth_load: LoadOp::Clear(depth),
+            depth_store: StoreOp::Store,
+            stencil_load: LoadOp::Clear(0),
+            stencil_store: StoreOp::Store,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RenderPassDesc {
+    pub colors: Vec<ColorAttachment>,
+    pub depth_stencil: Option<DepthStencilAttachment>,
+    /// Vertex, index and shader views the pass or its bundles bind. Their
+    /// buffers are transitioned with the attachments before the pass starts,
+    /// the pass itself records no barriers.
+    pub buffer_views: Vec<RenderHandle<BufferView>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub min_depth: f32,
+    pub max_depth: f32,
+}
+
+impl Viewport {
+    pub fn new(width: f32, height: f32) -> Self {
+        Self {
+            x: 0.0,
+            y: 0.0,
+            width,
+            height,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Scissor {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Scissor {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        }
+    }
 }
 
 // TODO: Split abstraction level
 
-pub trait RenderEncoder {}
+/// Debug groups and markers shown by graphics debuggers. Groups nest and must
+/// be closed by the encoder that opened them.
+pub trait DebugEncoder {
+    fn push_debug_group(&self, name: &str);
+    fn pop_debug_group(&self);
+    fn insert_marker(&self, name: &str);
+
+    /// Opens a debug group that is closed when the returned guard is dropped.
+    fn debug_group(&self, name: &str) -> DebugGroup<'_, Self>
+    where
+        Self: Sized,
+    {
+        self.push_debug_group(name);
+        DebugGroup { encoder: self }
+    }
+}
+
+/// Guard returned by [`DebugEncoder::debug_group`], dereferences to the encoder.
+pub struct DebugGroup<'a, E: DebugEncoder> {
+    encoder: &'a E,
+}
+
+impl<E: DebugEncoder> Deref for DebugGroup<'_, E> {
+    type Target = E;
+
+    fn deref(&self) -> &Self::Target {
+        self.encoder
+    }
+}
+
+impl<E: DebugEncoder> Drop for DebugGroup<'_, E> {
+    fn drop(&mut self) {
+        self.encoder.pop_debug_group();
+    }
+}
+
+/// Records draws into a render pass. The viewport and scissor start out
+/// covering the first attachment of the pass, binding a vertex or index view
+/// transitions its buffer.
+pub trait RenderEncoder: DebugEncoder {
+    type Buffer;
+    type BufferView;
+    type Pipeline;
+    type Bundle;
+
+    fn set_viewport(&self, viewport: Viewport);
+    fn set_scissor(&self, scissor: Scissor);
+    fn set_stencil_reference(&self, reference: u32);
+    fn set_blend_constants(&self, constants: [f32; 4]);
+
+    fn set_pipeline(&self, pipeline: &Self::Pipeline);
+    fn set_vertex_buffer(&self, slot: u32, view: &Self::BufferView);
+    fn set_index_buffer(&self, view: &Self::BufferView);
+
+    fn draw(&self, vertices: Range<u32>) {
+        self.draw_instanced(vertices, 0..1);
+    }
+
+    fn draw_instanced(&self, vertices: Range<u32>, instances: Range<u32>);
 
-pub trait DynRenderEncoder: RenderEncoder {}
+    fn draw_indexed(&self, indices: Range<u32>, base_vertex: i32) {
+        self.draw_indexed_instanced(indices, base_vertex, 0..1);
+    }
 
-pub trait ComputeEncoder {}
+    fn draw_indexed_instanced(&self, indices: Range<u32>, base_vertex: i32, instances: Range<u32>);
 
-pub trait DynComputeEncoder: ComputeEncoder {}
+    /// Issues `draw_count` draws with [`DrawIndirectArgs`] read from `args` at `offset`.
+    ///
+    /// [`DrawIndirectArgs`]: super::indirect::DrawIndirectArgs
+    fn draw_indirect(
+        &self,
+        args: &Self::Buffer,
+        offset: usize,
+        draw_count: u32,
+    ) -> Result<(), IndirectError>;
+
+    /// Indexed counterpart of [`RenderEncoder::draw_indirect`], reading
+    /// [`DrawIndexedIndirectArgs`].
+    ///
+    /// [`DrawIndexedIndirectArgs`]: super::indirect::DrawIndexedIndirectArgs
+    fn draw_indexed_indirect(
+        &self,
+        args: &Self::Buffer,
+        offset: usize,
+        draw_count: u32,
+    ) -> Result<(), IndirectError>;
+
+    /// Like [`RenderEncoder::draw_indirect`], but the number of draws is the
+    /// `u32` at `count_offset` in `count`, clamped to `max_draws`.
+    fn draw_indirect_count(
+        &self,
+        args: &Self::Buffer,
+        offset: usize,
+        count: &Self::Buffer,
+        count_offset: usize,
+        max_draws: u32,
+    ) -> Result<(), IndirectError>;
+
+    fn draw_indexed_indirect_count(
+        &self,
+        args: &Self::Buffer,
+        offset: usize,
+        count: &Self::Buffer,
+        count_offset: usize,
+        max_draws: u32,
+    ) -> Result<(), IndirectError>;
+
+    /// Replays the draws of `bundle`, which must have been recorded for the
+    /// attachment formats of this pass.
+    fn execute_bundle(&self, bundle: &Self::Bundle) -> Result<(), BundleError>;
+}
 
-pub trait TransferEncoder {
+pub trait DynRenderEncoder: RenderEncoder {
+    fn set_pipeline(&self, pipeline: RenderHandle<RenderPipeline>);
+    fn set_vertex_buffer(&self, slot: u32, view: RenderHandle<BufferView>);
+    fn set_index_buffer(&self, view: RenderHandle<BufferView>);
+
+    fn draw_indirect(
+        &self,
+        args: RenderHandle<Buffer>,
+        offset: usize,
+        draw_count: u32,
+    ) -> Result<(), IndirectError>;
+
+    fn draw_indexed_indirect(
+        &self,
+        args: RenderHandle<Buffer>,
+        offset: usize,
+        draw_count: u32,
+    ) -> Result<(), IndirectError>;
+
+    fn draw_indirect_count(
+        &self,
+        args: RenderHandle<Buffer>,
+        offset: usize,
+        count: RenderHandle<Buffer>,
+        count_offset: usize,
+        max_draws: u32,
+    ) -> Result<(), IndirectError>;
+
+    fn draw_indexed_indirect_count(
+        &self,
+        args: RenderHandle<Buffer>,
+        offset: usize,
+        count: RenderHandle<Buffer>,
+        count_offset: usize,
+        max_draws: u32,
+    ) -> Result<(), IndirectError>;
+}
+
+/// Records dispatches. Binding slots are root parameter indices of the
+/// pipeline layout, binding a view transitions its buffer for the view type.
+pub trait ComputeEncoder: DebugEncoder {
+    type Buffer;
+    type BufferView;
+    type Pipeline;
+
+    fn set_pipeline(&self, pipeline: &Self::Pipeline);
+    fn set_buffer_view(&self, slot: u32, view: &Self::BufferView);
+    fn set_constants(&self, slot: u32, constants: &[u32]);
+
+    fn dispatch(&self, x: u32, y: u32, z: u32);
+    /// Reads [`DispatchIndirectArgs`] from `buffer` at `offset`.
+    ///
+    /// [`DispatchIndirectArgs`]: super::indirect::DispatchIndirectArgs
+    fn dispatch_indirect(&self, buffer: &Self::Buffer, offset: usize) -> Result<(), IndirectError>;
+}
+
+pub trait DynComputeEncoder: ComputeEncoder {
+    fn set_pipeline(&self, pipeline: RenderHandle<ComputePipeline>);
+    fn set_buffer_view(&self, slot: u32, view: RenderHandle<BufferView>);
+    fn dispatch_indirect(
+        &self,
+        buffer: RenderHandle<Buffer>,
+        offset: usize,
+    ) -> Result<(), IndirectError>;
+}
+
+/// Copies, clears and fills. Operations the backend can not record for the
+/// given resources or queue return an error instead of being dropped.
+pub trait TransferEncoder: DebugEncoder {
     type Buffer;
     type Texture;
 
-    fn copy_buffer_to_buffer(&self, dst: &Self::Buffer, src: &Self::Buffer);
-    fn copy_texture_to_texture(&self, dst: &Self::Texture, src: &Self::Texture);
-    fn upload_to_texture(&self, dst: &Self::Texture, src: &Self::Buffer, data: &[u8]);
+    fn copy_buffer_to_buffer(
+        &self,
+        dst: &Self::Buffer,
+        src: &Self::Buffer,
+    ) -> Result<(), TransferError>;
+    fn copy_texture_to_texture(
+        &self,
+        dst: &Self::Texture,
+        src: &Self::Texture,
+    ) -> Result<(), TransferError>;
+    fn upload_to_texture(
+        &self,
+        dst: &Self::Texture,
+        src: &Self::Buffer,
+        data: &[u8],
+    ) -> Result<(), TransferError>;
+
+    fn copy_buffer_region(
+        &self,
+        dst: &Self::Buffer,
+        dst_offset: usize,
+        src: &Self::Buffer,
+        src_offset: usize,
+        size: usize,
+    ) -> Result<(), TransferError>;
+
+    fn copy_buffer_to_texture(
+        &self,
+        dst: &Self::Texture,
+        region: TextureRegion,
+        src: &Self::Buffer,
+        layout: BufferTextureLayout,
+    ) -> Result<(), TransferError>;
+
+    fn copy_texture_to_buffer(
+        &self,
+        dst: &Self::Buffer,
+        layout: BufferTextureLayout,
+        src: &Self::Texture,
+        region: TextureRegion,
+    ) -> Result<(), TransferError>;
+
+    /// Both regions must have the same extent.
+    fn copy_texture_region(
+        &self,
+        dst: &Self::Texture,
+        dst_region: TextureRegion,
+        src: &Self::Texture,
+        src_region: TextureRegion,
+    ) -> Result<(), TransferError>;
+
+    /// Fills `range` with a repeated 32 bit value, the range must be 4 byte aligned.
+    fn fill_buffer(
+        &self,
+        dst: &Self::Buffer,
+        range: Range<usize>,
+        value: u32,
+    ) -> Result<(), TransferError>;
+
+    fn clear_buffer(&self, dst: &Self::Buffer, range: Range<usize>) -> Result<(), TransferError> {
+        self.fill_buffer(dst, range, 0)
+    }
+
+    /// Clears render target, depth and storage textures.
+    fn clear_texture(
+        &self,
+        dst: &Self::Texture,
+        range: SubresourceRange,
+        value: ClearValue,
+    ) -> Result<(), TransferError>;
 }
 
 pub trait DynTransferEncoder: TransferEncoder {
-    fn copy_buffer_to_buffer(&self, dst: RenderHandle<Buffer>, src: RenderHandle<Buffer>);
+    fn copy_buffer_to_buffer(
+        &self,
+        dst: RenderHandle<Buffer>,
+        src: RenderHandle<Buffer>,
+    ) -> Result<(), TransferError>;
+
+    fn copy_texture_to_texture(
+        &self,
+        dst: RenderHandle<Texture>,
+        src: RenderHandle<Texture>,
+    ) -> Result<(), TransferError>;
+
+    fn upload_to_texture(
+        &self,
+        dst: RenderHandle<Texture>,
+        src: RenderHandle<Buffer>,
+        data: &[u8],
+    ) -> Result<(), TransferError>;
+
+    fn copy_buffer_region(
+        &self,
+        dst: RenderHandle<Buffer>,
+        dst_offset: usize,
+        src: RenderHandle<Buffer>,
+        src_offset: usize,
+        size: usize,
+    ) -> Result<(), TransferError>;
+
+    fn copy_buffer_to_texture(
+        &self,
+        dst: RenderHandle<Texture>,
+        region: TextureRegion,
+        src: RenderHandle<Buffer>,
+        layout: BufferTextureLayout,
+    ) -> Result<(), TransferError>;
+
+    fn copy_texture_to_buffer(
+        &self,
+        dst: RenderHandle<Buffer>,
+        layout: BufferTextureLayout,
+        src: RenderHandle<Texture>,
+        region: TextureRegion,
+    ) -> Result<(), TransferError>;
+
+    fn copy_texture_region(
+        &self,
+        dst: RenderHandle<Texture>,
+        dst_region: TextureRegion,
+        src: RenderHandle<Texture>,
+        src_region: TextureRegion,
+    ) -> Result<(), TransferError>;
+
+    fn fill_buffer(
+        &self,
+        dst: RenderHandle<Buffer>,
+        range: Range<usize>,
+        value: u32,
+    ) -> Result<(), TransferError>;
 
-    fn copy_texture_to_texture(&self, dst: RenderHandle<Texture>, src: RenderHandle<Texture>);
+    fn clear_buffer(
+        &self,
+        dst: RenderHandle<Buffer>,
+        range: Range<usize>,
+    ) -> Result<(), TransferError> {
+        <Self as DynTransferEncoder>::fill_buffer(self, dst, range, 0)
+    }
 
-    fn upload_to_texture(&self, dst: RenderHandle<Texture>, src: RenderHandle<Buffer>, data: &[u8]);
+    fn clear_texture(
+        &self,
+        dst: RenderHandle<Texture>,
+        range: SubresourceRange,
+        value: ClearValue,
+    ) -> Result<(), TransferError>;
 }
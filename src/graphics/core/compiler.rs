@@ -0,0 +1,531 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fmt,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use naga::{
+    back::hlsl,
+    front::{spv, wgsl},
+    valid::{Capabilities, ValidationFlags, Validator},
+    Module,
+};
+use parking_lot::Mutex;
+use tracing::warn;
+
+use super::shader::{EntryPoint, ShaderLanguage, ShaderModuleDesc, ShaderSource, ShaderStage};
+
+const MODULE_MAGIC: [u8; 8] = *b"ASPXSHD\0";
+/// Bumped whenever the output of a compile changes for the same source, which
+/// also invalidates every cached module.
+const MODULE_VERSION: u32 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SourceLocation {
+    /// 1-based.
+    pub line: u32,
+    /// 1-based, in bytes.
+    pub column: u32,
+}
+
+/// Error pointing into the source of a module.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    /// Module name or path.
+    pub file: String,
+    pub location: Option<SourceLocation>,
+    pub message: String,
+    /// The message with the offending source lines, to print as is.
+    pub rendered: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.location {
+            Some(SourceLocation { line, column }) => {
+                write!(f, "{}:{}:{}: {}", self.file, line, column, self.message)
+            }
+            None => write!(f, "{}: {}", self.file, self.message),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CompileError {
+    Io(io::Error),
+    /// Cached or built module that can not be read back.
+    Malformed(String),
+    UnsupportedSource(ShaderLanguage),
+    UnsupportedTarget(ShaderLanguage),
+    Parse(Diagnostic),
+    Validation(Diagnostic),
+    MissingEntryPoint(EntryPoint),
+    Output(String),
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompileError::Io(err) => write!(f, "shader io failed: {}", err),
+            CompileError::Malformed(reason) => write!(f, "shader module is malformed: {}", reason),
+            CompileError::UnsupportedSource(language) => {
+                write!(f, "{:?} shaders can not be cross-compiled", language)
+            }
+            CompileError::UnsupportedTarget(language) => {
+                write!(f, "shaders can not be compiled to {:?}", language)
+            }
+            CompileError::Parse(diagnostic) => write!(f, "parsing failed: {}", diagnostic),
+            CompileError::Validation(diagnostic) => {
+                write!(f, "validation failed: {}", diagnostic)
+            }
+            CompileError::MissingEntryPoint(entry) => write!(
+                f,
+                "module has no {:?} entry point {}",
+                entry.stage, entry.name
+            ),
+            CompileError::Output(reason) => write!(f, "writing the output failed: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+impl From<io::Error> for CompileError {
+    fn from(err: io::Error) -> Self {
+        CompileError::Io(err)
+    }
+}
+
+impl From<bincode::Error> for CompileError {
+    fn from(err: bincode::Error) -> Self {
+        match *err {
+            bincode::ErrorKind::Io(err) => CompileError::Io(err),
+            err => CompileError::Malformed(err.to_string()),
+        }
+    }
+}
+
+/// Cross-compiles WGSL and SPIR-V modules with naga into a form a backend accepts,
+/// see `RenderDeviceInfo::shader_languages`. Only HLSL can be emitted yet.
+///
+/// Outputs are cached by a hash of their source, target and entry points, in
+/// memory and in `cache_dir` if set, so unchanged shaders are compiled once.
+#[derive(Debug, Default)]
+pub struct ShaderCompiler {
+    cache: Mutex<HashMap<u64, ShaderModuleDesc>>,
+    cache_dir: Option<PathBuf>,
+}
+
+impl ShaderCompiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keeps compiled modules in `dir` across runs, created on first write.
+    pub fn with_cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.cache_dir = Some(dir.into());
+        self
+    }
+
+    /// Compiles `desc` to `target`, a module already in `target` is returned as is.
+    /// Entry points keep their names unless they clash with a keyword of `target`.
+    pub fn compile(
+        &self,
+        desc: &ShaderModuleDesc,
+        target: ShaderLanguage,
+    ) -> Result<ShaderModuleDesc, CompileError> {
+        let file = desc.name.as_deref().unwrap_or("shader");
+        self.compile_named(desc, target, file)
+    }
+
+    /// Compiles the file at `path`, its extension picks the language: `wgsl`, `spv`,
+    /// `hlsl` or `dxil`.
+    pub fn compile_file(
+        &self,
+        path: impl AsRef<Path>,
+        entry_points: Vec<EntryPoint>,
+        target: ShaderLanguage,
+    ) -> Result<ShaderModuleDesc, CompileError> {
+        let path = path.as_ref();
+        let source = match path.extension().and_then(|e| e.to_str()) {
+            Some("wgsl") => ShaderSource::Wgsl(fs::read_to_string(path)?.into()),
+            Some("hlsl") => ShaderSource::Hlsl(fs::read_to_string(path)?.into()),
+            Some("spv") => ShaderSource::SpirV(fs::read(path)?.into()),
+            Some("dxil") => ShaderSource::Dxil(fs::read(path)?.into()),
+            _ => {
+                return Err(CompileError::Io(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("unknown shader extension of {}", path.display()),
+                )))
+            }
+        };
+
+        let desc = ShaderModuleDesc {
+            name: path
+                .file_stem()
+                .map(|s| Cow::Owned(s.to_string_lossy().into_owned())),
+            source,
+            entry_points,
+        };
+
+        self.compile_named(&desc, target, &path.display().to_string())
+    }
+
+    fn compile_named(
+        &self,
+        desc: &ShaderModuleDesc,
+        target: ShaderLanguage,
+        file: &str,
+    ) -> Result<ShaderModuleDesc, CompileError> {
+        if desc.source.language() == target {
+            return Ok(desc.clone());
+        }
+
+        let hash = source_hash(desc, target);
+        if let Some(module) = self.cache.lock().get(&hash) {
+            return Ok(module.clone());
+        }
+
+        let cached = self
+            .cache_dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{:016x}.bin", hash)));
+        let module = match cached.as_ref().filter(|path| path.exists()) {
+            Some(path) => match load_module(path) {
+                Ok(module) => Some(module),
+                Err(err) => {
+                    warn!("Ignoring cached shader {}: {}", path.display(), err);
+                    None
+                }
+            },
+            None => None,
+        };
+
+        let module = match module {
+            Some(module) => module,
+            None => {
+                let module = cross_compile(desc, target, file)?;
+                if let Some(path) = &cached {
+                    if let Some(dir) = path.parent() {
+                        fs::create_dir_all(dir)?;
+                    }
+                    save_module(&module, path)?;
+                }
+                module
+            }
+        };
+
+        self.cache.lock().insert(hash, module.clone());
+        Ok(module)
+    }
+}
+
+pub fn write_module(module: &ShaderModuleDesc, mut writer: impl Write) -> Result<(), CompileError> {
+    writer.write_all(&MODULE_MAGIC)?;
+    writer.write_all(&MODULE_VERSION.to_le_bytes())?;
+    bincode::serialize_into(&mut writer, module)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+pub fn read_module(mut reader: impl Read) -> Result<ShaderModuleDesc, CompileError> {
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+    if magic != MODULE_MAGIC {
+        return Err(CompileError::Malformed("not a shader module".to_string()));
+    }
+
+    let mut version = [0; 4];
+    reader.read_exact(&mut version)?;
+    let found = u32::from_le_bytes(version);
+    if found != MODULE_VERSION {
+        return Err(CompileError::Malformed(format!(
+            "module version {} is not supported, expected {}",
+            found, MODULE_VERSION
+        )));
+    }
+
+    Ok(bincode::deserialize_from(reader)?)
+}
+
+fn save_module(module: &ShaderModuleDesc, path: &Path) -> Result<(), CompileError> {
+    write_module(module, BufWriter::new(File::create(path)?))
+}
+
+fn load_module(path: &Path) -> Result<ShaderModuleDesc, CompileError> {
+    read_module(BufReader::new(File::open(path)?))
+}
+
+/// FNV-1a over everything the output depends on. Stable across runs and
+/// toolchains, unlike `DefaultHasher`, so it can name files on disk.
+fn source_hash(desc: &ShaderModuleDesc, target: ShaderLanguage) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    let mut write = |bytes: &[u8]| {
+        for &byte in bytes {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    };
+
+    let source: &[u8] = match &desc.source {
+        ShaderSource::Hlsl(source) | ShaderSource::Wgsl(source) => source.as_bytes(),
+        ShaderSource::Dxil(bytes) | ShaderSource::SpirV(bytes) => bytes,
+    };
+
+    write(&MODULE_VERSION.to_le_bytes());
+    write(&[desc.source.language() as u8, target as u8]);
+    write(&(source.len() as u64).to_le_bytes());
+    write(source);
+    for entry in &desc.entry_points {
+        write(&[entry.stage as u8]);
+        write(entry.name.as_bytes());
+        write(&[0]);
+    }
+
+    hash
+}
+
+fn cross_compile(
+    desc: &ShaderModuleDesc,
+    target: ShaderLanguage,
+    file: &str,
+) -> Result<ShaderModuleDesc, CompileError> {
+    if target != ShaderLanguage::Hlsl {
+        return Err(CompileError::UnsupportedTarget(target));
+    }
+
+    let (module, text) = match &desc.source {
+        ShaderSource::Wgsl(source) => {
+            let module = wgsl::parse_str(source).map_err(|err| {
+                CompileError::Parse(Diagnostic {
+                    file: file.to_string(),
+                    location: err.location(source).map(location),
+                    message: err.message().to_string(),
+                    rendered: err.emit_to_string_with_path(source, file),
+                })
+            })?;
+            (module, Some(source.as_ref()))
+        }
+        ShaderSource::SpirV(bytes) => {
+            let module = spv::parse_u8_slice(bytes, &spv::Options::default()).map_err(|err| {
+                CompileError::Parse(Diagnostic {
+                    file: file.to_string(),
+                    location: None,
+                    message: err.to_string(),
+                    rendered: err.to_string(),
+                })
+            })?;
+            (module, None)
+        }
+        ShaderSource::Hlsl(_) | ShaderSource::Dxil(_) => {
+            return Err(CompileError::UnsupportedSource(desc.source.language()))
+        }
+    };
+
+    let info = Validator::new(ValidationFlags::all(), Capabilities::default())
+        .validate(&module)
+        .map_err(|err| {
+            // SPIR-V has no text to point into, only the error chain is reported.
+            let (location, rendered) = match text {
+                Some(source) => (
+                    err.location(source).map(location),
+                    err.emit_to_string_with_path(source, file),
+                ),
+                None => (None, error_chain(err.as_inner())),
+            };
+
+            CompileError::Validation(Diagnostic {
+                file: file.to_string(),
+                location,
+                message: err.as_inner().to_string(),
+                rendered,
+            })
+        })?;
+
+    let entry_points = find_entry_points(&module, &desc.entry_points)?;
+
+    let mut output = String::new();
+    let options = hlsl::Options::default();
+    let reflection = hlsl::Writer::new(&mut output, &options, &Default::default())
+        .write(&module, &info, None)
+        .map_err(|err| CompileError::Output(err.to_string()))?;
+
+    let entry_points = entry_points
+        .into_iter()
+        .map(
+            |(index, entry)| match &reflection.entry_point_names[index] {
+                Ok(name) => Ok(EntryPoint {
+                    name: name.clone().into(),
+                    stage: entry.stage,
+                }),
+                Err(err) => Err(CompileError::Output(format!("{}: {}", entry.name, err))),
+            },
+        )
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(ShaderModuleDesc {
+        name: desc.name.clone(),
+        source: ShaderSource::Hlsl(output.into()),
+        entry_points,
+    })
+}
+
+/// Index of every requested entry point in `module`.
+fn find_entry_points<'a>(
+    module: &Module,
+    entry_points: &'a [EntryPoint],
+) -> Result<Vec<(usize, &'a EntryPoint)>, CompileError> {
+    entry_points
+        .iter()
+        .map(|entry| {
+            let stage = match entry.stage {
+                ShaderStage::Vertex => naga::ShaderStage::Vertex,
+                ShaderStage::Pixel => naga::ShaderStage::Fragment,
+                ShaderStage::Compute => naga::ShaderStage::Compute,
+            };
+
+            module
+                .entry_points
+                .iter()
+                .position(|e| e.stage == stage && e.name == entry.name)
+                .map(|index| (index, entry))
+                .ok_or_else(|| CompileError::MissingEntryPoint(entry.clone()))
+        })
+        .collect()
+}
+
+fn location(location: naga::SourceLocation) -> SourceLocation {
+    SourceLocation {
+        line: location.line_number,
+        column: location.line_position,
+    }
+}
+
+fn error_chain(err: &dyn std::error::Error) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(err) = source {
+        message.push_str(": ");
+        message.push_str(&err.to_string());
+        source = err.source();
+    }
+
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHADER: &str = "@group(0) @binding(0)
+var<storage, read_write> values: array<u32>;
+
+@compute @workgroup_size(64)
+fn double(@builtin(global_invocation_id) id: vec3<u32>) {
+    values[id.x] = values[id.x] * 2u;
+}
+";
+
+    fn wgsl(source: &'static str) -> ShaderModuleDesc {
+        ShaderModuleDesc {
+            name: Some("double.wgsl".into()),
+            source: ShaderSource::Wgsl(source.into()),
+            entry_points: vec![EntryPoint {
+                name: "double".into(),
+                stage: ShaderStage::Compute,
+            }],
+        }
+    }
+
+    #[test]
+    fn wgsl_compiles_to_hlsl_and_reports_source_locations() {
+        let compiler = ShaderCompiler::new();
+
+        let module = compiler
+            .compile(&wgsl(SHADER), ShaderLanguage::Hlsl)
+            .unwrap();
+        let ShaderSource::Hlsl(hlsl) = &module.source else {
+            panic!("expected HLSL, got {:?}", module.source.language());
+        };
+        assert!(hlsl.contains("[numthreads(64, 1, 1)]"));
+        // `double` is an HLSL keyword, the entry point is renamed.
+        assert_eq!(module.entry_points[0].name, "double_");
+        assert!(hlsl.contains("void double_("));
+
+        let broken = "@compute @workgroup_size(1)\nfn main() {\n    let x: u32 = 1.0;\n}\n";
+        let err = compiler
+            .compile(&wgsl(broken), ShaderLanguage::Hlsl)
+            .unwrap_err();
+        let CompileError::Parse(diagnostic) = &err else {
+            panic!("expected a parse error, got {}", err);
+        };
+        assert_eq!(
+            diagnostic.location,
+            Some(SourceLocation { line: 3, column: 9 })
+        );
+        assert!(err
+            .to_string()
+            .starts_with("parsing failed: double.wgsl:3:9: "));
+        assert!(diagnostic.rendered.contains("let x: u32 = 1.0;"));
+
+        let missing = ShaderModuleDesc {
+            entry_points: vec![EntryPoint {
+                name: "double".into(),
+                stage: ShaderStage::Vertex,
+            }],
+            ..wgsl(SHADER)
+        };
+        assert!(matches!(
+            compiler.compile(&missing, ShaderLanguage::Hlsl),
+            Err(CompileError::MissingEntryPoint(_))
+        ));
+        let spirv = ShaderModuleDesc {
+            source: ShaderSource::SpirV(vec![0; 8].into()),
+            ..wgsl(SHADER)
+        };
+        assert!(matches!(
+            compiler.compile(&spirv, ShaderLanguage::Hlsl),
+            Err(CompileError::Parse(Diagnostic { location: None, .. }))
+        ));
+        assert!(matches!(
+            compiler.compile(&wgsl(SHADER), ShaderLanguage::SpirV),
+            Err(CompileError::UnsupportedTarget(ShaderLanguage::SpirV))
+        ));
+    }
+
+    #[test]
+    fn outputs_are_cached_on_disk_by_source_hash() {
+        let dir = std::env::temp_dir().join(format!("asphyx-shaders-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let compiled = ShaderCompiler::new()
+            .with_cache_dir(&dir)
+            .compile(&wgsl(SHADER), ShaderLanguage::Hlsl)
+            .unwrap();
+        let path = dir.join(format!(
+            "{:016x}.bin",
+            source_hash(&wgsl(SHADER), ShaderLanguage::Hlsl)
+        ));
+
+        // A fresh compiler reads the cached file instead of compiling again.
+        let mut cached = compiled.clone();
+        cached.source = ShaderSource::Hlsl("// cached".into());
+        save_module(&cached, &path).unwrap();
+
+        let module = ShaderCompiler::new()
+            .with_cache_dir(&dir)
+            .compile(&wgsl(SHADER), ShaderLanguage::Hlsl)
+            .unwrap();
+        assert!(matches!(module.source, ShaderSource::Hlsl(ref s) if s == "// cached"));
+        assert_ne!(
+            source_hash(&wgsl(SHADER), ShaderLanguage::Hlsl),
+            source_hash(&wgsl(SHADER.trim_end()), ShaderLanguage::Hlsl)
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,189 @@
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Weak,
+    },
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+use parking_lot::{Condvar, Mutex};
+
+use super::commands::{FenceValue, SyncPoint};
+
+/// How long the completion thread waits on the fence before picking up new
+/// waiters, and before checking if its timeline is gone.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Fence of a queue as seen by the CPU.
+pub trait Timeline: fmt::Debug + Send + Sync + 'static {
+    fn completed(&self) -> FenceValue;
+
+    /// Blocks until `value` is reached or `timeout` elapses, returns whether it was reached.
+    fn wait(&self, value: FenceValue, timeout: Duration) -> bool;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WaitTimeout {
+    pub sync_point: SyncPoint,
+    pub completed: FenceValue,
+}
+
+impl fmt::Display for WaitTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "timed out waiting for {:?} to reach {}, it is at {}",
+            self.sync_point.ty(),
+            self.sync_point.value(),
+            self.completed
+        )
+    }
+}
+
+impl std::error::Error for WaitTimeout {}
+
+/// Timeline of a queue shared with the futures created on it. Pending futures
+/// are woken by a completion thread, spawned on first use and stopped once the
+/// timeline is dropped.
+#[derive(Debug)]
+pub struct GpuTimeline {
+    timeline: Arc<dyn Timeline>,
+    waiters: Mutex<Vec<(FenceValue, Waker)>>,
+    registered: Condvar,
+    thread: AtomicBool,
+}
+
+impl GpuTimeline {
+    pub fn new(timeline: Arc<dyn Timeline>) -> Arc<Self> {
+        Arc::new(Self {
+            timeline,
+            waiters: Default::default(),
+            registered: Default::default(),
+            thread: Default::default(),
+        })
+    }
+
+    pub fn completed(&self) -> FenceValue {
+        self.timeline.completed()
+    }
+
+    /// `sync_point` must be on this timeline, devices check it before.
+    pub fn future(self: &Arc<Self>, sync_point: SyncPoint) -> GpuFuture {
+        GpuFuture {
+            timeline: Arc::clone(self),
+            sync_point,
+        }
+    }
+
+    fn register(self: &Arc<Self>, value: FenceValue, waker: &Waker) {
+        {
+            let mut waiters = self.waiters.lock();
+            if !waiters
+                .iter()
+                .any(|(v, w)| *v == value && w.will_wake(waker))
+            {
+                waiters.push((value, waker.clone()));
+            }
+        }
+        self.registered.notify_one();
+
+        if !self.thread.swap(true, Ordering::AcqRel) {
+            let timeline = Arc::downgrade(self);
+            std::thread::Builder::new()
+                .name("gpu-completion".to_owned())
+                .spawn(move || complete(timeline))
+                .expect("failed to spawn completion thread");
+        }
+    }
+}
+
+fn complete(timeline: Weak<GpuTimeline>) {
+    while let Some(timeline) = timeline.upgrade() {
+        let next = timeline.waiters.lock().iter().map(|(v, _)| *v).min();
+        match next {
+            Some(value) => {
+                timeline.timeline.wait(value, POLL_INTERVAL);
+            }
+            None => {
+                let mut waiters = timeline.waiters.lock();
+                if waiters.is_empty() {
+                    timeline.registered.wait_for(&mut waiters, POLL_INTERVAL);
+                }
+            }
+        }
+
+        let completed = timeline.completed();
+        let mut ready = vec![];
+        timeline.waiters.lock().retain(|(value, waker)| {
+            if *value > completed {
+                return true;
+            }
+
+            ready.push(waker.clone());
+            false
+        });
+
+        for waker in ready {
+            waker.wake();
+        }
+    }
+}
+
+/// Sync point of a queue, resolved once the queue reaches it.
+///
+/// Awaiting it does not block, the completion thread of the queue wakes the
+/// task. Nothing drives mock queues in the background, their futures resolve
+/// once something flushes the queue.
+#[derive(Clone, Debug)]
+pub struct GpuFuture {
+    timeline: Arc<GpuTimeline>,
+    sync_point: SyncPoint,
+}
+
+impl GpuFuture {
+    pub fn sync_point(&self) -> SyncPoint {
+        self.sync_point
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.timeline.completed() >= self.sync_point.value()
+    }
+
+    pub fn wait_timeout(&self, timeout: Duration) -> Result<(), WaitTimeout> {
+        if self.is_complete()
+            || self
+                .timeline
+                .timeline
+                .wait(self.sync_point.value(), timeout)
+        {
+            return Ok(());
+        }
+
+        Err(WaitTimeout {
+            sync_point: self.sync_point,
+            completed: self.timeline.completed(),
+        })
+    }
+}
+
+impl Future for GpuFuture {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.is_complete() {
+            return Poll::Ready(());
+        }
+
+        self.timeline.register(self.sync_point.value(), cx.waker());
+
+        // The queue may have passed the value before the waker was registered.
+        match self.is_complete() {
+            true => Poll::Ready(()),
+            false => Poll::Pending,
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use std::{hash::Hash, marker::PhantomData, mem::MaybeUninit};
 
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
 use static_assertions::const_assert_eq;
 
 use crate::utils::non_max::NonMax;
@@ -46,6 +47,25 @@ impl<T> std::fmt::Debug for RenderHandle<T> {
     }
 }
 
+impl<T> Serialize for RenderHandle<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (self.index, self.gen()).serialize(serializer)
+    }
+}
+
+impl<'de, T> Deserialize<'de> for RenderHandle<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (index, gen) = <(u32, u32)>::deserialize(deserializer)?;
+        let gen = NonMax::new(gen).ok_or_else(|| D::Error::custom("wrong gen"))?;
+
+        Ok(Self {
+            index,
+            gen,
+            _marker: PhantomData,
+        })
+    }
+}
+
 impl<T> RenderHandle<T> {
     pub fn new(index: u32, gen: u32) -> Self {
         Self {
@@ -156,6 +176,14 @@ impl<U, W> SparseArray<U, W> {
         }
     }
 
+    /// Stored values, in no particular order.
+    pub fn values(&self) -> impl Iterator<Item = &W> {
+        self.sparse
+            .iter()
+            .flatten()
+            .map(|h| unsafe { self.dense[h.index as usize].assume_init_ref() })
+    }
+
     pub fn get(&self, handle: RenderHandle<U>) -> Option<&W> {
         self.sparse.get(handle.index as usize).and_then(|h| {
             if let Some(h) = h {
@@ -203,8 +231,9 @@ impl<U, W> SparseArray<U, W> {
         self.dense_to_sparse.swap_remove(dense_pos);
         self.sparse[handle.index as usize] = None;
 
-        if self.dense_to_sparse.len() > 0 {
-            let Some(Some(handle)) = self.sparse.get_mut(self.dense_to_sparse[dense_pos]) else {
+        // The last element moved into the removed slot, unless it was the removed one.
+        if let Some(&moved) = self.dense_to_sparse.get(dense_pos) {
+            let Some(Some(handle)) = self.sparse.get_mut(moved) else {
                 return;
             };
 
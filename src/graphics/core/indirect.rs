@@ -0,0 +1,154 @@
+use std::fmt;
+
+use bytemuck::{Pod, Zeroable};
+
+use super::resource::{BufferDesc, BufferUsages};
+
+/// Arguments of a single indirect draw, laid out like the HLSL struct
+/// `uint4(vertex_count, instance_count, first_vertex, first_instance)`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DrawIndirectArgs {
+    pub vertex_count: u32,
+    pub instance_count: u32,
+    pub first_vertex: u32,
+    pub first_instance: u32,
+}
+
+/// Arguments of a single indirect indexed draw, five tightly packed 32 bit values.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DrawIndexedIndirectArgs {
+    pub index_count: u32,
+    pub instance_count: u32,
+    pub first_index: u32,
+    pub base_vertex: i32,
+    pub first_instance: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DispatchIndirectArgs {
+    pub x: u32,
+    pub y: u32,
+    pub z: u32,
+}
+
+// SAFETY: plain `#[repr(C)]` structs of 32 bit integers without padding.
+unsafe impl Zeroable for DrawIndirectArgs {}
+unsafe impl Pod for DrawIndirectArgs {}
+unsafe impl Zeroable for DrawIndexedIndirectArgs {}
+unsafe impl Pod for DrawIndexedIndirectArgs {}
+unsafe impl Zeroable for DispatchIndirectArgs {}
+unsafe impl Pod for DispatchIndirectArgs {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndirectError {
+    /// The buffer was not created with [`BufferUsages::Indirect`].
+    MissingUsage,
+    /// Offsets into argument and count buffers must be 4 byte aligned.
+    Misaligned {
+        offset: usize,
+    },
+    OutOfBounds {
+        end: usize,
+        size: usize,
+    },
+    /// A buffer handle passed to a dynamic encoder is not bound.
+    DeadHandle,
+}
+
+impl fmt::Display for IndirectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndirectError::MissingUsage => write!(f, "buffer is missing the indirect usage"),
+            IndirectError::Misaligned { offset } => {
+                write!(f, "indirect offset {} is not 4 byte aligned", offset)
+            }
+            IndirectError::OutOfBounds { end, size } => {
+                write!(
+                    f,
+                    "indirect arguments end at {} but buffer size is {}",
+                    end, size
+                )
+            }
+            IndirectError::DeadHandle => write!(f, "indirect buffer handle is not bound"),
+        }
+    }
+}
+
+impl std::error::Error for IndirectError {}
+
+/// Checks that `count` arguments of type `T` starting at `offset` can be read from `buffer`.
+pub fn validate<T: Pod>(
+    buffer: &BufferDesc,
+    offset: usize,
+    count: u32,
+) -> Result<(), IndirectError> {
+    if !buffer.usage.contains(BufferUsages::Indirect) {
+        return Err(IndirectError::MissingUsage);
+    }
+
+    if !offset.is_multiple_of(4) {
+        return Err(IndirectError::Misaligned { offset });
+    }
+
+    let end = offset + size_of::<T>() * count as usize;
+    if end > buffer.size {
+        return Err(IndirectError::OutOfBounds {
+            end,
+            size: buffer.size,
+        });
+    }
+
+    Ok(())
+}
+
+/// Reads `count` arguments from tightly packed argument buffer contents.
+pub fn read_args<T: Pod>(data: &[u8], offset: usize, count: u32) -> Vec<T> {
+    data[offset..]
+        .chunks_exact(size_of::<T>())
+        .take(count as usize)
+        .map(bytemuck::pod_read_unaligned)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer(size: usize, usage: BufferUsages) -> BufferDesc {
+        BufferDesc {
+            name: None,
+            size,
+            stride: 0,
+            usage,
+        }
+    }
+
+    #[test]
+    fn argument_layouts_match_the_gpu() {
+        assert_eq!(size_of::<DrawIndirectArgs>(), 16);
+        assert_eq!(size_of::<DrawIndexedIndirectArgs>(), 20);
+        assert_eq!(size_of::<DispatchIndirectArgs>(), 12);
+    }
+
+    #[test]
+    fn validates_usage_alignment_and_bounds() {
+        let desc = buffer(40, BufferUsages::Indirect);
+
+        assert_eq!(
+            validate::<DrawIndexedIndirectArgs>(&buffer(40, BufferUsages::Storage), 0, 1),
+            Err(IndirectError::MissingUsage)
+        );
+        assert_eq!(
+            validate::<DrawIndirectArgs>(&desc, 2, 1),
+            Err(IndirectError::Misaligned { offset: 2 })
+        );
+        assert_eq!(
+            validate::<DrawIndexedIndirectArgs>(&desc, 4, 2),
+            Err(IndirectError::OutOfBounds { end: 44, size: 40 })
+        );
+        assert!(validate::<DrawIndexedIndirectArgs>(&desc, 0, 2).is_ok());
+    }
+}
@@ -0,0 +1,250 @@
+use std::sync::{
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+    Arc,
+};
+
+use tracing::info;
+
+use super::{backend::RenderDeviceId, resource::BufferUsages};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MemoryLocation {
+    /// Video memory, or the device-local part of system memory on UMA adapters.
+    Device,
+    Upload,
+    Readback,
+}
+
+impl MemoryLocation {
+    pub const ALL: [MemoryLocation; 3] = [
+        MemoryLocation::Device,
+        MemoryLocation::Upload,
+        MemoryLocation::Readback,
+    ];
+
+    pub fn for_buffer(usage: BufferUsages) -> Self {
+        if usage.intersects(BufferUsages::Copy | BufferUsages::Uniform) {
+            MemoryLocation::Upload
+        } else if usage.contains(BufferUsages::QueryResolve) {
+            MemoryLocation::Readback
+        } else {
+            MemoryLocation::Device
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MemoryCategory {
+    Buffer,
+    Texture,
+    Heap,
+    Descriptor,
+}
+
+impl MemoryCategory {
+    pub const ALL: [MemoryCategory; 4] = [
+        MemoryCategory::Buffer,
+        MemoryCategory::Texture,
+        MemoryCategory::Heap,
+        MemoryCategory::Descriptor,
+    ];
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryStats {
+    pub count: usize,
+    pub bytes: u64,
+}
+
+impl std::ops::Add for MemoryStats {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self {
+            count: self.count + rhs.count,
+            bytes: self.bytes + rhs.bytes,
+        }
+    }
+}
+
+/// Budget reported by the OS or driver for the device local segment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryBudget {
+    pub budget: u64,
+    pub usage: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryReport {
+    pub device: RenderDeviceId,
+    pub budget: Option<MemoryBudget>,
+    stats: [[MemoryStats; 4]; 3],
+}
+
+impl MemoryReport {
+    pub fn get(&self, location: MemoryLocation, category: MemoryCategory) -> MemoryStats {
+        self.stats[location as usize][category as usize]
+    }
+
+    pub fn location(&self, location: MemoryLocation) -> MemoryStats {
+        MemoryCategory::ALL
+            .into_iter()
+            .map(|c| self.get(location, c))
+            .fold(MemoryStats::default(), |acc, s| acc + s)
+    }
+
+    pub fn category(&self, category: MemoryCategory) -> MemoryStats {
+        MemoryLocation::ALL
+            .into_iter()
+            .map(|l| self.get(l, category))
+            .fold(MemoryStats::default(), |acc, s| acc + s)
+    }
+
+    pub fn total(&self) -> MemoryStats {
+        MemoryLocation::ALL
+            .into_iter()
+            .map(|l| self.location(l))
+            .fold(MemoryStats::default(), |acc, s| acc + s)
+    }
+
+    pub fn log(&self) {
+        let total = self.total();
+        match self.budget {
+            Some(budget) => info!(
+                "Device {} memory: {} bytes in {} allocations, budget {} of {} bytes used",
+                self.device, total.bytes, total.count, budget.usage, budget.budget
+            ),
+            None => info!(
+                "Device {} memory: {} bytes in {} allocations",
+                self.device, total.bytes, total.count
+            ),
+        }
+
+        for location in MemoryLocation::ALL {
+            for category in MemoryCategory::ALL {
+                let stats = self.get(location, category);
+                if stats.count > 0 {
+                    info!(
+                        "  {:?}/{:?}: {} bytes in {} allocations",
+                        location, category, stats.bytes, stats.count
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Per-device allocation counters shared by every resource of the device.
+#[derive(Debug, Default)]
+pub struct MemoryCounters {
+    counts: [[AtomicUsize; 4]; 3],
+    bytes: [[AtomicU64; 4]; 3],
+}
+
+impl MemoryCounters {
+    /// Accounts `size` bytes until the returned allocation is dropped.
+    pub fn allocate(
+        self: &Arc<Self>,
+        location: MemoryLocation,
+        category: MemoryCategory,
+        size: u64,
+    ) -> MemoryAllocation {
+        self.counts[location as usize][category as usize].fetch_add(1, Ordering::Relaxed);
+        self.bytes[location as usize][category as usize].fetch_add(size, Ordering::Relaxed);
+
+        MemoryAllocation {
+            counters: Arc::clone(self),
+            location,
+            category,
+            size,
+        }
+    }
+
+    pub fn report(&self, device: RenderDeviceId, budget: Option<MemoryBudget>) -> MemoryReport {
+        let mut stats = [[MemoryStats::default(); 4]; 3];
+
+        for location in MemoryLocation::ALL {
+            for category in MemoryCategory::ALL {
+                let (l, c) = (location as usize, category as usize);
+                stats[l][c] = MemoryStats {
+                    count: self.counts[l][c].load(Ordering::Relaxed),
+                    bytes: self.bytes[l][c].load(Ordering::Relaxed),
+                };
+            }
+        }
+
+        MemoryReport {
+            device,
+            budget,
+            stats,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct MemoryAllocation {
+    counters: Arc<MemoryCounters>,
+    location: MemoryLocation,
+    category: MemoryCategory,
+    size: u64,
+}
+
+impl MemoryAllocation {
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Changes the accounted size, e.g. when part of a resource is paged out.
+    pub fn resize(&mut self, size: u64) {
+        let (l, c) = (self.location as usize, self.category as usize);
+        self.counters.bytes[l][c].fetch_add(size, Ordering::Relaxed);
+        self.counters.bytes[l][c].fetch_sub(self.size, Ordering::Relaxed);
+        self.size = size;
+    }
+}
+
+impl Drop for MemoryAllocation {
+    fn drop(&mut self) {
+        let (l, c) = (self.location as usize, self.category as usize);
+        self.counters.counts[l][c].fetch_sub(1, Ordering::Relaxed);
+        self.counters.bytes[l][c].fetch_sub(self.size, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dropping_allocation_releases_it() {
+        let counters = Arc::new(MemoryCounters::default());
+
+        let buffer = counters.allocate(MemoryLocation::Upload, MemoryCategory::Buffer, 256);
+        let _texture = counters.allocate(MemoryLocation::Device, MemoryCategory::Texture, 1024);
+
+        let report = counters.report(0, None);
+        assert_eq!(
+            report.total(),
+            MemoryStats {
+                count: 2,
+                bytes: 1280
+            }
+        );
+        assert_eq!(
+            report.location(MemoryLocation::Upload),
+            MemoryStats {
+                count: 1,
+                bytes: 256
+            }
+        );
+
+        drop(buffer);
+
+        let report = counters.report(0, None);
+        assert_eq!(
+            report.category(MemoryCategory::Buffer),
+            MemoryStats::default()
+        );
+        assert_eq!(report.total().bytes, 1024);
+    }
+}
@@ -0,0 +1,272 @@
+use super::{resource::TextureDesc, types::Format};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Component {
+    Unorm8,
+    Unorm16,
+    Float32,
+}
+
+fn pixel_layout(format: Format) -> Option<(Component, usize)> {
+    match format {
+        Format::R8Unorm => Some((Component::Unorm8, 1)),
+        Format::Rg8Unorm => Some((Component::Unorm8, 2)),
+        Format::Rgba8Unorm
+        | Format::Rgba8UnormSrgb
+        | Format::Bgra8Unorm
+        | Format::Bgra8UnormSrgb => Some((Component::Unorm8, 4)),
+        Format::Rgba16Unorm => Some((Component::Unorm16, 4)),
+        Format::R32 => Some((Component::Float32, 1)),
+        Format::Rg32 => Some((Component::Float32, 2)),
+        Format::Rgb32 => Some((Component::Float32, 3)),
+        Format::Rgba32 => Some((Component::Float32, 4)),
+        _ => None,
+    }
+}
+
+pub fn is_supported(format: Format) -> bool {
+    pixel_layout(format).is_some()
+}
+
+/// Extends a texture that only has its top level to the full mip chain.
+/// Returns `None` for formats that can not be filtered on the CPU.
+pub fn generate_mip_chain(desc: &TextureDesc, data: &[u8]) -> Option<(TextureDesc, Vec<u8>)> {
+    if !is_supported(desc.format) {
+        return None;
+    }
+
+    let mut full = desc.clone();
+    full.mip_levels = desc.max_mip_levels();
+
+    let mut out = vec![0; full.packed_size()];
+
+    for (base, dst) in desc.subresource_layouts().into_iter().zip(
+        full.subresource_layouts()
+            .into_iter()
+            .step_by(full.mip_levels as usize),
+    ) {
+        out[dst.offset..(dst.offset + dst.size)]
+            .copy_from_slice(&data[base.offset..(base.offset + base.size)]);
+    }
+
+    fill_mips(&full, &mut out);
+
+    Some((full, out))
+}
+
+/// Regenerates every mip below the top level of each layer in place. `data` holds
+/// all subresources tightly packed in subresource order.
+/// Returns `false` for formats that can not be filtered on the CPU.
+pub fn fill_mips(desc: &TextureDesc, data: &mut [u8]) -> bool {
+    let Some((component, channels)) = pixel_layout(desc.format) else {
+        return false;
+    };
+    let srgb = desc.format.is_srgb();
+
+    let layouts = desc.subresource_layouts();
+
+    for layer in layouts.chunks(desc.mip_levels as usize) {
+        let base = layer[0];
+        let mut extent = (base.width, base.height, base.depth);
+        let mut texels = decode(
+            &data[base.offset..(base.offset + base.size)],
+            component,
+            channels,
+            srgb,
+        );
+
+        for mip in &layer[1..] {
+            let next = (mip.width, mip.height, mip.depth);
+            texels = downsample(&texels, channels, extent, next);
+            encode(
+                &texels,
+                component,
+                channels,
+                srgb,
+                &mut data[mip.offset..(mip.offset + mip.size)],
+            );
+            extent = next;
+        }
+    }
+
+    true
+}
+
+/// Source texels covered by each of `dst` texels along one axis, weighted by the
+/// covered fraction. With an odd `src` the texels between two destination texels
+/// are split, so no row or column is dropped.
+fn footprint(src: u32, dst: u32) -> Vec<Vec<(u32, f32)>> {
+    let scale = src as f32 / dst as f32;
+
+    (0..dst)
+        .map(|i| {
+            let (start, end) = (i as f32 * scale, (i + 1) as f32 * scale);
+
+            (start.floor() as u32..(end.ceil() as u32).min(src))
+                .map(|s| (s, end.min(s as f32 + 1.0) - start.max(s as f32)))
+                .filter(|(_, weight)| *weight > 0.0)
+                .collect()
+        })
+        .collect()
+}
+
+fn downsample(
+    src: &[f32],
+    channels: usize,
+    (sw, sh, sd): (u32, u32, u32),
+    (dw, dh, dd): (u32, u32, u32),
+) -> Vec<f32> {
+    let mut dst = vec![0.0; (dw * dh * dd) as usize * channels];
+    let (fx, fy, fz) = (footprint(sw, dw), footprint(sh, dh), footprint(sd, dd));
+
+    for z in 0..dd {
+        for y in 0..dh {
+            for x in 0..dw {
+                let di = ((z * dh + y) * dw + x) as usize * channels;
+                let mut total = 0.0;
+
+                for &(sz, wz) in &fz[z as usize] {
+                    for &(sy, wy) in &fy[y as usize] {
+                        for &(sx, wx) in &fx[x as usize] {
+                            let si = ((sz * sh + sy) * sw + sx) as usize * channels;
+                            let weight = wx * wy * wz;
+
+                            for c in 0..channels {
+                                dst[di + c] += src[si + c] * weight;
+                            }
+                            total += weight;
+                        }
+                    }
+                }
+
+                for c in 0..channels {
+                    dst[di + c] /= total;
+                }
+            }
+        }
+    }
+
+    dst
+}
+
+/// Encodes a linear color as a single texel of `format`, used for clears.
+pub fn encode_texel(format: Format, color: [f32; 4]) -> Option<Vec<u8>> {
+    let (component, channels) = pixel_layout(format)?;
+
+    let mut texel = color;
+    if matches!(format, Format::Bgra8Unorm | Format::Bgra8UnormSrgb) {
+        texel.swap(0, 2);
+    }
+
+    let size = match component {
+        Component::Unorm8 => channels,
+        Component::Unorm16 => channels * 2,
+        Component::Float32 => channels * 4,
+    };
+
+    let mut out = vec![0; size];
+    encode(
+        &texel[..channels],
+        component,
+        channels,
+        format.is_srgb(),
+        &mut out,
+    );
+
+    Some(out)
+}
+
+fn is_color_channel(srgb: bool, c: usize) -> bool {
+    srgb && c < 3
+}
+
+fn decode(data: &[u8], component: Component, channels: usize, srgb: bool) -> Vec<f32> {
+    let values: Vec<f32> = match component {
+        Component::Unorm8 => data.iter().map(|v| *v as f32 / 255.0).collect(),
+        Component::Unorm16 => data
+            .chunks_exact(2)
+            .map(|v| u16::from_ne_bytes([v[0], v[1]]) as f32 / 65535.0)
+            .collect(),
+        Component::Float32 => data
+            .chunks_exact(4)
+            .map(|v| f32::from_ne_bytes([v[0], v[1], v[2], v[3]]))
+            .collect(),
+    };
+
+    values
+        .into_iter()
+        .enumerate()
+        .map(|(i, v)| {
+            if is_color_channel(srgb, i % channels) {
+                srgb_to_linear(v)
+            } else {
+                v
+            }
+        })
+        .collect()
+}
+
+fn encode(texels: &[f32], component: Component, channels: usize, srgb: bool, out: &mut [u8]) {
+    for (i, v) in texels.iter().enumerate() {
+        let v = if is_color_channel(srgb, i % channels) {
+            linear_to_srgb(*v)
+        } else {
+            *v
+        };
+
+        match component {
+            Component::Unorm8 => out[i] = (v.clamp(0.0, 1.0) * 255.0).round() as u8,
+            Component::Unorm16 => out[(i * 2)..(i * 2 + 2)]
+                .copy_from_slice(&((v.clamp(0.0, 1.0) * 65535.0).round() as u16).to_ne_bytes()),
+            Component::Float32 => out[(i * 4)..(i * 4 + 4)].copy_from_slice(&v.to_ne_bytes()),
+        }
+    }
+}
+
+fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(v: f32) -> f32 {
+    if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::core::resource::{TextureType, TextureUsages};
+
+    #[test]
+    fn odd_sizes_keep_the_edge_texels() {
+        let desc = TextureDesc {
+            name: None,
+            ty: TextureType::D2,
+            width: 3,
+            height: 1,
+            depth: 1,
+            mip_levels: 1,
+            format: Format::R8Unorm,
+            usage: TextureUsages::Resource,
+        };
+
+        let (full, data) = generate_mip_chain(&desc, &[0, 90, 180]).unwrap();
+        assert_eq!(full.mip_levels, 2);
+        assert_eq!(data, [0, 90, 180, 90]);
+
+        assert_eq!(
+            footprint(5, 2),
+            [
+                vec![(0, 1.0), (1, 1.0), (2, 0.5)],
+                vec![(2, 0.5), (3, 1.0), (4, 1.0)]
+            ]
+        );
+    }
+}
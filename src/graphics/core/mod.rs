@@ -1,7 +1,22 @@
 pub mod backend;
+pub mod bundle;
+pub mod capture;
 pub mod commands;
+pub mod compiler;
+pub mod future;
 pub mod handle;
+pub mod indirect;
+pub mod memory;
+pub mod mips;
+pub mod ownership;
+pub mod profiler;
+pub mod query;
+pub mod residency;
 pub mod resource;
 pub mod shader;
+pub mod stream;
 pub mod swapchain;
+pub mod tracker;
+pub mod transfer;
 pub mod types;
+pub mod validation;
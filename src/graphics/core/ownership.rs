@@ -0,0 +1,183 @@
+use std::fmt;
+
+use super::{commands::CommandBufferType, types::ResourceState};
+
+/// Whether command buffers of `queue` can use resources in `state`. Transfer
+/// queues only copy, compute queues can not touch render pass and input
+/// assembler states.
+pub fn supports(queue: CommandBufferType, state: ResourceState) -> bool {
+    match queue {
+        CommandBufferType::Graphics => true,
+        CommandBufferType::Compute => !matches!(
+            state,
+            ResourceState::VertexBuffer
+                | ResourceState::IndexBuffer
+                | ResourceState::RenderTarget
+                | ResourceState::DepthWrite
+                | ResourceState::DepthRead
+                | ResourceState::Present
+        ),
+        CommandBufferType::Transfer => matches!(
+            state,
+            ResourceState::Common | ResourceState::CopySrc | ResourceState::CopyDst
+        ),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OwnershipError {
+    /// Used on `queue` while `owner` still holds it in a state other than `Common`.
+    NotReleased {
+        owner: CommandBufferType,
+        queue: CommandBufferType,
+    },
+    /// Released by a queue that does not own it.
+    NotOwner {
+        owner: CommandBufferType,
+        queue: CommandBufferType,
+    },
+    /// Released to `to`, then used on `queue` before `to` acquired it.
+    ReleasedElsewhere {
+        to: CommandBufferType,
+        queue: CommandBufferType,
+    },
+    UnsupportedState {
+        state: ResourceState,
+        queue: CommandBufferType,
+    },
+}
+
+impl fmt::Display for OwnershipError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OwnershipError::NotReleased { owner, queue } => write!(
+                f,
+                "used on the {:?} queue without a release from the {:?} queue",
+                queue, owner
+            ),
+            OwnershipError::NotOwner { owner, queue } => write!(
+                f,
+                "released by the {:?} queue but owned by the {:?} queue",
+                queue, owner
+            ),
+            OwnershipError::ReleasedElsewhere { to, queue } => write!(
+                f,
+                "used on the {:?} queue after a release to the {:?} queue",
+                queue, to
+            ),
+            OwnershipError::UnsupportedState { state, queue } => {
+                write!(f, "{:?} is not supported on the {:?} queue", state, queue)
+            }
+        }
+    }
+}
+
+impl std::error::Error for OwnershipError {}
+
+/// Queue owning a resource, tracked next to its state in recording order.
+///
+/// A queue acquires a resource on first use. Handing it to another queue needs
+/// a release on the owner, which also leaves it in a state the other queue
+/// supports. Resources in `Common` move between queues implicitly, like fresh
+/// uploads finished on the transfer queue.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum QueueOwnership {
+    #[default]
+    Unowned,
+    Owned(CommandBufferType),
+    Released {
+        from: CommandBufferType,
+        to: CommandBufferType,
+    },
+}
+
+impl QueueOwnership {
+    /// Takes the resource for `queue`, `state` being its state before the use or
+    /// `None` if its subresources differ. The resource moves to `queue` even
+    /// when the handoff is invalid, so every bad handoff is reported once.
+    pub fn acquire(
+        &mut self,
+        queue: CommandBufferType,
+        state: Option<ResourceState>,
+    ) -> Result<(), OwnershipError> {
+        let result = match *self {
+            QueueOwnership::Unowned => Ok(()),
+            QueueOwnership::Owned(owner) if owner == queue => Ok(()),
+            QueueOwnership::Owned(_) if state == Some(ResourceState::Common) => Ok(()),
+            QueueOwnership::Owned(owner) => Err(OwnershipError::NotReleased { owner, queue }),
+            QueueOwnership::Released { to, .. } if to == queue => Ok(()),
+            QueueOwnership::Released { to, .. } => {
+                Err(OwnershipError::ReleasedElsewhere { to, queue })
+            }
+        };
+
+        *self = QueueOwnership::Owned(queue);
+        result
+    }
+
+    /// Hands the resource from `from` to `to`, which acquires it on its next use.
+    pub fn release(
+        &mut self,
+        from: CommandBufferType,
+        to: CommandBufferType,
+    ) -> Result<(), OwnershipError> {
+        let result = match *self {
+            QueueOwnership::Owned(owner) if owner != from => {
+                Err(OwnershipError::NotOwner { owner, queue: from })
+            }
+            QueueOwnership::Released { to: owner, .. } if owner != from => {
+                Err(OwnershipError::NotOwner { owner, queue: from })
+            }
+            _ => Ok(()),
+        };
+
+        *self = QueueOwnership::Released { from, to };
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handoffs_need_a_release_unless_the_resource_is_common() {
+        let (gfx, compute, copy) = (
+            CommandBufferType::Graphics,
+            CommandBufferType::Compute,
+            CommandBufferType::Transfer,
+        );
+        let mut owner = QueueOwnership::default();
+
+        assert_eq!(owner.acquire(copy, Some(ResourceState::Common)), Ok(()));
+        assert_eq!(owner.acquire(gfx, Some(ResourceState::Common)), Ok(()));
+        assert_eq!(
+            owner.acquire(compute, Some(ResourceState::RenderTarget)),
+            Err(OwnershipError::NotReleased {
+                owner: gfx,
+                queue: compute
+            })
+        );
+
+        assert_eq!(owner.release(compute, copy), Ok(()));
+        assert_eq!(
+            owner.acquire(gfx, Some(ResourceState::CopySrc)),
+            Err(OwnershipError::ReleasedElsewhere {
+                to: copy,
+                queue: gfx
+            })
+        );
+        assert_eq!(
+            owner.release(copy, compute),
+            Err(OwnershipError::NotOwner {
+                owner: gfx,
+                queue: copy
+            })
+        );
+        assert_eq!(owner.acquire(compute, None), Ok(()));
+
+        assert!(!supports(copy, ResourceState::StorageWrite));
+        assert!(!supports(compute, ResourceState::VertexBuffer));
+        assert!(supports(compute, ResourceState::Sampled));
+    }
+}
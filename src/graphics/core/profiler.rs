@@ -0,0 +1,325 @@
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    ops::{Deref, DerefMut, Range},
+};
+
+use tracing::info;
+
+use super::{
+    commands::{CommandBuffer, FenceValue},
+    query::{QuerySetDesc, QueryType},
+    resource::{BufferDesc, BufferUsages},
+};
+
+/// Timestamp queries available to the scopes of one frame.
+pub const PROFILER_CAPACITY: u32 = 512;
+
+/// Query set written by the scopes of one profiler frame.
+pub fn query_set_desc() -> QuerySetDesc {
+    QuerySetDesc {
+        name: Some(Cow::Borrowed("Profiler Queries")),
+        ty: QueryType::Timestamp,
+        count: PROFILER_CAPACITY,
+    }
+}
+
+/// Buffer the timestamps of one profiler frame are resolved into.
+pub fn readback_desc() -> BufferDesc {
+    BufferDesc {
+        name: Some(Cow::Borrowed("Profiler Readback")),
+        size: PROFILER_CAPACITY as usize * QueryType::Timestamp.result_size(),
+        stride: 0,
+        usage: BufferUsages::QueryResolve,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GpuTiming {
+    pub name: &'static str,
+    /// Number of scopes this one is nested in.
+    pub depth: u32,
+    pub milliseconds: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RecordedScope {
+    pub name: &'static str,
+    pub depth: u32,
+    pub begin: u32,
+    pub end: u32,
+}
+
+/// Scopes of a single command buffer. A scope whose timestamps could not be
+/// allocated is still tracked, so nesting stays balanced, but never recorded.
+#[derive(Debug, Default)]
+pub struct ScopeStack {
+    open: Vec<(&'static str, Option<u32>)>,
+    recorded: Vec<RecordedScope>,
+}
+
+impl ScopeStack {
+    pub fn push(&mut self, name: &'static str, begin: Option<u32>) {
+        self.open.push((name, begin));
+    }
+
+    pub fn pop(&mut self, end: Option<u32>) {
+        let Some((name, begin)) = self.open.pop() else {
+            debug_assert!(false, "scope ended without being started");
+            return;
+        };
+
+        if let (Some(begin), Some(end)) = (begin, end) {
+            self.recorded.push(RecordedScope {
+                name,
+                depth: self.open.len() as u32,
+                begin,
+                end,
+            });
+        }
+    }
+
+    /// Scopes closed so far, called when the command buffer is submitted.
+    pub fn take(&mut self) -> Vec<RecordedScope> {
+        debug_assert!(self.open.is_empty(), "command buffer has unfinished scopes");
+        self.open.clear();
+
+        std::mem::take(&mut self.recorded)
+    }
+}
+
+#[derive(Debug)]
+struct ProfilerFrame<Q, B> {
+    queries: Q,
+    readback: B,
+    scopes: Vec<RecordedScope>,
+    next_query: u32,
+    sync_point: FenceValue,
+}
+
+/// Turns the scopes of submitted command buffers into per-frame timings.
+///
+/// Every frame owns a timestamp query set and a readback buffer. Once the frame
+/// ends its queries are resolved, and the timings are available as soon as the
+/// frame's sync point completes.
+#[derive(Debug)]
+pub struct GpuProfiler<Q, B> {
+    /// Milliseconds per timestamp tick.
+    period: f64,
+    current: Option<ProfilerFrame<Q, B>>,
+    in_flight: VecDeque<ProfilerFrame<Q, B>>,
+    free: Vec<(Q, B)>,
+    timings: Vec<GpuTiming>,
+}
+
+impl<Q, B> GpuProfiler<Q, B> {
+    pub fn new(period: f64) -> Self {
+        Self {
+            period,
+            current: None,
+            in_flight: VecDeque::new(),
+            free: vec![],
+            timings: vec![],
+        }
+    }
+
+    /// Starts recording scopes, reusing the resources of a collected frame if
+    /// there is one.
+    pub fn begin_frame(&mut self, create: impl FnOnce() -> (Q, B)) {
+        debug_assert!(self.current.is_none(), "profiler frame already started");
+
+        let (queries, readback) = self.free.pop().unwrap_or_else(create);
+        self.current = Some(ProfilerFrame {
+            queries,
+            readback,
+            scopes: vec![],
+            next_query: 0,
+            sync_point: 0,
+        });
+    }
+
+    /// Reserves a timestamp in the current frame, `None` once the frame is full.
+    pub fn allocate(&mut self) -> Option<(&Q, u32)> {
+        let frame = self.current.as_mut()?;
+        if frame.next_query >= PROFILER_CAPACITY {
+            return None;
+        }
+
+        frame.next_query += 1;
+        Some((&frame.queries, frame.next_query - 1))
+    }
+
+    pub fn submit(&mut self, scopes: Vec<RecordedScope>) {
+        if let Some(frame) = self.current.as_mut() {
+            frame.scopes.extend(scopes);
+        }
+    }
+
+    /// Queries written in the current frame, to be resolved into the returned
+    /// buffer before the frame ends.
+    pub fn pending(&self) -> Option<(&Q, Range<u32>, &B)> {
+        let frame = self.current.as_ref()?;
+        if frame.next_query == 0 {
+            return None;
+        }
+
+        Some((&frame.queries, 0..frame.next_query, &frame.readback))
+    }
+
+    /// Ends the current frame, its timings are ready once `sync_point` completes.
+    pub fn end_frame(&mut self, sync_point: FenceValue) {
+        if let Some(mut frame) = self.current.take() {
+            frame.sync_point = sync_point;
+            self.in_flight.push_back(frame);
+        }
+    }
+
+    /// Reads back every frame finished by `completed`, `read` returns the
+    /// resolved ticks of a readback buffer. Returns true if the timings changed.
+    pub fn collect(&mut self, completed: FenceValue, read: impl Fn(&B) -> Vec<u64>) -> bool {
+        let mut changed = false;
+
+        while self
+            .in_flight
+            .front()
+            .is_some_and(|frame| frame.sync_point <= completed)
+        {
+            let frame = self.in_flight.pop_front().expect("unreachable");
+
+            let ticks = read(&frame.readback);
+            self.timings = frame
+                .scopes
+                .iter()
+                .map(|scope| GpuTiming {
+                    name: scope.name,
+                    depth: scope.depth,
+                    milliseconds: ticks[scope.end as usize]
+                        .saturating_sub(ticks[scope.begin as usize])
+                        as f64
+                        * self.period,
+                })
+                .collect();
+            changed = true;
+
+            self.free.push((frame.queries, frame.readback));
+        }
+
+        changed
+    }
+
+    /// Timings of the latest frame that finished on the GPU.
+    pub fn timings(&self) -> &[GpuTiming] {
+        &self.timings
+    }
+
+    pub fn log(&self) {
+        for timing in &self.timings {
+            info!(
+                "{:indent$}{}: {:.3} ms",
+                "",
+                timing.name,
+                timing.milliseconds,
+                indent = timing.depth as usize * 2
+            );
+        }
+    }
+}
+
+/// Ends its scope on drop, created by [`CommandBuffer::scope`] and [`gpu_scope!`].
+///
+/// [`gpu_scope!`]: crate::gpu_scope
+pub struct GpuScope<'a, C: CommandBuffer> {
+    cmd_buffer: &'a mut C,
+}
+
+impl<'a, C: CommandBuffer> GpuScope<'a, C> {
+    pub fn new(cmd_buffer: &'a mut C, name: &'static str) -> Self {
+        cmd_buffer.begin_scope(name);
+        Self { cmd_buffer }
+    }
+}
+
+impl<C: CommandBuffer> Deref for GpuScope<'_, C> {
+    type Target = C;
+
+    fn deref(&self) -> &Self::Target {
+        self.cmd_buffer
+    }
+}
+
+impl<C: CommandBuffer> DerefMut for GpuScope<'_, C> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.cmd_buffer
+    }
+}
+
+impl<C: CommandBuffer> Drop for GpuScope<'_, C> {
+    fn drop(&mut self) {
+        self.cmd_buffer.end_scope();
+    }
+}
+
+/// Times the rest of the enclosing block on the GPU under `name`. The command
+/// buffer is shadowed by a [`GpuScope`] that dereferences to it.
+///
+/// ```ignore
+/// {
+///     gpu_scope!("shadows", cmd);
+///     let encoder = cmd.render_encoder(&pass);
+/// }
+/// device.push_cmd_buffer(cmd);
+/// ```
+#[macro_export]
+macro_rules! gpu_scope {
+    ($name:expr, $cmd:ident) => {
+        #[allow(unused_mut)]
+        let mut $cmd = {
+            use $crate::graphics::core::commands::CommandBuffer as _;
+            $cmd.scope($name)
+        };
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_turn_scopes_into_timings_once_completed() {
+        let mut profiler = GpuProfiler::new(0.5);
+        profiler.begin_frame(|| (0, vec![0, 10, 16, 20]));
+        let mut stack = ScopeStack::default();
+
+        let mut timestamp = || profiler.allocate().map(|(_, index)| index);
+        stack.push("frame", timestamp());
+        stack.push("shadows", timestamp());
+        stack.pop(timestamp());
+        stack.pop(timestamp());
+        profiler.submit(stack.take());
+
+        assert_eq!(profiler.pending().map(|(_, range, _)| range), Some(0..4));
+        profiler.end_frame(1);
+        assert_eq!(profiler.pending(), None);
+
+        assert!(!profiler.collect(0, |ticks| ticks.clone()));
+        assert!(profiler.collect(1, |ticks| ticks.clone()));
+        assert_eq!(
+            profiler.timings(),
+            [
+                GpuTiming {
+                    name: "shadows",
+                    depth: 1,
+                    milliseconds: 3.0
+                },
+                GpuTiming {
+                    name: "frame",
+                    depth: 0,
+                    milliseconds: 10.0
+                }
+            ]
+        );
+
+        profiler.begin_frame(|| unreachable!());
+        assert_eq!(profiler.allocate().map(|(set, _)| *set), Some(0));
+    }
+}
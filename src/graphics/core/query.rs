@@ -0,0 +1,210 @@
+use std::{borrow::Cow, fmt, ops::Range};
+
+use bytemuck::{Pod, Zeroable};
+
+use super::{
+    commands::CommandBufferType,
+    resource::{BufferDesc, BufferUsages},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QueryType {
+    /// Written with `write_timestamp`, resolves to a `u64` tick count.
+    Timestamp,
+    /// Bracketed with `begin_query` and `end_query`, resolves to [`PipelineStatistics`].
+    PipelineStatistics,
+}
+
+impl QueryType {
+    pub fn result_size(self) -> usize {
+        match self {
+            QueryType::Timestamp => size_of::<u64>(),
+            QueryType::PipelineStatistics => size_of::<PipelineStatistics>(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct QuerySetDesc {
+    pub name: Option<Cow<'static, str>>,
+    pub ty: QueryType,
+    pub count: u32,
+}
+
+/// Resolved pipeline statistics, laid out like `D3D12_QUERY_DATA_PIPELINE_STATISTICS`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PipelineStatistics {
+    pub input_vertices: u64,
+    pub input_primitives: u64,
+    pub vertex_invocations: u64,
+    pub geometry_invocations: u64,
+    pub geometry_primitives: u64,
+    pub clipper_invocations: u64,
+    pub clipper_primitives: u64,
+    pub pixel_invocations: u64,
+    pub hull_invocations: u64,
+    pub domain_invocations: u64,
+    pub compute_invocations: u64,
+}
+
+// SAFETY: `#[repr(C)]` struct of 64 bit integers without padding.
+unsafe impl Zeroable for PipelineStatistics {}
+unsafe impl Pod for PipelineStatistics {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QueryError {
+    WrongType {
+        expected: QueryType,
+        found: QueryType,
+    },
+    OutOfRange {
+        index: u32,
+        count: u32,
+    },
+    /// The resolve buffer was not created with [`BufferUsages::QueryResolve`].
+    MissingUsage,
+    /// Resolve offsets must be 8 byte aligned.
+    Misaligned {
+        offset: usize,
+    },
+    OutOfBounds {
+        end: usize,
+        size: usize,
+    },
+    /// Queries are recorded and resolved on graphics and compute buffers only.
+    TransferQueue,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueryError::WrongType { expected, found } => {
+                write!(f, "expected a {:?} query set, found {:?}", expected, found)
+            }
+            QueryError::OutOfRange { index, count } => {
+                write!(f, "query {} is out of range for a set of {}", index, count)
+            }
+            QueryError::MissingUsage => write!(f, "buffer is missing the query resolve usage"),
+            QueryError::Misaligned { offset } => {
+                write!(f, "resolve offset {} is not 8 byte aligned", offset)
+            }
+            QueryError::OutOfBounds { end, size } => {
+                write!(
+                    f,
+                    "resolved queries end at {} but buffer size is {}",
+                    end, size
+                )
+            }
+            QueryError::TransferQueue => write!(f, "queries are not supported on transfer buffers"),
+        }
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+pub fn validate_query(
+    set: &QuerySetDesc,
+    ty: QueryType,
+    index: u32,
+    queue: CommandBufferType,
+) -> Result<(), QueryError> {
+    if queue == CommandBufferType::Transfer {
+        return Err(QueryError::TransferQueue);
+    }
+
+    if set.ty != ty {
+        return Err(QueryError::WrongType {
+            expected: ty,
+            found: set.ty,
+        });
+    }
+
+    if index >= set.count {
+        return Err(QueryError::OutOfRange {
+            index,
+            count: set.count,
+        });
+    }
+
+    Ok(())
+}
+
+/// Checks that `queries` exist in `set` and that their results fit in `buffer` at `offset`.
+pub fn validate_resolve(
+    set: &QuerySetDesc,
+    queries: &Range<u32>,
+    buffer: &BufferDesc,
+    offset: usize,
+    queue: CommandBufferType,
+) -> Result<(), QueryError> {
+    if queue == CommandBufferType::Transfer {
+        return Err(QueryError::TransferQueue);
+    }
+
+    if queries.end > set.count || queries.start > queries.end {
+        return Err(QueryError::OutOfRange {
+            index: queries.end,
+            count: set.count,
+        });
+    }
+
+    if !buffer.usage.contains(BufferUsages::QueryResolve) {
+        return Err(QueryError::MissingUsage);
+    }
+
+    if !offset.is_multiple_of(8) {
+        return Err(QueryError::Misaligned { offset });
+    }
+
+    let end = offset + queries.len() * set.ty.result_size();
+    if end > buffer.size {
+        return Err(QueryError::OutOfBounds {
+            end,
+            size: buffer.size,
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_query_types_and_resolve_targets() {
+        let set = QuerySetDesc {
+            name: None,
+            ty: QueryType::PipelineStatistics,
+            count: 2,
+        };
+        let buffer = BufferDesc {
+            name: None,
+            size: 128,
+            stride: 0,
+            usage: BufferUsages::QueryResolve,
+        };
+
+        assert_eq!(size_of::<PipelineStatistics>(), 88);
+        assert_eq!(
+            validate_query(&set, QueryType::Timestamp, 0, CommandBufferType::Graphics),
+            Err(QueryError::WrongType {
+                expected: QueryType::Timestamp,
+                found: QueryType::PipelineStatistics
+            })
+        );
+        assert_eq!(
+            validate_resolve(&set, &(0..2), &buffer, 0, CommandBufferType::Compute),
+            Err(QueryError::OutOfBounds {
+                end: 176,
+                size: 128
+            })
+        );
+        assert!(validate_resolve(&set, &(1..2), &buffer, 40, CommandBufferType::Graphics).is_ok());
+        assert_eq!(
+            validate_resolve(&set, &(1..2), &buffer, 40, CommandBufferType::Transfer),
+            Err(QueryError::TransferQueue)
+        );
+    }
+}
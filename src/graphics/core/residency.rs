@@ -0,0 +1,288 @@
+use std::{collections::HashMap, hash::Hash};
+
+use super::{commands::FenceValue, resource::TextureDesc};
+
+/// Order in which resources are given up when the device runs over budget.
+/// `Critical` resources are never evicted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ResidencyPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+    Critical,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Residency {
+    Resident,
+    /// Only mips starting from `first_mip` are kept in device memory.
+    Downgraded {
+        first_mip: u32,
+    },
+    Evicted,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResidencyAction {
+    Evict,
+    Downgrade { first_mip: u32 },
+    MakeResident,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ResidencyChange<K> {
+    pub key: K,
+    pub action: ResidencyAction,
+    /// Device memory used by the resource once the change is applied.
+    pub size: u64,
+}
+
+#[derive(Debug)]
+struct ResidencyEntry {
+    priority: ResidencyPriority,
+    last_used: FenceValue,
+    /// Size of every mip summed over all array layers.
+    mip_sizes: Vec<u64>,
+    residency: Residency,
+}
+
+impl ResidencyEntry {
+    fn resident_size(&self) -> u64 {
+        match self.residency {
+            Residency::Resident => self.mip_sizes.iter().sum(),
+            Residency::Downgraded { first_mip } => {
+                self.mip_sizes[first_mip as usize..].iter().sum()
+            }
+            Residency::Evicted => 0,
+        }
+    }
+
+    fn first_mip(&self) -> u32 {
+        match self.residency {
+            Residency::Downgraded { first_mip } => first_mip,
+            _ => 0,
+        }
+    }
+}
+
+/// Least-recently-used residency policy for the resources of one device.
+///
+/// Usage is recorded against the sync point of the submission being recorded,
+/// and only resources whose last use has completed on the GPU are given up.
+#[derive(Debug)]
+pub struct ResidencySet<K> {
+    entries: HashMap<K, ResidencyEntry>,
+    pending: FenceValue,
+    allow_downgrade: bool,
+}
+
+impl<K: Copy + Eq + Hash> ResidencySet<K> {
+    /// `allow_downgrade` lets the set drop the top mips of a texture before
+    /// evicting it entirely.
+    pub fn new(allow_downgrade: bool) -> Self {
+        Self {
+            entries: HashMap::new(),
+            pending: 1,
+            allow_downgrade,
+        }
+    }
+
+    pub fn insert(&mut self, key: K, priority: ResidencyPriority, mip_sizes: Vec<u64>) {
+        self.entries.insert(
+            key,
+            ResidencyEntry {
+                priority,
+                last_used: self.pending,
+                mip_sizes,
+                residency: Residency::Resident,
+            },
+        );
+    }
+
+    pub fn insert_texture(&mut self, key: K, priority: ResidencyPriority, desc: &TextureDesc) {
+        let mut mip_sizes = vec![0; desc.mip_levels.max(1) as usize];
+        for layout in desc.subresource_layouts() {
+            mip_sizes[layout.mip as usize] += layout.size as u64;
+        }
+
+        self.insert(key, priority, mip_sizes);
+    }
+
+    pub fn remove(&mut self, key: K) {
+        self.entries.remove(&key);
+    }
+
+    pub fn set_priority(&mut self, key: K, priority: ResidencyPriority) {
+        if let Some(entry) = self.entries.get_mut(&key) {
+            entry.priority = priority;
+        }
+    }
+
+    pub fn residency(&self, key: K) -> Option<Residency> {
+        self.entries.get(&key).map(|e| e.residency)
+    }
+
+    /// Called once a submission is committed, later uses land in the next one.
+    pub fn advance(&mut self, committed: FenceValue) {
+        self.pending = self.pending.max(committed + 1);
+    }
+
+    /// Marks `key` as used by the submission being recorded. Returns the change
+    /// that has to be applied before the resource is accessed if it was given up.
+    pub fn touch(&mut self, key: K) -> Option<ResidencyChange<K>> {
+        let entry = self.entries.get_mut(&key)?;
+        entry.last_used = self.pending;
+
+        if entry.residency == Residency::Resident {
+            return None;
+        }
+
+        entry.residency = Residency::Resident;
+        Some(ResidencyChange {
+            key,
+            action: ResidencyAction::MakeResident,
+            size: entry.resident_size(),
+        })
+    }
+
+    /// Gives up least recently used resources, lowest priority first, until
+    /// `usage` fits in `budget`. Resources still used by work after `completed`
+    /// are left alone.
+    pub fn trim(
+        &mut self,
+        mut usage: u64,
+        budget: u64,
+        completed: FenceValue,
+    ) -> Vec<ResidencyChange<K>> {
+        if usage <= budget {
+            return vec![];
+        }
+
+        let mut candidates = self
+            .entries
+            .iter()
+            .filter(|(_, e)| {
+                e.priority != ResidencyPriority::Critical
+                    && e.residency != Residency::Evicted
+                    && e.last_used <= completed
+            })
+            .map(|(key, e)| (*key, e.priority, e.last_used))
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|(_, priority, last_used)| (*priority, *last_used));
+
+        let mut changes = vec![];
+        for (key, _, _) in candidates {
+            if usage <= budget {
+                break;
+            }
+
+            let entry = self.entries.get_mut(&key).expect("unreachable");
+            let size = entry.resident_size();
+            let tail = (entry.mip_sizes.len() as u32).saturating_sub(1);
+
+            let mut first_mip = entry.first_mip();
+            let mut remaining = size;
+            if self.allow_downgrade {
+                while first_mip < tail && usage - (size - remaining) > budget {
+                    remaining -= entry.mip_sizes[first_mip as usize];
+                    first_mip += 1;
+                }
+            }
+
+            let (residency, action) =
+                if usage - (size - remaining) <= budget && first_mip > entry.first_mip() {
+                    (
+                        Residency::Downgraded { first_mip },
+                        ResidencyAction::Downgrade { first_mip },
+                    )
+                } else {
+                    remaining = 0;
+                    (Residency::Evicted, ResidencyAction::Evict)
+                };
+
+            usage -= size - remaining;
+            entry.residency = residency;
+            changes.push(ResidencyChange {
+                key,
+                action,
+                size: remaining,
+            });
+        }
+
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_used_low_priority_first() {
+        let mut set = ResidencySet::new(false);
+        set.insert(0, ResidencyPriority::Normal, vec![100]);
+        set.insert(1, ResidencyPriority::Low, vec![100]);
+        set.advance(1);
+        set.insert(2, ResidencyPriority::Low, vec![100]);
+        set.insert(3, ResidencyPriority::Critical, vec![100]);
+
+        let changes = set.trim(400, 250, 2);
+
+        assert_eq!(
+            changes.iter().map(|c| c.key).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert_eq!(set.residency(0), Some(Residency::Resident));
+        assert_eq!(set.residency(2), Some(Residency::Evicted));
+    }
+
+    #[test]
+    fn never_evicts_critical_or_in_flight_resources() {
+        let mut set = ResidencySet::new(false);
+        set.insert(0, ResidencyPriority::Critical, vec![100]);
+        set.advance(4);
+        set.insert(1, ResidencyPriority::Low, vec![100]);
+
+        assert!(set.trim(200, 0, 4).is_empty());
+    }
+
+    #[test]
+    fn downgrades_mips_before_evicting() {
+        let mut set = ResidencySet::new(true);
+        set.insert(0, ResidencyPriority::Low, vec![64, 16, 4]);
+
+        let changes = set.trim(84, 30, 1);
+
+        assert_eq!(
+            changes,
+            vec![ResidencyChange {
+                key: 0,
+                action: ResidencyAction::Downgrade { first_mip: 1 },
+                size: 20,
+            }]
+        );
+
+        let changes = set.trim(20, 2, 1);
+        assert_eq!(changes[0].action, ResidencyAction::Evict);
+    }
+
+    #[test]
+    fn touch_makes_resident_again() {
+        let mut set = ResidencySet::new(true);
+        set.insert(0, ResidencyPriority::Low, vec![64, 16]);
+        set.trim(80, 0, 1);
+
+        set.advance(1);
+        assert_eq!(
+            set.touch(0),
+            Some(ResidencyChange {
+                key: 0,
+                action: ResidencyAction::MakeResident,
+                size: 80,
+            })
+        );
+        assert_eq!(set.touch(0), None);
+        assert!(set.trim(80, 0, 1).is_empty());
+    }
+}
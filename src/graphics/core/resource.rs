@@ -1,8 +1,13 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, fmt, ops::Range};
 
 use bytemuck::Pod;
+use serde::{Deserialize, Serialize};
 
-use super::types::Format;
+use super::{
+    memory::MemoryReport,
+    query::QuerySetDesc,
+    types::{Format, ResourceState},
+};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Buffer;
@@ -10,17 +15,29 @@ pub struct Buffer;
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Texture;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BufferView;
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Sampler;
 
 pub trait ResourceDevice {
     type Buffer;
+    type BufferView;
     type Texture;
     type Sampler;
+    type QuerySet;
 
     fn create_buffer<T: Pod>(&self, desc: BufferDesc, init_data: Option<&[T]>) -> Self::Buffer;
     fn destroy_buffer(&self, buffer: Self::Buffer);
 
+    fn create_buffer_view(
+        &self,
+        buffer: &Self::Buffer,
+        desc: BufferViewDesc,
+    ) -> Result<Self::BufferView, BufferViewError>;
+    fn destroy_buffer_view(&self, view: Self::BufferView);
+
     fn create_texture<T: Pod>(&self, desc: TextureDesc, init_data: Option<&[T]>) -> Self::Texture;
     fn destroy_texture(&self, buffer: Self::Texture);
 
@@ -30,9 +47,14 @@ pub trait ResourceDevice {
 
     fn create_sampler(&self, desc: SamplerDesc) -> Self::Sampler;
     fn destroy_sampler(&self, buffer: Self::Sampler);
+
+    fn create_query_set(&self, desc: QuerySetDesc) -> Self::QuerySet;
+    fn destroy_query_set(&self, set: Self::QuerySet);
+
+    fn memory_report(&self) -> MemoryReport;
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BufferDesc {
     pub name: Option<Cow<'static, str>>,
     pub size: usize,
@@ -40,7 +62,175 @@ pub struct BufferDesc {
     pub usage: BufferUsages,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BufferViewType {
+    Vertex,
+    Index,
+    ShaderResource,
+    Storage,
+}
+
+impl BufferViewType {
+    /// State the viewed buffer is transitioned to when the view is bound.
+    pub fn resource_state(self) -> ResourceState {
+        match self {
+            BufferViewType::Vertex => ResourceState::VertexBuffer,
+            BufferViewType::Index => ResourceState::IndexBuffer,
+            BufferViewType::ShaderResource => ResourceState::StorageRead,
+            BufferViewType::Storage => ResourceState::StorageWrite,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BufferViewKind {
+    /// 4-byte words addressed as a byte buffer.
+    Raw,
+    /// Elements of `stride` bytes. A stride of 0 uses `BufferDesc::stride`.
+    Structured { stride: usize },
+    /// Elements of an uncompressed, non sRGB format.
+    Typed(Format),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BufferViewDesc {
+    pub ty: BufferViewType,
+    pub kind: BufferViewKind,
+    pub first_element: usize,
+    /// Number of viewed elements, `None` covers the rest of the buffer.
+    pub count: Option<usize>,
+}
+
+impl BufferViewDesc {
+    pub fn vertex(stride: usize) -> Self {
+        Self {
+            ty: BufferViewType::Vertex,
+            kind: BufferViewKind::Structured { stride },
+            first_element: 0,
+            count: None,
+        }
+    }
+
+    pub fn index(format: Format) -> Self {
+        Self {
+            ty: BufferViewType::Index,
+            kind: BufferViewKind::Typed(format),
+            first_element: 0,
+            count: None,
+        }
+    }
+
+    pub fn element_size(&self, buffer: &BufferDesc) -> usize {
+        match self.kind {
+            BufferViewKind::Raw => 4,
+            BufferViewKind::Structured { stride: 0 } => buffer.stride,
+            BufferViewKind::Structured { stride } => stride,
+            BufferViewKind::Typed(format) => format.block_size(),
+        }
+    }
+
+    /// Checks the view against the buffer and returns the number of viewed elements.
+    pub fn validate(&self, buffer: &BufferDesc) -> Result<usize, BufferViewError> {
+        let required = match self.ty {
+            BufferViewType::Vertex => BufferUsages::Vertex,
+            BufferViewType::Index => BufferUsages::Index,
+            BufferViewType::ShaderResource | BufferViewType::Storage => BufferUsages::Storage,
+        };
+
+        if !buffer.usage.contains(required) {
+            return Err(BufferViewError::MissingUsage(required));
+        }
+
+        match (self.ty, self.kind) {
+            (BufferViewType::Vertex, BufferViewKind::Structured { .. }) => {}
+            (BufferViewType::Index, BufferViewKind::Typed(Format::R16Uint | Format::R32Uint)) => {}
+            (BufferViewType::Index, BufferViewKind::Typed(format)) => {
+                return Err(BufferViewError::UnsupportedFormat(format))
+            }
+            (BufferViewType::Vertex | BufferViewType::Index, _) => {
+                return Err(BufferViewError::InvalidKind(self.ty, self.kind))
+            }
+            (_, BufferViewKind::Typed(format))
+                if format == Format::Unknown || format.is_compressed() || format.is_srgb() =>
+            {
+                return Err(BufferViewError::UnsupportedFormat(format))
+            }
+            _ => {}
+        }
+
+        let element_size = self.element_size(buffer);
+        if element_size == 0 {
+            return Err(BufferViewError::InvalidStride);
+        }
+
+        // Ranges past `usize` can not fit in any buffer, they end at `usize::MAX`.
+        let out_of_bounds = || BufferViewError::OutOfBounds {
+            end: usize::MAX,
+            size: buffer.size,
+        };
+
+        let offset = self
+            .first_element
+            .checked_mul(element_size)
+            .ok_or_else(out_of_bounds)?;
+        let count = match self.count {
+            Some(count) => count,
+            None => buffer.size.saturating_sub(offset) / element_size,
+        };
+
+        if count == 0 {
+            return Err(BufferViewError::Empty);
+        }
+
+        let end = count
+            .checked_mul(element_size)
+            .and_then(|size| offset.checked_add(size))
+            .ok_or_else(out_of_bounds)?;
+        if end > buffer.size {
+            return Err(BufferViewError::OutOfBounds {
+                end,
+                size: buffer.size,
+            });
+        }
+
+        Ok(count)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BufferViewError {
+    MissingUsage(BufferUsages),
+    InvalidKind(BufferViewType, BufferViewKind),
+    UnsupportedFormat(Format),
+    InvalidStride,
+    Empty,
+    OutOfBounds { end: usize, size: usize },
+}
+
+impl fmt::Display for BufferViewError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BufferViewError::MissingUsage(usage) => {
+                write!(f, "buffer is missing usage {:?}", usage)
+            }
+            BufferViewError::InvalidKind(ty, kind) => {
+                write!(f, "{:?} view can not be {:?}", ty, kind)
+            }
+            BufferViewError::UnsupportedFormat(format) => {
+                write!(f, "format {:?} can not be used for this view", format)
+            }
+            BufferViewError::InvalidStride => write!(f, "view stride is zero"),
+            BufferViewError::Empty => write!(f, "view has no elements"),
+            BufferViewError::OutOfBounds { end, size } => {
+                write!(f, "view ends at {} but buffer size is {}", end, size)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BufferViewError {}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TextureDesc {
     pub name: Option<Cow<'static, str>>,
     pub ty: TextureType,
@@ -52,6 +242,107 @@ pub struct TextureDesc {
     pub usage: TextureUsages,
 }
 
+impl TextureDesc {
+    pub fn array_layers(&self) -> u32 {
+        match self.ty {
+            TextureType::D3 => 1,
+            _ => self.depth as u32,
+        }
+    }
+
+    pub fn subresource_count(&self) -> u32 {
+        self.mip_levels * self.array_layers()
+    }
+
+    pub fn subresource_index(&self, mip: u32, layer: u32) -> u32 {
+        mip + layer * self.mip_levels
+    }
+
+    pub fn max_mip_levels(&self) -> u32 {
+        let (_, _, depth) = self.mip_extent(0);
+        32 - self.width.max(self.height).max(depth).leading_zeros()
+    }
+
+    pub fn mip_extent(&self, mip: u32) -> (u32, u32, u32) {
+        let depth = match self.ty {
+            TextureType::D3 => (self.depth as u32 >> mip).max(1),
+            _ => 1,
+        };
+
+        (
+            (self.width >> mip).max(1),
+            (self.height >> mip).max(1),
+            depth,
+        )
+    }
+
+    /// Layout of every subresource when tightly packed in subresource order
+    /// (all mips of layer 0, then all mips of layer 1, ...). This is the layout
+    /// expected for texture init data.
+    pub fn subresource_layouts(&self) -> Vec<SubresourceLayout> {
+        let mut offset = 0;
+        let mut layouts = Vec::with_capacity(self.subresource_count() as usize);
+
+        for layer in 0..self.array_layers() {
+            for mip in 0..self.mip_levels {
+                let (width, height, depth) = self.mip_extent(mip);
+                let row_pitch = self.format.row_pitch(width);
+                let slice_pitch = self.format.slice_pitch(width, height);
+                let size = slice_pitch * depth as usize;
+
+                layouts.push(SubresourceLayout {
+                    mip,
+                    layer,
+                    width,
+                    height,
+                    depth,
+                    offset,
+                    row_pitch,
+                    slice_pitch,
+                    size,
+                });
+
+                offset += size;
+            }
+        }
+
+        layouts
+    }
+
+    pub fn packed_size(&self) -> usize {
+        self.subresource_layouts().iter().map(|l| l.size).sum()
+    }
+}
+
+/// Mips and array layers of a texture. Cube faces count as layers.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubresourceRange {
+    pub mips: Range<u32>,
+    pub layers: Range<u32>,
+}
+
+impl SubresourceRange {
+    pub fn mip(mip: u32, layers: Range<u32>) -> Self {
+        Self {
+            mips: mip..(mip + 1),
+            layers,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SubresourceLayout {
+    pub mip: u32,
+    pub layer: u32,
+    pub width: u32,
+    pub height: u32,
+    pub depth: u32,
+    pub offset: usize,
+    pub row_pitch: usize,
+    pub slice_pitch: usize,
+    pub size: usize,
+}
+
 #[derive(Clone, Debug)]
 pub struct TextureViewDesc {}
 
@@ -59,7 +350,7 @@ pub struct TextureViewDesc {}
 pub struct SamplerDesc {}
 
 bitflags::bitflags! {
-    #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+    #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
     pub struct BufferUsages: u32 {
         const Copy = 1 << 0;
         const Uniform = 1 << 1;
@@ -67,18 +358,21 @@ bitflags::bitflags! {
         const Index = 1 << 3;
         const Storage = 1 << 4;
         const QueryResolve = 1 << 5;
+        const Indirect = 1 << 6;
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum TextureType {
     D1,
     D2,
     D3,
+    /// `depth` holds the number of faces, a multiple of 6.
+    Cube,
 }
 
 bitflags::bitflags! {
-    #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+    #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
     pub struct TextureUsages: u32 {
         const Copy = 1 << 0;
         const Resource = 1 << 1;
@@ -88,3 +382,83 @@ bitflags::bitflags! {
         const Shared = 1 << 5;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer(size: usize, stride: usize, usage: BufferUsages) -> BufferDesc {
+        BufferDesc {
+            name: None,
+            size,
+            stride,
+            usage,
+        }
+    }
+
+    #[test]
+    fn structured_view_falls_back_to_buffer_stride() {
+        let desc = buffer(64, 16, BufferUsages::Storage);
+        let view = BufferViewDesc {
+            ty: BufferViewType::ShaderResource,
+            kind: BufferViewKind::Structured { stride: 0 },
+            first_element: 1,
+            count: None,
+        };
+
+        assert_eq!(view.validate(&desc), Ok(3));
+    }
+
+    #[test]
+    fn view_requires_usage() {
+        let desc = buffer(64, 0, BufferUsages::Copy);
+
+        assert_eq!(
+            BufferViewDesc::vertex(16).validate(&desc),
+            Err(BufferViewError::MissingUsage(BufferUsages::Vertex))
+        );
+    }
+
+    #[test]
+    fn view_must_fit_in_buffer() {
+        let desc = buffer(64, 0, BufferUsages::Storage);
+        let view = BufferViewDesc {
+            ty: BufferViewType::Storage,
+            kind: BufferViewKind::Typed(Format::Rgba32),
+            first_element: 2,
+            count: Some(3),
+        };
+
+        assert_eq!(
+            view.validate(&desc),
+            Err(BufferViewError::OutOfBounds { end: 80, size: 64 })
+        );
+
+        let overflow = BufferViewDesc {
+            first_element: usize::MAX / 8,
+            count: Some(usize::MAX / 16),
+            ..view
+        };
+        assert_eq!(
+            overflow.validate(&desc),
+            Err(BufferViewError::OutOfBounds {
+                end: usize::MAX,
+                size: 64
+            })
+        );
+    }
+
+    #[test]
+    fn index_view_requires_uint_format() {
+        let desc = buffer(64, 0, BufferUsages::Index);
+
+        assert_eq!(
+            BufferViewDesc::index(Format::R16Uint).validate(&desc),
+            Ok(32)
+        );
+        assert_eq!(
+            BufferViewDesc::index(Format::R32).validate(&desc),
+            Err(BufferViewError::UnsupportedFormat(Format::R32))
+        );
+    }
+}
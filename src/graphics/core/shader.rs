@@ -1,5 +1,378 @@
+use std::{borrow::Cow, fmt};
+
+use serde::{Deserialize, Serialize};
+
+use super::{handle::RenderHandle, types::Format};
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct ComputePipeline;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct RenderPipeline;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ShaderModule;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ShaderStage {
+    Vertex,
+    Pixel,
+    Compute,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ShaderLanguage {
+    Hlsl,
+    Wgsl,
+    Dxil,
+    SpirV,
+}
+
+bitflags::bitflags! {
+    /// Shader forms a backend accepts, see `RenderDeviceInfo::shader_languages`.
+    #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+    pub struct ShaderLanguages: u32 {
+        const Hlsl = 1 << 0;
+        const Wgsl = 1 << 1;
+        const Dxil = 1 << 2;
+        const SpirV = 1 << 3;
+    }
+}
+
+impl From<ShaderLanguage> for ShaderLanguages {
+    fn from(language: ShaderLanguage) -> Self {
+        match language {
+            ShaderLanguage::Hlsl => ShaderLanguages::Hlsl,
+            ShaderLanguage::Wgsl => ShaderLanguages::Wgsl,
+            ShaderLanguage::Dxil => ShaderLanguages::Dxil,
+            ShaderLanguage::SpirV => ShaderLanguages::SpirV,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum ShaderSource {
+    Hlsl(Cow<'static, str>),
+    Wgsl(Cow<'static, str>),
+    /// Compiled DXIL container, starting with `DXBC`.
+    Dxil(Cow<'static, [u8]>),
+    /// SPIR-V words in little endian.
+    SpirV(Cow<'static, [u8]>),
+}
+
+impl ShaderSource {
+    pub fn language(&self) -> ShaderLanguage {
+        match self {
+            ShaderSource::Hlsl(_) => ShaderLanguage::Hlsl,
+            ShaderSource::Wgsl(_) => ShaderLanguage::Wgsl,
+            ShaderSource::Dxil(_) => ShaderLanguage::Dxil,
+            ShaderSource::SpirV(_) => ShaderLanguage::SpirV,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryPoint {
+    pub name: Cow<'static, str>,
+    pub stage: ShaderStage,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ShaderModuleDesc {
+    pub name: Option<Cow<'static, str>>,
+    pub source: ShaderSource,
+    pub entry_points: Vec<EntryPoint>,
+}
+
+const DXIL_MAGIC: &[u8] = b"DXBC";
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+
+impl ShaderModuleDesc {
+    /// Checks the module against the forms a backend accepts, in `supported`.
+    pub fn validate(&self, supported: ShaderLanguages) -> Result<(), ShaderError> {
+        let language = self.source.language();
+        if !supported.contains(language.into()) {
+            return Err(ShaderError::UnsupportedLanguage {
+                language,
+                supported,
+            });
+        }
+
+        match &self.source {
+            ShaderSource::Dxil(bytes) if !bytes.starts_with(DXIL_MAGIC) => {
+                return Err(ShaderError::InvalidBytecode(language))
+            }
+            ShaderSource::SpirV(bytes)
+                if bytes.len() % 4 != 0 || !bytes.starts_with(&SPIRV_MAGIC.to_le_bytes()) =>
+            {
+                return Err(ShaderError::InvalidBytecode(language))
+            }
+            _ => {}
+        }
+
+        if self.entry_points.is_empty() {
+            return Err(ShaderError::NoEntryPoints);
+        }
+
+        for (i, entry) in self.entry_points.iter().enumerate() {
+            if self.entry_points[..i].iter().any(|e| e.name == entry.name) {
+                return Err(ShaderError::DuplicateEntryPoint(entry.name.to_string()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ShaderError {
+    UnsupportedLanguage {
+        language: ShaderLanguage,
+        supported: ShaderLanguages,
+    },
+    InvalidBytecode(ShaderLanguage),
+    NoEntryPoints,
+    DuplicateEntryPoint(String),
+    /// The backend compiler rejected `entry`.
+    Compilation {
+        entry: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderError::UnsupportedLanguage {
+                language,
+                supported,
+            } => write!(
+                f,
+                "{:?} shaders are not supported by this backend, it accepts {:?}",
+                language, supported
+            ),
+            ShaderError::InvalidBytecode(language) => {
+                write!(f, "bytes are not a {:?} module", language)
+            }
+            ShaderError::NoEntryPoints => write!(f, "module has no entry points"),
+            ShaderError::DuplicateEntryPoint(name) => {
+                write!(f, "entry point {} is listed twice", name)
+            }
+            ShaderError::Compilation { entry, message } => {
+                write!(f, "failed to compile entry point {}: {}", entry, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+/// Root parameter of a pipeline. Encoders address bindings by their index in
+/// [`PipelineLayout::bindings`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PipelineBinding {
+    /// A shader resource view at `t<register>`.
+    ShaderResource { register: u32, space: u32 },
+    /// An unordered access view at `u<register>`.
+    Storage { register: u32, space: u32 },
+    /// `count` 32 bit values at `b<register>`, set with `set_constants`.
+    Constants {
+        register: u32,
+        space: u32,
+        count: u32,
+    },
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PipelineLayout {
+    pub bindings: Vec<PipelineBinding>,
+}
+
+/// Entry point `name` of a bound shader module.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShaderEntry {
+    pub module: RenderHandle<ShaderModule>,
+    pub name: Cow<'static, str>,
+}
+
+impl ShaderEntry {
+    /// Index of the entry point in `entry_points` of the module, which must run at `stage`.
+    pub fn find<'a>(
+        &self,
+        entry_points: impl IntoIterator<Item = &'a EntryPoint>,
+        stage: ShaderStage,
+    ) -> Result<usize, PipelineError> {
+        let (index, entry) = entry_points
+            .into_iter()
+            .enumerate()
+            .find(|(_, e)| e.name == self.name)
+            .ok_or_else(|| PipelineError::MissingEntryPoint(self.name.to_string()))?;
+
+        if entry.stage != stage {
+            return Err(PipelineError::WrongStage {
+                entry: self.name.to_string(),
+                expected: stage,
+                found: entry.stage,
+            });
+        }
+
+        Ok(index)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ComputePipelineDesc {
+    pub name: Option<Cow<'static, str>>,
+    pub layout: PipelineLayout,
+    pub shader: ShaderEntry,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PrimitiveTopology {
+    PointList,
+    LineList,
+    LineStrip,
+    #[default]
+    TriangleList,
+    TriangleStrip,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VertexSemantic {
+    Position,
+    Normal,
+    Tangent,
+    Color,
+    TexCoord,
+}
+
+/// Vertex shader input `semantic<index>`, read at `offset` from the vertex
+/// buffer bound at `slot`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VertexAttribute {
+    pub semantic: VertexSemantic,
+    pub index: u8,
+    pub format: Format,
+    pub slot: u32,
+    pub offset: u32,
+}
+
+/// Blending is off and no faces are culled. With a `depth_format` the depth
+/// test passes closer fragments and writes their depth.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RenderPipelineDesc {
+    pub name: Option<Cow<'static, str>>,
+    pub layout: PipelineLayout,
+    pub vertex: ShaderEntry,
+    pub pixel: Option<ShaderEntry>,
+    pub vertex_attributes: Vec<VertexAttribute>,
+    pub topology: PrimitiveTopology,
+    pub color_formats: Vec<Format>,
+    pub depth_format: Option<Format>,
+}
+
+/// Render targets a pipeline can write at once.
+pub const MAX_COLOR_TARGETS: usize = 8;
+
+impl RenderPipelineDesc {
+    pub fn validate(&self) -> Result<(), PipelineError> {
+        if self.color_formats.len() > MAX_COLOR_TARGETS {
+            return Err(PipelineError::TooManyColorTargets(self.color_formats.len()));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PipelineError {
+    /// A shader entry names a module that is not bound.
+    MissingModule,
+    MissingEntryPoint(String),
+    WrongStage {
+        entry: String,
+        expected: ShaderStage,
+        found: ShaderStage,
+    },
+    TooManyColorTargets(usize),
+    /// The backend failed to create the pipeline state.
+    Creation(String),
+}
+
+impl fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PipelineError::MissingModule => write!(f, "shader module is not bound"),
+            PipelineError::MissingEntryPoint(name) => {
+                write!(f, "shader module has no entry point {}", name)
+            }
+            PipelineError::WrongStage {
+                entry,
+                expected,
+                found,
+            } => write!(
+                f,
+                "entry point {} is a {:?} shader, expected {:?}",
+                entry, found, expected
+            ),
+            PipelineError::TooManyColorTargets(count) => write!(
+                f,
+                "pipeline writes {} color targets, at most {} are supported",
+                count, MAX_COLOR_TARGETS
+            ),
+            PipelineError::Creation(reason) => write!(f, "failed to create pipeline: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for PipelineError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module(source: ShaderSource, entry_points: &[&'static str]) -> ShaderModuleDesc {
+        ShaderModuleDesc {
+            name: None,
+            source,
+            entry_points: entry_points
+                .iter()
+                .map(|&name| EntryPoint {
+                    name: name.into(),
+                    stage: ShaderStage::Compute,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn validate_reports_unsupported_forms_and_bad_entry_points() {
+        let dx = ShaderLanguages::Hlsl | ShaderLanguages::Dxil;
+
+        assert_eq!(
+            module(ShaderSource::Wgsl("".into()), &["main"]).validate(dx),
+            Err(ShaderError::UnsupportedLanguage {
+                language: ShaderLanguage::Wgsl,
+                supported: dx
+            })
+        );
+        assert_eq!(
+            module(ShaderSource::Dxil(b"SPIR".as_slice().into()), &["main"]).validate(dx),
+            Err(ShaderError::InvalidBytecode(ShaderLanguage::Dxil))
+        );
+        assert_eq!(
+            module(ShaderSource::Hlsl("".into()), &[]).validate(dx),
+            Err(ShaderError::NoEntryPoints)
+        );
+        assert_eq!(
+            module(ShaderSource::Hlsl("".into()), &["main", "main"]).validate(dx),
+            Err(ShaderError::DuplicateEntryPoint("main".to_string()))
+        );
+
+        let spirv = SPIRV_MAGIC.to_le_bytes().to_vec();
+        assert_eq!(
+            module(ShaderSource::SpirV(spirv.into()), &["main"]).validate(ShaderLanguages::all()),
+            Ok(())
+        );
+    }
+}
@@ -0,0 +1,526 @@
+use std::{borrow::Cow, fmt, ops::Range};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    commands::{CommandBufferType, LoadOp, Scissor, Viewport},
+    handle::RenderHandle,
+    resource::{Buffer, BufferView, SubresourceRange, Texture},
+    shader::{ComputePipeline, RenderPipeline},
+    transfer::{BufferTextureLayout, ClearValue, TextureRegion},
+    types::ResourceState,
+};
+
+/// Resource referenced by a [`StreamCommand`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StreamRef {
+    Buffer(RenderHandle<Buffer>),
+    BufferView(RenderHandle<BufferView>),
+    Texture(RenderHandle<Texture>),
+    RenderPipeline(RenderHandle<RenderPipeline>),
+    ComputePipeline(RenderHandle<ComputePipeline>),
+    /// Created on the device directly instead of bound to a handle.
+    Unbound,
+}
+
+impl StreamRef {
+    pub fn buffer(self) -> Option<RenderHandle<Buffer>> {
+        match self {
+            StreamRef::Buffer(handle) => Some(handle),
+            _ => None,
+        }
+    }
+
+    pub fn buffer_view(self) -> Option<RenderHandle<BufferView>> {
+        match self {
+            StreamRef::BufferView(handle) => Some(handle),
+            _ => None,
+        }
+    }
+
+    pub fn texture(self) -> Option<RenderHandle<Texture>> {
+        match self {
+            StreamRef::Texture(handle) => Some(handle),
+            _ => None,
+        }
+    }
+
+    pub fn render_pipeline(self) -> Option<RenderHandle<RenderPipeline>> {
+        match self {
+            StreamRef::RenderPipeline(handle) => Some(handle),
+            _ => None,
+        }
+    }
+
+    pub fn compute_pipeline(self) -> Option<RenderHandle<ComputePipeline>> {
+        match self {
+            StreamRef::ComputePipeline(handle) => Some(handle),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for StreamRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (kind, index, gen) = match self {
+            StreamRef::Buffer(h) => ("buffer", h.idx(), h.gen()),
+            StreamRef::BufferView(h) => ("buffer_view", h.idx(), h.gen()),
+            StreamRef::Texture(h) => ("texture", h.idx(), h.gen()),
+            StreamRef::RenderPipeline(h) => ("render_pipeline", h.idx(), h.gen()),
+            StreamRef::ComputePipeline(h) => ("compute_pipeline", h.idx(), h.gen()),
+            StreamRef::Unbound => return write!(f, "<unbound>"),
+        };
+
+        match gen {
+            0 => write!(f, "{}#{}", kind, index),
+            gen => write!(f, "{}#{}.{}", kind, index, gen),
+        }
+    }
+}
+
+/// Backend-neutral form of the GPU work recorded into a command buffer.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum StreamCommand {
+    /// Only transitions that change the state of the resource are recorded.
+    Barrier {
+        resource: StreamRef,
+        range: Option<SubresourceRange>,
+        state: ResourceState,
+    },
+    CopyBuffer {
+        dst: StreamRef,
+        src: StreamRef,
+    },
+    CopyTexture {
+        dst: StreamRef,
+        src: StreamRef,
+    },
+    /// Carries the uploaded data so captures can replay it.
+    UploadTexture {
+        dst: StreamRef,
+        src: StreamRef,
+        data: Vec<u8>,
+    },
+    CopyBufferRegion {
+        dst: StreamRef,
+        dst_offset: usize,
+        src: StreamRef,
+        src_offset: usize,
+        size: usize,
+    },
+    CopyBufferToTexture {
+        dst: StreamRef,
+        region: TextureRegion,
+        src: StreamRef,
+        layout: BufferTextureLayout,
+    },
+    CopyTextureToBuffer {
+        dst: StreamRef,
+        layout: BufferTextureLayout,
+        src: StreamRef,
+        region: TextureRegion,
+    },
+    CopyTextureRegion {
+        dst: StreamRef,
+        dst_region: TextureRegion,
+        src: StreamRef,
+        src_region: TextureRegion,
+    },
+    FillBuffer {
+        dst: StreamRef,
+        range: Range<usize>,
+        value: u32,
+    },
+    ClearTexture {
+        dst: StreamRef,
+        range: SubresourceRange,
+        value: ClearValue,
+    },
+    GenerateMips(StreamRef),
+    /// Hands the resource to another queue.
+    Release {
+        resource: StreamRef,
+        queue: CommandBufferType,
+    },
+    BeginRenderPass {
+        colors: Vec<(StreamRef, LoadOp<[f32; 4]>)>,
+        depth: Option<(StreamRef, LoadOp<f32>)>,
+        /// Buffer views whose buffers were transitioned before the pass.
+        views: Vec<StreamRef>,
+    },
+    EndRenderPass,
+    SetViewport(Viewport),
+    SetScissor(Scissor),
+    SetStencilReference(u32),
+    SetBlendConstants([f32; 4]),
+    SetPipeline(StreamRef),
+    SetVertexBuffer {
+        slot: u32,
+        view: StreamRef,
+    },
+    SetIndexBuffer(StreamRef),
+    SetBufferView {
+        slot: u32,
+        view: StreamRef,
+    },
+    SetConstants {
+        slot: u32,
+        constants: Vec<u32>,
+    },
+    /// `base_vertex` is only set for indexed draws.
+    Draw {
+        vertices: Range<u32>,
+        instances: Range<u32>,
+        base_vertex: Option<i32>,
+    },
+    DrawIndirect {
+        args: StreamRef,
+        offset: usize,
+        count: Option<(StreamRef, usize)>,
+        max_draws: u32,
+        indexed: bool,
+    },
+    Dispatch([u32; 3]),
+    DispatchIndirect {
+        args: StreamRef,
+        offset: usize,
+    },
+    ExecuteBundle(Option<Cow<'static, str>>),
+    PushDebugGroup(String),
+    PopDebugGroup,
+    Marker(String),
+}
+
+impl StreamCommand {
+    /// Every resource the command references, in field order.
+    pub fn refs(&self) -> Vec<StreamRef> {
+        match self {
+            StreamCommand::Barrier { resource, .. } => vec![*resource],
+            StreamCommand::CopyBuffer { dst, src }
+            | StreamCommand::CopyTexture { dst, src }
+            | StreamCommand::UploadTexture { dst, src, .. }
+            | StreamCommand::CopyBufferRegion { dst, src, .. }
+            | StreamCommand::CopyBufferToTexture { dst, src, .. }
+            | StreamCommand::CopyTextureToBuffer { dst, src, .. }
+            | StreamCommand::CopyTextureRegion { dst, src, .. } => vec![*dst, *src],
+            StreamCommand::FillBuffer { dst, .. } | StreamCommand::ClearTexture { dst, .. } => {
+                vec![*dst]
+            }
+            StreamCommand::GenerateMips(texture) => vec![*texture],
+            StreamCommand::Release { resource, .. } => vec![*resource],
+            StreamCommand::BeginRenderPass {
+                colors,
+                depth,
+                views,
+            } => colors
+                .iter()
+                .map(|(texture, _)| *texture)
+                .chain(depth.map(|(texture, _)| texture))
+                .chain(views.iter().copied())
+                .collect(),
+            StreamCommand::SetPipeline(resource)
+            | StreamCommand::SetIndexBuffer(resource)
+            | StreamCommand::SetVertexBuffer { view: resource, .. }
+            | StreamCommand::SetBufferView { view: resource, .. }
+            | StreamCommand::DispatchIndirect { args: resource, .. } => vec![*resource],
+            StreamCommand::DrawIndirect { args, count, .. } => std::iter::once(*args)
+                .chain(count.map(|(c, _)| c))
+                .collect(),
+            StreamCommand::EndRenderPass
+            | StreamCommand::SetViewport(_)
+            | StreamCommand::SetScissor(_)
+            | StreamCommand::SetStencilReference(_)
+            | StreamCommand::SetBlendConstants(_)
+            | StreamCommand::SetConstants { .. }
+            | StreamCommand::Draw { .. }
+            | StreamCommand::Dispatch(_)
+            | StreamCommand::ExecuteBundle(_)
+            | StreamCommand::PushDebugGroup(_)
+            | StreamCommand::PopDebugGroup
+            | StreamCommand::Marker(_) => vec![],
+        }
+    }
+}
+
+fn write_range(f: &mut fmt::Formatter<'_>, range: &SubresourceRange) -> fmt::Result {
+    write!(f, "mips {:?} layers {:?}", range.mips, range.layers)
+}
+
+fn write_region(f: &mut fmt::Formatter<'_>, region: &TextureRegion) -> fmt::Result {
+    write!(
+        f,
+        "mip {} layer {} at {:?} size {:?}",
+        region.mip, region.layer, region.origin, region.extent
+    )
+}
+
+fn write_layout(f: &mut fmt::Formatter<'_>, layout: &BufferTextureLayout) -> fmt::Result {
+    write!(
+        f,
+        "+{} pitch {}/{}",
+        layout.offset, layout.row_pitch, layout.slice_pitch
+    )
+}
+
+impl fmt::Display for StreamCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StreamCommand::Barrier {
+                resource,
+                range,
+                state,
+            } => {
+                write!(f, "barrier {} ", resource)?;
+                if let Some(range) = range {
+                    write_range(f, range)?;
+                    write!(f, " ")?;
+                }
+                write!(f, "-> {:?}", state)
+            }
+            StreamCommand::CopyBuffer { dst, src } => write!(f, "copy_buffer {} <- {}", dst, src),
+            StreamCommand::CopyTexture { dst, src } => {
+                write!(f, "copy_texture {} <- {}", dst, src)
+            }
+            StreamCommand::UploadTexture { dst, src, data } => {
+                write!(f, "upload_texture {} <- {} {} bytes", dst, src, data.len())
+            }
+            StreamCommand::CopyBufferRegion {
+                dst,
+                dst_offset,
+                src,
+                src_offset,
+                size,
+            } => write!(
+                f,
+                "copy_buffer_region {}+{} <- {}+{} {} bytes",
+                dst, dst_offset, src, src_offset, size
+            ),
+            StreamCommand::CopyBufferToTexture {
+                dst,
+                region,
+                src,
+                layout,
+            } => {
+                write!(f, "copy_buffer_to_texture {} ", dst)?;
+                write_region(f, region)?;
+                write!(f, " <- {}", src)?;
+                write_layout(f, layout)
+            }
+            StreamCommand::CopyTextureToBuffer {
+                dst,
+                layout,
+                src,
+                region,
+            } => {
+                write!(f, "copy_texture_to_buffer {}", dst)?;
+                write_layout(f, layout)?;
+                write!(f, " <- {} ", src)?;
+                write_region(f, region)
+            }
+            StreamCommand::CopyTextureRegion {
+                dst,
+                dst_region,
+                src,
+                src_region,
+            } => {
+                write!(f, "copy_texture_region {} ", dst)?;
+                write_region(f, dst_region)?;
+                write!(f, " <- {} ", src)?;
+                write_region(f, src_region)
+            }
+            StreamCommand::FillBuffer { dst, range, value } => {
+                write!(f, "fill_buffer {} {:?} = {:#010x}", dst, range, value)
+            }
+            StreamCommand::ClearTexture { dst, range, value } => {
+                write!(f, "clear_texture {} ", dst)?;
+                write_range(f, range)?;
+                write!(f, " = {:?}", value)
+            }
+            StreamCommand::GenerateMips(texture) => write!(f, "generate_mips {}", texture),
+            StreamCommand::Release { resource, queue } => {
+                write!(f, "release {} -> {:?}", resource, queue)
+            }
+            StreamCommand::BeginRenderPass {
+                colors,
+                depth,
+                views,
+            } => {
+                write!(f, "begin_render_pass")?;
+                for (texture, load) in colors {
+                    write!(f, " {} {:?}", texture, load)?;
+                }
+                if let Some((texture, load)) = depth {
+                    write!(f, " depth {} {:?}", texture, load)?;
+                }
+                for view in views {
+                    write!(f, " view {}", view)?;
+                }
+                Ok(())
+            }
+            StreamCommand::EndRenderPass => write!(f, "end_render_pass"),
+            StreamCommand::SetViewport(v) => write!(
+                f,
+                "set_viewport {},{} {}x{} depth {}..{}",
+                v.x, v.y, v.width, v.height, v.min_depth, v.max_depth
+            ),
+            StreamCommand::SetScissor(s) => {
+                write!(f, "set_scissor {},{} {}x{}", s.x, s.y, s.width, s.height)
+            }
+            StreamCommand::SetStencilReference(reference) => {
+                write!(f, "set_stencil_reference {}", reference)
+            }
+            StreamCommand::SetBlendConstants(constants) => {
+                write!(f, "set_blend_constants {:?}", constants)
+            }
+            StreamCommand::SetPipeline(pipeline) => write!(f, "set_pipeline {}", pipeline),
+            StreamCommand::SetVertexBuffer { slot, view } => {
+                write!(f, "set_vertex_buffer {} {}", slot, view)
+            }
+            StreamCommand::SetIndexBuffer(view) => write!(f, "set_index_buffer {}", view),
+            StreamCommand::SetBufferView { slot, view } => {
+                write!(f, "set_buffer_view {} {}", slot, view)
+            }
+            StreamCommand::SetConstants { slot, constants } => {
+                write!(f, "set_constants {} {:?}", slot, constants)
+            }
+            StreamCommand::Draw {
+                vertices,
+                instances,
+                base_vertex: None,
+            } => write!(f, "draw vertices {:?} instances {:?}", vertices, instances),
+            StreamCommand::Draw {
+                vertices,
+                instances,
+                base_vertex: Some(base_vertex),
+            } => write!(
+                f,
+                "draw_indexed indices {:?} base_vertex {} instances {:?}",
+                vertices, base_vertex, instances
+            ),
+            StreamCommand::DrawIndirect {
+                args,
+                offset,
+                count,
+                max_draws,
+                indexed,
+            } => {
+                let name = match indexed {
+                    true => "draw_indexed_indirect",
+                    false => "draw_indirect",
+                };
+                write!(f, "{} {}+{}", name, args, offset)?;
+                if let Some((count, count_offset)) = count {
+                    write!(f, " count {}+{}", count, count_offset)?;
+                }
+                write!(f, " max {}", max_draws)
+            }
+            StreamCommand::Dispatch([x, y, z]) => write!(f, "dispatch {}x{}x{}", x, y, z),
+            StreamCommand::DispatchIndirect { args, offset } => {
+                write!(f, "dispatch_indirect {}+{}", args, offset)
+            }
+            StreamCommand::ExecuteBundle(Some(name)) => write!(f, "execute_bundle {:?}", name),
+            StreamCommand::ExecuteBundle(None) => write!(f, "execute_bundle"),
+            StreamCommand::PushDebugGroup(name) => write!(f, "push_debug_group {:?}", name),
+            StreamCommand::PopDebugGroup => write!(f, "pop_debug_group"),
+            StreamCommand::Marker(name) => write!(f, "marker {:?}", name),
+        }
+    }
+}
+
+/// Commands mirrored from a command buffer, in recording order.
+///
+/// Displays as one command per line, indented inside render passes and debug
+/// groups, so the work of a feature can be compared against a snapshot.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CommandStream {
+    commands: Vec<StreamCommand>,
+}
+
+impl CommandStream {
+    pub fn push(&mut self, command: StreamCommand) {
+        self.commands.push(command);
+    }
+
+    pub fn commands(&self) -> &[StreamCommand] {
+        &self.commands
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+}
+
+impl fmt::Display for CommandStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut depth = 0usize;
+
+        for command in &self.commands {
+            if matches!(
+                command,
+                StreamCommand::EndRenderPass | StreamCommand::PopDebugGroup
+            ) {
+                depth = depth.saturating_sub(1);
+            }
+
+            writeln!(f, "{:indent$}{}", "", command, indent = depth * 2)?;
+
+            if matches!(
+                command,
+                StreamCommand::BeginRenderPass { .. } | StreamCommand::PushDebugGroup(_)
+            ) {
+                depth += 1;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dumps_nested_passes_and_groups_as_indented_text() {
+        let mut stream = CommandStream::default();
+        stream.push(StreamCommand::PushDebugGroup("shadows".to_owned()));
+        stream.push(StreamCommand::Barrier {
+            resource: StreamRef::Texture(RenderHandle::new(2, 1)),
+            range: Some(SubresourceRange::mip(0, 0..1)),
+            state: ResourceState::RenderTarget,
+        });
+        stream.push(StreamCommand::BeginRenderPass {
+            colors: vec![(
+                StreamRef::Texture(RenderHandle::new(2, 1)),
+                LoadOp::Clear([0.0, 0.0, 0.0, 1.0]),
+            )],
+            depth: None,
+            views: vec![],
+        });
+        stream.push(StreamCommand::SetPipeline(StreamRef::Unbound));
+        stream.push(StreamCommand::Draw {
+            vertices: 0..3,
+            instances: 0..1,
+            base_vertex: None,
+        });
+        stream.push(StreamCommand::EndRenderPass);
+        stream.push(StreamCommand::PopDebugGroup);
+        stream.push(StreamCommand::FillBuffer {
+            dst: StreamRef::Buffer(RenderHandle::new(0, 0)),
+            range: 0..16,
+            value: 42,
+        });
+
+        assert_eq!(
+            stream.to_string(),
+            "push_debug_group \"shadows\"\n\
+             \x20 barrier texture#2.1 mips 0..1 layers 0..1 -> RenderTarget\n\
+             \x20 begin_render_pass texture#2.1 Clear([0.0, 0.0, 0.0, 1.0])\n\
+             \x20   set_pipeline <unbound>\n\
+             \x20   draw vertices 0..3 instances 0..1\n\
+             \x20 end_render_pass\n\
+             pop_debug_group\n\
+             fill_buffer buffer#0 0..16 = 0x0000002a\n"
+        );
+    }
+}
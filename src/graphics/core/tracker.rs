@@ -0,0 +1,216 @@
+use super::resource::SubresourceRange;
+
+/// Transition produced by [`StateTracker`]. `subresource` is `None` when the
+/// whole resource changes state at once.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StateTransition<S> {
+    pub subresource: Option<u32>,
+    pub before: S,
+    pub after: S,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum States<S> {
+    Uniform(S),
+    PerSubresource(Vec<S>),
+}
+
+/// Tracks the state of every mip and array layer of a resource. Subresources
+/// are indexed like [`TextureDesc::subresource_index`], and the tracker keeps a
+/// single state while all of them agree.
+///
+/// [`TextureDesc::subresource_index`]: super::resource::TextureDesc::subresource_index
+#[derive(Clone, Debug)]
+pub struct StateTracker<S> {
+    mip_levels: u32,
+    array_layers: u32,
+    states: States<S>,
+}
+
+impl<S: Copy + PartialEq> StateTracker<S> {
+    pub fn new(mip_levels: u32, array_layers: u32, initial: S) -> Self {
+        Self {
+            mip_levels: mip_levels.max(1),
+            array_layers: array_layers.max(1),
+            states: States::Uniform(initial),
+        }
+    }
+
+    pub fn subresource_count(&self) -> u32 {
+        self.mip_levels * self.array_layers
+    }
+
+    /// State shared by every subresource, `None` if they differ.
+    pub fn uniform_state(&self) -> Option<S> {
+        match &self.states {
+            States::Uniform(state) => Some(*state),
+            States::PerSubresource(_) => None,
+        }
+    }
+
+    pub fn state(&self, mip: u32, layer: u32) -> S {
+        match &self.states {
+            States::Uniform(state) => *state,
+            States::PerSubresource(states) => states[self.index(mip, layer) as usize],
+        }
+    }
+
+    /// Moves `range` (or the whole resource) into `after` and returns the
+    /// transitions needed to get there. Subresources already in `after` are skipped.
+    pub fn transition(
+        &mut self,
+        range: Option<SubresourceRange>,
+        after: S,
+    ) -> Vec<StateTransition<S>> {
+        let range = range.filter(|r| !self.is_full(r));
+
+        let Some(range) = range else {
+            return match std::mem::replace(&mut self.states, States::Uniform(after)) {
+                States::Uniform(before) if before == after => vec![],
+                States::Uniform(before) => vec![StateTransition {
+                    subresource: None,
+                    before,
+                    after,
+                }],
+                States::PerSubresource(states) => states
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(_, before)| *before != after)
+                    .map(|(i, before)| StateTransition {
+                        subresource: Some(i as u32),
+                        before,
+                        after,
+                    })
+                    .collect(),
+            };
+        };
+
+        let count = self.subresource_count() as usize;
+        let index = |mip: u32, layer: u32| self.index(mip, layer);
+        let indices = range
+            .layers
+            .clone()
+            .filter(|layer| *layer < self.array_layers)
+            .flat_map(|layer| {
+                range
+                    .mips
+                    .clone()
+                    .filter(|mip| *mip < self.mip_levels)
+                    .map(move |mip| index(mip, layer))
+            })
+            .collect::<Vec<_>>();
+
+        if let States::Uniform(state) = self.states {
+            if state == after {
+                return vec![];
+            }
+
+            self.states = States::PerSubresource(vec![state; count]);
+        }
+
+        let States::PerSubresource(states) = &mut self.states else {
+            unreachable!()
+        };
+
+        let transitions = indices
+            .into_iter()
+            .filter_map(|i| {
+                let before = std::mem::replace(&mut states[i as usize], after);
+
+                (before != after).then_some(StateTransition {
+                    subresource: Some(i),
+                    before,
+                    after,
+                })
+            })
+            .collect();
+
+        if states.iter().all(|s| *s == after) {
+            self.states = States::Uniform(after);
+        }
+
+        transitions
+    }
+
+    fn index(&self, mip: u32, layer: u32) -> u32 {
+        mip + layer * self.mip_levels
+    }
+
+    fn is_full(&self, range: &SubresourceRange) -> bool {
+        range.mips.start == 0
+            && range.mips.end >= self.mip_levels
+            && range.layers.start == 0
+            && range.layers.end >= self.array_layers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    enum State {
+        Common,
+        Read,
+        Write,
+    }
+
+    #[test]
+    fn whole_resource_transition_is_single_barrier() {
+        let mut tracker = StateTracker::new(4, 2, State::Common);
+
+        assert_eq!(
+            tracker.transition(None, State::Read),
+            vec![StateTransition {
+                subresource: None,
+                before: State::Common,
+                after: State::Read,
+            }]
+        );
+        assert!(tracker.transition(None, State::Read).is_empty());
+    }
+
+    #[test]
+    fn mip_transition_only_touches_that_mip() {
+        let mut tracker = StateTracker::new(3, 2, State::Write);
+
+        let transitions = tracker.transition(Some(SubresourceRange::mip(1, 0..2)), State::Read);
+
+        assert_eq!(
+            transitions
+                .iter()
+                .map(|t| t.subresource)
+                .collect::<Vec<_>>(),
+            vec![Some(1), Some(4)]
+        );
+        assert_eq!(tracker.uniform_state(), None);
+        assert_eq!(tracker.state(0, 1), State::Write);
+        assert_eq!(tracker.state(1, 1), State::Read);
+    }
+
+    #[test]
+    fn collapses_back_to_uniform() {
+        let mut tracker = StateTracker::new(2, 1, State::Common);
+
+        tracker.transition(Some(SubresourceRange::mip(0, 0..1)), State::Read);
+        tracker.transition(Some(SubresourceRange::mip(1, 0..1)), State::Read);
+
+        assert_eq!(tracker.uniform_state(), Some(State::Read));
+    }
+
+    #[test]
+    fn whole_transition_from_mixed_states_skips_matching_subresources() {
+        let mut tracker = StateTracker::new(2, 1, State::Common);
+        tracker.transition(Some(SubresourceRange::mip(0, 0..1)), State::Write);
+
+        assert_eq!(
+            tracker.transition(None, State::Write),
+            vec![StateTransition {
+                subresource: Some(1),
+                before: State::Common,
+                after: State::Write,
+            }]
+        );
+        assert_eq!(tracker.uniform_state(), Some(State::Write));
+    }
+}
@@ -0,0 +1,394 @@
+use std::{fmt, ops::Range};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    commands::CommandBufferType,
+    resource::{BufferDesc, BufferUsages, TextureDesc, TextureType, TextureUsages},
+    types::{Format, ResourceState},
+};
+
+/// Part of a single mip of one array layer or cube face. Origin and extent are
+/// in texels and must be block aligned for compressed formats.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextureRegion {
+    pub mip: u32,
+    pub layer: u32,
+    pub origin: [u32; 3],
+    pub extent: [u32; 3],
+}
+
+impl TextureRegion {
+    pub fn mip(desc: &TextureDesc, mip: u32, layer: u32) -> Self {
+        let (width, height, depth) = desc.mip_extent(mip);
+
+        Self {
+            mip,
+            layer,
+            origin: [0; 3],
+            extent: [width, height, depth],
+        }
+    }
+
+    pub fn validate(&self, desc: &TextureDesc) -> Result<(), TransferError> {
+        if self.mip >= desc.mip_levels || self.layer >= desc.array_layers() {
+            return Err(TransferError::InvalidRegion);
+        }
+
+        let (width, height, depth) = desc.mip_extent(self.mip);
+        let (bw, bh) = desc.format.block_dimensions();
+
+        for axis in 0..3 {
+            let (origin, extent) = (self.origin[axis], self.extent[axis]);
+            let (size, block) = [(width, bw), (height, bh), (depth, 1)][axis];
+
+            if extent == 0 || origin + extent > size {
+                return Err(TransferError::InvalidRegion);
+            }
+
+            if origin % block != 0 {
+                return Err(TransferError::Misaligned {
+                    value: origin as usize,
+                    alignment: block as usize,
+                });
+            }
+
+            // Partial blocks are only allowed at the edge of the mip.
+            if extent % block != 0 && origin + extent != size {
+                return Err(TransferError::Misaligned {
+                    value: extent as usize,
+                    alignment: block as usize,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Placement of texel data in a buffer for buffer/texture copies.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BufferTextureLayout {
+    pub offset: usize,
+    /// Distance between rows of texel blocks.
+    pub row_pitch: usize,
+    /// Distance between depth slices.
+    pub slice_pitch: usize,
+}
+
+impl BufferTextureLayout {
+    /// Tightly packed rows and slices of `extent` starting at `offset`.
+    pub fn packed(offset: usize, format: Format, extent: [u32; 3]) -> Self {
+        Self {
+            offset,
+            row_pitch: format.row_pitch(extent[0]),
+            slice_pitch: format.slice_pitch(extent[0], extent[1]),
+        }
+    }
+
+    pub fn validate(
+        &self,
+        buffer: &BufferDesc,
+        format: Format,
+        extent: [u32; 3],
+    ) -> Result<(), TransferError> {
+        let (_, bh) = format.block_dimensions();
+
+        if self.row_pitch < format.row_pitch(extent[0])
+            || self.slice_pitch < self.row_pitch * extent[1].div_ceil(bh) as usize
+        {
+            return Err(TransferError::InvalidRegion);
+        }
+
+        let (rows, row_size) = region_rows(format, [0; 3], extent, *self);
+        let end = rows.last().map_or(self.offset, |row| row + row_size);
+        if end > buffer.size {
+            return Err(TransferError::OutOfBounds {
+                end,
+                size: buffer.size,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ClearValue {
+    /// Used for render target and storage textures.
+    Color([f32; 4]),
+    DepthStencil {
+        depth: f32,
+        stencil: u8,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransferError {
+    InvalidRegion,
+    OutOfBounds {
+        end: usize,
+        size: usize,
+    },
+    Misaligned {
+        value: usize,
+        alignment: usize,
+    },
+    FormatMismatch,
+    /// Whole resource copies need the same type, extent, layers and mips.
+    ShapeMismatch,
+    /// The backend can not record the operation for this resource or queue.
+    Unsupported(&'static str),
+    /// A resource handle passed to a dynamic encoder is not bound.
+    DeadHandle,
+}
+
+impl fmt::Display for TransferError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransferError::InvalidRegion => write!(f, "region is outside of the resource"),
+            TransferError::OutOfBounds { end, size } => {
+                write!(f, "transfer ends at {} but resource size is {}", end, size)
+            }
+            TransferError::Misaligned { value, alignment } => {
+                write!(f, "{} is not aligned to {}", value, alignment)
+            }
+            TransferError::FormatMismatch => write!(f, "source and destination formats differ"),
+            TransferError::ShapeMismatch => {
+                write!(f, "source and destination extents, layers or mips differ")
+            }
+            TransferError::Unsupported(reason) => write!(f, "unsupported transfer: {}", reason),
+            TransferError::DeadHandle => write!(f, "transfer resource handle is not bound"),
+        }
+    }
+}
+
+impl std::error::Error for TransferError {}
+
+pub fn validate_buffer_range(
+    buffer: &BufferDesc,
+    range: &Range<usize>,
+) -> Result<(), TransferError> {
+    if range.start > range.end {
+        return Err(TransferError::InvalidRegion);
+    }
+
+    if range.end > buffer.size {
+        return Err(TransferError::OutOfBounds {
+            end: range.end,
+            size: buffer.size,
+        });
+    }
+
+    Ok(())
+}
+
+/// Whole texture copies map to `CopyResource`, which needs identical textures
+/// apart from their usage.
+pub fn validate_texture_copy(dst: &TextureDesc, src: &TextureDesc) -> Result<(), TransferError> {
+    if dst.format != src.format {
+        return Err(TransferError::FormatMismatch);
+    }
+
+    if (dst.ty, dst.width, dst.height, dst.depth, dst.mip_levels)
+        != (src.ty, src.width, src.height, src.depth, src.mip_levels)
+    {
+        return Err(TransferError::ShapeMismatch);
+    }
+
+    Ok(())
+}
+
+/// Fills write through a storage view, so the buffer needs [`BufferUsages::Storage`]
+/// and a graphics or compute queue.
+pub fn validate_fill(
+    buffer: &BufferDesc,
+    range: &Range<usize>,
+    queue: CommandBufferType,
+) -> Result<(), TransferError> {
+    validate_buffer_range(buffer, range)?;
+
+    for value in [range.start, range.end] {
+        if !value.is_multiple_of(4) {
+            return Err(TransferError::Misaligned {
+                value,
+                alignment: 4,
+            });
+        }
+    }
+
+    if !buffer.usage.contains(BufferUsages::Storage) {
+        return Err(TransferError::Unsupported("fills need a storage buffer"));
+    }
+
+    if queue == CommandBufferType::Transfer {
+        return Err(TransferError::Unsupported(
+            "fills need a graphics or compute queue",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Returns the state `texture` is cleared in. Render target and depth clears
+/// need a graphics queue, storage clears a graphics or compute queue.
+pub fn clear_state(
+    texture: &TextureDesc,
+    value: &ClearValue,
+    queue: CommandBufferType,
+) -> Result<ResourceState, TransferError> {
+    if !matches!(texture.ty, TextureType::D2 | TextureType::Cube) {
+        return Err(TransferError::Unsupported(
+            "only 2D and cube textures can be cleared",
+        ));
+    }
+
+    let state = match value {
+        ClearValue::Color(_) if texture.usage.contains(TextureUsages::RenderTarget) => {
+            ResourceState::RenderTarget
+        }
+        ClearValue::DepthStencil { .. } if texture.usage.contains(TextureUsages::DepthTarget) => {
+            ResourceState::DepthWrite
+        }
+        ClearValue::Color(_) if texture.usage.contains(TextureUsages::Storage) => {
+            ResourceState::StorageWrite
+        }
+        _ => {
+            return Err(TransferError::Unsupported(
+                "texture can not be cleared with this value",
+            ))
+        }
+    };
+
+    match (state, queue) {
+        (_, CommandBufferType::Transfer) => Err(TransferError::Unsupported(
+            "clears need a graphics or compute queue",
+        )),
+        (ResourceState::RenderTarget | ResourceState::DepthWrite, CommandBufferType::Compute) => {
+            Err(TransferError::Unsupported(
+                "render target and depth clears need a graphics queue",
+            ))
+        }
+        _ => Ok(state),
+    }
+}
+
+/// Byte offsets of every row of texel blocks covered by `origin` and `extent`
+/// in memory described by `layout`, together with the size of one row.
+pub fn region_rows(
+    format: Format,
+    origin: [u32; 3],
+    extent: [u32; 3],
+    layout: BufferTextureLayout,
+) -> (Vec<usize>, usize) {
+    let (bw, bh) = format.block_dimensions();
+    let first_row = (origin[1] / bh) as usize;
+    let rows = extent[1].div_ceil(bh) as usize;
+    let column = (origin[0] / bw) as usize * format.block_size();
+
+    let offsets = (origin[2] as usize..(origin[2] + extent[2]) as usize)
+        .flat_map(|z| {
+            (first_row..(first_row + rows)).map(move |y| {
+                layout.offset + z * layout.slice_pitch + y * layout.row_pitch + column
+            })
+        })
+        .collect();
+
+    (offsets, format.row_pitch(extent[0]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::core::resource::{BufferUsages, TextureType, TextureUsages};
+
+    fn texture(format: Format) -> TextureDesc {
+        TextureDesc {
+            name: None,
+            ty: TextureType::D2,
+            width: 16,
+            height: 16,
+            depth: 2,
+            mip_levels: 2,
+            format,
+            usage: TextureUsages::Copy,
+        }
+    }
+
+    #[test]
+    fn validates_regions_against_mips_and_blocks() {
+        let desc = texture(Format::Bc1Unorm);
+
+        assert!(TextureRegion::mip(&desc, 1, 1).validate(&desc).is_ok());
+        assert_eq!(
+            TextureRegion::mip(&desc, 2, 0).validate(&desc),
+            Err(TransferError::InvalidRegion)
+        );
+
+        let region = TextureRegion {
+            mip: 0,
+            layer: 0,
+            origin: [2, 0, 0],
+            extent: [4, 4, 1],
+        };
+        assert_eq!(
+            region.validate(&desc),
+            Err(TransferError::Misaligned {
+                value: 2,
+                alignment: 4
+            })
+        );
+    }
+
+    #[test]
+    fn whole_copies_need_identical_textures() {
+        let src = texture(Format::Rgba8Unorm);
+
+        assert_eq!(validate_texture_copy(&src, &src), Ok(()));
+        assert_eq!(
+            validate_texture_copy(&texture(Format::R32), &src),
+            Err(TransferError::FormatMismatch)
+        );
+
+        // Same packed size, different shape.
+        let dst = TextureDesc {
+            width: 8,
+            height: 32,
+            ..src.clone()
+        };
+        assert_eq!(dst.packed_size(), src.packed_size());
+        assert_eq!(
+            validate_texture_copy(&dst, &src),
+            Err(TransferError::ShapeMismatch)
+        );
+    }
+
+    #[test]
+    fn rows_follow_pitches_and_blocks() {
+        let layout = BufferTextureLayout {
+            offset: 8,
+            row_pitch: 64,
+            slice_pitch: 256,
+        };
+
+        let (rows, size) = region_rows(Format::Rgba8Unorm, [1, 2, 0], [2, 2, 1], layout);
+        assert_eq!((rows, size), (vec![8 + 128 + 4, 8 + 192 + 4], 8));
+
+        let (rows, size) = region_rows(Format::Bc1Unorm, [4, 4, 0], [8, 8, 1], layout);
+        assert_eq!((rows, size), (vec![8 + 64 + 8, 8 + 128 + 8], 16));
+
+        let buffer = BufferDesc {
+            name: None,
+            size: 200,
+            stride: 0,
+            usage: BufferUsages::Copy,
+        };
+        assert_eq!(
+            layout.validate(&buffer, Format::Rgba8Unorm, [16, 4, 1]),
+            Err(TransferError::OutOfBounds {
+                end: 8 + 3 * 64 + 64,
+                size: 200
+            })
+        );
+    }
+}
@@ -1,18 +1,170 @@
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Format {
     Unknown,
 
+    R8Unorm,
+    Rg8Unorm,
     Rgba8Unorm,
+    Rgba8UnormSrgb,
+    Bgra8Unorm,
+    Bgra8UnormSrgb,
+
+    Rgba16Unorm,
+    Rgba16Float,
+
+    R16Uint,
+    R32Uint,
 
     R32,
     Rg32,
     Rgb32,
     Rgba32,
+
+    Bc1Unorm,
+    Bc1UnormSrgb,
+    Bc2Unorm,
+    Bc2UnormSrgb,
+    Bc3Unorm,
+    Bc3UnormSrgb,
+    Bc4Unorm,
+    Bc4Snorm,
+    Bc5Unorm,
+    Bc5Snorm,
+    Bc6hUfloat,
+    Bc6hSfloat,
+    Bc7Unorm,
+    Bc7UnormSrgb,
+}
+
+impl Format {
+    pub fn is_compressed(self) -> bool {
+        self.block_dimensions() != (1, 1)
+    }
+
+    pub fn is_srgb(self) -> bool {
+        matches!(
+            self,
+            Format::Rgba8UnormSrgb
+                | Format::Bgra8UnormSrgb
+                | Format::Bc1UnormSrgb
+                | Format::Bc2UnormSrgb
+                | Format::Bc3UnormSrgb
+                | Format::Bc7UnormSrgb
+        )
+    }
+
+    pub fn to_srgb(self) -> Self {
+        match self {
+            Format::Rgba8Unorm => Format::Rgba8UnormSrgb,
+            Format::Bgra8Unorm => Format::Bgra8UnormSrgb,
+            Format::Bc1Unorm => Format::Bc1UnormSrgb,
+            Format::Bc2Unorm => Format::Bc2UnormSrgb,
+            Format::Bc3Unorm => Format::Bc3UnormSrgb,
+            Format::Bc7Unorm => Format::Bc7UnormSrgb,
+            other => other,
+        }
+    }
+
+    pub fn to_linear(self) -> Self {
+        match self {
+            Format::Rgba8UnormSrgb => Format::Rgba8Unorm,
+            Format::Bgra8UnormSrgb => Format::Bgra8Unorm,
+            Format::Bc1UnormSrgb => Format::Bc1Unorm,
+            Format::Bc2UnormSrgb => Format::Bc2Unorm,
+            Format::Bc3UnormSrgb => Format::Bc3Unorm,
+            Format::Bc7UnormSrgb => Format::Bc7Unorm,
+            other => other,
+        }
+    }
+
+    /// Size of a texel block in pixels. Uncompressed formats use 1x1 blocks.
+    pub fn block_dimensions(self) -> (u32, u32) {
+        match self {
+            Format::Bc1Unorm
+            | Format::Bc1UnormSrgb
+            | Format::Bc2Unorm
+            | Format::Bc2UnormSrgb
+            | Format::Bc3Unorm
+            | Format::Bc3UnormSrgb
+            | Format::Bc4Unorm
+            | Format::Bc4Snorm
+            | Format::Bc5Unorm
+            | Format::Bc5Snorm
+            | Format::Bc6hUfloat
+            | Format::Bc6hSfloat
+            | Format::Bc7Unorm
+            | Format::Bc7UnormSrgb => (4, 4),
+            _ => (1, 1),
+        }
+    }
+
+    /// Size of a texel block in bytes.
+    pub fn block_size(self) -> usize {
+        match self {
+            Format::Unknown => 0,
+
+            Format::R8Unorm => 1,
+            Format::Rg8Unorm => 2,
+            Format::Rgba8Unorm
+            | Format::Rgba8UnormSrgb
+            | Format::Bgra8Unorm
+            | Format::Bgra8UnormSrgb => 4,
+
+            Format::Rgba16Unorm | Format::Rgba16Float => 8,
+
+            Format::R16Uint => 2,
+            Format::R32Uint => 4,
+
+            Format::R32 => 4,
+            Format::Rg32 => 8,
+            Format::Rgb32 => 12,
+            Format::Rgba32 => 16,
+
+            Format::Bc1Unorm | Format::Bc1UnormSrgb | Format::Bc4Unorm | Format::Bc4Snorm => 8,
+            Format::Bc2Unorm
+            | Format::Bc2UnormSrgb
+            | Format::Bc3Unorm
+            | Format::Bc3UnormSrgb
+            | Format::Bc5Unorm
+            | Format::Bc5Snorm
+            | Format::Bc6hUfloat
+            | Format::Bc6hSfloat
+            | Format::Bc7Unorm
+            | Format::Bc7UnormSrgb => 16,
+        }
+    }
+
+    /// Tightly packed size of one row of blocks.
+    pub fn row_pitch(self, width: u32) -> usize {
+        let (bw, _) = self.block_dimensions();
+        width.div_ceil(bw) as usize * self.block_size()
+    }
+
+    /// Tightly packed size of one 2D slice.
+    pub fn slice_pitch(self, width: u32, height: u32) -> usize {
+        let (_, bh) = self.block_dimensions();
+        self.row_pitch(width) * height.div_ceil(bh) as usize
+    }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// How a resource is used by the GPU. Encoders record these per handle and the
+/// command buffer derives the transitions from them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ResourceState {
     Common,
+    CopySrc,
+    CopyDst,
+    VertexBuffer,
+    IndexBuffer,
+    Uniform,
+    Indirect,
+    Sampled,
+    StorageRead,
+    StorageWrite,
     RenderTarget,
+    DepthWrite,
+    DepthRead,
     Present,
 }
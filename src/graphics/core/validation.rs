@@ -0,0 +1,614 @@
+use std::{borrow::Cow, collections::HashMap, fmt, sync::Arc};
+
+use parking_lot::Mutex;
+use tracing::error;
+
+use super::{
+    commands::CommandBufferType,
+    handle::RenderHandle,
+    ownership::OwnershipError,
+    resource::{
+        Buffer, BufferDesc, BufferUsages, BufferView, BufferViewDesc, BufferViewType, Texture,
+        TextureDesc, TextureUsages,
+    },
+    stream::{StreamCommand, StreamRef},
+    transfer::{self, TransferError},
+    types::ResourceState,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValidationError {
+    /// The handle was never bound on the device or has been unbound since.
+    DeadHandle(String),
+    MissingBufferUsage {
+        buffer: String,
+        usage: BufferUsages,
+    },
+    MissingTextureUsage {
+        texture: String,
+        usage: TextureUsages,
+    },
+    /// Copy, fill or clear the backend would reject.
+    Transfer {
+        resource: String,
+        error: TransferError,
+    },
+    /// Work recorded while a render pass of the same command buffer is still open.
+    EncoderInterleaved(&'static str),
+    /// The command buffer was submitted with an open render pass.
+    UnclosedRenderPass,
+    /// Barrier into a state the resource can not be used in.
+    StateMismatch {
+        resource: String,
+        state: ResourceState,
+    },
+    /// Invalid handoff between queues, found by the backend while recording.
+    Ownership {
+        resource: String,
+        error: OwnershipError,
+    },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::DeadHandle(resource) => {
+                write!(f, "{} is not bound on the device", resource)
+            }
+            ValidationError::MissingBufferUsage { buffer, usage } => {
+                write!(f, "{} is used as {:?} without that usage", buffer, usage)
+            }
+            ValidationError::MissingTextureUsage { texture, usage } => {
+                write!(f, "{} is used as {:?} without that usage", texture, usage)
+            }
+            ValidationError::Transfer { resource, error } => {
+                write!(f, "invalid transfer on {}: {}", resource, error)
+            }
+            ValidationError::EncoderInterleaved(what) => {
+                write!(f, "{} recorded inside an open render pass", what)
+            }
+            ValidationError::UnclosedRenderPass => {
+                write!(f, "command buffer submitted with an open render pass")
+            }
+            ValidationError::StateMismatch { resource, state } => {
+                write!(f, "{} can not be transitioned to {:?}", resource, state)
+            }
+            ValidationError::Ownership { resource, error } => write!(f, "{} {}", resource, error),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+#[derive(Debug)]
+enum TrackedResource {
+    Buffer(BufferDesc),
+    BufferView {
+        buffer: RenderHandle<Buffer>,
+        desc: BufferViewDesc,
+    },
+    Texture(TextureDesc),
+    Pipeline,
+}
+
+#[derive(Debug)]
+struct Tracked {
+    gen: u32,
+    live: bool,
+    resource: TrackedResource,
+}
+
+/// Slot of a handle, shared by every generation of it.
+fn slot(resource: StreamRef) -> Option<((&'static str, u32), u32)> {
+    let (kind, index, gen) = match resource {
+        StreamRef::Buffer(h) => ("buffer", h.idx(), h.gen()),
+        StreamRef::BufferView(h) => ("buffer_view", h.idx(), h.gen()),
+        StreamRef::Texture(h) => ("texture", h.idx(), h.gen()),
+        StreamRef::RenderPipeline(h) => ("render_pipeline", h.idx(), h.gen()),
+        StreamRef::ComputePipeline(h) => ("compute_pipeline", h.idx(), h.gen()),
+        StreamRef::Unbound => return None,
+    };
+
+    Some(((kind, index), gen))
+}
+
+/// Usage a buffer needs to be in `state`, `None` if buffers can never be in it.
+fn buffer_state_usage(state: ResourceState) -> Option<BufferUsages> {
+    match state {
+        ResourceState::VertexBuffer => Some(BufferUsages::Vertex),
+        ResourceState::IndexBuffer => Some(BufferUsages::Index),
+        ResourceState::Uniform => Some(BufferUsages::Uniform),
+        ResourceState::Indirect => Some(BufferUsages::Indirect),
+        ResourceState::StorageRead | ResourceState::StorageWrite => Some(BufferUsages::Storage),
+        ResourceState::Common
+        | ResourceState::CopySrc
+        | ResourceState::CopyDst
+        | ResourceState::Sampled => Some(BufferUsages::empty()),
+        ResourceState::RenderTarget
+        | ResourceState::DepthWrite
+        | ResourceState::DepthRead
+        | ResourceState::Present => None,
+    }
+}
+
+fn texture_state_usage(state: ResourceState) -> Option<TextureUsages> {
+    match state {
+        ResourceState::Sampled => Some(TextureUsages::Resource),
+        ResourceState::StorageRead | ResourceState::StorageWrite => Some(TextureUsages::Storage),
+        ResourceState::RenderTarget => Some(TextureUsages::RenderTarget),
+        ResourceState::DepthWrite | ResourceState::DepthRead => Some(TextureUsages::DepthTarget),
+        ResourceState::Common
+        | ResourceState::CopySrc
+        | ResourceState::CopyDst
+        | ResourceState::Present => Some(TextureUsages::empty()),
+        ResourceState::VertexBuffer
+        | ResourceState::IndexBuffer
+        | ResourceState::Uniform
+        | ResourceState::Indirect => None,
+    }
+}
+
+/// Resources bound on a device as seen by the CPU validation layer, enabled
+/// with [`DebugFlags::CpuValidation`](crate::graphics::DebugFlags).
+///
+/// Backends register every bind and unbind, and check handle based work with
+/// a [`CommandValidator`] before recording it. Copies are allowed on every
+/// resource, [`BufferUsages::Copy`] only selects upload memory.
+#[derive(Debug, Default)]
+pub struct Validator {
+    resources: Mutex<HashMap<(&'static str, u32), Tracked>>,
+    errors: Mutex<Vec<ValidationError>>,
+}
+
+impl Validator {
+    pub fn new() -> Arc<Self> {
+        Arc::default()
+    }
+
+    pub fn bind_buffer(&self, handle: RenderHandle<Buffer>, desc: &BufferDesc) {
+        self.bind(
+            StreamRef::Buffer(handle),
+            TrackedResource::Buffer(desc.clone()),
+        );
+    }
+
+    pub fn bind_buffer_view(
+        &self,
+        handle: RenderHandle<BufferView>,
+        buffer: RenderHandle<Buffer>,
+        desc: BufferViewDesc,
+    ) {
+        self.bind(
+            StreamRef::BufferView(handle),
+            TrackedResource::BufferView { buffer, desc },
+        );
+    }
+
+    pub fn bind_texture(&self, handle: RenderHandle<Texture>, desc: &TextureDesc) {
+        self.bind(
+            StreamRef::Texture(handle),
+            TrackedResource::Texture(desc.clone()),
+        );
+    }
+
+    pub fn bind_pipeline(&self, pipeline: StreamRef) {
+        self.bind(pipeline, TrackedResource::Pipeline);
+    }
+
+    /// Keeps the description, so later uses of the handle still report its name.
+    pub fn unbind(&self, resource: StreamRef) {
+        let Some((slot, gen)) = slot(resource) else {
+            return;
+        };
+
+        if let Some(tracked) = self.resources.lock().get_mut(&slot) {
+            if tracked.gen == gen {
+                tracked.live = false;
+            }
+        }
+    }
+
+    fn bind(&self, resource: StreamRef, tracked: TrackedResource) {
+        let Some((slot, gen)) = slot(resource) else {
+            return;
+        };
+
+        self.resources.lock().insert(
+            slot,
+            Tracked {
+                gen,
+                live: true,
+                resource: tracked,
+            },
+        );
+    }
+
+    /// Errors reported since the last call, every one was also logged.
+    pub fn take_errors(&self) -> Vec<ValidationError> {
+        std::mem::take(&mut *self.errors.lock())
+    }
+
+    /// Reports a handoff the backend found invalid while tracking queue ownership.
+    pub fn report_ownership(&self, resource: StreamRef, error: OwnershipError) {
+        let resource = self.check_with(CommandBufferType::Graphics, |c| c.label(resource));
+        self.report(ValidationError::Ownership { resource, error });
+    }
+
+    fn report(&self, error: ValidationError) {
+        error!("Validation failed: {}", error);
+        self.errors.lock().push(error);
+    }
+
+    fn check(&self, ty: CommandBufferType, command: &StreamCommand) -> Vec<ValidationError> {
+        self.check_with(ty, |checker| checker.check(command))
+    }
+
+    fn check_with<R>(&self, ty: CommandBufferType, f: impl FnOnce(Checker<'_>) -> R) -> R {
+        let resources = self.resources.lock();
+
+        f(Checker {
+            resources: &resources,
+            ty,
+            errors: vec![],
+        })
+    }
+}
+
+/// Tracked resources borrowed for the checks of one command.
+struct Checker<'a> {
+    resources: &'a HashMap<(&'static str, u32), Tracked>,
+    ty: CommandBufferType,
+    errors: Vec<ValidationError>,
+}
+
+impl<'a> Checker<'a> {
+    fn get(&self, resource: StreamRef) -> Option<&'a Tracked> {
+        let (slot, gen) = slot(resource)?;
+        self.resources.get(&slot).filter(|t| t.gen == gen)
+    }
+
+    fn label(&self, resource: StreamRef) -> String {
+        let name = |name: &Option<Cow<'static, str>>| match name {
+            Some(name) => format!("{} \"{}\"", resource, name),
+            None => resource.to_string(),
+        };
+
+        match self.get(resource).map(|t| &t.resource) {
+            Some(TrackedResource::Buffer(desc)) => name(&desc.name),
+            Some(TrackedResource::Texture(desc)) => name(&desc.name),
+            Some(TrackedResource::BufferView { buffer, .. }) => {
+                format!("{} of {}", resource, self.label(StreamRef::Buffer(*buffer)))
+            }
+            Some(TrackedResource::Pipeline) | None => resource.to_string(),
+        }
+    }
+
+    fn buffer(&self, resource: StreamRef) -> Option<&'a BufferDesc> {
+        match &self.get(resource)?.resource {
+            TrackedResource::Buffer(desc) => Some(desc),
+            _ => None,
+        }
+    }
+
+    fn texture(&self, resource: StreamRef) -> Option<&'a TextureDesc> {
+        match &self.get(resource)?.resource {
+            TrackedResource::Texture(desc) => Some(desc),
+            _ => None,
+        }
+    }
+
+    /// Buffer behind a view, along with the view description.
+    fn view(&self, view: StreamRef) -> Option<(StreamRef, &'a BufferViewDesc)> {
+        match &self.get(view)?.resource {
+            TrackedResource::BufferView { buffer, desc } => {
+                Some((StreamRef::Buffer(*buffer), desc))
+            }
+            _ => None,
+        }
+    }
+
+    fn live(&mut self, resource: StreamRef) -> bool {
+        if resource == StreamRef::Unbound || self.get(resource).is_some_and(|t| t.live) {
+            return true;
+        }
+
+        self.errors
+            .push(ValidationError::DeadHandle(self.label(resource)));
+        false
+    }
+
+    fn buffer_usage(&mut self, buffer: StreamRef, usage: BufferUsages) {
+        if let Some(desc) = self.buffer(buffer) {
+            if !desc.usage.contains(usage) {
+                self.errors.push(ValidationError::MissingBufferUsage {
+                    buffer: self.label(buffer),
+                    usage,
+                });
+            }
+        }
+    }
+
+    fn view_usage(&mut self, view: StreamRef, usage: BufferUsages) {
+        if let Some((buffer, _)) = self.view(view) {
+            self.buffer_usage(buffer, usage);
+        }
+    }
+
+    fn texture_usage(&mut self, texture: StreamRef, usage: TextureUsages) {
+        if let Some(desc) = self.texture(texture) {
+            if !desc.usage.contains(usage) {
+                self.errors.push(ValidationError::MissingTextureUsage {
+                    texture: self.label(texture),
+                    usage,
+                });
+            }
+        }
+    }
+
+    fn transfer(&mut self, resource: StreamRef, result: Result<(), TransferError>) {
+        if let Err(error) = result {
+            self.errors.push(ValidationError::Transfer {
+                resource: self.label(resource),
+                error,
+            });
+        }
+    }
+
+    fn barrier(&mut self, resource: StreamRef, state: ResourceState) {
+        let mismatch = match self.get(resource).map(|t| &t.resource) {
+            Some(TrackedResource::Buffer(_)) => match buffer_state_usage(state) {
+                Some(usage) => return self.buffer_usage(resource, usage),
+                None => true,
+            },
+            Some(TrackedResource::Texture(_)) => match texture_state_usage(state) {
+                Some(usage) => return self.texture_usage(resource, usage),
+                None => true,
+            },
+            _ => false,
+        };
+
+        if mismatch {
+            self.errors.push(ValidationError::StateMismatch {
+                resource: self.label(resource),
+                state,
+            });
+        }
+    }
+
+    fn check(mut self, command: &StreamCommand) -> Vec<ValidationError> {
+        // Usages and bounds are only known for live handles.
+        let mut live = true;
+        for resource in command.refs() {
+            live &= self.live(resource);
+        }
+        if !live {
+            return self.errors;
+        }
+
+        match command {
+            StreamCommand::Barrier {
+                resource, state, ..
+            } => self.barrier(*resource, *state),
+            StreamCommand::CopyBuffer { dst, src } => {
+                if let (Some(dst_desc), Some(src_desc)) = (self.buffer(*dst), self.buffer(*src)) {
+                    let result = transfer::validate_buffer_range(dst_desc, &(0..src_desc.size));
+                    self.transfer(*dst, result);
+                }
+            }
+            StreamCommand::CopyTexture { dst, src } => {
+                if let (Some(dst_desc), Some(src_desc)) = (self.texture(*dst), self.texture(*src)) {
+                    let result = transfer::validate_texture_copy(dst_desc, src_desc);
+                    self.transfer(*dst, result);
+                }
+            }
+            StreamCommand::UploadTexture { dst, src, data } => {
+                if let Some(desc) = self.texture(*dst) {
+                    if data.len() > desc.packed_size() {
+                        self.transfer(
+                            *dst,
+                            Err(TransferError::OutOfBounds {
+                                end: data.len(),
+                                size: desc.packed_size(),
+                            }),
+                        );
+                    }
+                }
+                if let Some(desc) = self.buffer(*src) {
+                    let result = transfer::validate_buffer_range(desc, &(0..data.len()));
+                    self.transfer(*src, result);
+                }
+            }
+            StreamCommand::CopyBufferRegion {
+                dst,
+                dst_offset,
+                src,
+                src_offset,
+                size,
+            } => {
+                for (buffer, offset) in [(*dst, *dst_offset), (*src, *src_offset)] {
+                    if let Some(desc) = self.buffer(buffer) {
+                        let result =
+                            transfer::validate_buffer_range(desc, &(offset..(offset + size)));
+                        self.transfer(buffer, result);
+                    }
+                }
+            }
+            StreamCommand::CopyBufferToTexture {
+                dst,
+                region,
+                src,
+                layout,
+            } => {
+                if let (Some(texture), Some(buffer)) = (self.texture(*dst), self.buffer(*src)) {
+                    self.transfer(*dst, region.validate(texture));
+                    let result = layout.validate(buffer, texture.format, region.extent);
+                    self.transfer(*src, result);
+                }
+            }
+            StreamCommand::CopyTextureToBuffer {
+                dst,
+                layout,
+                src,
+                region,
+            } => {
+                if let (Some(buffer), Some(texture)) = (self.buffer(*dst), self.texture(*src)) {
+                    self.transfer(*src, region.validate(texture));
+                    let result = layout.validate(buffer, texture.format, region.extent);
+                    self.transfer(*dst, result);
+                }
+            }
+            StreamCommand::CopyTextureRegion {
+                dst,
+                dst_region,
+                src,
+                src_region,
+            } => {
+                if let (Some(dst_desc), Some(src_desc)) = (self.texture(*dst), self.texture(*src)) {
+                    self.transfer(*dst, dst_region.validate(dst_desc));
+                    self.transfer(*src, src_region.validate(src_desc));
+
+                    if dst_desc.format != src_desc.format {
+                        self.transfer(*dst, Err(TransferError::FormatMismatch));
+                    } else if dst_region.extent != src_region.extent {
+                        self.transfer(*dst, Err(TransferError::InvalidRegion));
+                    }
+                }
+            }
+            StreamCommand::FillBuffer { dst, range, .. } => {
+                if let Some(desc) = self.buffer(*dst) {
+                    let result = transfer::validate_fill(desc, range, self.ty);
+                    self.transfer(*dst, result);
+                }
+            }
+            StreamCommand::ClearTexture { dst, range, value } => {
+                if let Some(desc) = self.texture(*dst) {
+                    let result = transfer::clear_state(desc, value, self.ty).and_then(|_| {
+                        match range.mips.end > desc.mip_levels
+                            || range.layers.end > desc.array_layers()
+                        {
+                            true => Err(TransferError::InvalidRegion),
+                            false => Ok(()),
+                        }
+                    });
+                    self.transfer(*dst, result);
+                }
+            }
+            StreamCommand::BeginRenderPass { colors, depth, .. } => {
+                for (texture, _) in colors {
+                    self.texture_usage(*texture, TextureUsages::RenderTarget);
+                }
+                if let Some((texture, _)) = depth {
+                    self.texture_usage(*texture, TextureUsages::DepthTarget);
+                }
+            }
+            StreamCommand::SetVertexBuffer { view, .. } => {
+                self.view_usage(*view, BufferUsages::Vertex)
+            }
+            StreamCommand::SetIndexBuffer(view) => self.view_usage(*view, BufferUsages::Index),
+            StreamCommand::SetBufferView { view, .. } => {
+                if self
+                    .view(*view)
+                    .is_some_and(|(_, desc)| desc.ty == BufferViewType::Storage)
+                {
+                    self.view_usage(*view, BufferUsages::Storage);
+                }
+            }
+            StreamCommand::DrawIndirect { args, count, .. } => {
+                self.buffer_usage(*args, BufferUsages::Indirect);
+                if let Some((count, _)) = count {
+                    self.buffer_usage(*count, BufferUsages::Indirect);
+                }
+            }
+            StreamCommand::DispatchIndirect { args, .. } => {
+                self.buffer_usage(*args, BufferUsages::Indirect)
+            }
+            StreamCommand::GenerateMips(_)
+            | StreamCommand::Release { .. }
+            | StreamCommand::EndRenderPass
+            | StreamCommand::SetViewport(_)
+            | StreamCommand::SetScissor(_)
+            | StreamCommand::SetStencilReference(_)
+            | StreamCommand::SetBlendConstants(_)
+            | StreamCommand::SetPipeline(_)
+            | StreamCommand::SetConstants { .. }
+            | StreamCommand::Draw { .. }
+            | StreamCommand::Dispatch(_)
+            | StreamCommand::ExecuteBundle(_)
+            | StreamCommand::PushDebugGroup(_)
+            | StreamCommand::PopDebugGroup
+            | StreamCommand::Marker(_) => {}
+        }
+
+        self.errors
+    }
+}
+
+/// Validation state of a single command buffer.
+#[derive(Debug)]
+pub struct CommandValidator {
+    validator: Arc<Validator>,
+    ty: CommandBufferType,
+    render_pass: bool,
+}
+
+impl CommandValidator {
+    pub fn new(validator: Arc<Validator>, ty: CommandBufferType) -> Self {
+        Self {
+            validator,
+            ty,
+            render_pass: false,
+        }
+    }
+
+    /// Called when a compute or transfer encoder is opened.
+    pub fn begin_encoder(&mut self, encoder: &'static str) {
+        if self.render_pass {
+            self.validator
+                .report(ValidationError::EncoderInterleaved(encoder));
+        }
+    }
+
+    /// Checks `command` before the backend records it.
+    pub fn validate(&mut self, command: &StreamCommand) {
+        let interleaved = match command {
+            StreamCommand::BeginRenderPass { .. } => {
+                std::mem::replace(&mut self.render_pass, true).then_some("render pass")
+            }
+            StreamCommand::EndRenderPass => {
+                self.render_pass = false;
+                None
+            }
+            StreamCommand::CopyBuffer { .. }
+            | StreamCommand::CopyTexture { .. }
+            | StreamCommand::UploadTexture { .. }
+            | StreamCommand::CopyBufferRegion { .. }
+            | StreamCommand::CopyBufferToTexture { .. }
+            | StreamCommand::CopyTextureToBuffer { .. }
+            | StreamCommand::CopyTextureRegion { .. }
+            | StreamCommand::FillBuffer { .. }
+            | StreamCommand::ClearTexture { .. }
+            | StreamCommand::Release { .. } => self.render_pass.then_some("transfer"),
+            StreamCommand::GenerateMips(_)
+            | StreamCommand::SetBufferView { .. }
+            | StreamCommand::Dispatch(_)
+            | StreamCommand::DispatchIndirect { .. } => self.render_pass.then_some("compute"),
+            _ => None,
+        };
+
+        if let Some(what) = interleaved {
+            self.validator
+                .report(ValidationError::EncoderInterleaved(what));
+        }
+
+        for error in self.validator.check(self.ty, command) {
+            self.validator.report(error);
+        }
+    }
+
+    /// Called when the command buffer is submitted.
+    pub fn finish(&mut self) {
+        if std::mem::take(&mut self.render_pass) {
+            self.validator.report(ValidationError::UnclosedRenderPass);
+        }
+    }
+}
@@ -6,7 +6,10 @@ use oxidx::dx::{
 use tracing::{debug, error, info, warn};
 
 use crate::graphics::{
-    core::backend::{Api, DeviceType, RenderDeviceId, RenderDeviceInfo},
+    core::{
+        backend::{Api, DeviceType, RenderDeviceId, RenderDeviceInfo},
+        shader::ShaderLanguages,
+    },
     DebugFlags,
 };
 
@@ -16,6 +19,7 @@ use super::context::DxRenderContext;
 pub struct DxBackend {
     factory: dx::Factory4,
     debug: Option<dx::Debug1>,
+    debug_flags: DebugFlags,
 
     adapters: Vec<dx::Adapter3>,
     adapter_infos: Vec<RenderDeviceInfo>,
@@ -97,6 +101,7 @@ impl DxBackend {
                                 .cross_adapter_row_major_texture_supported(),
                             is_uma: hardware.uma(),
                             ty,
+                            shader_languages: ShaderLanguages::Hlsl | ShaderLanguages::Dxil,
                         },
                     ));
                 }
@@ -141,6 +146,7 @@ impl DxBackend {
                                 .cross_adapter_row_major_texture_supported(),
                             is_uma: hardware.uma(),
                             ty,
+                            shader_languages: ShaderLanguages::Hlsl | ShaderLanguages::Dxil,
                         },
                     ));
                 }
@@ -172,6 +178,7 @@ impl DxBackend {
         Self {
             factory,
             debug,
+            debug_flags,
             adapters,
             adapter_infos,
         }
@@ -190,6 +197,7 @@ impl Api for DxBackend {
             self.adapters[index].clone(),
             self.factory.clone(),
             self.adapter_infos[index].clone(),
+            self.debug_flags,
         )
     }
 }
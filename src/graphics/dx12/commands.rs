@@ -1,30 +1,83 @@
 use std::hint::cold_path;
-use std::marker::PhantomData;
+use std::ops::Range;
 use std::sync::Arc;
 
-use oxidx::dx::{self, IGraphicsCommandList, IGraphicsCommandListExt};
+use oxidx::dx::{self, IDevice, IGraphicsCommandList, IGraphicsCommandListExt, PSO_NONE};
+use parking_lot::Mutex;
+use tracing::{error, warn};
 
+use crate::graphics::core::bundle::{
+    self, BundleDesc, BundleError, BundleReferences, BundleValidity,
+};
 use crate::graphics::core::commands::{
-    CommandBuffer, CommandBufferType, CommandDevice, ComputeEncoder, DynTransferEncoder,
-    RenderEncoder, SyncPoint, TransferEncoder,
+    BufferBarrier, CommandBuffer, CommandBufferType, CommandDevice, ComputeEncoder, DebugEncoder,
+    DeviceTag, DynComputeEncoder, DynRenderEncoder, DynTransferEncoder, FenceValue, LoadOp,
+    RenderEncoder, RenderPassDesc, ResourceUsages, Scissor, StoreOp, SyncPoint, TextureBarrier,
+    TransferEncoder, Viewport,
 };
+use crate::graphics::core::future::GpuFuture;
 use crate::graphics::core::handle::RenderHandle;
-use crate::graphics::core::resource::{Buffer, Texture};
-use crate::graphics::dx12::inner::commands::CommandAllocatorEntry;
+use crate::graphics::core::indirect::{
+    self, DispatchIndirectArgs, DrawIndexedIndirectArgs, DrawIndirectArgs, IndirectError,
+};
+use crate::graphics::core::ownership::{self, OwnershipError, QueueOwnership};
+use crate::graphics::core::profiler::ScopeStack;
+use crate::graphics::core::query::{self, QueryError, QueryType};
+use crate::graphics::core::resource::{
+    Buffer, BufferView, ResourceDevice, SubresourceRange, Texture, TextureDesc, TextureType,
+    TextureUsages,
+};
+use crate::graphics::core::shader::{ComputePipeline, RenderPipeline};
+use crate::graphics::core::stream::{StreamCommand, StreamRef};
+use crate::graphics::core::tracker::StateTracker;
+use crate::graphics::core::transfer::{
+    self, BufferTextureLayout, ClearValue, TextureRegion, TransferError,
+};
+use crate::graphics::core::types::{Format, ResourceState};
+use crate::graphics::core::validation::CommandValidator;
+use crate::graphics::dx12::inner::{
+    commands::CommandAllocatorEntry,
+    descriptors::{Descriptor, Descriptors},
+    indirect::IndirectSignatures,
+    mips::{MipGenerator, MIPS_GROUP_SIZE},
+    sync::DxSharedFence,
+};
+use crate::graphics::DebugFlags;
 
 use super::context::{DxRenderContext, HandleStorage};
-use super::resources::{DxBuffer, DxTexture, TextureState};
+use super::conv::{map_depth_format, map_format, map_resource_state};
+use super::resources::{
+    DxBuffer, DxBufferView, DxBufferViewKind, DxQuerySet, DxTexture, TextureState,
+};
+use super::shader::{DxComputePipeline, DxRenderPipeline};
 
 #[derive(Debug)]
 pub struct DxCommandBuffer {
+    pub(super) device: dx::Device,
     pub(super) handles: Arc<HandleStorage>,
+    pub(super) descriptors: Arc<Descriptors>,
+    /// `None` if the mips shader failed to compile.
+    pub(super) mip_generator: Option<Arc<MipGenerator>>,
+    pub(super) indirect: Arc<IndirectSignatures>,
     pub(super) ty: CommandBufferType,
     pub(super) list: dx::GraphicsCommandList,
     pub(super) allocator: CommandAllocatorEntry,
+    pub(super) scopes: Mutex<ScopeStack>,
+    /// Records debug events, set when PIX or RenderDoc is enabled.
+    pub(super) markers: bool,
+    /// Set with [`DebugFlags::CpuValidation`], checks handle based work before it is recorded.
+    pub(super) validation: Option<Mutex<CommandValidator>>,
 }
 
 impl CommandDevice for DxRenderContext {
     type CommandBuffer = DxCommandBuffer;
+    type SharedFence = DxSharedFence;
+    type Bundle = DxBundle;
+    type BundleEncoder = DxBundleEncoder;
+
+    fn tag(&self) -> DeviceTag {
+        self.tag
+    }
 
     fn create_command_buffer(&self, ty: CommandBufferType) -> Self::CommandBuffer {
         match ty {
@@ -40,6 +93,11 @@ impl CommandDevice for DxRenderContext {
         }
     }
 
+    fn allocate_command_buffer(&self, ty: CommandBufferType) -> Self::CommandBuffer {
+        self.queue(ty)
+            .allocate_command_buffer(Arc::clone(&self.handles))
+    }
+
     fn stash_cmd_buffer(&self, cmd: Self::CommandBuffer) {
         match cmd.ty {
             CommandBufferType::Graphics => self.gfx_queue.stash_cmd_buffer(cmd),
@@ -57,215 +115,2294 @@ impl CommandDevice for DxRenderContext {
     }
 
     fn commit(&self, ty: CommandBufferType) -> SyncPoint {
-        match ty {
-            CommandBufferType::Graphics => self.gfx_queue.commit(),
+        let value = match ty {
+            CommandBufferType::Graphics => {
+                self.resolve_profiler();
+                let value = self.gfx_queue.commit();
+
+                self.handles.residency.lock().advance(value);
+                self.trim_residency(0);
+                self.collect_profiler(value);
+
+                if self.debug.contains(DebugFlags::MemoryReport) {
+                    self.memory_report().log();
+                }
+
+                value
+            }
             CommandBufferType::Compute => self.compute_queue.commit(),
             CommandBufferType::Transfer => self.transfer_queue.commit(),
+        };
+
+        SyncPoint::new(self.tag, ty, value)
+    }
+
+    fn wait_cpu(&self, time: SyncPoint) {
+        self.queue(time.ty()).wait_cpu(time.value_on(self.tag));
+    }
+
+    fn future(&self, time: SyncPoint) -> GpuFuture {
+        time.value_on(self.tag);
+        self.queue(time.ty()).future(time)
+    }
+
+    fn wait_gpu(&self, waiting: CommandBufferType, time: SyncPoint) {
+        let value = time.value_on(self.tag);
+        if waiting == time.ty() {
+            return;
         }
+
+        self.queue(waiting).wait_queue(self.queue(time.ty()), value);
     }
 
-    fn wait_cpu(&self, ty: CommandBufferType, time: SyncPoint) {
-        match ty {
-            CommandBufferType::Graphics => {
-                self.gfx_queue.wait_cpu(time);
-            }
-            CommandBufferType::Compute => {
-                self.compute_queue.wait_cpu(time);
-            }
-            CommandBufferType::Transfer => {
-                self.transfer_queue.wait_cpu(time);
-            }
+    fn bundle_encoder(&self, desc: BundleDesc) -> Self::BundleEncoder {
+        let allocator = self
+            .gpu
+            .create_command_allocator(dx::CommandListType::Bundle)
+            .expect("failed to create bundle allocator");
+        let list = self
+            .gpu
+            .create_command_list(0, dx::CommandListType::Bundle, &allocator, PSO_NONE)
+            .expect("failed to create bundle list");
+
+        DxBundleEncoder {
+            handles: Arc::clone(&self.handles),
+            indirect: Arc::clone(&self.indirect),
+            desc,
+            list,
+            allocator,
+            references: Default::default(),
+            markers: self
+                .debug
+                .intersects(DebugFlags::Pix | DebugFlags::RenderDoc),
+        }
+    }
+
+    fn finish_bundle(&self, encoder: Self::BundleEncoder) -> Self::Bundle {
+        encoder.list.close().expect("failed to close bundle list");
+        let validity = self
+            .handles
+            .bundles
+            .lock()
+            .register(encoder.references.into_inner());
+
+        DxBundle {
+            desc: encoder.desc,
+            list: encoder.list,
+            _allocator: encoder.allocator,
+            validity,
         }
     }
+
+    fn create_shared_fence(&self) -> Self::SharedFence {
+        DxSharedFence::new(&self.gpu)
+    }
+
+    fn open_shared_fence(&self, fence: &Self::SharedFence, owner: &Self) -> Self::SharedFence {
+        fence.open(&self.gpu, &owner.gpu)
+    }
+
+    fn signal_shared(&self, ty: CommandBufferType, fence: &Self::SharedFence, value: FenceValue) {
+        self.queue(ty).signal_shared(fence, value);
+    }
+
+    fn wait_shared(&self, ty: CommandBufferType, fence: &Self::SharedFence, value: FenceValue) {
+        self.queue(ty).wait_shared(fence, value);
+    }
 }
 
 impl CommandBuffer for DxCommandBuffer {
-    type BufferBarrier<'a> = (&'a DxBuffer, dx::ResourceStates);
-    type TextureBarrier<'a> = (&'a DxTexture, dx::ResourceStates, bool);
+    type Buffer = DxBuffer;
+    type Texture = DxTexture;
+    type QuerySet = DxQuerySet;
 
     type RenderEncoder<'a> = DxRenderEncoder<'a>;
     type ComputeEncoder<'a> = DxComputeEncoder<'a>;
     type TransferEncoder<'a> = DxTransferEncoder<'a>;
 
-    fn render_encoder(&mut self) -> Self::RenderEncoder<'_> {
-        todo!()
+    fn render_encoder(&mut self, pass: &RenderPassDesc) -> Self::RenderEncoder<'_> {
+        debug_assert_eq!(
+            self.ty,
+            CommandBufferType::Graphics,
+            "render passes can only be recorded on a graphics buffer"
+        );
+
+        self.validate(|| StreamCommand::BeginRenderPass {
+            colors: pass
+                .colors
+                .iter()
+                .map(|a| (StreamRef::Texture(a.texture), a.load))
+                .collect(),
+            depth: pass
+                .depth_stencil
+                .as_ref()
+                .map(|a| (StreamRef::Texture(a.texture), a.depth_load)),
+            views: pass
+                .buffer_views
+                .iter()
+                .map(|&v| StreamRef::BufferView(v))
+                .collect(),
+        });
+
+        let handles = Arc::clone(&self.handles);
+        let views = handles.buffer_views.lock();
+        let buffers = handles.buffers.lock();
+        let buffer_barriers = pass
+            .buffer_views
+            .iter()
+            .filter_map(|&handle| {
+                let Some(view) = views.get(handle) else {
+                    cold_path();
+                    return None;
+                };
+
+                Some(BufferBarrier {
+                    buffer: buffers.get(view.buffer?)?,
+                    state: view.desc.ty.resource_state(),
+                })
+            })
+            .collect::<Vec<_>>();
+        let guard = handles.textures.lock();
+
+        let mut barriers = vec![];
+        // Skipped attachments leave no gap, so clears pair with their own view.
+        let mut colors = vec![];
+        let mut dsv = None;
+        let mut discards = vec![];
+        let mut extent = None;
+        let (mut color_formats, mut depth_format) = (vec![], None);
+
+        for attachment in &pass.colors {
+            let Some(texture) = guard.get(attachment.texture) else {
+                cold_path();
+                continue;
+            };
+            let Some(descriptor) = &texture.descriptor else {
+                error!("Texture {:?} is not a render target", texture.desc.name);
+                continue;
+            };
+
+            handles.touch_texture(&self.device, attachment.texture, texture);
+            barriers.push(TextureBarrier::new(texture, ResourceState::RenderTarget));
+            colors.push((attachment, descriptor.cpu));
+            color_formats.push(texture.desc.format);
+            extent.get_or_insert((texture.desc.width, texture.desc.height));
+
+            if attachment.store == StoreOp::Discard {
+                discards.extend(texture.pageable().cloned());
+            }
+        }
+
+        if let Some(attachment) = &pass.depth_stencil {
+            match guard.get(attachment.texture) {
+                Some(texture) => match &texture.descriptor {
+                    Some(descriptor) => {
+                        handles.touch_texture(&self.device, attachment.texture, texture);
+                        barriers.push(TextureBarrier::new(texture, ResourceState::DepthWrite));
+                        dsv = Some(descriptor.cpu);
+                        depth_format = Some(texture.desc.format);
+                        extent.get_or_insert((texture.desc.width, texture.desc.height));
+
+                        if attachment.depth_store == StoreOp::Discard
+                            && attachment.stencil_store == StoreOp::Discard
+                        {
+                            discards.extend(texture.pageable().cloned());
+                        }
+                    }
+                    None => error!("Texture {:?} is not a depth target", texture.desc.name),
+                },
+                None => cold_path(),
+            }
+        }
+
+        self.set_barriers(&buffer_barriers, &barriers);
+
+        for (attachment, rtv) in &colors {
+            if let LoadOp::Clear(color) = attachment.load {
+                self.list.clear_render_target_view(*rtv, color, &[]);
+            }
+        }
+
+        if let (Some(attachment), Some(dsv)) = (&pass.depth_stencil, dsv) {
+            let (mut flags, mut depth, mut stencil) = (dx::ClearFlags::empty(), 1.0, 0);
+
+            if let LoadOp::Clear(value) = attachment.depth_load {
+                flags |= dx::ClearFlags::Depth;
+                depth = value;
+            }
+
+            if let LoadOp::Clear(value) = attachment.stencil_load {
+                flags |= dx::ClearFlags::Stencil;
+                stencil = value;
+            }
+
+            if !flags.is_empty() {
+                self.list
+                    .clear_depth_stencil_view(dsv, flags, depth, stencil, &[]);
+            }
+        }
+
+        let rtvs = colors.iter().map(|(_, rtv)| *rtv).collect::<Vec<_>>();
+        self.list.om_set_render_targets(&rtvs, false, dsv);
+        drop((guard, buffers, views));
+
+        let (width, height) = extent.unwrap_or_default();
+        let encoder = DxRenderEncoder {
+            cmd_buffer: self,
+            discards,
+            color_formats,
+            depth_format,
+        };
+        encoder.set_viewport(Viewport::new(width as f32, height as f32));
+        encoder.set_scissor(Scissor::new(width, height));
+
+        encoder
     }
 
     fn compute_encoder(&mut self) -> Self::ComputeEncoder<'_> {
-        todo!()
+        debug_assert_ne!(
+            self.ty,
+            CommandBufferType::Transfer,
+            "compute can not be recorded on a transfer buffer"
+        );
+        self.begin_encoder("compute encoder");
+
+        let heap = self.descriptors.shader_heap.lock().heap.clone();
+        self.list.set_descriptor_heaps(&[Some(heap)]);
+
+        DxComputeEncoder { cmd_buffer: self }
     }
 
     fn transfer_encoder(&mut self) -> Self::TransferEncoder<'_> {
+        self.begin_encoder("transfer encoder");
+
         DxTransferEncoder { cmd_buffer: self }
     }
 
-    fn set_buffer_barriers(&self, barriers: &[Self::BufferBarrier<'_>]) {
-        let barriers = barriers
-            .iter()
-            .filter_map(|(b, s)| {
-                let mut old_state = b.state.lock();
+    fn set_barriers(
+        &self,
+        buffers: &[BufferBarrier<'_, Self::Buffer>],
+        textures: &[TextureBarrier<'_, Self::Texture>],
+    ) {
+        let buffer_barriers = buffers.iter().filter_map(|b| {
+            // Upload and readback heaps keep their initial state for their whole lifetime.
+            if !b.buffer.is_default_heap() {
+                return None;
+            }
 
-                if *old_state == *s {
-                    return None;
+            let state = map_resource_state(b.state);
+            let mut old_state = b.buffer.state.lock();
+            self.acquire(
+                b.buffer.stream_ref(),
+                &b.buffer.owner,
+                Some(*old_state),
+                b.state,
+            );
+
+            if *old_state == state {
+                return None;
+            }
+
+            let barrier = dx::ResourceBarrier::transition(&b.buffer.raw, *old_state, state, None);
+            *old_state = state;
+
+            Some(barrier)
+        });
+
+        let texture_barriers = textures.iter().flat_map(|t| {
+            let state = map_resource_state(t.state);
+            let (raw, tracker, cross) = match &t.texture.state {
+                TextureState::Local { raw, state } => (raw, Some(state), None),
+                TextureState::CrossAdapter { cross, state, .. } => (cross, None, Some(state)),
+                TextureState::Binded {
+                    local,
+                    cross,
+                    local_state,
+                    cross_state,
+                    ..
+                } => {
+                    if t.cross_adapter {
+                        (cross, None, Some(cross_state))
+                    } else {
+                        (local, Some(local_state), None)
+                    }
                 }
+            };
+
+            if let Some(tracker) = tracker {
+                let mut tracker = tracker.lock();
+                self.acquire(
+                    t.texture.stream_ref(),
+                    &t.texture.owner,
+                    tracker.uniform_state(),
+                    t.state,
+                );
+                return tracked_barriers(raw, &mut tracker, t.range.clone(), state);
+            }
 
-                let barrier = Some(dx::ResourceBarrier::transition(
-                    &b.raw, *old_state, *s, None,
-                ));
-                *old_state = *s;
+            // Cross adapter textures are row major and only have a single subresource.
+            let mut old_state = cross.expect("unreachable").lock();
+            self.acquire(
+                t.texture.stream_ref(),
+                &t.texture.owner,
+                Some(*old_state),
+                t.state,
+            );
+            if *old_state == state {
+                return vec![];
+            }
 
-                barrier
-            })
-            .collect::<Vec<_>>();
+            let barrier = dx::ResourceBarrier::transition(raw, *old_state, state, None);
+            *old_state = state;
+
+            vec![barrier]
+        });
+
+        let barriers = buffer_barriers.chain(texture_barriers).collect::<Vec<_>>();
 
         if !barriers.is_empty() {
             self.list.resource_barrier(&barriers);
         }
     }
 
-    fn set_texture_barriers(&self, barriers: &[Self::TextureBarrier<'_>]) {
-        let barriers = barriers
-            .iter()
-            .filter_map(|(t, s, b)| {
-                let (raw, mut old_state) = match &t.state {
-                    TextureState::Local { raw, state } => (raw, state.lock()),
-                    TextureState::CrossAdapter { cross, state, .. } => (cross, state.lock()),
-                    TextureState::Binded {
-                        local,
-                        cross,
-                        local_state,
-                        cross_state,
-                        ..
-                    } => {
-                        if *b {
-                            (cross, cross_state.lock())
-                        } else {
-                            (local, local_state.lock())
-                        }
-                    }
-                };
+    fn generate_mips(&mut self, handle: RenderHandle<Texture>) {
+        debug_assert!(
+            self.ty != CommandBufferType::Transfer,
+            "mips can not be generated on a transfer buffer"
+        );
+        if self.ty == CommandBufferType::Transfer {
+            return;
+        }
+        self.validate(|| StreamCommand::GenerateMips(StreamRef::Texture(handle)));
 
-                if *old_state == *s {
-                    return None;
-                }
+        let guard = self.handles.textures.lock();
+        let Some(texture) = guard.get(handle) else {
+            cold_path();
+            return;
+        };
+        self.handles.touch_texture(&self.device, handle, texture);
+
+        let desc = &texture.desc;
+        if desc.mip_levels < 2 {
+            return;
+        }
 
-                let barrier = Some(dx::ResourceBarrier::transition(raw, *old_state, *s, None));
-                *old_state = *s;
+        let Some(generator) = self.mip_generator.clone() else {
+            error!(
+                "Mips of texture {:?} can not be generated, the mips shader failed to compile",
+                desc.name
+            );
+            return;
+        };
 
-                barrier
-            })
-            .collect::<Vec<_>>();
+        let TextureState::Local { raw, state } = &texture.state else {
+            error!("Mips can only be generated for local textures");
+            return;
+        };
+
+        if !matches!(desc.ty, TextureType::D2 | TextureType::Cube)
+            || !desc.usage.contains(TextureUsages::Storage)
+            || desc.format.is_compressed()
+        {
+            error!(
+                "Mips can not be generated for texture {:?}, it requires a 2D storage texture",
+                desc.name
+            );
+            return;
+        }
+
+        let layers = desc.array_layers();
+        let mut tracker = state.lock();
+        self.acquire(
+            texture.stream_ref(),
+            &texture.owner,
+            tracker.uniform_state(),
+            ResourceState::Sampled,
+        );
+
+        let srv_format = map_format(desc.format);
+        let uav_format = map_format(desc.format.to_linear());
+        let is_srgb = desc.format.is_srgb() as u32;
+
+        let heap = self.descriptors.shader_heap.lock();
+        let heap_raw = heap.heap.clone();
+        let inc_size = heap.inc_size;
+        drop(heap);
+
+        self.list
+            .set_compute_root_signature(Some(&generator.root_signature));
+        self.list.set_pipeline_state(&generator.pso);
+        self.list.set_descriptor_heaps(&[Some(heap_raw)]);
+
+        for mip in 1..desc.mip_levels {
+            let mut barriers = tracked_barriers(
+                raw,
+                &mut tracker,
+                Some(SubresourceRange::mip(mip - 1, 0..layers)),
+                dx::ResourceStates::NonPixelShaderResource,
+            );
+            barriers.extend(tracked_barriers(
+                raw,
+                &mut tracker,
+                Some(SubresourceRange::mip(mip, 0..layers)),
+                dx::ResourceStates::UnorderedAccess,
+            ));
+            if !barriers.is_empty() {
+                self.list.resource_barrier(&barriers);
+            }
+
+            let descriptor = self.descriptors.shader_heap.lock().alloc(2);
+
+            self.device.create_shader_resource_view(
+                Some(raw),
+                Some(&dx::ShaderResourceViewDesc::texture_2d_array(
+                    srv_format,
+                    mip - 1,
+                    1,
+                    0..layers,
+                    0,
+                    0.0,
+                )),
+                descriptor.cpu,
+            );
+            self.device.create_unordered_access_view(
+                Some(raw),
+                dx::RES_NONE,
+                Some(&dx::UnorderedAccessViewDesc::texture_2d_array(
+                    uav_format,
+                    mip,
+                    0..layers,
+                    0,
+                )),
+                descriptor.cpu.advance(1, inc_size),
+            );
+
+            let (width, height, _) = desc.mip_extent(mip);
+
+            self.list.set_compute_root_32bit_constants(
+                0,
+                &[
+                    (1.0 / width as f32).to_bits(),
+                    (1.0 / height as f32).to_bits(),
+                    is_srgb,
+                ],
+                0,
+            );
+            self.list
+                .set_compute_root_descriptor_table(1, descriptor.gpu);
+            self.list
+                .set_compute_root_descriptor_table(2, descriptor.gpu.advance(1, inc_size));
+
+            self.list.dispatch(
+                width.div_ceil(MIPS_GROUP_SIZE),
+                height.div_ceil(MIPS_GROUP_SIZE),
+                layers,
+            );
+
+            self.allocator.descriptors.get_mut().push(descriptor);
+        }
 
+        let barriers = tracked_barriers(
+            raw,
+            &mut tracker,
+            None,
+            map_resource_state(ResourceState::Sampled),
+        );
         if !barriers.is_empty() {
             self.list.resource_barrier(&barriers);
         }
     }
-}
 
-pub struct DxRenderEncoder<'a> {
-    _marker: PhantomData<&'a ()>,
-}
+    /// D3D12 has no explicit ownership, a resource in `Common` is promoted by
+    /// whichever queue uses it next. The handoff is still tracked to report misuse.
+    fn release_buffer(&self, handle: RenderHandle<Buffer>, to: CommandBufferType) {
+        self.validate(|| StreamCommand::Release {
+            resource: StreamRef::Buffer(handle),
+            queue: to,
+        });
 
-impl<'a> RenderEncoder for DxRenderEncoder<'a> {}
+        let guard = self.handles.buffers.lock();
+        let Some(buffer) = guard.get(handle) else {
+            cold_path();
+            return;
+        };
 
-pub struct DxComputeEncoder<'a> {
-    _marker: PhantomData<&'a ()>,
-}
+        self.set_buffer_barriers(&[BufferBarrier {
+            buffer,
+            state: ResourceState::Common,
+        }]);
+        // Upload and readback heaps are readable on every queue without a handoff.
+        if buffer.is_default_heap() {
+            self.release(buffer.stream_ref(), &buffer.owner, to);
+        }
+    }
 
-impl<'a> ComputeEncoder for DxComputeEncoder<'a> {}
+    fn release_texture(&self, handle: RenderHandle<Texture>, to: CommandBufferType) {
+        self.validate(|| StreamCommand::Release {
+            resource: StreamRef::Texture(handle),
+            queue: to,
+        });
 
-pub struct DxTransferEncoder<'a> {
-    cmd_buffer: &'a mut DxCommandBuffer,
-}
+        let guard = self.handles.textures.lock();
+        let Some(texture) = guard.get(handle) else {
+            cold_path();
+            return;
+        };
 
-impl<'a> TransferEncoder for DxTransferEncoder<'a> {
-    type Buffer = DxBuffer;
-    type Texture = DxTexture;
+        self.set_texture_barriers(&[TextureBarrier::new(texture, ResourceState::Common)]);
+        self.release(texture.stream_ref(), &texture.owner, to);
+    }
+
+    fn write_timestamp(&self, set: &Self::QuerySet, index: u32) -> Result<(), QueryError> {
+        query::validate_query(&set.desc, QueryType::Timestamp, index, self.ty)?;
+        self.list.end_query(&set.raw, set.raw_type(), index);
 
-    fn copy_buffer_to_buffer(&self, dst: &Self::Buffer, src: &Self::Buffer) {
-        self.cmd_buffer.list.copy_resource(&dst.raw, &src.raw);
+        Ok(())
     }
 
-    fn copy_texture_to_texture(&self, dst: &Self::Texture, src: &Self::Texture) {
-        match (&dst.state, &src.state) {
-            (
-                TextureState::Local { raw: dst_raw, .. },
-                TextureState::Local { raw: src_raw, .. },
-            ) => self.cmd_buffer.list.copy_resource(dst_raw, src_raw),
-            _ => return,
-        };
+    fn begin_query(&self, set: &Self::QuerySet, index: u32) -> Result<(), QueryError> {
+        query::validate_query(&set.desc, QueryType::PipelineStatistics, index, self.ty)?;
+        self.list.begin_query(&set.raw, set.raw_type(), index);
+
+        Ok(())
     }
 
-    fn upload_to_texture(&self, dst: &Self::Texture, src: &Self::Buffer, data: &[u8]) {
-        let dst_res = match &dst.state {
-            TextureState::Local { raw, .. } => raw,
-            _ => return,
-        };
+    fn end_query(&self, set: &Self::QuerySet, index: u32) -> Result<(), QueryError> {
+        query::validate_query(&set.desc, QueryType::PipelineStatistics, index, self.ty)?;
+        self.list.end_query(&set.raw, set.raw_type(), index);
 
-        let copied = self.cmd_buffer.list.update_subresources_fixed::<1, _, _>(
-            dst_res,
-            &src.raw,
-            0,
-            0..1,
-            &[dx::SubresourceData::new(data).with_row_pitch(4 * dst.desc.width as usize)],
+        Ok(())
+    }
+
+    fn resolve_queries(
+        &self,
+        set: &Self::QuerySet,
+        queries: Range<u32>,
+        dst: &Self::Buffer,
+        offset: usize,
+    ) -> Result<(), QueryError> {
+        query::validate_resolve(&set.desc, &queries, &dst.desc, offset, self.ty)?;
+        self.use_resources(ResourceUsages::default().use_buffer(dst, ResourceState::CopyDst));
+
+        self.list.resolve_query_data(
+            &set.raw,
+            set.raw_type(),
+            queries.start,
+            queries.len() as u32,
+            &dst.raw,
+            offset as u64,
         );
 
-        debug_assert!(copied > 0);
+        Ok(())
+    }
+
+    fn begin_scope(&self, name: &'static str) {
+        let begin = self.profiler_timestamp();
+        self.scopes.lock().push(name, begin);
+    }
+
+    fn end_scope(&self) {
+        let end = self.profiler_timestamp();
+        self.scopes.lock().pop(end);
     }
 }
 
-impl<'a> DynTransferEncoder for DxTransferEncoder<'a> {
-    fn copy_buffer_to_buffer(&self, dst: RenderHandle<Buffer>, src: RenderHandle<Buffer>) {
-        let guard = self.cmd_buffer.handles.buffers.lock();
+impl DxCommandBuffer {
+    fn validate(&self, command: impl FnOnce() -> StreamCommand) {
+        if let Some(validation) = &self.validation {
+            validation.lock().validate(&command());
+        }
+    }
 
-        let Some(dst) = guard.get(dst) else {
-            cold_path();
+    fn begin_encoder(&self, encoder: &'static str) {
+        if let Some(validation) = &self.validation {
+            validation.lock().begin_encoder(encoder);
+        }
+    }
+
+    /// Takes a resource used in `after` for the queue of this buffer, `before`
+    /// being its raw state before the use or `None` if its subresources differ.
+    fn acquire(
+        &self,
+        resource: StreamRef,
+        owner: &Mutex<QueueOwnership>,
+        before: Option<dx::ResourceStates>,
+        after: ResourceState,
+    ) {
+        // Only a resource left in `Common` moves between queues implicitly.
+        let before = (before == Some(dx::ResourceStates::Common)).then_some(ResourceState::Common);
+        if let Err(error) = owner.lock().acquire(self.ty, before) {
+            self.ownership_error(resource, error);
+        }
+
+        if !ownership::supports(self.ty, after) {
+            self.ownership_error(
+                resource,
+                OwnershipError::UnsupportedState {
+                    state: after,
+                    queue: self.ty,
+                },
+            );
+        }
+    }
+
+    fn release(&self, resource: StreamRef, owner: &Mutex<QueueOwnership>, to: CommandBufferType) {
+        if let Err(error) = owner.lock().release(self.ty, to) {
+            self.ownership_error(resource, error);
+        }
+    }
+
+    /// Handoffs are tracked on every device, the validation layer only adds the
+    /// name of the resource.
+    fn ownership_error(&self, resource: StreamRef, error: OwnershipError) {
+        match &self.handles.validator {
+            Some(validator) => validator.report_ownership(resource, error),
+            None => error!("Invalid queue handoff of {}: {}", resource, error),
+        }
+    }
+
+    /// Transitions the buffer behind a bound view for the view type, views
+    /// created without a handle are left as they are.
+    fn use_buffer_view(&self, view: &DxBufferView) {
+        let Some(handle) = view.buffer else {
             return;
         };
-
-        let Some(src) = guard.get(src) else {
+        let guard = self.handles.buffers.lock();
+        let Some(buffer) = guard.get(handle) else {
             cold_path();
             return;
         };
 
-        <Self as TransferEncoder>::copy_buffer_to_buffer(&self, dst, src);
+        self.use_resources(
+            ResourceUsages::default().use_buffer(buffer, view.desc.ty.resource_state()),
+        );
     }
 
-    fn copy_texture_to_texture(&self, dst: RenderHandle<Texture>, src: RenderHandle<Texture>) {
-        let guard = self.cmd_buffer.handles.textures.lock();
-
-        let Some(dst) = guard.get(dst) else {
-            cold_path();
+    /// Render passes record no barriers, a view bound inside one must have been
+    /// listed in [`RenderPassDesc::buffer_views`].
+    fn check_pass_buffer_view(&self, view: &DxBufferView) {
+        let Some(handle) = view.buffer else {
             return;
         };
-
-        let Some(src) = guard.get(src) else {
+        let guard = self.handles.buffers.lock();
+        let Some(buffer) = guard.get(handle) else {
             cold_path();
             return;
         };
 
-        <Self as TransferEncoder>::copy_texture_to_texture(&self, dst, src);
+        let state = view.desc.ty.resource_state();
+        let current = *buffer.state.lock();
+        if buffer.is_default_heap() && current != map_resource_state(state) {
+            error!(
+                "Buffer view {:?} is not listed in its render pass, the buffer is in {:?} instead of {:?}",
+                view.desc, current, state
+            );
+        }
     }
 
-    fn upload_to_texture(
+    /// Only graphics lists are timed, the profiler converts ticks with the
+    /// frequency of the graphics queue.
+    fn profiler_timestamp(&self) -> Option<u32> {
+        if self.ty != CommandBufferType::Graphics {
+            return None;
+        }
+
+        let mut profiler = self.handles.profiler.lock();
+        let (set, index) = profiler.allocate()?;
+        self.list
+            .end_query(&set.raw, dx::QueryType::Timestamp, index);
+
+        Some(index)
+    }
+
+    fn begin_event(&self, name: &str) {
+        if self.markers {
+            self.list.begin_event(DEBUG_EVENT_COLOR, name);
+        }
+    }
+
+    fn end_event(&self) {
+        if self.markers {
+            self.list.end_event();
+        }
+    }
+
+    fn set_marker(&self, name: &str) {
+        if self.markers {
+            self.list.set_marker(DEBUG_EVENT_COLOR, name);
+        }
+    }
+}
+
+/// Color of debug events in PIX, RenderDoc ignores it.
+const DEBUG_EVENT_COLOR: u64 = 0xff_00_80_ff;
+
+fn tracked_barriers(
+    raw: &dx::Resource,
+    tracker: &mut StateTracker<dx::ResourceStates>,
+    range: Option<SubresourceRange>,
+    after: dx::ResourceStates,
+) -> Vec<dx::ResourceBarrier<'_>> {
+    tracker
+        .transition(range, after)
+        .into_iter()
+        .map(|t| dx::ResourceBarrier::transition(raw, t.before, t.after, t.subresource))
+        .collect()
+}
+
+pub struct DxRenderEncoder<'a> {
+    cmd_buffer: &'a mut DxCommandBuffer,
+    /// Attachments with a discard store op, discarded once the pass ends.
+    discards: Vec<dx::Resource>,
+    color_formats: Vec<Format>,
+    depth_format: Option<Format>,
+}
+
+impl<'a> DxRenderEncoder<'a> {
+    fn execute_indirect<T: bytemuck::Pod>(
         &self,
-        dst: RenderHandle<Texture>,
-        src: RenderHandle<Buffer>,
-        data: &[u8],
-    ) {
-        let bguard = self.cmd_buffer.handles.buffers.lock();
-        let tguard = self.cmd_buffer.handles.textures.lock();
+        signature: &dx::CommandSignature,
+        args: &DxBuffer,
+        offset: usize,
+        count: Option<(&DxBuffer, usize)>,
+        max_draws: u32,
+    ) -> Result<(), IndirectError> {
+        indirect::validate::<T>(&args.desc, offset, max_draws)?;
 
-        let Some(dst) = tguard.get(dst) else {
-            cold_path();
-            return;
-        };
+        let mut usages = ResourceUsages::default();
+        usages.use_buffer(args, ResourceState::Indirect);
 
-        let Some(src) = bguard.get(src) else {
-            cold_path();
-            return;
-        };
+        if let Some((count, count_offset)) = count {
+            indirect::validate::<u32>(&count.desc, count_offset, 1)?;
+            usages.use_buffer(count, ResourceState::Indirect);
+        }
+
+        self.cmd_buffer.use_resources(&usages);
+        self.cmd_buffer.list.execute_indirect(
+            signature,
+            max_draws,
+            &args.raw,
+            offset as u64,
+            count.map(|(count, _)| &count.raw),
+            count.map_or(0, |(_, count_offset)| count_offset as u64),
+        );
+
+        Ok(())
+    }
+}
+
+impl<'a> RenderEncoder for DxRenderEncoder<'a> {
+    type Buffer = DxBuffer;
+    type BufferView = DxBufferView;
+    type Pipeline = DxRenderPipeline;
+    type Bundle = DxBundle;
+
+    fn set_viewport(&self, viewport: Viewport) {
+        self.cmd_buffer.list.rs_set_viewports(&[dx::Viewport::new(
+            viewport.x,
+            viewport.y,
+            viewport.width,
+            viewport.height,
+            viewport.min_depth,
+            viewport.max_depth,
+        )]);
+    }
+
+    fn set_scissor(&self, scissor: Scissor) {
+        self.cmd_buffer.list.rs_set_scissor_rects(&[dx::Rect::new(
+            scissor.x,
+            scissor.y,
+            scissor.x + scissor.width as i32,
+            scissor.y + scissor.height as i32,
+        )]);
+    }
+
+    fn set_stencil_reference(&self, reference: u32) {
+        self.cmd_buffer.list.om_set_stencil_ref(reference);
+    }
+
+    fn set_blend_constants(&self, constants: [f32; 4]) {
+        self.cmd_buffer.list.om_set_blend_factor(constants);
+    }
+
+    fn set_pipeline(&self, pipeline: &Self::Pipeline) {
+        let list = &self.cmd_buffer.list;
+
+        list.set_graphics_root_signature(Some(&pipeline.root_signature));
+        list.set_pipeline_state(&pipeline.pso);
+        list.ia_set_primitive_topology(pipeline.topology);
+    }
+
+    fn set_vertex_buffer(&self, slot: u32, view: &Self::BufferView) {
+        let DxBufferViewKind::Vertex(vbv) = &view.kind else {
+            error!("Buffer view {:?} is not a vertex view", view.desc);
+            return;
+        };
+        self.cmd_buffer.check_pass_buffer_view(view);
+
+        self.cmd_buffer
+            .list
+            .ia_set_vertex_buffers(slot, std::slice::from_ref(vbv));
+    }
+
+    fn set_index_buffer(&self, view: &Self::BufferView) {
+        let DxBufferViewKind::Index(ibv) = &view.kind else {
+            error!("Buffer view {:?} is not an index view", view.desc);
+            return;
+        };
+        self.cmd_buffer.check_pass_buffer_view(view);
+
+        self.cmd_buffer.list.ia_set_index_buffer(Some(ibv));
+    }
+
+    fn draw_instanced(&self, vertices: Range<u32>, instances: Range<u32>) {
+        self.cmd_buffer.list.draw_instanced(
+            vertices.len() as u32,
+            instances.len() as u32,
+            vertices.start,
+            instances.start,
+        );
+    }
+
+    fn draw_indexed_instanced(&self, indices: Range<u32>, base_vertex: i32, instances: Range<u32>) {
+        self.cmd_buffer.list.draw_indexed_instanced(
+            indices.len() as u32,
+            instances.len() as u32,
+            indices.start,
+            base_vertex,
+            instances.start,
+        );
+    }
+
+    fn draw_indirect(
+        &self,
+        args: &Self::Buffer,
+        offset: usize,
+        draw_count: u32,
+    ) -> Result<(), IndirectError> {
+        self.execute_indirect::<DrawIndirectArgs>(
+            &self.cmd_buffer.indirect.draw,
+            args,
+            offset,
+            None,
+            draw_count,
+        )
+    }
+
+    fn draw_indexed_indirect(
+        &self,
+        args: &Self::Buffer,
+        offset: usize,
+        draw_count: u32,
+    ) -> Result<(), IndirectError> {
+        self.execute_indirect::<DrawIndexedIndirectArgs>(
+            &self.cmd_buffer.indirect.draw_indexed,
+            args,
+            offset,
+            None,
+            draw_count,
+        )
+    }
+
+    fn draw_indirect_count(
+        &self,
+        args: &Self::Buffer,
+        offset: usize,
+        count: &Self::Buffer,
+        count_offset: usize,
+        max_draws: u32,
+    ) -> Result<(), IndirectError> {
+        self.execute_indirect::<DrawIndirectArgs>(
+            &self.cmd_buffer.indirect.draw,
+            args,
+            offset,
+            Some((count, count_offset)),
+            max_draws,
+        )
+    }
+
+    fn draw_indexed_indirect_count(
+        &self,
+        args: &Self::Buffer,
+        offset: usize,
+        count: &Self::Buffer,
+        count_offset: usize,
+        max_draws: u32,
+    ) -> Result<(), IndirectError> {
+        self.execute_indirect::<DrawIndexedIndirectArgs>(
+            &self.cmd_buffer.indirect.draw_indexed,
+            args,
+            offset,
+            Some((count, count_offset)),
+            max_draws,
+        )
+    }
+
+    fn execute_bundle(&self, bundle: &Self::Bundle) -> Result<(), BundleError> {
+        if !bundle.validity.is_valid() {
+            return Err(BundleError::Invalidated);
+        }
+
+        bundle::validate_pass(&bundle.desc, &self.color_formats, self.depth_format)?;
+        self.cmd_buffer.list.execute_bundle(&bundle.list);
+
+        Ok(())
+    }
+}
+
+impl<'a> DebugEncoder for DxRenderEncoder<'a> {
+    fn push_debug_group(&self, name: &str) {
+        self.cmd_buffer.begin_event(name);
+    }
+
+    fn pop_debug_group(&self) {
+        self.cmd_buffer.end_event();
+    }
+
+    fn insert_marker(&self, name: &str) {
+        self.cmd_buffer.set_marker(name);
+    }
+}
+
+impl<'a> DynRenderEncoder for DxRenderEncoder<'a> {
+    fn set_pipeline(&self, pipeline: RenderHandle<RenderPipeline>) {
+        self.cmd_buffer
+            .validate(|| StreamCommand::SetPipeline(StreamRef::RenderPipeline(pipeline)));
+        let guard = self.cmd_buffer.handles.render_pipelines.lock();
+
+        let Some(pipeline) = guard.get(pipeline) else {
+            cold_path();
+            return;
+        };
+
+        <Self as RenderEncoder>::set_pipeline(self, pipeline);
+    }
+
+    fn set_vertex_buffer(&self, slot: u32, view: RenderHandle<BufferView>) {
+        self.cmd_buffer.validate(|| StreamCommand::SetVertexBuffer {
+            slot,
+            view: StreamRef::BufferView(view),
+        });
+        let guard = self.cmd_buffer.handles.buffer_views.lock();
+
+        let Some(view) = guard.get(view) else {
+            cold_path();
+            return;
+        };
+
+        <Self as RenderEncoder>::set_vertex_buffer(self, slot, view);
+    }
+
+    fn set_index_buffer(&self, view: RenderHandle<BufferView>) {
+        self.cmd_buffer
+            .validate(|| StreamCommand::SetIndexBuffer(StreamRef::BufferView(view)));
+        let guard = self.cmd_buffer.handles.buffer_views.lock();
+
+        let Some(view) = guard.get(view) else {
+            cold_path();
+            return;
+        };
+
+        <Self as RenderEncoder>::set_index_buffer(self, view);
+    }
+
+    fn draw_indirect(
+        &self,
+        args: RenderHandle<Buffer>,
+        offset: usize,
+        draw_count: u32,
+    ) -> Result<(), IndirectError> {
+        self.cmd_buffer.validate(|| StreamCommand::DrawIndirect {
+            args: StreamRef::Buffer(args),
+            offset,
+            count: None,
+            max_draws: draw_count,
+            indexed: false,
+        });
+        let guard = self.cmd_buffer.handles.buffers.lock();
+
+        let Some(args) = guard.get(args) else {
+            cold_path();
+            return Err(IndirectError::DeadHandle);
+        };
+
+        <Self as RenderEncoder>::draw_indirect(self, args, offset, draw_count)
+    }
+
+    fn draw_indexed_indirect(
+        &self,
+        args: RenderHandle<Buffer>,
+        offset: usize,
+        draw_count: u32,
+    ) -> Result<(), IndirectError> {
+        self.cmd_buffer.validate(|| StreamCommand::DrawIndirect {
+            args: StreamRef::Buffer(args),
+            offset,
+            count: None,
+            max_draws: draw_count,
+            indexed: true,
+        });
+        let guard = self.cmd_buffer.handles.buffers.lock();
+
+        let Some(args) = guard.get(args) else {
+            cold_path();
+            return Err(IndirectError::DeadHandle);
+        };
+
+        <Self as RenderEncoder>::draw_indexed_indirect(self, args, offset, draw_count)
+    }
+
+    fn draw_indirect_count(
+        &self,
+        args: RenderHandle<Buffer>,
+        offset: usize,
+        count: RenderHandle<Buffer>,
+        count_offset: usize,
+        max_draws: u32,
+    ) -> Result<(), IndirectError> {
+        self.cmd_buffer.validate(|| StreamCommand::DrawIndirect {
+            args: StreamRef::Buffer(args),
+            offset,
+            count: Some((StreamRef::Buffer(count), count_offset)),
+            max_draws,
+            indexed: false,
+        });
+        let guard = self.cmd_buffer.handles.buffers.lock();
+
+        let (Some(args), Some(count)) = (guard.get(args), guard.get(count)) else {
+            cold_path();
+            return Err(IndirectError::DeadHandle);
+        };
+
+        <Self as RenderEncoder>::draw_indirect_count(
+            self,
+            args,
+            offset,
+            count,
+            count_offset,
+            max_draws,
+        )
+    }
+
+    fn draw_indexed_indirect_count(
+        &self,
+        args: RenderHandle<Buffer>,
+        offset: usize,
+        count: RenderHandle<Buffer>,
+        count_offset: usize,
+        max_draws: u32,
+    ) -> Result<(), IndirectError> {
+        self.cmd_buffer.validate(|| StreamCommand::DrawIndirect {
+            args: StreamRef::Buffer(args),
+            offset,
+            count: Some((StreamRef::Buffer(count), count_offset)),
+            max_draws,
+            indexed: true,
+        });
+        let guard = self.cmd_buffer.handles.buffers.lock();
+
+        let (Some(args), Some(count)) = (guard.get(args), guard.get(count)) else {
+            cold_path();
+            return Err(IndirectError::DeadHandle);
+        };
+
+        <Self as RenderEncoder>::draw_indexed_indirect_count(
+            self,
+            args,
+            offset,
+            count,
+            count_offset,
+            max_draws,
+        )
+    }
+}
+
+impl<'a> Drop for DxRenderEncoder<'a> {
+    fn drop(&mut self) {
+        self.cmd_buffer.validate(|| StreamCommand::EndRenderPass);
+        for resource in self.discards.drain(..) {
+            self.cmd_buffer.list.discard_resource(&resource, None);
+        }
+    }
+}
+
+/// Bundle list replayed with `ExecuteBundle`, the allocator lives as long as the list.
+#[derive(Debug)]
+pub struct DxBundle {
+    desc: BundleDesc,
+    list: dx::GraphicsCommandList,
+    _allocator: dx::CommandAllocator,
+    validity: BundleValidity,
+}
+
+pub struct DxBundleEncoder {
+    handles: Arc<HandleStorage>,
+    indirect: Arc<IndirectSignatures>,
+    desc: BundleDesc,
+    list: dx::GraphicsCommandList,
+    allocator: dx::CommandAllocator,
+    references: Mutex<BundleReferences>,
+    markers: bool,
+}
+
+impl DxBundleEncoder {
+    /// Bundles can not transition resources, the pass has to.
+    fn execute_indirect<T: bytemuck::Pod>(
+        &self,
+        signature: &dx::CommandSignature,
+        args: &DxBuffer,
+        offset: usize,
+        count: Option<(&DxBuffer, usize)>,
+        max_draws: u32,
+    ) -> Result<(), IndirectError> {
+        indirect::validate::<T>(&args.desc, offset, max_draws)?;
+
+        if let Some((count, count_offset)) = count {
+            indirect::validate::<u32>(&count.desc, count_offset, 1)?;
+        }
+
+        self.list.execute_indirect(
+            signature,
+            max_draws,
+            &args.raw,
+            offset as u64,
+            count.map(|(count, _)| &count.raw),
+            count.map_or(0, |(_, count_offset)| count_offset as u64),
+        );
+
+        Ok(())
+    }
+}
+
+impl RenderEncoder for DxBundleEncoder {
+    type Buffer = DxBuffer;
+    type BufferView = DxBufferView;
+    type Pipeline = DxRenderPipeline;
+    type Bundle = DxBundle;
+
+    fn set_viewport(&self, _viewport: Viewport) {
+        warn!("Ignoring a viewport set in a bundle, bundles inherit it from the pass");
+    }
+
+    fn set_scissor(&self, _scissor: Scissor) {
+        warn!("Ignoring a scissor set in a bundle, bundles inherit it from the pass");
+    }
+
+    fn set_stencil_reference(&self, reference: u32) {
+        self.list.om_set_stencil_ref(reference);
+    }
+
+    fn set_blend_constants(&self, constants: [f32; 4]) {
+        self.list.om_set_blend_factor(constants);
+    }
+
+    fn set_pipeline(&self, pipeline: &Self::Pipeline) {
+        self.list
+            .set_graphics_root_signature(Some(&pipeline.root_signature));
+        self.list.set_pipeline_state(&pipeline.pso);
+        self.list.ia_set_primitive_topology(pipeline.topology);
+    }
+
+    fn set_vertex_buffer(&self, slot: u32, view: &Self::BufferView) {
+        let DxBufferViewKind::Vertex(vbv) = &view.kind else {
+            error!("Buffer view {:?} is not a vertex view", view.desc);
+            return;
+        };
+
+        self.list
+            .ia_set_vertex_buffers(slot, std::slice::from_ref(vbv));
+    }
+
+    fn set_index_buffer(&self, view: &Self::BufferView) {
+        let DxBufferViewKind::Index(ibv) = &view.kind else {
+            error!("Buffer view {:?} is not an index view", view.desc);
+            return;
+        };
+
+        self.list.ia_set_index_buffer(Some(ibv));
+    }
+
+    fn draw_instanced(&self, vertices: Range<u32>, instances: Range<u32>) {
+        self.list.draw_instanced(
+            vertices.len() as u32,
+            instances.len() as u32,
+            vertices.start,
+            instances.start,
+        );
+    }
+
+    fn draw_indexed_instanced(&self, indices: Range<u32>, base_vertex: i32, instances: Range<u32>) {
+        self.list.draw_indexed_instanced(
+            indices.len() as u32,
+            instances.len() as u32,
+            indices.start,
+            base_vertex,
+            instances.start,
+        );
+    }
+
+    fn draw_indirect(
+        &self,
+        args: &Self::Buffer,
+        offset: usize,
+        draw_count: u32,
+    ) -> Result<(), IndirectError> {
+        self.execute_indirect::<DrawIndirectArgs>(
+            &self.indirect.draw,
+            args,
+            offset,
+            None,
+            draw_count,
+        )
+    }
+
+    fn draw_indexed_indirect(
+        &self,
+        args: &Self::Buffer,
+        offset: usize,
+        draw_count: u32,
+    ) -> Result<(), IndirectError> {
+        self.execute_indirect::<DrawIndexedIndirectArgs>(
+            &self.indirect.draw_indexed,
+            args,
+            offset,
+            None,
+            draw_count,
+        )
+    }
+
+    fn draw_indirect_count(
+        &self,
+        args: &Self::Buffer,
+        offset: usize,
+        count: &Self::Buffer,
+        count_offset: usize,
+        max_draws: u32,
+    ) -> Result<(), IndirectError> {
+        self.execute_indirect::<DrawIndirectArgs>(
+            &self.indirect.draw,
+            args,
+            offset,
+            Some((count, count_offset)),
+            max_draws,
+        )
+    }
+
+    fn draw_indexed_indirect_count(
+        &self,
+        args: &Self::Buffer,
+        offset: usize,
+        count: &Self::Buffer,
+        count_offset: usize,
+        max_draws: u32,
+    ) -> Result<(), IndirectError> {
+        self.execute_indirect::<DrawIndexedIndirectArgs>(
+            &self.indirect.draw_indexed,
+            args,
+            offset,
+            Some((count, count_offset)),
+            max_draws,
+        )
+    }
+
+    fn execute_bundle(&self, _bundle: &Self::Bundle) -> Result<(), BundleError> {
+        Err(BundleError::Nested)
+    }
+}
+
+impl DebugEncoder for DxBundleEncoder {
+    fn push_debug_group(&self, name: &str) {
+        if self.markers {
+            self.list.begin_event(DEBUG_EVENT_COLOR, name);
+        }
+    }
+
+    fn pop_debug_group(&self) {
+        if self.markers {
+            self.list.end_event();
+        }
+    }
+
+    fn insert_marker(&self, name: &str) {
+        if self.markers {
+            self.list.set_marker(DEBUG_EVENT_COLOR, name);
+        }
+    }
+}
+
+impl DynRenderEncoder for DxBundleEncoder {
+    fn set_pipeline(&self, handle: RenderHandle<RenderPipeline>) {
+        let guard = self.handles.render_pipelines.lock();
+
+        let Some(pipeline) = guard.get(handle) else {
+            cold_path();
+            return;
+        };
+
+        self.references.lock().use_render_pipeline(handle);
+        <Self as RenderEncoder>::set_pipeline(self, pipeline);
+    }
+
+    fn set_vertex_buffer(&self, slot: u32, handle: RenderHandle<BufferView>) {
+        let guard = self.handles.buffer_views.lock();
+
+        let Some(view) = guard.get(handle) else {
+            cold_path();
+            return;
+        };
+
+        let mut references = self.references.lock();
+        references.use_buffer_view(handle);
+        if let Some(buffer) = view.buffer {
+            references.use_buffer(buffer);
+        }
+        drop(references);
+        <Self as RenderEncoder>::set_vertex_buffer(self, slot, view);
+    }
+
+    fn set_index_buffer(&self, handle: RenderHandle<BufferView>) {
+        let guard = self.handles.buffer_views.lock();
+
+        let Some(view) = guard.get(handle) else {
+            cold_path();
+            return;
+        };
+
+        let mut references = self.references.lock();
+        references.use_buffer_view(handle);
+        if let Some(buffer) = view.buffer {
+            references.use_buffer(buffer);
+        }
+        drop(references);
+        <Self as RenderEncoder>::set_index_buffer(self, view);
+    }
+
+    fn draw_indirect(
+        &self,
+        args_handle: RenderHandle<Buffer>,
+        offset: usize,
+        draw_count: u32,
+    ) -> Result<(), IndirectError> {
+        let guard = self.handles.buffers.lock();
+
+        let Some(args) = guard.get(args_handle) else {
+            cold_path();
+            return Err(IndirectError::DeadHandle);
+        };
+
+        self.references.lock().use_buffer(args_handle);
+        <Self as RenderEncoder>::draw_indirect(self, args, offset, draw_count)
+    }
+
+    fn draw_indexed_indirect(
+        &self,
+        args_handle: RenderHandle<Buffer>,
+        offset: usize,
+        draw_count: u32,
+    ) -> Result<(), IndirectError> {
+        let guard = self.handles.buffers.lock();
+
+        let Some(args) = guard.get(args_handle) else {
+            cold_path();
+            return Err(IndirectError::DeadHandle);
+        };
+
+        self.references.lock().use_buffer(args_handle);
+        <Self as RenderEncoder>::draw_indexed_indirect(self, args, offset, draw_count)
+    }
+
+    fn draw_indirect_count(
+        &self,
+        args_handle: RenderHandle<Buffer>,
+        offset: usize,
+        count_handle: RenderHandle<Buffer>,
+        count_offset: usize,
+        max_draws: u32,
+    ) -> Result<(), IndirectError> {
+        let guard = self.handles.buffers.lock();
+
+        let (Some(args), Some(count)) = (guard.get(args_handle), guard.get(count_handle)) else {
+            cold_path();
+            return Err(IndirectError::DeadHandle);
+        };
+
+        let mut references = self.references.lock();
+        references.use_buffer(args_handle);
+        references.use_buffer(count_handle);
+        drop(references);
+
+        <Self as RenderEncoder>::draw_indirect_count(
+            self,
+            args,
+            offset,
+            count,
+            count_offset,
+            max_draws,
+        )
+    }
+
+    fn draw_indexed_indirect_count(
+        &self,
+        args_handle: RenderHandle<Buffer>,
+        offset: usize,
+        count_handle: RenderHandle<Buffer>,
+        count_offset: usize,
+        max_draws: u32,
+    ) -> Result<(), IndirectError> {
+        let guard = self.handles.buffers.lock();
+
+        let (Some(args), Some(count)) = (guard.get(args_handle), guard.get(count_handle)) else {
+            cold_path();
+            return Err(IndirectError::DeadHandle);
+        };
+
+        let mut references = self.references.lock();
+        references.use_buffer(args_handle);
+        references.use_buffer(count_handle);
+        drop(references);
+
+        <Self as RenderEncoder>::draw_indexed_indirect_count(
+            self,
+            args,
+            offset,
+            count,
+            count_offset,
+            max_draws,
+        )
+    }
+}
+
+pub struct DxComputeEncoder<'a> {
+    cmd_buffer: &'a mut DxCommandBuffer,
+}
+
+impl<'a> ComputeEncoder for DxComputeEncoder<'a> {
+    type Buffer = DxBuffer;
+    type BufferView = DxBufferView;
+    type Pipeline = DxComputePipeline;
+
+    fn set_pipeline(&self, pipeline: &Self::Pipeline) {
+        let list = &self.cmd_buffer.list;
+
+        list.set_compute_root_signature(Some(&pipeline.root_signature));
+        list.set_pipeline_state(&pipeline.pso);
+    }
+
+    fn set_buffer_view(&self, slot: u32, view: &Self::BufferView) {
+        let DxBufferViewKind::Descriptor(Some(descriptor)) = &view.kind else {
+            error!("Buffer view {:?} can not be bound to a shader", view.desc);
+            return;
+        };
+        self.cmd_buffer.use_buffer_view(view);
+
+        self.cmd_buffer
+            .list
+            .set_compute_root_descriptor_table(slot, descriptor.gpu);
+    }
+
+    fn set_constants(&self, slot: u32, constants: &[u32]) {
+        self.cmd_buffer
+            .list
+            .set_compute_root_32bit_constants(slot, constants, 0);
+    }
+
+    fn dispatch(&self, x: u32, y: u32, z: u32) {
+        self.cmd_buffer.list.dispatch(x, y, z);
+    }
+
+    fn dispatch_indirect(&self, buffer: &Self::Buffer, offset: usize) -> Result<(), IndirectError> {
+        indirect::validate::<DispatchIndirectArgs>(&buffer.desc, offset, 1)?;
+
+        self.cmd_buffer
+            .use_resources(ResourceUsages::default().use_buffer(buffer, ResourceState::Indirect));
+
+        self.cmd_buffer.list.execute_indirect(
+            &self.cmd_buffer.indirect.dispatch,
+            1,
+            &buffer.raw,
+            offset as u64,
+            None,
+            0,
+        );
+
+        Ok(())
+    }
+}
+
+impl<'a> DebugEncoder for DxComputeEncoder<'a> {
+    fn push_debug_group(&self, name: &str) {
+        self.cmd_buffer.begin_event(name);
+    }
+
+    fn pop_debug_group(&self) {
+        self.cmd_buffer.end_event();
+    }
+
+    fn insert_marker(&self, name: &str) {
+        self.cmd_buffer.set_marker(name);
+    }
+}
+
+impl<'a> DynComputeEncoder for DxComputeEncoder<'a> {
+    fn set_pipeline(&self, pipeline: RenderHandle<ComputePipeline>) {
+        self.cmd_buffer
+            .validate(|| StreamCommand::SetPipeline(StreamRef::ComputePipeline(pipeline)));
+        let guard = self.cmd_buffer.handles.compute_pipelines.lock();
+
+        let Some(pipeline) = guard.get(pipeline) else {
+            cold_path();
+            return;
+        };
+
+        <Self as ComputeEncoder>::set_pipeline(self, pipeline);
+    }
+
+    fn set_buffer_view(&self, slot: u32, view: RenderHandle<BufferView>) {
+        self.cmd_buffer.validate(|| StreamCommand::SetBufferView {
+            slot,
+            view: StreamRef::BufferView(view),
+        });
+        let guard = self.cmd_buffer.handles.buffer_views.lock();
+
+        let Some(view) = guard.get(view) else {
+            cold_path();
+            return;
+        };
+
+        <Self as ComputeEncoder>::set_buffer_view(self, slot, view);
+    }
+
+    fn dispatch_indirect(
+        &self,
+        buffer: RenderHandle<Buffer>,
+        offset: usize,
+    ) -> Result<(), IndirectError> {
+        self.cmd_buffer
+            .validate(|| StreamCommand::DispatchIndirect {
+                args: StreamRef::Buffer(buffer),
+                offset,
+            });
+        let guard = self.cmd_buffer.handles.buffers.lock();
+
+        let Some(buffer) = guard.get(buffer) else {
+            cold_path();
+            return Err(IndirectError::DeadHandle);
+        };
+
+        <Self as ComputeEncoder>::dispatch_indirect(self, buffer, offset)
+    }
+}
+
+pub struct DxTransferEncoder<'a> {
+    cmd_buffer: &'a mut DxCommandBuffer,
+}
+
+/// `D3D12_TEXTURE_DATA_PITCH_ALIGNMENT` and `D3D12_TEXTURE_DATA_PLACEMENT_ALIGNMENT`.
+const TEXTURE_PITCH_ALIGNMENT: usize = 256;
+const TEXTURE_PLACEMENT_ALIGNMENT: usize = 512;
+
+fn local_texture(texture: &DxTexture) -> Result<&dx::Resource, TransferError> {
+    match &texture.state {
+        TextureState::Local { raw, .. } => Ok(raw),
+        _ => Err(TransferError::Unsupported(
+            "cross adapter textures can not be used in transfers",
+        )),
+    }
+}
+
+fn region_location<'r>(
+    raw: &'r dx::Resource,
+    desc: &TextureDesc,
+    region: &TextureRegion,
+) -> dx::TextureCopyLocation<'r> {
+    dx::TextureCopyLocation::subresource(raw, desc.subresource_index(region.mip, region.layer))
+}
+
+fn region_box(region: &TextureRegion) -> dx::DxBox {
+    let [x, y, z] = region.origin;
+    let [width, height, depth] = region.extent;
+
+    dx::DxBox::new(x, y, z, x + width, y + height, z + depth)
+}
+
+fn footprint_location<'r>(
+    buffer: &'r DxBuffer,
+    format: Format,
+    extent: [u32; 3],
+    layout: BufferTextureLayout,
+) -> Result<dx::TextureCopyLocation<'r>, TransferError> {
+    layout.validate(&buffer.desc, format, extent)?;
+
+    if !layout.offset.is_multiple_of(TEXTURE_PLACEMENT_ALIGNMENT) {
+        return Err(TransferError::Misaligned {
+            value: layout.offset,
+            alignment: TEXTURE_PLACEMENT_ALIGNMENT,
+        });
+    }
+
+    if !layout.row_pitch.is_multiple_of(TEXTURE_PITCH_ALIGNMENT) {
+        return Err(TransferError::Misaligned {
+            value: layout.row_pitch,
+            alignment: TEXTURE_PITCH_ALIGNMENT,
+        });
+    }
+
+    let (bw, bh) = format.block_dimensions();
+    let rows = extent[1].div_ceil(bh);
+    if layout.slice_pitch != layout.row_pitch * rows as usize {
+        return Err(TransferError::Unsupported(
+            "buffer slices must directly follow each other",
+        ));
+    }
+
+    Ok(dx::TextureCopyLocation::placed_footprint(
+        &buffer.raw,
+        dx::PlacedSubresourceFootprint::new(
+            layout.offset as u64,
+            dx::SubresourceFootprint::new(
+                map_format(format),
+                extent[0].next_multiple_of(bw),
+                rows * bh,
+                extent[2],
+                layout.row_pitch as u32,
+            ),
+        ),
+    ))
+}
+
+impl<'a> DxTransferEncoder<'a> {
+    fn set_shader_heap(&self) {
+        let heap = self.cmd_buffer.descriptors.shader_heap.lock().heap.clone();
+        self.cmd_buffer.list.set_descriptor_heaps(&[Some(heap)]);
+    }
+
+    /// Storage clears need the view both in the shader visible heap and in a
+    /// CPU only heap. The CPU copy is consumed at record time.
+    fn clear_storage(
+        &self,
+        raw: &dx::Resource,
+        desc: &dx::UnorderedAccessViewDesc,
+        clear: impl FnOnce(&dx::GraphicsCommandList, &Descriptor, &Descriptor),
+    ) {
+        let descriptors = &self.cmd_buffer.descriptors;
+        let gpu = descriptors.shader_heap.lock().alloc(1);
+        let cpu = descriptors.staging_heap.lock().alloc(1);
+
+        for descriptor in [&gpu, &cpu] {
+            self.cmd_buffer.device.create_unordered_access_view(
+                Some(raw),
+                dx::RES_NONE,
+                Some(desc),
+                descriptor.cpu,
+            );
+        }
+
+        self.set_shader_heap();
+        clear(&self.cmd_buffer.list, &gpu, &cpu);
+
+        descriptors.staging_heap.lock().free(cpu);
+        self.cmd_buffer.allocator.descriptors.lock().push(gpu);
+    }
+}
+
+impl<'a> TransferEncoder for DxTransferEncoder<'a> {
+    type Buffer = DxBuffer;
+    type Texture = DxTexture;
+
+    fn copy_buffer_to_buffer(
+        &self,
+        dst: &Self::Buffer,
+        src: &Self::Buffer,
+    ) -> Result<(), TransferError> {
+        transfer::validate_buffer_range(&dst.desc, &(0..src.desc.size))?;
+
+        self.cmd_buffer.use_resources(
+            ResourceUsages::default()
+                .use_buffer(dst, ResourceState::CopyDst)
+                .use_buffer(src, ResourceState::CopySrc),
+        );
+
+        if dst.desc.size == src.desc.size {
+            self.cmd_buffer.list.copy_resource(&dst.raw, &src.raw);
+        } else {
+            self.cmd_buffer
+                .list
+                .copy_buffer_region(&dst.raw, 0, &src.raw, 0, src.desc.size as u64);
+        }
+
+        Ok(())
+    }
+
+    fn copy_texture_to_texture(
+        &self,
+        dst: &Self::Texture,
+        src: &Self::Texture,
+    ) -> Result<(), TransferError> {
+        let (dst_raw, src_raw) = (local_texture(dst)?, local_texture(src)?);
+
+        transfer::validate_texture_copy(&dst.desc, &src.desc)?;
+
+        self.cmd_buffer.use_resources(
+            ResourceUsages::default()
+                .use_texture(dst, ResourceState::CopyDst)
+                .use_texture(src, ResourceState::CopySrc),
+        );
+
+        self.cmd_buffer.list.copy_resource(dst_raw, src_raw);
+
+        Ok(())
+    }
+
+    fn upload_to_texture(
+        &self,
+        dst: &Self::Texture,
+        src: &Self::Buffer,
+        data: &[u8],
+    ) -> Result<(), TransferError> {
+        let dst_res = local_texture(dst)?;
+
+        let layouts = dst.desc.subresource_layouts();
+        let size = layouts.iter().map(|l| l.size).sum();
+        if data.len() < size {
+            return Err(TransferError::OutOfBounds {
+                end: size,
+                size: data.len(),
+            });
+        }
+
+        self.cmd_buffer
+            .use_resources(ResourceUsages::default().use_texture(dst, ResourceState::CopyDst));
+
+        let subresources = layouts
+            .iter()
+            .map(|l| {
+                dx::SubresourceData::new(&data[l.offset..(l.offset + l.size)])
+                    .with_row_pitch(l.row_pitch)
+                    .with_slice_pitch(l.slice_pitch)
+            })
+            .collect::<Vec<_>>();
+
+        let copied = self.cmd_buffer.list.update_subresources(
+            dst_res,
+            &src.raw,
+            0,
+            0..(subresources.len() as u32),
+            &subresources,
+        );
+
+        debug_assert!(copied > 0);
+
+        Ok(())
+    }
+
+    fn copy_buffer_region(
+        &self,
+        dst: &Self::Buffer,
+        dst_offset: usize,
+        src: &Self::Buffer,
+        src_offset: usize,
+        size: usize,
+    ) -> Result<(), TransferError> {
+        transfer::validate_buffer_range(&dst.desc, &(dst_offset..(dst_offset + size)))?;
+        transfer::validate_buffer_range(&src.desc, &(src_offset..(src_offset + size)))?;
+
+        self.cmd_buffer.use_resources(
+            ResourceUsages::default()
+                .use_buffer(dst, ResourceState::CopyDst)
+                .use_buffer(src, ResourceState::CopySrc),
+        );
+
+        self.cmd_buffer.list.copy_buffer_region(
+            &dst.raw,
+            dst_offset as u64,
+            &src.raw,
+            src_offset as u64,
+            size as u64,
+        );
+
+        Ok(())
+    }
+
+    fn copy_buffer_to_texture(
+        &self,
+        dst: &Self::Texture,
+        region: TextureRegion,
+        src: &Self::Buffer,
+        layout: BufferTextureLayout,
+    ) -> Result<(), TransferError> {
+        let dst_raw = local_texture(dst)?;
+        region.validate(&dst.desc)?;
+        let src_location = footprint_location(src, dst.desc.format, region.extent, layout)?;
+
+        self.cmd_buffer.use_resources(
+            ResourceUsages::default()
+                .use_subresources(
+                    dst,
+                    ResourceState::CopyDst,
+                    SubresourceRange::mip(region.mip, region.layer..(region.layer + 1)),
+                )
+                .use_buffer(src, ResourceState::CopySrc),
+        );
+
+        let [x, y, z] = region.origin;
+        self.cmd_buffer.list.copy_texture_region(
+            &region_location(dst_raw, &dst.desc, &region),
+            x,
+            y,
+            z,
+            &src_location,
+            None,
+        );
+
+        Ok(())
+    }
+
+    fn copy_texture_to_buffer(
+        &self,
+        dst: &Self::Buffer,
+        layout: BufferTextureLayout,
+        src: &Self::Texture,
+        region: TextureRegion,
+    ) -> Result<(), TransferError> {
+        let src_raw = local_texture(src)?;
+        region.validate(&src.desc)?;
+        let dst_location = footprint_location(dst, src.desc.format, region.extent, layout)?;
+
+        self.cmd_buffer.use_resources(
+            ResourceUsages::default()
+                .use_buffer(dst, ResourceState::CopyDst)
+                .use_subresources(
+                    src,
+                    ResourceState::CopySrc,
+                    SubresourceRange::mip(region.mip, region.layer..(region.layer + 1)),
+                ),
+        );
+
+        self.cmd_buffer.list.copy_texture_region(
+            &dst_location,
+            0,
+            0,
+            0,
+            &region_location(src_raw, &src.desc, &region),
+            Some(&region_box(&region)),
+        );
+
+        Ok(())
+    }
+
+    fn copy_texture_region(
+        &self,
+        dst: &Self::Texture,
+        dst_region: TextureRegion,
+        src: &Self::Texture,
+        src_region: TextureRegion,
+    ) -> Result<(), TransferError> {
+        let (dst_raw, src_raw) = (local_texture(dst)?, local_texture(src)?);
+
+        if dst.desc.format != src.desc.format {
+            return Err(TransferError::FormatMismatch);
+        }
+
+        if dst_region.extent != src_region.extent {
+            return Err(TransferError::InvalidRegion);
+        }
+
+        dst_region.validate(&dst.desc)?;
+        src_region.validate(&src.desc)?;
+
+        self.cmd_buffer.use_resources(
+            ResourceUsages::default()
+                .use_subresources(
+                    dst,
+                    ResourceState::CopyDst,
+                    SubresourceRange::mip(dst_region.mip, dst_region.layer..(dst_region.layer + 1)),
+                )
+                .use_subresources(
+                    src,
+                    ResourceState::CopySrc,
+                    SubresourceRange::mip(src_region.mip, src_region.layer..(src_region.layer + 1)),
+                ),
+        );
+
+        let [x, y, z] = dst_region.origin;
+        self.cmd_buffer.list.copy_texture_region(
+            &region_location(dst_raw, &dst.desc, &dst_region),
+            x,
+            y,
+            z,
+            &region_location(src_raw, &src.desc, &src_region),
+            Some(&region_box(&src_region)),
+        );
+
+        Ok(())
+    }
+
+    fn fill_buffer(
+        &self,
+        dst: &Self::Buffer,
+        range: Range<usize>,
+        value: u32,
+    ) -> Result<(), TransferError> {
+        transfer::validate_fill(&dst.desc, &range, self.cmd_buffer.ty)?;
+
+        if range.is_empty() {
+            return Ok(());
+        }
+
+        self.cmd_buffer
+            .use_resources(ResourceUsages::default().use_buffer(dst, ResourceState::StorageWrite));
+
+        let elements = (range.start as u64 / 4)..(range.end as u64 / 4);
+        let desc = dx::UnorderedAccessViewDesc::buffer(
+            dx::Format::R32Typeless,
+            elements,
+            0,
+            0,
+            dx::BufferUavFlags::Raw,
+        );
+
+        self.clear_storage(&dst.raw, &desc, |list, gpu, cpu| {
+            list.clear_unordered_access_view_uint(gpu.gpu, cpu.cpu, &dst.raw, [value; 4], &[]);
+        });
+
+        Ok(())
+    }
+
+    fn clear_texture(
+        &self,
+        dst: &Self::Texture,
+        range: SubresourceRange,
+        value: ClearValue,
+    ) -> Result<(), TransferError> {
+        let raw = local_texture(dst)?;
+        // Rejects 1D and 3D textures, the 2D array views below also cover cube faces.
+        let state = transfer::clear_state(&dst.desc, &value, self.cmd_buffer.ty)?;
+
+        if range.mips.end > dst.desc.mip_levels || range.layers.end > dst.desc.array_layers() {
+            return Err(TransferError::InvalidRegion);
+        }
+
+        self.cmd_buffer
+            .use_resources(ResourceUsages::default().use_subresources(dst, state, range.clone()));
+
+        let descriptors = &self.cmd_buffer.descriptors;
+        let device = &self.cmd_buffer.device;
+        let format = map_format(dst.desc.format);
+
+        for mip in range.mips.clone() {
+            let layers = range.layers.clone();
+
+            match value {
+                ClearValue::Color(color) if state == ResourceState::RenderTarget => {
+                    let descriptor = descriptors.rtv_heap.lock().alloc(1);
+                    device.create_render_target_view(
+                        Some(raw),
+                        Some(&dx::RenderTargetViewDesc::texture_2d_array(
+                            format, mip, layers, 0,
+                        )),
+                        descriptor.cpu,
+                    );
+
+                    self.cmd_buffer
+                        .list
+                        .clear_render_target_view(descriptor.cpu, color, &[]);
+                    descriptors.rtv_heap.lock().free(descriptor);
+                }
+                ClearValue::Color(color) => {
+                    let desc = dx::UnorderedAccessViewDesc::texture_2d_array(
+                        map_format(dst.desc.format.to_linear()),
+                        mip,
+                        layers,
+                        0,
+                    );
+
+                    self.clear_storage(raw, &desc, |list, gpu, cpu| {
+                        list.clear_unordered_access_view_float(gpu.gpu, cpu.cpu, raw, color, &[]);
+                    });
+                }
+                ClearValue::DepthStencil { depth, stencil } => {
+                    let depth_format = map_depth_format(dst.desc.format);
+
+                    let descriptor = descriptors.dsv_heap.lock().alloc(1);
+                    device.create_depth_stencil_view(
+                        Some(raw),
+                        Some(&dx::DepthStencilViewDesc::texture_2d_array(
+                            depth_format,
+                            mip,
+                            layers,
+                        )),
+                        descriptor.cpu,
+                    );
+
+                    self.cmd_buffer.list.clear_depth_stencil_view(
+                        descriptor.cpu,
+                        dx::ClearFlags::Depth | dx::ClearFlags::Stencil,
+                        depth,
+                        stencil,
+                        &[],
+                    );
+                    descriptors.dsv_heap.lock().free(descriptor);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> DebugEncoder for DxTransferEncoder<'a> {
+    fn push_debug_group(&self, name: &str) {
+        self.cmd_buffer.begin_event(name);
+    }
+
+    fn pop_debug_group(&self) {
+        self.cmd_buffer.end_event();
+    }
+
+    fn insert_marker(&self, name: &str) {
+        self.cmd_buffer.set_marker(name);
+    }
+}
+
+impl<'a> DynTransferEncoder for DxTransferEncoder<'a> {
+    fn copy_buffer_to_buffer(
+        &self,
+        dst: RenderHandle<Buffer>,
+        src: RenderHandle<Buffer>,
+    ) -> Result<(), TransferError> {
+        self.cmd_buffer.validate(|| StreamCommand::CopyBuffer {
+            dst: StreamRef::Buffer(dst),
+            src: StreamRef::Buffer(src),
+        });
+        let guard = self.cmd_buffer.handles.buffers.lock();
+
+        let Some(dst) = guard.get(dst) else {
+            cold_path();
+            return Err(TransferError::DeadHandle);
+        };
+
+        let Some(src) = guard.get(src) else {
+            cold_path();
+            return Err(TransferError::DeadHandle);
+        };
+
+        <Self as TransferEncoder>::copy_buffer_to_buffer(self, dst, src)
+    }
+
+    fn copy_texture_to_texture(
+        &self,
+        dst_handle: RenderHandle<Texture>,
+        src_handle: RenderHandle<Texture>,
+    ) -> Result<(), TransferError> {
+        self.cmd_buffer.validate(|| StreamCommand::CopyTexture {
+            dst: StreamRef::Texture(dst_handle),
+            src: StreamRef::Texture(src_handle),
+        });
+        let handles = &self.cmd_buffer.handles;
+        let guard = handles.textures.lock();
+
+        let Some(dst) = guard.get(dst_handle) else {
+            cold_path();
+            return Err(TransferError::DeadHandle);
+        };
+
+        let Some(src) = guard.get(src_handle) else {
+            cold_path();
+            return Err(TransferError::DeadHandle);
+        };
+
+        handles.touch_texture(&self.cmd_buffer.device, dst_handle, dst);
+        handles.touch_texture(&self.cmd_buffer.device, src_handle, src);
+
+        <Self as TransferEncoder>::copy_texture_to_texture(self, dst, src)
+    }
+
+    fn upload_to_texture(
+        &self,
+        dst_handle: RenderHandle<Texture>,
+        src: RenderHandle<Buffer>,
+        data: &[u8],
+    ) -> Result<(), TransferError> {
+        self.cmd_buffer.validate(|| StreamCommand::UploadTexture {
+            dst: StreamRef::Texture(dst_handle),
+            src: StreamRef::Buffer(src),
+            data: data.to_vec(),
+        });
+        let bguard = self.cmd_buffer.handles.buffers.lock();
+        let tguard = self.cmd_buffer.handles.textures.lock();
+
+        let Some(dst) = tguard.get(dst_handle) else {
+            cold_path();
+            return Err(TransferError::DeadHandle);
+        };
+
+        let Some(src) = bguard.get(src) else {
+            cold_path();
+            return Err(TransferError::DeadHandle);
+        };
+
+        self.cmd_buffer
+            .handles
+            .touch_texture(&self.cmd_buffer.device, dst_handle, dst);
+
+        <Self as TransferEncoder>::upload_to_texture(self, dst, src, data)
+    }
+
+    fn copy_buffer_region(
+        &self,
+        dst: RenderHandle<Buffer>,
+        dst_offset: usize,
+        src: RenderHandle<Buffer>,
+        src_offset: usize,
+        size: usize,
+    ) -> Result<(), TransferError> {
+        self.cmd_buffer
+            .validate(|| StreamCommand::CopyBufferRegion {
+                dst: StreamRef::Buffer(dst),
+                dst_offset,
+                src: StreamRef::Buffer(src),
+                src_offset,
+                size,
+            });
+        let guard = self.cmd_buffer.handles.buffers.lock();
+
+        let (Some(dst), Some(src)) = (guard.get(dst), guard.get(src)) else {
+            cold_path();
+            return Err(TransferError::DeadHandle);
+        };
+
+        <Self as TransferEncoder>::copy_buffer_region(self, dst, dst_offset, src, src_offset, size)
+    }
+
+    fn copy_buffer_to_texture(
+        &self,
+        dst_handle: RenderHandle<Texture>,
+        region: TextureRegion,
+        src: RenderHandle<Buffer>,
+        layout: BufferTextureLayout,
+    ) -> Result<(), TransferError> {
+        self.cmd_buffer
+            .validate(|| StreamCommand::CopyBufferToTexture {
+                dst: StreamRef::Texture(dst_handle),
+                region,
+                src: StreamRef::Buffer(src),
+                layout,
+            });
+        let bguard = self.cmd_buffer.handles.buffers.lock();
+        let tguard = self.cmd_buffer.handles.textures.lock();
+
+        let (Some(dst), Some(src)) = (tguard.get(dst_handle), bguard.get(src)) else {
+            cold_path();
+            return Err(TransferError::DeadHandle);
+        };
+
+        self.cmd_buffer
+            .handles
+            .touch_texture(&self.cmd_buffer.device, dst_handle, dst);
+
+        <Self as TransferEncoder>::copy_buffer_to_texture(self, dst, region, src, layout)
+    }
+
+    fn copy_texture_to_buffer(
+        &self,
+        dst: RenderHandle<Buffer>,
+        layout: BufferTextureLayout,
+        src_handle: RenderHandle<Texture>,
+        region: TextureRegion,
+    ) -> Result<(), TransferError> {
+        self.cmd_buffer
+            .validate(|| StreamCommand::CopyTextureToBuffer {
+                dst: StreamRef::Buffer(dst),
+                layout,
+                src: StreamRef::Texture(src_handle),
+                region,
+            });
+        let bguard = self.cmd_buffer.handles.buffers.lock();
+        let tguard = self.cmd_buffer.handles.textures.lock();
+
+        let (Some(dst), Some(src)) = (bguard.get(dst), tguard.get(src_handle)) else {
+            cold_path();
+            return Err(TransferError::DeadHandle);
+        };
+
+        self.cmd_buffer
+            .handles
+            .touch_texture(&self.cmd_buffer.device, src_handle, src);
+
+        <Self as TransferEncoder>::copy_texture_to_buffer(self, dst, layout, src, region)
+    }
+
+    fn copy_texture_region(
+        &self,
+        dst_handle: RenderHandle<Texture>,
+        dst_region: TextureRegion,
+        src_handle: RenderHandle<Texture>,
+        src_region: TextureRegion,
+    ) -> Result<(), TransferError> {
+        self.cmd_buffer
+            .validate(|| StreamCommand::CopyTextureRegion {
+                dst: StreamRef::Texture(dst_handle),
+                dst_region,
+                src: StreamRef::Texture(src_handle),
+                src_region,
+            });
+        let handles = &self.cmd_buffer.handles;
+        let guard = handles.textures.lock();
+
+        let (Some(dst), Some(src)) = (guard.get(dst_handle), guard.get(src_handle)) else {
+            cold_path();
+            return Err(TransferError::DeadHandle);
+        };
+
+        handles.touch_texture(&self.cmd_buffer.device, dst_handle, dst);
+        handles.touch_texture(&self.cmd_buffer.device, src_handle, src);
+
+        <Self as TransferEncoder>::copy_texture_region(self, dst, dst_region, src, src_region)
+    }
+
+    fn fill_buffer(
+        &self,
+        dst: RenderHandle<Buffer>,
+        range: Range<usize>,
+        value: u32,
+    ) -> Result<(), TransferError> {
+        self.cmd_buffer.validate(|| StreamCommand::FillBuffer {
+            dst: StreamRef::Buffer(dst),
+            range: range.clone(),
+            value,
+        });
+        let guard = self.cmd_buffer.handles.buffers.lock();
+
+        let Some(dst) = guard.get(dst) else {
+            cold_path();
+            return Err(TransferError::DeadHandle);
+        };
+
+        <Self as TransferEncoder>::fill_buffer(self, dst, range, value)
+    }
+
+    fn clear_texture(
+        &self,
+        dst_handle: RenderHandle<Texture>,
+        range: SubresourceRange,
+        value: ClearValue,
+    ) -> Result<(), TransferError> {
+        self.cmd_buffer.validate(|| StreamCommand::ClearTexture {
+            dst: StreamRef::Texture(dst_handle),
+            range: range.clone(),
+            value,
+        });
+        let guard = self.cmd_buffer.handles.textures.lock();
+
+        let Some(dst) = guard.get(dst_handle) else {
+            cold_path();
+            return Err(TransferError::DeadHandle);
+        };
+
+        self.cmd_buffer
+            .handles
+            .touch_texture(&self.cmd_buffer.device, dst_handle, dst);
 
-        <Self as TransferEncoder>::upload_to_texture(&self, dst, src, data);
+        <Self as TransferEncoder>::clear_texture(self, dst, range, value)
     }
 }
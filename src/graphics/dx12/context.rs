@@ -1,32 +1,47 @@
 use std::sync::Arc;
 
-use oxidx::dx;
+use oxidx::dx::{self, IDevice};
 use parking_lot::Mutex;
-use tracing::info;
+use tracing::{debug, error, info, warn};
 
 use crate::graphics::{
     context::RenderContext,
     core::{
         backend::RenderDeviceInfo,
-        commands::CommandBufferType,
+        bundle::BundleTracker,
+        capture::Capture,
+        commands::{CommandBuffer, CommandBufferType, DeviceTag, FenceValue},
         handle::{RenderHandle, SparseArray},
+        memory::{MemoryAllocation, MemoryCategory, MemoryCounters, MemoryLocation},
+        profiler::{self, GpuProfiler, GpuTiming},
+        residency::{ResidencyAction, ResidencyPriority, ResidencySet},
         resource::{
-            Buffer, BufferDesc, ResourceDevice, Sampler, SamplerDesc, Texture, TextureDesc,
-            TextureViewDesc,
+            Buffer, BufferDesc, BufferView, BufferViewDesc, BufferViewError, ResourceDevice,
+            Sampler, SamplerDesc, Texture, TextureDesc, TextureViewDesc,
         },
-        shader::{ComputePipeline, RenderPipeline},
+        shader::{
+            ComputePipeline, ComputePipelineDesc, PipelineError, RenderPipeline,
+            RenderPipelineDesc, ShaderError, ShaderModule, ShaderModuleDesc,
+        },
+        stream::StreamRef,
+        validation::{ValidationError, Validator},
     },
+    DebugFlags,
 };
 
 use super::{
-    inner::{commands::DxCommandQueue, descriptors::Descriptors},
-    resources::{DxBuffer, DxTexture},
+    inner::{
+        commands::DxCommandQueue, descriptors::Descriptors, indirect::IndirectSignatures,
+        mips::MipGenerator,
+    },
+    resources::{DxBuffer, DxBufferView, DxQuerySet, DxTexture},
+    shader::{DxComputePipeline, DxRenderPipeline, DxShaderModule},
 };
 
 #[derive(Debug)]
 pub struct DxRenderContext {
     pub(super) gpu: dx::Device,
-    adapter: dx::Adapter3,
+    pub(super) adapter: dx::Adapter3,
     pub(super) factory: dx::Factory4,
 
     pub(super) gfx_queue: DxCommandQueue,
@@ -34,9 +49,13 @@ pub struct DxRenderContext {
     pub(super) transfer_queue: DxCommandQueue,
 
     pub(super) desc: RenderDeviceInfo,
+    pub(super) tag: DeviceTag,
+    pub(super) debug: DebugFlags,
 
+    pub(super) memory: Arc<MemoryCounters>,
     pub(super) handles: Arc<HandleStorage>,
     pub(super) descriptors: Arc<Descriptors>,
+    pub(super) indirect: Arc<IndirectSignatures>,
 }
 
 impl DxRenderContext {
@@ -44,6 +63,7 @@ impl DxRenderContext {
         adapter: dx::Adapter3,
         factory: dx::Factory4,
         desc: RenderDeviceInfo,
+        debug: DebugFlags,
     ) -> Self {
         info!(
             "Creating device with adapter {} and id {}",
@@ -59,13 +79,49 @@ impl DxRenderContext {
             info!("Cross Adapter Row Major Texture is NOT supported");
         }
 
-        let gfx_queue = DxCommandQueue::new(&device, CommandBufferType::Graphics, None);
-        let compute_queue = DxCommandQueue::new(&device, CommandBufferType::Compute, None);
-        let transfer_queue = DxCommandQueue::new(&device, CommandBufferType::Transfer, None);
+        let memory = Arc::new(MemoryCounters::default());
+        let descriptors = Arc::new(Descriptors::new(&device, &memory));
+        let mip_generator = match MipGenerator::new(&device) {
+            Ok(generator) => Some(Arc::new(generator)),
+            Err(err) => {
+                error!("GPU mip generation is unavailable: {}", err);
+                None
+            }
+        };
+        let indirect = Arc::new(IndirectSignatures::new(&device));
+        let markers = debug.intersects(DebugFlags::Pix | DebugFlags::RenderDoc);
+
+        let gfx_queue = DxCommandQueue::new(
+            &device,
+            CommandBufferType::Graphics,
+            None,
+            Arc::clone(&descriptors),
+            mip_generator.clone(),
+            Arc::clone(&indirect),
+            markers,
+        );
+        let compute_queue = DxCommandQueue::new(
+            &device,
+            CommandBufferType::Compute,
+            None,
+            Arc::clone(&descriptors),
+            mip_generator.clone(),
+            Arc::clone(&indirect),
+            markers,
+        );
+        let transfer_queue = DxCommandQueue::new(
+            &device,
+            CommandBufferType::Transfer,
+            None,
+            Arc::clone(&descriptors),
+            mip_generator.clone(),
+            Arc::clone(&indirect),
+            markers,
+        );
 
-        let descriptors = Arc::new(Descriptors::new(&device));
+        let period = gfx_queue.timestamp_period();
 
-        Self {
+        let context = Self {
             gpu: device,
             adapter,
             factory,
@@ -73,12 +129,133 @@ impl DxRenderContext {
             compute_queue,
             transfer_queue,
             desc,
+            tag: DeviceTag::unique(),
+            debug,
+            memory,
             handles: Arc::new(HandleStorage {
                 buffers: Mutex::new(SparseArray::new(128)),
+                buffer_views: Mutex::new(SparseArray::new(128)),
                 textures: Mutex::new(SparseArray::new(128)),
+                shader_modules: Mutex::new(SparseArray::new(128)),
+                compute_pipelines: Mutex::new(SparseArray::new(128)),
+                render_pipelines: Mutex::new(SparseArray::new(128)),
+                residency: Mutex::new(ResidencySet::new(false)),
+                profiler: Mutex::new(GpuProfiler::new(period)),
+                bundles: Default::default(),
+                validator: debug
+                    .contains(DebugFlags::CpuValidation)
+                    .then(Validator::new),
             }),
             descriptors,
+            indirect,
+        };
+
+        context
+            .handles
+            .profiler
+            .lock()
+            .begin_frame(|| context.profiler_frame());
+
+        context
+    }
+
+    /// Errors the validation layer reported since the last call, empty without
+    /// [`DebugFlags::CpuValidation`].
+    pub fn validation_errors(&self) -> Vec<ValidationError> {
+        self.handles
+            .validator
+            .as_ref()
+            .map(|v| v.take_errors())
+            .unwrap_or_default()
+    }
+
+    fn profiler_frame(&self) -> (DxQuerySet, DxBuffer) {
+        (
+            self.create_query_set(profiler::query_set_desc()),
+            self.create_buffer::<u8>(profiler::readback_desc(), None),
+        )
+    }
+
+    /// Records the resolve of this frame's profiler timestamps behind the
+    /// pending graphics work.
+    pub(super) fn resolve_profiler(&self) {
+        let profiler = self.handles.profiler.lock();
+        let Some((queries, range, readback)) = profiler.pending() else {
+            return;
+        };
+
+        let cmd_buffer = self
+            .gfx_queue
+            .allocate_command_buffer(Arc::clone(&self.handles));
+        cmd_buffer
+            .resolve_queries(queries, range, readback, 0)
+            .expect("failed to resolve profiler queries");
+        drop(profiler);
+
+        self.gfx_queue.push_cmd_buffer(cmd_buffer);
+    }
+
+    /// Ends the profiler frame submitted at `sync_point` and reads back every finished one.
+    pub(super) fn collect_profiler(&self, sync_point: FenceValue) {
+        let mut profiler = self.handles.profiler.lock();
+        profiler.end_frame(sync_point);
+
+        let changed = profiler.collect(self.gfx_queue.completed(), |readback| {
+            readback.map::<u64>().pointer.to_vec()
+        });
+        if changed && self.debug.contains(DebugFlags::GpuTimings) {
+            profiler.log();
         }
+
+        profiler.begin_frame(|| self.profiler_frame());
+    }
+
+    /// Evicts textures until `incoming` more bytes fit in the budget reported by the OS.
+    pub(super) fn trim_residency(&self, incoming: u64) {
+        let Some(budget) = self.memory_report().budget else {
+            return;
+        };
+
+        let changes = self.handles.residency.lock().trim(
+            budget.usage + incoming,
+            budget.budget,
+            self.gfx_queue.completed(),
+        );
+
+        let textures = self.handles.textures.lock();
+        let evicted = changes
+            .iter()
+            .inspect(|change| debug!("Residency change {:?}", change))
+            .filter(|change| change.action == ResidencyAction::Evict)
+            .filter_map(|change| textures.get(change.key))
+            .filter_map(|texture| texture.pageable().cloned())
+            .collect::<Vec<_>>();
+
+        if !evicted.is_empty() {
+            self.gpu.evict(&evicted).expect("failed to evict resources");
+        }
+    }
+
+    pub(super) fn queue(&self, ty: CommandBufferType) -> &DxCommandQueue {
+        match ty {
+            CommandBufferType::Graphics => &self.gfx_queue,
+            CommandBufferType::Compute => &self.compute_queue,
+            CommandBufferType::Transfer => &self.transfer_queue,
+        }
+    }
+
+    pub(super) fn track_resource(
+        &self,
+        desc: &dx::ResourceDesc,
+        category: MemoryCategory,
+    ) -> MemoryAllocation {
+        let size = self
+            .gpu
+            .get_resource_allocation_info(0, std::slice::from_ref(desc))
+            .size_in_bytes();
+
+        self.memory
+            .allocate(MemoryLocation::Device, category, size as u64)
     }
 }
 
@@ -89,28 +266,87 @@ impl RenderContext for DxRenderContext {
         desc: BufferDesc,
         init_data: Option<&[u8]>,
     ) {
-        let buffer = self.create_buffer(desc, init_data);
+        let mut buffer = self.create_buffer(desc, init_data);
+        buffer.handle = Some(handle);
+        if let Some(validator) = &self.handles.validator {
+            validator.bind_buffer(handle, &buffer.desc);
+        }
         self.handles.buffers.lock().set(handle, buffer);
     }
 
     fn unbind_buffer(&self, handle: RenderHandle<Buffer>) {
+        self.handles
+            .bundles
+            .lock()
+            .invalidate(|r| r.buffers.contains(&handle));
+        self.handles.unbind(StreamRef::Buffer(handle));
         self.handles.buffers.lock().remove(handle);
     }
 
+    fn bind_buffer_view(
+        &self,
+        handle: RenderHandle<BufferView>,
+        buffer: RenderHandle<Buffer>,
+        desc: BufferViewDesc,
+    ) -> Result<(), BufferViewError> {
+        let mut view = {
+            let guard = self.handles.buffers.lock();
+            let buffer = guard.get(buffer).expect("Wrong handle");
+            self.create_buffer_view(buffer, desc)?
+        };
+        view.buffer = Some(buffer);
+        if let Some(validator) = &self.handles.validator {
+            validator.bind_buffer_view(handle, buffer, desc);
+        }
+        self.handles.buffer_views.lock().set(handle, view);
+
+        Ok(())
+    }
+
+    fn unbind_buffer_view(&self, handle: RenderHandle<BufferView>) {
+        self.handles
+            .bundles
+            .lock()
+            .invalidate(|r| r.buffer_views.contains(&handle));
+        self.handles.unbind(StreamRef::BufferView(handle));
+        let mut guard = self.handles.buffer_views.lock();
+        if let Some(view) = guard.get_mut(handle) {
+            view.release(self);
+        }
+        guard.remove(handle);
+    }
+
     fn bind_texture(
         self: &Arc<Self>,
         handle: RenderHandle<Texture>,
         desc: TextureDesc,
         init_data: Option<&[u8]>,
     ) {
-        let texture = self.create_texture(desc, init_data);
+        self.trim_residency(desc.packed_size() as u64);
+
+        self.handles
+            .residency
+            .lock()
+            .insert_texture(handle, ResidencyPriority::default(), &desc);
+
+        let mut texture = self.create_texture(desc, init_data);
+        texture.handle = Some(handle);
+        if let Some(validator) = &self.handles.validator {
+            validator.bind_texture(handle, &texture.desc);
+        }
         self.handles.textures.lock().set(handle, texture);
     }
 
     fn unbind_texture(&self, handle: RenderHandle<Texture>) {
+        self.handles.residency.lock().remove(handle);
+        self.handles.unbind(StreamRef::Texture(handle));
         self.handles.textures.lock().remove(handle);
     }
 
+    fn set_texture_priority(&self, handle: RenderHandle<Texture>, priority: ResidencyPriority) {
+        self.handles.residency.lock().set_priority(handle, priority);
+    }
+
     fn bind_texture_view(
         &self,
         handle: RenderHandle<Texture>,
@@ -121,11 +357,15 @@ impl RenderContext for DxRenderContext {
     }
 
     fn open_texture_handle(&self, handle: RenderHandle<Texture>, other: &Self) {
-        let texture = {
+        let mut texture = {
             let guard = other.handles.textures.lock();
             let texture = guard.get(handle).expect("Wrong handle");
             self.open_texture(texture, other)
         };
+        texture.handle = Some(handle);
+        if let Some(validator) = &self.handles.validator {
+            validator.bind_texture(handle, &texture.desc);
+        }
         self.handles.textures.lock().set(handle, texture);
     }
 
@@ -137,25 +377,120 @@ impl RenderContext for DxRenderContext {
         todo!()
     }
 
-    fn bind_compute_pipeline(&self, handle: RenderHandle<ComputePipeline>, desc: ()) {
-        todo!()
+    fn bind_shader_module(
+        &self,
+        handle: RenderHandle<ShaderModule>,
+        desc: ShaderModuleDesc,
+    ) -> Result<(), ShaderError> {
+        desc.validate(self.desc.shader_languages)?;
+        let module = DxShaderModule::new(desc)?;
+        self.handles.shader_modules.lock().set(handle, module);
+
+        Ok(())
+    }
+
+    fn unbind_shader_module(&self, handle: RenderHandle<ShaderModule>) {
+        self.handles.shader_modules.lock().remove(handle);
+    }
+
+    fn bind_compute_pipeline(
+        &self,
+        handle: RenderHandle<ComputePipeline>,
+        desc: ComputePipelineDesc,
+    ) -> Result<(), PipelineError> {
+        let pipeline =
+            DxComputePipeline::new(&self.gpu, &desc, &self.handles.shader_modules.lock())?;
+        if let Some(validator) = &self.handles.validator {
+            validator.bind_pipeline(StreamRef::ComputePipeline(handle));
+        }
+        self.handles.compute_pipelines.lock().set(handle, pipeline);
+
+        Ok(())
     }
 
     fn unbind_compute_pipeline(&self, handle: RenderHandle<ComputePipeline>) {
-        todo!()
+        self.handles.unbind(StreamRef::ComputePipeline(handle));
+        self.handles.compute_pipelines.lock().remove(handle);
     }
 
-    fn bind_render_pipeline(&self, handle: RenderHandle<RenderPipeline>, desc: ()) {
-        todo!()
+    fn bind_render_pipeline(
+        &self,
+        handle: RenderHandle<RenderPipeline>,
+        desc: RenderPipelineDesc,
+    ) -> Result<(), PipelineError> {
+        let pipeline =
+            DxRenderPipeline::new(&self.gpu, &desc, &self.handles.shader_modules.lock())?;
+        if let Some(validator) = &self.handles.validator {
+            validator.bind_pipeline(StreamRef::RenderPipeline(handle));
+        }
+        self.handles.render_pipelines.lock().set(handle, pipeline);
+
+        Ok(())
     }
 
     fn unbind_render_pipeline(&self, handle: RenderHandle<RenderPipeline>) {
-        todo!()
+        self.handles
+            .bundles
+            .lock()
+            .invalidate(|r| r.render_pipelines.contains(&handle));
+        self.handles.unbind(StreamRef::RenderPipeline(handle));
+        self.handles.render_pipelines.lock().remove(handle);
+    }
+
+    fn gpu_timings(&self) -> Vec<GpuTiming> {
+        self.handles.profiler.lock().timings().to_vec()
+    }
+
+    fn begin_capture(&self, frames: u32) {
+        warn!(
+            "Ignoring a capture of {} frames, DX12 command buffers do not mirror their commands",
+            frames
+        );
+    }
+
+    fn take_capture(&self) -> Option<Capture> {
+        None
     }
 }
 
 #[derive(Debug)]
 pub(super) struct HandleStorage {
     pub(super) buffers: Mutex<SparseArray<Buffer, DxBuffer>>,
+    pub(super) buffer_views: Mutex<SparseArray<BufferView, DxBufferView>>,
     pub(super) textures: Mutex<SparseArray<Texture, DxTexture>>,
+    pub(super) shader_modules: Mutex<SparseArray<ShaderModule, DxShaderModule>>,
+    pub(super) compute_pipelines: Mutex<SparseArray<ComputePipeline, DxComputePipeline>>,
+    pub(super) render_pipelines: Mutex<SparseArray<RenderPipeline, DxRenderPipeline>>,
+    /// DX12 pages whole resources, so textures are only ever evicted, never downgraded.
+    pub(super) residency: Mutex<ResidencySet<RenderHandle<Texture>>>,
+    pub(super) profiler: Mutex<GpuProfiler<DxQuerySet, DxBuffer>>,
+    pub(super) bundles: Mutex<BundleTracker>,
+    /// Set with [`DebugFlags::CpuValidation`].
+    pub(super) validator: Option<Arc<Validator>>,
+}
+
+impl HandleStorage {
+    fn unbind(&self, resource: StreamRef) {
+        if let Some(validator) = &self.validator {
+            validator.unbind(resource);
+        }
+    }
+
+    /// Records a use of `texture` and makes it resident again if it was evicted.
+    pub(super) fn touch_texture(
+        &self,
+        device: &dx::Device,
+        handle: RenderHandle<Texture>,
+        texture: &DxTexture,
+    ) {
+        if self.residency.lock().touch(handle).is_none() {
+            return;
+        }
+
+        if let Some(raw) = texture.pageable() {
+            device
+                .make_resident(std::slice::from_ref(raw))
+                .expect("failed to make resource resident");
+        }
+    }
 }
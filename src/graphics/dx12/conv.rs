@@ -1,6 +1,11 @@
 use oxidx::dx;
 
-use crate::graphics::core::{commands::CommandBufferType, resource::TextureUsages, types::Format};
+use crate::graphics::core::{
+    commands::CommandBufferType,
+    resource::TextureUsages,
+    shader::{PrimitiveTopology, VertexSemantic},
+    types::{Format, ResourceState},
+};
 
 pub(super) fn map_command_buffer_type(ty: CommandBufferType) -> dx::CommandListType {
     match ty {
@@ -10,16 +15,85 @@ pub(super) fn map_command_buffer_type(ty: CommandBufferType) -> dx::CommandListT
     }
 }
 
+pub(super) fn map_resource_state(state: ResourceState) -> dx::ResourceStates {
+    match state {
+        ResourceState::Common => dx::ResourceStates::Common,
+        ResourceState::CopySrc => dx::ResourceStates::CopySource,
+        ResourceState::CopyDst => dx::ResourceStates::CopyDest,
+        ResourceState::VertexBuffer | ResourceState::Uniform => {
+            dx::ResourceStates::VertexAndConstantBuffer
+        }
+        ResourceState::IndexBuffer => dx::ResourceStates::IndexBuffer,
+        ResourceState::Indirect => dx::ResourceStates::IndirectArgument,
+        ResourceState::Sampled | ResourceState::StorageRead => {
+            dx::ResourceStates::PixelShaderResource | dx::ResourceStates::NonPixelShaderResource
+        }
+        ResourceState::StorageWrite => dx::ResourceStates::UnorderedAccess,
+        ResourceState::RenderTarget => dx::ResourceStates::RenderTarget,
+        ResourceState::DepthWrite => dx::ResourceStates::DepthWrite,
+        ResourceState::DepthRead => dx::ResourceStates::DepthRead,
+        ResourceState::Present => dx::ResourceStates::Present,
+    }
+}
+
 pub(super) fn map_format(format: Format) -> dx::Format {
     match format {
         Format::Unknown => dx::Format::Unknown,
 
+        Format::R8Unorm => dx::Format::R8Unorm,
+        Format::Rg8Unorm => dx::Format::Rg8Unorm,
+        Format::Rgba8Unorm => dx::Format::Rgba8Unorm,
+        Format::Rgba8UnormSrgb => dx::Format::Rgba8UnormSrgb,
+        Format::Bgra8Unorm => dx::Format::Bgra8Unorm,
+        Format::Bgra8UnormSrgb => dx::Format::Bgra8UnormSrgb,
+
+        Format::Rgba16Unorm => dx::Format::Rgba16Unorm,
+        Format::Rgba16Float => dx::Format::Rgba16Float,
+
+        Format::R16Uint => dx::Format::R16Uint,
+        Format::R32Uint => dx::Format::R32Uint,
+
         Format::R32 => dx::Format::R32Float,
         Format::Rg32 => dx::Format::Rg32Float,
         Format::Rgb32 => dx::Format::Rgb32Float,
         Format::Rgba32 => dx::Format::Rgba32Float,
 
-        Format::Rgba8Unorm => dx::Format::Rgba8Unorm,
+        Format::Bc1Unorm => dx::Format::Bc1Unorm,
+        Format::Bc1UnormSrgb => dx::Format::Bc1UnormSrgb,
+        Format::Bc2Unorm => dx::Format::Bc2Unorm,
+        Format::Bc2UnormSrgb => dx::Format::Bc2UnormSrgb,
+        Format::Bc3Unorm => dx::Format::Bc3Unorm,
+        Format::Bc3UnormSrgb => dx::Format::Bc3UnormSrgb,
+        Format::Bc4Unorm => dx::Format::Bc4Unorm,
+        Format::Bc4Snorm => dx::Format::Bc4Snorm,
+        Format::Bc5Unorm => dx::Format::Bc5Unorm,
+        Format::Bc5Snorm => dx::Format::Bc5Snorm,
+        Format::Bc6hUfloat => dx::Format::Bc6hUf16,
+        Format::Bc6hSfloat => dx::Format::Bc6hSf16,
+        Format::Bc7Unorm => dx::Format::Bc7Unorm,
+        Format::Bc7UnormSrgb => dx::Format::Bc7UnormSrgb,
+    }
+}
+
+/// Depth targets are created with the color format of the same layout.
+pub(super) fn map_depth_format(format: Format) -> dx::Format {
+    match format {
+        Format::R32 => dx::Format::D32Float,
+        other => map_format(other),
+    }
+}
+
+/// Storage textures can not have sRGB views, so such resources are created typeless
+/// and viewed through both variants.
+pub(super) fn map_resource_format(format: Format, usage: TextureUsages) -> dx::Format {
+    if !usage.contains(TextureUsages::Storage) {
+        return map_format(format);
+    }
+
+    match format {
+        Format::Rgba8UnormSrgb => dx::Format::Rgba8Typeless,
+        Format::Bgra8UnormSrgb => dx::Format::Bgra8Typeless,
+        _ => map_format(format),
     }
 }
 
@@ -54,3 +128,35 @@ pub(super) fn map_texture_flags(
 
     f
 }
+
+pub(super) fn map_topology(topology: PrimitiveTopology) -> dx::PrimitiveTopology {
+    match topology {
+        PrimitiveTopology::PointList => dx::PrimitiveTopology::PointList,
+        PrimitiveTopology::LineList => dx::PrimitiveTopology::LineList,
+        PrimitiveTopology::LineStrip => dx::PrimitiveTopology::LineStrip,
+        PrimitiveTopology::TriangleList => dx::PrimitiveTopology::TriangleList,
+        PrimitiveTopology::TriangleStrip => dx::PrimitiveTopology::TriangleStrip,
+    }
+}
+
+pub(super) fn map_topology_type(topology: PrimitiveTopology) -> dx::PipelinePrimitiveTopology {
+    match topology {
+        PrimitiveTopology::PointList => dx::PipelinePrimitiveTopology::Point,
+        PrimitiveTopology::LineList | PrimitiveTopology::LineStrip => {
+            dx::PipelinePrimitiveTopology::Line
+        }
+        PrimitiveTopology::TriangleList | PrimitiveTopology::TriangleStrip => {
+            dx::PipelinePrimitiveTopology::Triangle
+        }
+    }
+}
+
+pub(super) fn map_semantic(semantic: VertexSemantic, index: u8) -> dx::SemanticName {
+    match semantic {
+        VertexSemantic::Position => dx::SemanticName::Position(index),
+        VertexSemantic::Normal => dx::SemanticName::Normal(index),
+        VertexSemantic::Tangent => dx::SemanticName::Tangent(index),
+        VertexSemantic::Color => dx::SemanticName::Color(index),
+        VertexSemantic::TexCoord => dx::SemanticName::TexCoord(index),
+    }
+}
@@ -4,11 +4,20 @@ use oxidx::dx::{self, ICommandAllocator, ICommandQueue, IDevice, IGraphicsComman
 use parking_lot::Mutex;
 
 use crate::graphics::{
-    core::commands::{CommandBufferType, SyncPoint},
+    core::{
+        commands::{CommandBufferType, FenceValue, SyncPoint},
+        future::{GpuFuture, GpuTimeline},
+        validation::CommandValidator,
+    },
     dx12::{commands::DxCommandBuffer, context::HandleStorage, conv::map_command_buffer_type},
 };
 
-use super::sync::DxFence;
+use super::{
+    descriptors::{Descriptor, Descriptors},
+    indirect::IndirectSignatures,
+    mips::MipGenerator,
+    sync::{DxFence, DxSharedFence},
+};
 
 #[derive(Debug)]
 pub(crate) struct DxCommandQueue {
@@ -17,61 +26,85 @@ pub(crate) struct DxCommandQueue {
     ty_raw: dx::CommandListType,
     ty: CommandBufferType,
 
-    fence: DxFence,
+    fence: Arc<DxFence>,
+    completion: Arc<GpuTimeline>,
+
+    descriptors: Arc<Descriptors>,
+    mip_generator: Option<Arc<MipGenerator>>,
+    indirect: Arc<IndirectSignatures>,
 
+    /// Allocators past which recording waits for the oldest submitted one
+    /// instead of creating more, `None` for no limit.
     capacity: Option<usize>,
-    cmd_allocators: Mutex<VecDeque<CommandAllocatorEntry>>,
-    cmd_lists: Mutex<Vec<dx::GraphicsCommandList>>,
+    /// Shared by every recording thread but only locked to take or return an
+    /// allocator and list, recording itself runs unlocked.
+    pool: Mutex<CommandPool>,
 
     in_record: Mutex<Vec<DxCommandBuffer>>,
     pending: Mutex<Vec<DxCommandBuffer>>,
 
     frequency: f64,
+    markers: bool,
 }
 
 impl DxCommandQueue {
-    pub(crate) fn new(device: &dx::Device, ty: CommandBufferType, capacity: Option<usize>) -> Self {
+    pub(crate) fn new(
+        device: &dx::Device,
+        ty: CommandBufferType,
+        capacity: Option<usize>,
+        descriptors: Arc<Descriptors>,
+        mip_generator: Option<Arc<MipGenerator>>,
+        indirect: Arc<IndirectSignatures>,
+        markers: bool,
+    ) -> Self {
         let queue = device
             .create_command_queue(&dx::CommandQueueDesc::new(map_command_buffer_type(ty)))
             .expect("failed to create command queue");
 
-        let fence = DxFence::new(device);
+        let fence = Arc::new(DxFence::new(device));
+        let completion = GpuTimeline::new(fence.clone());
 
         let frequency = 1000.0
             / queue
                 .get_timestamp_frequency()
                 .expect("failed to fetch timestamp frequency") as f64;
 
-        let cmd_allocators = (0..3)
+        let allocators = (0..3)
             .map(|_| CommandAllocatorEntry {
                 raw: device
                     .create_command_allocator(map_command_buffer_type(ty))
                     .expect("failed to create command allocator"),
                 sync_point: 0,
+                descriptors: Default::default(),
             })
             .collect::<VecDeque<_>>();
 
         let cmd_list = device
-            .create_command_list(
-                0,
-                map_command_buffer_type(ty),
-                &cmd_allocators[0].raw,
-                PSO_NONE,
-            )
+            .create_command_list(0, map_command_buffer_type(ty), &allocators[0].raw, PSO_NONE)
             .expect("failed to create command list");
         cmd_list.close().expect("failed to close list");
 
+        let pool = CommandPool {
+            created: allocators.len(),
+            allocators,
+            lists: vec![cmd_list],
+        };
+
         Self {
             device: device.clone(),
             queue: Mutex::new(queue),
             ty: ty.clone(),
             ty_raw: map_command_buffer_type(ty),
             fence,
+            completion,
             frequency,
+            markers,
+            descriptors,
+            mip_generator,
+            indirect,
 
             capacity,
-            cmd_allocators: Mutex::new(cmd_allocators),
-            cmd_lists: Mutex::new(vec![cmd_list]),
+            pool: Mutex::new(pool),
             in_record: Default::default(),
             pending: Default::default(),
         }
@@ -91,47 +124,58 @@ impl DxCommandQueue {
         self.fence.get_completed_value() >= value
     }
 
+    pub(crate) fn completed(&self) -> FenceValue {
+        self.fence.get_completed_value()
+    }
+
     pub(crate) fn signal_queue(&self) -> u64 {
         self.signal(&self.fence)
     }
 
+    /// Milliseconds per tick of the timestamps written on this queue.
+    pub(crate) fn timestamp_period(&self) -> f64 {
+        self.frequency
+    }
+
     pub(crate) fn create_command_buffer(&self, handles: Arc<HandleStorage>) -> DxCommandBuffer {
         if let Some(buffer) = self.in_record.lock().pop() {
             return buffer;
         };
 
-        let allocator = if let Some(allocator) =
-            self.cmd_allocators.lock().pop_front().and_then(|a| {
-                if self.is_complete(a.sync_point) {
-                    Some(a)
-                } else {
-                    None
-                }
-            }) {
-            allocator
-                .raw
-                .reset()
-                .expect("failed to reset command allocator");
+        self.allocate_command_buffer(handles)
+    }
 
-            allocator
-        } else {
-            if self.capacity.is_some() {
-                let entry = self.cmd_allocators.lock().pop_front().expect("unreachable");
+    /// Always returns an empty buffer, ignoring stashed ones.
+    pub(crate) fn allocate_command_buffer(&self, handles: Arc<HandleStorage>) -> DxCommandBuffer {
+        let mut guard = self.pool.lock();
+
+        let allocator = match guard.allocators.pop_front() {
+            Some(entry) if self.is_complete(entry.sync_point) => self.reset_allocator(entry),
+            Some(entry) if self.capacity.is_some_and(|c| guard.created >= c) => {
                 self.fence.wait(entry.sync_point);
 
-                entry
-            } else {
+                self.reset_allocator(entry)
+            }
+            entry => {
+                // In flight below capacity, or every allocator is out for
+                // recording and can not be waited for. Keep it for a later buffer.
+                if let Some(entry) = entry {
+                    guard.allocators.push_front(entry);
+                }
+
+                guard.created += 1;
                 CommandAllocatorEntry {
                     raw: self
                         .device
                         .create_command_allocator(self.ty_raw)
                         .expect("failed to create command allocator"),
                     sync_point: 0,
+                    descriptors: Default::default(),
                 }
             }
         };
 
-        let list = if let Some(list) = self.cmd_lists.lock().pop() {
+        let list = if let Some(list) = guard.lists.pop() {
             list.reset(&allocator.raw, PSO_NONE)
                 .expect("Failed to reset list");
             list
@@ -143,25 +187,64 @@ impl DxCommandQueue {
             list.close().expect("failed to close list");
             list
         };
+        drop(guard);
+
+        let validation = handles
+            .validator
+            .as_ref()
+            .map(|v| Mutex::new(CommandValidator::new(Arc::clone(v), self.ty)));
 
         DxCommandBuffer {
+            device: self.device.clone(),
             handles,
+            descriptors: Arc::clone(&self.descriptors),
+            mip_generator: self.mip_generator.clone(),
+            indirect: Arc::clone(&self.indirect),
             ty: self.ty,
             list,
             allocator,
+            scopes: Default::default(),
+            markers: self.markers,
+            validation,
         }
     }
 
+    fn reset_allocator(&self, mut allocator: CommandAllocatorEntry) -> CommandAllocatorEntry {
+        allocator
+            .raw
+            .reset()
+            .expect("failed to reset command allocator");
+
+        let descriptors = allocator.descriptors.get_mut();
+        if !descriptors.is_empty() {
+            let mut heap = self.descriptors.shader_heap.lock();
+            for descriptor in descriptors.drain(..) {
+                heap.free(descriptor);
+            }
+        }
+
+        allocator
+    }
+
     pub(crate) fn stash_cmd_buffer(&self, cmd_buffer: DxCommandBuffer) {
         self.in_record.lock().push(cmd_buffer);
     }
 
     pub(crate) fn push_cmd_buffer(&self, cmd_buffer: DxCommandBuffer) {
+        if let Some(validation) = &cmd_buffer.validation {
+            validation.lock().finish();
+        }
+
+        let scopes = cmd_buffer.scopes.lock().take();
+        if !scopes.is_empty() {
+            cmd_buffer.handles.profiler.lock().submit(scopes);
+        }
+
         cmd_buffer.list.close().expect("failed to close list");
         self.pending.lock().push(cmd_buffer);
     }
 
-    pub(crate) fn commit(&self) -> SyncPoint {
+    pub(crate) fn commit(&self) -> FenceValue {
         let cmd_buffers = self.pending.lock().drain(..).collect::<Vec<_>>();
         let lists = cmd_buffers
             .iter()
@@ -171,25 +254,74 @@ impl DxCommandQueue {
         self.queue.lock().execute_command_lists(&lists);
         let fence_value = self.signal_queue();
 
-        let allocators = cmd_buffers.into_iter().map(|mut buffer| {
+        let mut pool = self.pool.lock();
+        for mut buffer in cmd_buffers {
             buffer.allocator.sync_point = fence_value;
-            buffer.allocator
-        });
-        self.cmd_allocators.lock().extend(allocators);
 
-        let lists = lists.into_iter().map(|list| list.unwrap());
-        self.cmd_lists.lock().extend(lists);
+            pool.allocators.push_back(buffer.allocator);
+            pool.lists.push(buffer.list);
+        }
 
         fence_value
     }
 
-    pub(crate) fn wait_cpu(&self, time: SyncPoint) {
+    pub(crate) fn wait_cpu(&self, time: FenceValue) {
         self.fence.wait(time);
     }
+
+    /// `time` must be a sync point of this queue.
+    pub(crate) fn future(&self, time: SyncPoint) -> GpuFuture {
+        debug_assert!(
+            time.value() <= self.fence.get_current_value(),
+            "waiting for a sync point that was never committed"
+        );
+
+        self.completion.future(time)
+    }
+
+    /// Makes lists executed after this call wait until `other` reaches `time`.
+    pub(crate) fn wait_queue(&self, other: &DxCommandQueue, time: FenceValue) {
+        debug_assert!(
+            time <= other.fence.get_current_value(),
+            "waiting for a sync point that was never committed"
+        );
+
+        self.queue
+            .lock()
+            .wait(&other.fence.fence, time)
+            .expect("failed to wait for queue");
+    }
+
+    /// Sets `fence` to `value` once the lists executed so far are done.
+    pub(crate) fn signal_shared(&self, fence: &DxSharedFence, value: FenceValue) {
+        self.queue
+            .lock()
+            .signal(&fence.fence, value)
+            .expect("failed to signal shared fence");
+    }
+
+    /// Makes lists executed after this call wait until `fence` reaches `value`.
+    pub(crate) fn wait_shared(&self, fence: &DxSharedFence, value: FenceValue) {
+        self.queue
+            .lock()
+            .wait(&fence.fence, value)
+            .expect("failed to wait for shared fence");
+    }
+}
+
+/// Allocators and closed lists of a queue, oldest submitted allocator first.
+#[derive(Debug)]
+struct CommandPool {
+    /// Allocators of the queue, including the ones out for recording.
+    created: usize,
+    allocators: VecDeque<CommandAllocatorEntry>,
+    lists: Vec<dx::GraphicsCommandList>,
 }
 
 #[derive(Debug)]
 pub(crate) struct CommandAllocatorEntry {
     raw: dx::CommandAllocator,
-    sync_point: SyncPoint,
+    sync_point: FenceValue,
+    /// Transient shader descriptors, released once the recorded work is done.
+    pub(crate) descriptors: Mutex<Vec<Descriptor>>,
 }
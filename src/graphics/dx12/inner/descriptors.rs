@@ -1,8 +1,12 @@
-use std::{cmp::Reverse, collections::BinaryHeap, ops::Range};
+use std::{cmp::Reverse, collections::BinaryHeap, ops::Range, sync::Arc};
 
 use oxidx::dx::{self, IDescriptorHeap, IDevice};
 use parking_lot::Mutex;
 
+use crate::graphics::core::memory::{
+    MemoryAllocation, MemoryCategory, MemoryCounters, MemoryLocation,
+};
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct InnerRange(Range<usize>);
 
@@ -44,20 +48,32 @@ pub(crate) struct Descriptors {
     pub(crate) dsv_heap: Mutex<DescriptorHeap>,
     pub(crate) shader_heap: Mutex<DescriptorHeap>,
     pub(crate) sampler_heap: Mutex<DescriptorHeap>,
+    /// CPU only shader descriptors, needed by storage clears.
+    pub(crate) staging_heap: Mutex<DescriptorHeap>,
 }
 
 impl Descriptors {
-    pub fn new(device: &dx::Device) -> Self {
-        let rtv_heap = DescriptorHeap::new(&device, dx::DescriptorHeapType::Rtv, 128);
-        let dsv_heap = DescriptorHeap::new(&device, dx::DescriptorHeapType::Dsv, 128);
-        let shader_heap = DescriptorHeap::new(&device, dx::DescriptorHeapType::CbvSrvUav, 1024);
-        let sampler_heap = DescriptorHeap::new(&device, dx::DescriptorHeapType::Sampler, 32);
+    pub fn new(device: &dx::Device, memory: &Arc<MemoryCounters>) -> Self {
+        let rtv_heap = DescriptorHeap::new(&device, memory, dx::DescriptorHeapType::Rtv, 128);
+        let dsv_heap = DescriptorHeap::new(&device, memory, dx::DescriptorHeapType::Dsv, 128);
+        let shader_heap =
+            DescriptorHeap::new(&device, memory, dx::DescriptorHeapType::CbvSrvUav, 1024);
+        let sampler_heap =
+            DescriptorHeap::new(&device, memory, dx::DescriptorHeapType::Sampler, 32);
+        let staging_heap = DescriptorHeap::with_visibility(
+            &device,
+            memory,
+            dx::DescriptorHeapType::CbvSrvUav,
+            32,
+            false,
+        );
 
         Self {
             rtv_heap: Mutex::new(rtv_heap),
             dsv_heap: Mutex::new(dsv_heap),
             shader_heap: Mutex::new(shader_heap),
             sampler_heap: Mutex::new(sampler_heap),
+            staging_heap: Mutex::new(staging_heap),
         }
     }
 }
@@ -70,16 +86,34 @@ pub(crate) struct DescriptorHeap {
     pub(crate) inc_size: usize,
     free_ranges: BinaryHeap<Reverse<InnerRange>>,
     shader_visible: bool,
+    _allocation: MemoryAllocation,
 }
 
 impl DescriptorHeap {
-    pub(crate) fn new(device: &dx::Device, ty: dx::DescriptorHeapType, size: usize) -> Self {
-        let (shader_visible, flags) =
-            if ty == dx::DescriptorHeapType::CbvSrvUav || ty == dx::DescriptorHeapType::Sampler {
-                (true, dx::DescriptorHeapFlags::ShaderVisible)
-            } else {
-                (false, dx::DescriptorHeapFlags::empty())
-            };
+    pub(crate) fn new(
+        device: &dx::Device,
+        memory: &Arc<MemoryCounters>,
+        ty: dx::DescriptorHeapType,
+        size: usize,
+    ) -> Self {
+        let shader_visible =
+            ty == dx::DescriptorHeapType::CbvSrvUav || ty == dx::DescriptorHeapType::Sampler;
+
+        Self::with_visibility(device, memory, ty, size, shader_visible)
+    }
+
+    pub(crate) fn with_visibility(
+        device: &dx::Device,
+        memory: &Arc<MemoryCounters>,
+        ty: dx::DescriptorHeapType,
+        size: usize,
+        shader_visible: bool,
+    ) -> Self {
+        let flags = if shader_visible {
+            dx::DescriptorHeapFlags::ShaderVisible
+        } else {
+            dx::DescriptorHeapFlags::empty()
+        };
 
         let inc_size = device.get_descriptor_handle_increment_size(ty);
 
@@ -90,6 +124,12 @@ impl DescriptorHeap {
         let mut free_ranges = BinaryHeap::new();
         free_ranges.push(Reverse(InnerRange(0..size)));
 
+        let allocation = memory.allocate(
+            MemoryLocation::Device,
+            MemoryCategory::Descriptor,
+            (size * inc_size) as u64,
+        );
+
         Self {
             heap,
             ty,
@@ -97,6 +137,7 @@ impl DescriptorHeap {
             inc_size,
             free_ranges,
             shader_visible,
+            _allocation: allocation,
         }
     }
 
@@ -0,0 +1,53 @@
+use oxidx::dx::{self, IDevice};
+
+use crate::graphics::core::indirect::{
+    DispatchIndirectArgs, DrawIndexedIndirectArgs, DrawIndirectArgs,
+};
+
+/// Command signatures shared by every indirect call of a device.
+#[derive(Debug)]
+pub(crate) struct IndirectSignatures {
+    pub(crate) draw: dx::CommandSignature,
+    pub(crate) draw_indexed: dx::CommandSignature,
+    pub(crate) dispatch: dx::CommandSignature,
+}
+
+impl IndirectSignatures {
+    pub(crate) fn new(device: &dx::Device) -> Self {
+        let draw = device
+            .create_command_signature(
+                &dx::CommandSignatureDesc::new(
+                    size_of::<DrawIndirectArgs>() as u32,
+                    &[dx::IndirectArgumentDesc::draw()],
+                ),
+                None,
+            )
+            .expect("failed to create draw command signature");
+
+        let draw_indexed = device
+            .create_command_signature(
+                &dx::CommandSignatureDesc::new(
+                    size_of::<DrawIndexedIndirectArgs>() as u32,
+                    &[dx::IndirectArgumentDesc::draw_indexed()],
+                ),
+                None,
+            )
+            .expect("failed to create indexed draw command signature");
+
+        let dispatch = device
+            .create_command_signature(
+                &dx::CommandSignatureDesc::new(
+                    size_of::<DispatchIndirectArgs>() as u32,
+                    &[dx::IndirectArgumentDesc::dispatch()],
+                ),
+                None,
+            )
+            .expect("failed to create dispatch command signature");
+
+        Self {
+            draw,
+            draw_indexed,
+            dispatch,
+        }
+    }
+}
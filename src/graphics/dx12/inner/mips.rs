@@ -0,0 +1,105 @@
+use oxidx::dx::{self, IDevice};
+
+use crate::graphics::core::shader::ShaderError;
+
+const MIPS_SHADER: &str = r#"
+Texture2DArray<float4> SrcMip : register(t0);
+RWTexture2DArray<float4> DstMip : register(u0);
+SamplerState LinearClamp : register(s0);
+
+cbuffer Constants : register(b0) {
+    float2 TexelSize;
+    uint IsSrgb;
+};
+
+float3 LinearToSrgb(float3 x) {
+    // FXC has no `select`, the ternary is component-wise on vectors.
+    return x < 0.0031308 ? 12.92 * x : 1.055 * pow(abs(x), 1.0 / 2.4) - 0.055;
+}
+
+[numthreads(8, 8, 1)]
+void main(uint3 id : SV_DispatchThreadID) {
+    uint width, height, layers;
+    DstMip.GetDimensions(width, height, layers);
+
+    if (id.x >= width || id.y >= height) {
+        return;
+    }
+
+    float2 uv = (float2(id.xy) + 0.5) * TexelSize;
+    float4 color = SrcMip.SampleLevel(LinearClamp, float3(uv, id.z), 0);
+
+    if (IsSrgb) {
+        color.rgb = LinearToSrgb(color.rgb);
+    }
+
+    DstMip[id] = color;
+}
+"#;
+
+pub(crate) const MIPS_GROUP_SIZE: u32 = 8;
+
+/// Compute pipeline that downsamples one mip into the next. Sources are read
+/// through an sRGB view when needed, and the shader encodes the result manually
+/// because storage views can not be sRGB.
+#[derive(Debug)]
+pub(crate) struct MipGenerator {
+    pub(crate) root_signature: dx::RootSignature,
+    pub(crate) pso: dx::PipelineState,
+}
+
+impl MipGenerator {
+    /// Fails if the built-in shader does not compile. `generate_mips` then reports
+    /// an error, loaded assets can still get their mips on the CPU.
+    pub(crate) fn new(device: &dx::Device) -> Result<Self, ShaderError> {
+        let error = |message: String| ShaderError::Compilation {
+            entry: "main".to_string(),
+            message,
+        };
+
+        let shader = dx::Blob::compile_from_source(
+            MIPS_SHADER,
+            &[],
+            c"main",
+            c"cs_5_1",
+            dx::COMPILE_OPT3,
+            0,
+        )
+        .map_err(|e| error(format!("{:?}", e)))?;
+
+        let srv_range = [dx::DescriptorRange::srv(1, 0)];
+        let uav_range = [dx::DescriptorRange::uav(1, 0)];
+
+        let parameters = [
+            dx::RootParameter::constant_32bit(0, 0, 3),
+            dx::RootParameter::descriptor_table(&srv_range),
+            dx::RootParameter::descriptor_table(&uav_range),
+        ];
+
+        let samplers = [dx::StaticSamplerDesc::linear()
+            .with_address_u(dx::AddressMode::Clamp)
+            .with_address_v(dx::AddressMode::Clamp)
+            .with_address_w(dx::AddressMode::Clamp)];
+
+        let root_signature = device
+            .serialize_and_create_root_signature(
+                &dx::RootSignatureDesc::default()
+                    .with_parameters(&parameters)
+                    .with_samplers(&samplers),
+                dx::RootSignatureVersion::V1_0,
+                0,
+            )
+            .map_err(|e| error(format!("failed to create root signature: {:?}", e)))?;
+
+        let pso = device
+            .create_compute_pipeline(
+                &dx::ComputePipelineStateDesc::new(&shader).with_root_signature(&root_signature),
+            )
+            .map_err(|e| error(format!("failed to create pipeline: {:?}", e)))?;
+
+        Ok(Self {
+            root_signature,
+            pso,
+        })
+    }
+}
@@ -1,3 +1,5 @@
 pub mod commands;
 pub mod descriptors;
+pub mod indirect;
+pub mod mips;
 pub mod sync;
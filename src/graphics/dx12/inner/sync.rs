@@ -1,7 +1,12 @@
-use std::sync::atomic::AtomicU64;
+use std::{sync::atomic::AtomicU64, time::Duration};
 
 use oxidx::dx::{self, IDevice, IFence};
 
+use crate::graphics::core::{commands::FenceValue, future::Timeline};
+
+/// Result of a timed out event wait.
+const WAIT_TIMEOUT: u32 = 0x00000102;
+
 #[derive(Debug)]
 pub struct DxFence {
     pub(super) fence: dx::Fence,
@@ -22,11 +27,7 @@ impl DxFence {
 
     pub fn wait(&self, value: u64) -> bool {
         if self.get_completed_value() < value {
-            let event = dx::Event::create(false, false).expect("failed to create event");
-            self.fence
-                .set_event_on_completion(value, event)
-                .expect("failed to bind fence to event");
-            if event.wait(10_000_000) == 0x00000102 {
+            if !self.wait_event(value, 10_000_000) {
                 panic!("device lost")
             }
 
@@ -36,6 +37,16 @@ impl DxFence {
         }
     }
 
+    /// Blocks for at most `millis`, returns whether the fence reached `value`.
+    fn wait_event(&self, value: u64, millis: u32) -> bool {
+        let event = dx::Event::create(false, false).expect("failed to create event");
+        self.fence
+            .set_event_on_completion(value, event)
+            .expect("failed to bind fence to event");
+
+        event.wait(millis) != WAIT_TIMEOUT
+    }
+
     pub fn inc_value(&self) -> u64 {
         self.value
             .fetch_add(1, std::sync::atomic::Ordering::Relaxed)
@@ -50,3 +61,51 @@ impl DxFence {
         self.value.load(std::sync::atomic::Ordering::Relaxed)
     }
 }
+
+impl Timeline for DxFence {
+    fn completed(&self) -> FenceValue {
+        self.get_completed_value()
+    }
+
+    fn wait(&self, value: FenceValue, timeout: Duration) -> bool {
+        if self.get_completed_value() >= value {
+            return true;
+        }
+
+        // `u32::MAX` waits forever.
+        let millis = u32::try_from(timeout.as_millis()).unwrap_or(u32::MAX - 1);
+        self.wait_event(value, millis)
+    }
+}
+
+/// Fence created with a shared handle, so queues of other adapters can open it.
+#[derive(Debug)]
+pub struct DxSharedFence {
+    pub(super) fence: dx::Fence,
+}
+
+impl DxSharedFence {
+    pub(crate) fn new(device: &dx::Device) -> Self {
+        let fence = device
+            .create_fence(
+                0,
+                dx::FenceFlags::Shared | dx::FenceFlags::SharedCrossAdapter,
+            )
+            .expect("failed to create shared fence");
+
+        Self { fence }
+    }
+
+    /// Opens the fence created on `owner` on `device`.
+    pub(crate) fn open(&self, device: &dx::Device, owner: &dx::Device) -> Self {
+        let handle = owner
+            .create_shared_handle(&self.fence, None)
+            .expect("failed to create shared fence handle");
+        let fence: dx::Fence = device
+            .open_shared_handle(handle)
+            .expect("failed to open shared fence");
+        handle.close().expect("failed to close handle");
+
+        Self { fence }
+    }
+}
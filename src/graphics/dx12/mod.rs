@@ -2,6 +2,7 @@ pub mod backend;
 pub mod commands;
 pub mod context;
 pub mod resources;
+pub mod shader;
 pub mod swapchain;
 
 mod conv;
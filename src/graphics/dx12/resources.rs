@@ -1,25 +1,34 @@
 use bytemuck::Pod;
-use oxidx::dx::{self, IDevice, IResource};
+use oxidx::dx::{self, IAdapter3, IDevice, IResource};
 use parking_lot::{Mutex, MutexGuard};
 
 use crate::graphics::core::{
     commands::{CommandBuffer, CommandBufferType, CommandDevice, TransferEncoder},
+    handle::RenderHandle,
+    memory::{MemoryAllocation, MemoryBudget, MemoryCategory, MemoryLocation, MemoryReport},
+    ownership::QueueOwnership,
+    query::{QuerySetDesc, QueryType},
     resource::{
-        BufferDesc, BufferUsages, ResourceDevice, SamplerDesc, TextureDesc, TextureType,
+        Buffer, BufferDesc, BufferUsages, BufferViewDesc, BufferViewError, BufferViewKind,
+        BufferViewType, ResourceDevice, SamplerDesc, Texture, TextureDesc, TextureType,
         TextureUsages, TextureViewDesc,
     },
+    stream::StreamRef,
+    tracker::StateTracker,
 };
 
 use super::{
     context::DxRenderContext,
-    conv::{map_format, map_texture_flags},
+    conv::{map_format, map_resource_format, map_texture_flags},
     inner::descriptors::Descriptor,
 };
 
 impl ResourceDevice for DxRenderContext {
     type Buffer = DxBuffer;
+    type BufferView = DxBufferView;
     type Texture = DxTexture;
     type Sampler = ();
+    type QuerySet = DxQuerySet;
 
     fn create_buffer<T: Pod>(&self, desc: BufferDesc, init_data: Option<&[T]>) -> Self::Buffer {
         let buffer = DxBuffer::new(self, desc);
@@ -44,13 +53,12 @@ impl ResourceDevice for DxRenderContext {
                 let mut cmd = self.create_command_buffer(CommandBufferType::Transfer);
                 {
                     let encoder = cmd.transfer_encoder();
-                    encoder.copy_buffer_to_buffer(&buffer, &staging_buffer);
+                    encoder
+                        .copy_buffer_to_buffer(&buffer, &staging_buffer)
+                        .expect("failed to upload buffer data");
                 }
                 self.push_cmd_buffer(cmd);
-                self.wait_cpu(
-                    CommandBufferType::Transfer,
-                    self.commit(CommandBufferType::Transfer),
-                );
+                self.wait_cpu(self.commit(CommandBufferType::Transfer));
 
                 self.destroy_buffer(staging_buffer);
             }
@@ -61,6 +69,92 @@ impl ResourceDevice for DxRenderContext {
 
     fn destroy_buffer(&self, _buffer: Self::Buffer) {}
 
+    fn create_buffer_view(
+        &self,
+        buffer: &Self::Buffer,
+        desc: BufferViewDesc,
+    ) -> Result<Self::BufferView, BufferViewError> {
+        let count = desc.validate(&buffer.desc)?;
+        let element_size = desc.element_size(&buffer.desc);
+        let offset = desc.first_element * element_size;
+        let size = count * element_size;
+
+        let (format, stride, is_raw) = match desc.kind {
+            BufferViewKind::Raw => (dx::Format::R32Typeless, 0, true),
+            BufferViewKind::Structured { .. } => (dx::Format::Unknown, element_size, false),
+            BufferViewKind::Typed(format) => (map_format(format), 0, false),
+        };
+
+        let elements = (desc.first_element as u64)..((desc.first_element + count) as u64);
+
+        let kind = match desc.ty {
+            BufferViewType::Vertex => DxBufferViewKind::Vertex(dx::VertexBufferView::new(
+                buffer.raw.get_gpu_virtual_address() + offset as u64,
+                stride as u32,
+                size as u32,
+            )),
+            BufferViewType::Index => DxBufferViewKind::Index(dx::IndexBufferView::new(
+                buffer.raw.get_gpu_virtual_address() + offset as u64,
+                size as u32,
+                format,
+            )),
+            BufferViewType::ShaderResource => {
+                let descriptor = self.descriptors.shader_heap.lock().alloc(1);
+                let flags = if is_raw {
+                    dx::BufferSrvFlags::Raw
+                } else {
+                    dx::BufferSrvFlags::empty()
+                };
+
+                self.gpu.create_shader_resource_view(
+                    Some(&buffer.raw),
+                    Some(&dx::ShaderResourceViewDesc::buffer(
+                        format,
+                        elements,
+                        stride as u32,
+                        flags,
+                    )),
+                    descriptor.cpu,
+                );
+
+                DxBufferViewKind::Descriptor(Some(descriptor))
+            }
+            BufferViewType::Storage => {
+                let descriptor = self.descriptors.shader_heap.lock().alloc(1);
+                let flags = if is_raw {
+                    dx::BufferUavFlags::Raw
+                } else {
+                    dx::BufferUavFlags::empty()
+                };
+
+                self.gpu.create_unordered_access_view(
+                    Some(&buffer.raw),
+                    dx::RES_NONE,
+                    Some(&dx::UnorderedAccessViewDesc::buffer(
+                        format,
+                        elements,
+                        stride as u32,
+                        0,
+                        flags,
+                    )),
+                    descriptor.cpu,
+                );
+
+                DxBufferViewKind::Descriptor(Some(descriptor))
+            }
+        };
+
+        Ok(DxBufferView {
+            buffer: None,
+            desc,
+            kind,
+        })
+    }
+
+    fn destroy_buffer_view(&self, mut view: Self::BufferView) {
+        view.release(self);
+    }
+
     fn create_texture<T: Pod>(&self, desc: TextureDesc, init_data: Option<&[T]>) -> Self::Texture {
         let texture = DxTexture::new(self, desc);
 
@@ -78,13 +172,12 @@ impl ResourceDevice for DxRenderContext {
             let mut cmd = self.create_command_buffer(CommandBufferType::Transfer);
             {
                 let encoder = cmd.transfer_encoder();
-                encoder.upload_to_texture(&texture, &staging_buffer, bytemuck::cast_slice(data));
+                encoder
+                    .upload_to_texture(&texture, &staging_buffer, bytemuck::cast_slice(data))
+                    .expect("failed to upload texture data");
             }
             self.push_cmd_buffer(cmd);
-            self.wait_cpu(
-                CommandBufferType::Transfer,
-                self.commit(CommandBufferType::Transfer),
-            );
+            self.wait_cpu(self.commit(CommandBufferType::Transfer));
 
             self.destroy_buffer(staging_buffer);
         }
@@ -116,6 +209,8 @@ impl ResourceDevice for DxRenderContext {
             self.desc.is_cross_adapter_texture_supported,
         );
 
+        let rtv_desc = typeless_rtv_desc(&texture.desc);
+
         let cross_desc = if flags.contains(dx::ResourceFlags::AllowCrossAdapter) {
             desc
         } else {
@@ -130,8 +225,11 @@ impl ResourceDevice for DxRenderContext {
         if flags.contains(dx::ResourceFlags::AllowCrossAdapter) {
             let descriptor = if desc.flags().contains(dx::ResourceFlags::AllowRenderTarget) {
                 let descriptor = self.descriptors.rtv_heap.lock().alloc(1);
-                self.gpu
-                    .create_render_target_view(Some(&cross_res), None, descriptor.cpu);
+                self.gpu.create_render_target_view(
+                    Some(&cross_res),
+                    rtv_desc.as_ref(),
+                    descriptor.cpu,
+                );
                 Some(descriptor)
             } else if desc.flags().contains(dx::ResourceFlags::AllowDepthStencil) {
                 let descriptor = self.descriptors.dsv_heap.lock().alloc(1);
@@ -143,6 +241,8 @@ impl ResourceDevice for DxRenderContext {
             };
 
             DxTexture {
+                handle: None,
+                owner: Default::default(),
                 desc: texture.desc.clone(),
                 state: TextureState::CrossAdapter {
                     heap: open_heap,
@@ -151,6 +251,7 @@ impl ResourceDevice for DxRenderContext {
                 },
                 size: texture.size,
                 descriptor,
+                allocations: vec![],
             }
         } else {
             let d = match texture.desc.ty {
@@ -165,11 +266,15 @@ impl ResourceDevice for DxRenderContext {
                     texture.desc.height,
                     texture.desc.depth,
                 ),
+                TextureType::Cube => {
+                    dx::ResourceDesc::texture_2d(texture.desc.width, texture.desc.height)
+                        .with_array_size(texture.desc.depth)
+                }
             };
 
             let d = d
                 .with_alignment(dx::HeapAlignment::ResourcePlacement)
-                .with_format(map_format(texture.desc.format))
+                .with_format(map_resource_format(texture.desc.format, texture.desc.usage))
                 .with_mip_levels(texture.desc.mip_levels)
                 .with_layout(dx::TextureLayout::Unknown)
                 .with_flags(flags);
@@ -187,8 +292,11 @@ impl ResourceDevice for DxRenderContext {
 
             let descriptor = if desc.flags().contains(dx::ResourceFlags::AllowRenderTarget) {
                 let descriptor = self.descriptors.rtv_heap.lock().alloc(1);
-                self.gpu
-                    .create_render_target_view(Some(&local_res), None, descriptor.cpu);
+                self.gpu.create_render_target_view(
+                    Some(&local_res),
+                    rtv_desc.as_ref(),
+                    descriptor.cpu,
+                );
                 Some(descriptor)
             } else if desc.flags().contains(dx::ResourceFlags::AllowDepthStencil) {
                 let descriptor = self.descriptors.dsv_heap.lock().alloc(1);
@@ -200,16 +308,23 @@ impl ResourceDevice for DxRenderContext {
             };
 
             DxTexture {
+                handle: None,
+                owner: Default::default(),
                 desc: texture.desc.clone(),
                 state: TextureState::Binded {
                     heap: open_heap,
                     cross: cross_res,
                     local: local_res,
-                    local_state: Mutex::new(dx::ResourceStates::Common),
+                    local_state: Mutex::new(StateTracker::new(
+                        texture.desc.mip_levels,
+                        texture.desc.array_layers(),
+                        dx::ResourceStates::Common,
+                    )),
                     cross_state: Mutex::new(dx::ResourceStates::Common),
                 },
                 size: texture.size,
                 descriptor,
+                allocations: vec![self.track_resource(&d, MemoryCategory::Texture)],
             }
         }
     }
@@ -225,15 +340,48 @@ impl ResourceDevice for DxRenderContext {
     fn destroy_sampler(&self, buffer: Self::Sampler) {
         todo!()
     }
+
+    fn create_query_set(&self, desc: QuerySetDesc) -> Self::QuerySet {
+        let ty = match desc.ty {
+            QueryType::Timestamp => dx::QueryHeapType::Timestamp,
+            QueryType::PipelineStatistics => dx::QueryHeapType::PipelineStatistics,
+        };
+
+        let raw = self
+            .gpu
+            .create_query_heap(&dx::QueryHeapDesc::new(ty, desc.count))
+            .expect("failed to create query heap");
+
+        DxQuerySet { raw, desc }
+    }
+
+    fn destroy_query_set(&self, _set: Self::QuerySet) {}
+
+    fn memory_report(&self) -> MemoryReport {
+        let budget = self
+            .adapter
+            .query_video_memory_info(0, dx::MemorySegmentGroup::Local)
+            .ok()
+            .map(|info| MemoryBudget {
+                budget: info.budget(),
+                usage: info.current_usage(),
+            });
+
+        self.memory.report(self.desc.id, budget)
+    }
 }
 
 #[derive(Debug)]
 pub struct DxBuffer {
+    /// Set once bound, names the buffer in validation reports.
+    pub(super) handle: Option<RenderHandle<Buffer>>,
     pub(super) raw: dx::Resource,
     pub(super) desc: BufferDesc,
     pub(super) state: Mutex<dx::ResourceStates>,
+    pub(super) owner: Mutex<QueueOwnership>,
 
     map_guard: Mutex<()>,
+    _allocation: MemoryAllocation,
 }
 
 impl DxBuffer {
@@ -265,14 +413,34 @@ impl DxBuffer {
             .create_committed_resource(&heap_props, dx::HeapFlags::empty(), &d, initial_state, None)
             .expect("Failed to create buffer");
 
+        let allocation = device.memory.allocate(
+            MemoryLocation::for_buffer(desc.usage),
+            MemoryCategory::Buffer,
+            desc.size as u64,
+        );
+
         Self {
+            handle: None,
             raw,
             desc,
             state: Mutex::new(initial_state),
+            owner: Default::default(),
             map_guard: Mutex::new(()),
+            _allocation: allocation,
         }
     }
 
+    pub(super) fn is_default_heap(&self) -> bool {
+        !self
+            .desc
+            .usage
+            .intersects(BufferUsages::Uniform | BufferUsages::Copy | BufferUsages::QueryResolve)
+    }
+
+    pub(super) fn stream_ref(&self) -> StreamRef {
+        self.handle.map_or(StreamRef::Unbound, StreamRef::Buffer)
+    }
+
     pub fn map<T>(&self) -> BufferMap<'_, T> {
         let size = self.desc.size / size_of::<T>();
 
@@ -290,6 +458,47 @@ impl DxBuffer {
     }
 }
 
+#[derive(Debug)]
+pub struct DxQuerySet {
+    pub(super) raw: dx::QueryHeap,
+    pub(super) desc: QuerySetDesc,
+}
+
+impl DxQuerySet {
+    pub(super) fn raw_type(&self) -> dx::QueryType {
+        match self.desc.ty {
+            QueryType::Timestamp => dx::QueryType::Timestamp,
+            QueryType::PipelineStatistics => dx::QueryType::PipelineStatistics,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DxBufferView {
+    /// Set once bound, binding the view then transitions this buffer.
+    pub(super) buffer: Option<RenderHandle<Buffer>>,
+    pub(super) desc: BufferViewDesc,
+    pub(super) kind: DxBufferViewKind,
+}
+
+impl DxBufferView {
+    /// Returns the shader descriptor to the heap, views without one are left untouched.
+    pub(super) fn release(&mut self, device: &DxRenderContext) {
+        if let DxBufferViewKind::Descriptor(descriptor) = &mut self.kind {
+            if let Some(descriptor) = descriptor.take() {
+                device.descriptors.shader_heap.lock().free(descriptor);
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(super) enum DxBufferViewKind {
+    Vertex(dx::VertexBufferView),
+    Index(dx::IndexBufferView),
+    Descriptor(Option<Descriptor>),
+}
+
 #[derive(Debug)]
 pub struct BufferMap<'a, T> {
     _guard: MutexGuard<'a, ()>,
@@ -298,10 +507,14 @@ pub struct BufferMap<'a, T> {
 
 #[derive(Debug)]
 pub struct DxTexture {
+    /// Set once bound, names the texture in validation reports.
+    pub(super) handle: Option<RenderHandle<Texture>>,
     pub(super) desc: TextureDesc,
     pub(super) state: TextureState,
+    pub(super) owner: Mutex<QueueOwnership>,
     pub(super) size: usize,
     pub(super) descriptor: Option<Descriptor>,
+    pub(super) allocations: Vec<MemoryAllocation>,
 }
 
 impl DxTexture {
@@ -312,11 +525,14 @@ impl DxTexture {
                 dx::ResourceDesc::texture_2d(desc.width, desc.height).with_array_size(desc.depth)
             }
             TextureType::D3 => dx::ResourceDesc::texture_3d(desc.width, desc.height, desc.depth),
+            TextureType::Cube => {
+                dx::ResourceDesc::texture_2d(desc.width, desc.height).with_array_size(desc.depth)
+            }
         };
 
         let d = d
             .with_alignment(dx::HeapAlignment::ResourcePlacement)
-            .with_format(map_format(desc.format))
+            .with_format(map_resource_format(desc.format, desc.usage))
             .with_mip_levels(desc.mip_levels)
             .with_layout(dx::TextureLayout::Unknown)
             .with_flags(map_texture_flags(
@@ -324,6 +540,9 @@ impl DxTexture {
                 device.desc.is_cross_adapter_texture_supported,
             ));
 
+        let rtv_desc = typeless_rtv_desc(&desc);
+        let (mip_levels, array_layers) = (desc.mip_levels, desc.array_layers());
+
         if desc.usage.contains(TextureUsages::Shared) {
             let cross_desc = if d.flags().contains(dx::ResourceFlags::AllowCrossAdapter) {
                 d.clone().with_layout(dx::TextureLayout::RowMajor)
@@ -345,22 +564,33 @@ impl DxTexture {
                         .with_flags(dx::HeapFlags::SharedCrossAdapter | dx::HeapFlags::Shared),
                 )
                 .expect("Failed to create shared heap");
+            let heap_allocation =
+                device
+                    .memory
+                    .allocate(MemoryLocation::Device, MemoryCategory::Heap, size as u64);
 
             let cross_res = device
                 .gpu
                 .create_placed_resource(&heap, 0, &cross_desc, dx::ResourceStates::Common, None)
                 .expect("failed to create cross texture");
 
-            let size = device
-                .gpu
-                .get_copyable_footprints(&d, 0..1, 0, None, None, None);
+            let size = device.gpu.get_copyable_footprints(
+                &d,
+                0..desc.subresource_count(),
+                0,
+                None,
+                None,
+                None,
+            );
 
             if d.flags().contains(dx::ResourceFlags::AllowCrossAdapter) {
                 let descriptor = if d.flags().contains(dx::ResourceFlags::AllowRenderTarget) {
                     let descriptor = device.descriptors.rtv_heap.lock().alloc(1);
-                    device
-                        .gpu
-                        .create_render_target_view(Some(&cross_res), None, descriptor.cpu);
+                    device.gpu.create_render_target_view(
+                        Some(&cross_res),
+                        rtv_desc.as_ref(),
+                        descriptor.cpu,
+                    );
                     Some(descriptor)
                 } else if d.flags().contains(dx::ResourceFlags::AllowDepthStencil) {
                     let descriptor = device.descriptors.dsv_heap.lock().alloc(1);
@@ -373,6 +603,8 @@ impl DxTexture {
                 };
 
                 Self {
+                    handle: None,
+                    owner: Default::default(),
                     desc,
                     state: TextureState::CrossAdapter {
                         heap,
@@ -381,6 +613,7 @@ impl DxTexture {
                     },
                     size,
                     descriptor,
+                    allocations: vec![heap_allocation],
                 }
             } else {
                 let local_res = device
@@ -396,9 +629,11 @@ impl DxTexture {
 
                 let descriptor = if d.flags().contains(dx::ResourceFlags::AllowRenderTarget) {
                     let descriptor = device.descriptors.rtv_heap.lock().alloc(1);
-                    device
-                        .gpu
-                        .create_render_target_view(Some(&local_res), None, descriptor.cpu);
+                    device.gpu.create_render_target_view(
+                        Some(&local_res),
+                        rtv_desc.as_ref(),
+                        descriptor.cpu,
+                    );
                     Some(descriptor)
                 } else if d.flags().contains(dx::ResourceFlags::AllowDepthStencil) {
                     let descriptor = device.descriptors.dsv_heap.lock().alloc(1);
@@ -411,23 +646,38 @@ impl DxTexture {
                 };
 
                 Self {
+                    handle: None,
+                    owner: Default::default(),
                     desc,
                     state: TextureState::Binded {
                         heap,
                         cross: cross_res,
                         local: local_res,
 
-                        local_state: Mutex::new(dx::ResourceStates::Common),
+                        local_state: Mutex::new(StateTracker::new(
+                            mip_levels,
+                            array_layers,
+                            dx::ResourceStates::Common,
+                        )),
                         cross_state: Mutex::new(dx::ResourceStates::Common),
                     },
                     size,
                     descriptor,
+                    allocations: vec![
+                        heap_allocation,
+                        device.track_resource(&d, MemoryCategory::Texture),
+                    ],
                 }
             }
         } else {
-            let size = device
-                .gpu
-                .get_copyable_footprints(&d, 0..1, 0, None, None, None);
+            let size = device.gpu.get_copyable_footprints(
+                &d,
+                0..desc.subresource_count(),
+                0,
+                None,
+                None,
+                None,
+            );
 
             let raw = device
                 .gpu
@@ -444,7 +694,7 @@ impl DxTexture {
                 let descriptor = device.descriptors.rtv_heap.lock().alloc(1);
                 device
                     .gpu
-                    .create_render_target_view(Some(&raw), None, descriptor.cpu);
+                    .create_render_target_view(Some(&raw), rtv_desc.as_ref(), descriptor.cpu);
                 Some(descriptor)
             } else if d.flags().contains(dx::ResourceFlags::AllowDepthStencil) {
                 let descriptor = device.descriptors.dsv_heap.lock().alloc(1);
@@ -457,23 +707,56 @@ impl DxTexture {
             };
 
             Self {
+                handle: None,
                 size,
                 desc,
+                owner: Default::default(),
                 state: TextureState::Local {
                     raw,
-                    state: Mutex::new(dx::ResourceStates::Common),
+                    state: Mutex::new(StateTracker::new(
+                        mip_levels,
+                        array_layers,
+                        dx::ResourceStates::Common,
+                    )),
                 },
                 descriptor,
+                allocations: vec![device.track_resource(&d, MemoryCategory::Texture)],
             }
         }
     }
+
+    pub(super) fn stream_ref(&self) -> StreamRef {
+        self.handle.map_or(StreamRef::Unbound, StreamRef::Texture)
+    }
+
+    /// Resource owned by this device that can be paged in and out.
+    pub(super) fn pageable(&self) -> Option<&dx::Resource> {
+        match &self.state {
+            TextureState::Local { raw, .. } => Some(raw),
+            TextureState::Binded { local, .. } => Some(local),
+            TextureState::CrossAdapter { .. } => None,
+        }
+    }
+}
+
+fn typeless_rtv_desc(desc: &TextureDesc) -> Option<dx::RenderTargetViewDesc> {
+    if map_resource_format(desc.format, desc.usage) == map_format(desc.format) {
+        return None;
+    }
+
+    let format = map_format(desc.format);
+
+    Some(match desc.ty {
+        TextureType::D2 if desc.depth == 1 => dx::RenderTargetViewDesc::texture_2d(format, 0, 0),
+        _ => dx::RenderTargetViewDesc::texture_2d_array(format, 0, 0..(desc.depth as u32), 0),
+    })
 }
 
 #[derive(Debug)]
 pub enum TextureState {
     Local {
         raw: dx::Resource,
-        state: Mutex<dx::ResourceStates>,
+        state: Mutex<StateTracker<dx::ResourceStates>>,
     },
     CrossAdapter {
         heap: dx::Heap,
@@ -485,7 +768,7 @@ pub enum TextureState {
         cross: dx::Resource,
         local: dx::Resource,
 
-        local_state: Mutex<dx::ResourceStates>,
+        local_state: Mutex<StateTracker<dx::ResourceStates>>,
         cross_state: Mutex<dx::ResourceStates>,
     },
 }
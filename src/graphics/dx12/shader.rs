@@ -0,0 +1,237 @@
+use std::ffi::CString;
+
+use oxidx::dx::{self, IDevice};
+
+use crate::graphics::core::{
+    handle::SparseArray,
+    shader::{
+        ComputePipelineDesc, EntryPoint, PipelineBinding, PipelineError, PipelineLayout,
+        RenderPipelineDesc, ShaderEntry, ShaderError, ShaderModule, ShaderModuleDesc, ShaderSource,
+        ShaderStage,
+    },
+};
+
+use super::conv::{map_depth_format, map_format, map_semantic, map_topology, map_topology_type};
+
+#[derive(Debug)]
+pub struct DxRenderPipeline {
+    pub(super) root_signature: dx::RootSignature,
+    pub(super) pso: dx::PipelineState,
+    pub(super) topology: dx::PrimitiveTopology,
+}
+
+impl DxRenderPipeline {
+    pub(super) fn new(
+        device: &dx::Device,
+        desc: &RenderPipelineDesc,
+        modules: &SparseArray<ShaderModule, DxShaderModule>,
+    ) -> Result<Self, PipelineError> {
+        desc.validate()?;
+
+        let vs = bytecode(modules, &desc.vertex, ShaderStage::Vertex)?;
+        let ps = desc
+            .pixel
+            .as_ref()
+            .map(|entry| bytecode(modules, entry, ShaderStage::Pixel))
+            .transpose()?;
+
+        let root_signature = create_root_signature(
+            device,
+            &desc.layout,
+            dx::RootSignatureFlags::AllowInputAssemblerInputLayout,
+        )?;
+
+        let input_layout = desc
+            .vertex_attributes
+            .iter()
+            .map(|attribute| {
+                dx::InputElementDesc::per_vertex(
+                    map_semantic(attribute.semantic, attribute.index),
+                    map_format(attribute.format),
+                    attribute.slot,
+                )
+                .with_offset(attribute.offset)
+            })
+            .collect::<Vec<_>>();
+
+        let formats = desc
+            .color_formats
+            .iter()
+            .map(|&format| map_format(format))
+            .collect::<Vec<_>>();
+
+        let mut pso_desc = dx::GraphicsPipelineDesc::new(vs)
+            .with_root_signature(&root_signature)
+            .with_input_layout(&input_layout)
+            .with_rasterizer_state(dx::RasterizerDesc::default().with_cull_mode(dx::CullMode::None))
+            .with_blend_desc(dx::BlendDesc::default())
+            .with_primitive_topology(map_topology_type(desc.topology))
+            .with_render_targets(formats);
+
+        if let Some(ps) = ps {
+            pso_desc = pso_desc.with_ps(ps);
+        }
+
+        if let Some(format) = desc.depth_format {
+            pso_desc = pso_desc.with_depth_stencil(
+                dx::DepthStencilDesc::default()
+                    .enable_depth(dx::ComparisonFunc::Less)
+                    .with_depth_write_mask(dx::DepthWriteMask::All),
+                map_depth_format(format),
+            );
+        }
+
+        let pso = device
+            .create_graphics_pipeline(&pso_desc)
+            .map_err(|e| PipelineError::Creation(format!("{:?}", e)))?;
+
+        Ok(Self {
+            root_signature,
+            pso,
+            topology: map_topology(desc.topology),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct DxComputePipeline {
+    pub(super) root_signature: dx::RootSignature,
+    pub(super) pso: dx::PipelineState,
+}
+
+impl DxComputePipeline {
+    pub(super) fn new(
+        device: &dx::Device,
+        desc: &ComputePipelineDesc,
+        modules: &SparseArray<ShaderModule, DxShaderModule>,
+    ) -> Result<Self, PipelineError> {
+        let cs = bytecode(modules, &desc.shader, ShaderStage::Compute)?;
+        let root_signature =
+            create_root_signature(device, &desc.layout, dx::RootSignatureFlags::empty())?;
+
+        let pso = device
+            .create_compute_pipeline(
+                &dx::ComputePipelineStateDesc::new(cs).with_root_signature(&root_signature),
+            )
+            .map_err(|e| PipelineError::Creation(format!("{:?}", e)))?;
+
+        Ok(Self {
+            root_signature,
+            pso,
+        })
+    }
+}
+
+/// Bytecode of every entry point, HLSL is compiled once when the module is bound.
+#[derive(Debug)]
+pub struct DxShaderModule {
+    pub(super) entry_points: Vec<(EntryPoint, dx::Blob)>,
+}
+
+impl DxShaderModule {
+    /// Expects a `desc` already validated against the device languages.
+    pub(super) fn new(desc: ShaderModuleDesc) -> Result<Self, ShaderError> {
+        let entry_points = desc
+            .entry_points
+            .into_iter()
+            .map(|entry| {
+                let bytecode = match &desc.source {
+                    ShaderSource::Hlsl(source) => compile(source, &entry)?,
+                    ShaderSource::Dxil(bytes) => {
+                        dx::Blob::from_bytes(bytes).map_err(|e| ShaderError::Compilation {
+                            entry: entry.name.to_string(),
+                            message: format!("{:?}", e),
+                        })?
+                    }
+                    ShaderSource::Wgsl(_) | ShaderSource::SpirV(_) => {
+                        unreachable!("validated against the device languages")
+                    }
+                };
+
+                Ok((entry, bytecode))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { entry_points })
+    }
+}
+
+fn compile(source: &str, entry: &EntryPoint) -> Result<dx::Blob, ShaderError> {
+    let error = |message: String| ShaderError::Compilation {
+        entry: entry.name.to_string(),
+        message,
+    };
+
+    let name = CString::new(entry.name.as_bytes()).map_err(|e| error(e.to_string()))?;
+    let target = match entry.stage {
+        ShaderStage::Vertex => c"vs_5_1",
+        ShaderStage::Pixel => c"ps_5_1",
+        ShaderStage::Compute => c"cs_5_1",
+    };
+
+    dx::Blob::compile_from_source(source, &[], &name, target, dx::COMPILE_OPT3, 0)
+        .map_err(|e| error(format!("{:?}", e)))
+}
+
+fn bytecode<'a>(
+    modules: &'a SparseArray<ShaderModule, DxShaderModule>,
+    entry: &ShaderEntry,
+    stage: ShaderStage,
+) -> Result<&'a dx::Blob, PipelineError> {
+    let module = modules
+        .get(entry.module)
+        .ok_or(PipelineError::MissingModule)?;
+    let index = entry.find(module.entry_points.iter().map(|(e, _)| e), stage)?;
+
+    Ok(&module.entry_points[index].1)
+}
+
+/// Every binding of `layout` becomes a root parameter at its index, views
+/// through a single descriptor table range.
+fn create_root_signature(
+    device: &dx::Device,
+    layout: &PipelineLayout,
+    flags: dx::RootSignatureFlags,
+) -> Result<dx::RootSignature, PipelineError> {
+    let ranges = layout
+        .bindings
+        .iter()
+        .map(|binding| match *binding {
+            PipelineBinding::ShaderResource { register, space } => {
+                Some([dx::DescriptorRange::srv(1, register).with_register_space(space)])
+            }
+            PipelineBinding::Storage { register, space } => {
+                Some([dx::DescriptorRange::uav(1, register).with_register_space(space)])
+            }
+            PipelineBinding::Constants { .. } => None,
+        })
+        .collect::<Vec<_>>();
+
+    let parameters = layout
+        .bindings
+        .iter()
+        .zip(&ranges)
+        .map(|(binding, range)| match (binding, range) {
+            (
+                &PipelineBinding::Constants {
+                    register,
+                    space,
+                    count,
+                },
+                _,
+            ) => dx::RootParameter::constant_32bit(register, space, count),
+            (_, Some(range)) => dx::RootParameter::descriptor_table(range),
+            (_, None) => unreachable!("only constants have no range"),
+        })
+        .collect::<Vec<_>>();
+
+    device
+        .serialize_and_create_root_signature(
+            &dx::RootSignatureDesc::default()
+                .with_parameters(&parameters)
+                .with_flags(flags),
+            dx::RootSignatureVersion::V1_0,
+            0,
+        )
+        .map_err(|e| PipelineError::Creation(format!("failed to create root signature: {:?}", e)))
+}
@@ -4,9 +4,10 @@ use oxidx::dx::{self, IDevice, IFactory4, ISwapchain1};
 use parking_lot::Mutex;
 
 use crate::graphics::core::{
-    commands::SyncPoint,
+    commands::FenceValue,
     resource::{TextureDesc, TextureType, TextureUsages},
     swapchain::{RenderSwapchain, SwapchainDesc},
+    tracker::StateTracker,
     types::Format,
 };
 
@@ -18,7 +19,7 @@ use super::{
 #[derive(Debug)]
 pub struct SwapchainFrame {
     pub texture: DxTexture,
-    pub last_access: SyncPoint,
+    pub last_access: FenceValue,
 }
 
 #[derive(Debug)]
@@ -74,7 +75,7 @@ impl Swapchain {
         ctx: &DxRenderContext,
         width: u32,
         height: u32,
-        sync_point: SyncPoint,
+        sync_point: FenceValue,
     ) {
         {
             std::mem::take(&mut self.resources);
@@ -102,6 +103,8 @@ impl Swapchain {
             let descriptor = Some(descriptor);
 
             let texture = DxTexture {
+                handle: None,
+                owner: Default::default(),
                 desc: TextureDesc {
                     name: None,
                     ty: TextureType::D2,
@@ -114,10 +117,11 @@ impl Swapchain {
                 },
                 state: TextureState::Local {
                     raw: res,
-                    state: Mutex::new(dx::ResourceStates::Common),
+                    state: Mutex::new(StateTracker::new(1, 1, dx::ResourceStates::Common)),
                 },
                 size: 0, // TODO: Calculate
                 descriptor,
+                allocations: vec![],
             };
 
             self.resources.push(SwapchainFrame {
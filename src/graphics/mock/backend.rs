@@ -0,0 +1,75 @@
+use tracing::info;
+
+use crate::graphics::{
+    core::{
+        backend::{Api, DeviceType, RenderDeviceId, RenderDeviceInfo},
+        shader::ShaderLanguages,
+    },
+    DebugFlags,
+};
+
+use super::context::MockRenderContext;
+
+const MOCK_DEVICE_COUNT: usize = 2;
+
+#[derive(Debug)]
+pub struct MockBackend {
+    debug: DebugFlags,
+    adapter_infos: Vec<RenderDeviceInfo>,
+    memory_budget: Option<u64>,
+}
+
+impl MockBackend {
+    pub fn new(debug_flags: DebugFlags) -> Self {
+        let adapter_infos = (0..MOCK_DEVICE_COUNT)
+            .map(|id| RenderDeviceInfo {
+                name: format!("Mock Device {}", id),
+                id,
+                is_cross_adapter_texture_supported: true,
+                is_uma: true,
+                ty: DeviceType::Cpu,
+                shader_languages: ShaderLanguages::all(),
+            })
+            .collect::<Vec<_>>();
+
+        adapter_infos
+            .iter()
+            .for_each(|a| info!("Found adapter: {:?}", a));
+
+        Self {
+            debug: debug_flags,
+            adapter_infos,
+            memory_budget: None,
+        }
+    }
+
+    /// Reports `budget` bytes of device memory in every memory report.
+    pub fn with_memory_budget(mut self, budget: u64) -> Self {
+        self.memory_budget = Some(budget);
+        self
+    }
+
+    /// Only accepts shader modules in `languages`, like a backend without some compilers.
+    pub fn with_shader_languages(mut self, languages: ShaderLanguages) -> Self {
+        self.adapter_infos
+            .iter_mut()
+            .for_each(|a| a.shader_languages = languages);
+        self
+    }
+}
+
+impl Api for MockBackend {
+    type Device = MockRenderContext;
+
+    fn enumerate_devices<'a>(&'a self) -> impl Iterator<Item = &'a RenderDeviceInfo> + 'a {
+        self.adapter_infos.iter()
+    }
+
+    fn create_device(&self, index: RenderDeviceId) -> Self::Device {
+        MockRenderContext::new(
+            self.adapter_infos[index].clone(),
+            self.debug,
+            self.memory_budget,
+        )
+    }
+}
@@ -0,0 +1,4312 @@
+use std::collections::VecDeque;
+use std::hint::cold_path;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytemuck::Pod;
+use parking_lot::{Condvar, Mutex};
+use tracing::{error, warn};
+
+use crate::graphics::core::bundle::{
+    self, BundleDesc, BundleError, BundleReferences, BundleValidity,
+};
+use crate::graphics::core::commands::{
+    BufferBarrier, CommandBuffer, CommandBufferType, CommandDevice, ComputeEncoder, DebugEncoder,
+    DeviceTag, DynComputeEncoder, DynRenderEncoder, DynTransferEncoder, FenceValue, LoadOp,
+    RenderEncoder, RenderPassDesc, ResourceUsages, Scissor, SyncPoint, TextureBarrier,
+    TransferEncoder, Viewport,
+};
+use crate::graphics::core::future::{GpuFuture, GpuTimeline, Timeline};
+use crate::graphics::core::handle::RenderHandle;
+use crate::graphics::core::indirect::{
+    self, DispatchIndirectArgs, DrawIndexedIndirectArgs, DrawIndirectArgs, IndirectError,
+};
+use crate::graphics::core::mips;
+use crate::graphics::core::ownership::{self, OwnershipError, QueueOwnership};
+use crate::graphics::core::profiler::ScopeStack;
+use crate::graphics::core::query::{self, QueryError, QueryType};
+use crate::graphics::core::resource::{
+    Buffer, BufferView, BufferViewType, ResourceDevice, SubresourceRange, Texture, TextureDesc,
+};
+use crate::graphics::core::shader::{
+    ComputePipeline, ComputePipelineDesc, RenderPipeline, RenderPipelineDesc,
+};
+use crate::graphics::core::stream::{CommandStream, StreamCommand, StreamRef};
+use crate::graphics::core::transfer::{
+    self, BufferTextureLayout, ClearValue, TextureRegion, TransferError,
+};
+use crate::graphics::core::types::{Format, ResourceState};
+use crate::graphics::core::validation::CommandValidator;
+use crate::graphics::DebugFlags;
+
+use super::context::{HandleStorage, MockRenderContext};
+use super::resources::{MockBuffer, MockBufferView, MockPipeline, MockQuerySet, MockTexture};
+
+/// Milliseconds per tick of the mock queue clock, which advances by one tick
+/// for every executed command.
+pub(super) const MOCK_TIMESTAMP_PERIOD: f64 = 0.001;
+
+#[derive(Clone, Debug)]
+pub(super) enum MockCommand {
+    CopyBuffer {
+        dst: Arc<Mutex<Vec<u8>>>,
+        src: Arc<Mutex<Vec<u8>>>,
+    },
+    CopyTexture {
+        dst: Arc<Mutex<Vec<u8>>>,
+        src: Arc<Mutex<Vec<u8>>>,
+    },
+    UploadTexture {
+        dst: Arc<Mutex<Vec<u8>>>,
+        data: Vec<u8>,
+    },
+    GenerateMips {
+        desc: TextureDesc,
+        data: Arc<Mutex<Vec<u8>>>,
+    },
+    Clear {
+        dst: Arc<Mutex<Vec<u8>>>,
+        texel: Vec<u8>,
+    },
+    /// Copies `row_size` bytes for every `(dst, src)` offset pair.
+    CopyRegion {
+        dst: Arc<Mutex<Vec<u8>>>,
+        src: Arc<Mutex<Vec<u8>>>,
+        rows: Vec<(usize, usize)>,
+        row_size: usize,
+    },
+    Fill {
+        dst: Arc<Mutex<Vec<u8>>>,
+        ranges: Vec<Range<usize>>,
+        pattern: Vec<u8>,
+    },
+    WriteTimestamp {
+        dst: Arc<Mutex<Vec<u8>>>,
+        offset: usize,
+    },
+    PushDebugGroup(String),
+    PopDebugGroup,
+    Marker(String),
+    /// Draws and dispatches are only recorded, the mock backend runs no shaders.
+    Draw(MockDraw),
+    /// Arguments are interpreted when the draws are inspected, like the GPU
+    /// reading them at execution time.
+    DrawIndirect {
+        args: Arc<Mutex<Vec<u8>>>,
+        offset: usize,
+        count: MockDrawCount,
+        draw: MockDraw,
+    },
+    Dispatch(MockDispatch),
+    DispatchIndirect {
+        args: Arc<Mutex<Vec<u8>>>,
+        offset: usize,
+        dispatch: MockDispatch,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MockDispatch {
+    pub groups: [u32; 3],
+    pub indirect: bool,
+    pub views: Vec<u32>,
+    pub constants: Vec<(u32, Vec<u32>)>,
+}
+
+#[derive(Clone, Debug)]
+pub(super) enum MockDrawCount {
+    Fixed(u32),
+    Buffer {
+        data: Arc<Mutex<Vec<u8>>>,
+        offset: usize,
+        max: u32,
+    },
+}
+
+impl MockDrawCount {
+    fn get(&self) -> u32 {
+        match self {
+            MockDrawCount::Fixed(count) => *count,
+            MockDrawCount::Buffer { data, offset, max } => {
+                let count: u32 = bytemuck::pod_read_unaligned(&data.lock()[*offset..(*offset + 4)]);
+                count.min(*max)
+            }
+        }
+    }
+}
+
+/// Draw call together with the dynamic state it was recorded with. `vertices`
+/// holds the index range for indexed draws.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MockDraw {
+    pub vertices: Range<u32>,
+    pub instances: Range<u32>,
+    pub base_vertex: i32,
+    pub indexed: bool,
+    pub indirect: bool,
+    pub viewport: Viewport,
+    pub scissor: Scissor,
+    pub stencil_reference: u32,
+    pub blend_constants: [f32; 4],
+    pub vertex_buffers: Vec<u32>,
+}
+
+/// Structure of the recorded work, see [`MockCommandBuffer::log`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MockLogEntry {
+    PushDebugGroup(String),
+    PopDebugGroup,
+    Marker(String),
+    /// Any other command, by name.
+    Command(&'static str),
+}
+
+impl MockCommand {
+    fn log_entry(&self) -> MockLogEntry {
+        let name = match self {
+            MockCommand::PushDebugGroup(name) => return MockLogEntry::PushDebugGroup(name.clone()),
+            MockCommand::PopDebugGroup => return MockLogEntry::PopDebugGroup,
+            MockCommand::Marker(name) => return MockLogEntry::Marker(name.clone()),
+            MockCommand::CopyBuffer { .. } => "copy_buffer",
+            MockCommand::CopyTexture { .. } => "copy_texture",
+            MockCommand::UploadTexture { .. } => "upload_texture",
+            MockCommand::GenerateMips { .. } => "generate_mips",
+            MockCommand::Clear { .. } => "clear",
+            MockCommand::CopyRegion { .. } => "copy_region",
+            MockCommand::Fill { .. } => "fill",
+            MockCommand::WriteTimestamp { .. } => "write_timestamp",
+            MockCommand::Draw(_) => "draw",
+            MockCommand::DrawIndirect { .. } => "draw_indirect",
+            MockCommand::Dispatch(_) => "dispatch",
+            MockCommand::DispatchIndirect { .. } => "dispatch_indirect",
+        };
+
+        MockLogEntry::Command(name)
+    }
+
+    /// Timestamps and debug annotations take no time on the mock queue clock.
+    fn advances_clock(&self) -> bool {
+        !matches!(
+            self,
+            MockCommand::WriteTimestamp { .. }
+                | MockCommand::PushDebugGroup(_)
+                | MockCommand::PopDebugGroup
+                | MockCommand::Marker(_)
+        )
+    }
+
+    /// Copy of a bundle command for a pass, draws take the pass viewport and scissor.
+    fn replay(&self, viewport: Viewport, scissor: Scissor) -> MockCommand {
+        let mut command = self.clone();
+        if let MockCommand::Draw(draw) | MockCommand::DrawIndirect { draw, .. } = &mut command {
+            draw.viewport = viewport;
+            draw.scissor = scissor;
+        }
+
+        command
+    }
+
+    fn execute(self, clock: u64) {
+        match self {
+            MockCommand::CopyBuffer { dst, src } | MockCommand::CopyTexture { dst, src } => {
+                if Arc::ptr_eq(&dst, &src) {
+                    return;
+                }
+
+                let src = src.lock();
+                let mut dst = dst.lock();
+                let len = src.len().min(dst.len());
+                dst[..len].copy_from_slice(&src[..len]);
+            }
+            MockCommand::UploadTexture { dst, data } => {
+                let mut dst = dst.lock();
+                let len = data.len().min(dst.len());
+                dst[..len].copy_from_slice(&data[..len]);
+            }
+            MockCommand::GenerateMips { desc, data } => {
+                if !mips::fill_mips(&desc, &mut data.lock()) {
+                    error!(
+                        "Mip generation is not supported for format {:?}",
+                        desc.format
+                    );
+                }
+            }
+            MockCommand::Clear { dst, texel } => {
+                for chunk in dst.lock().chunks_exact_mut(texel.len()) {
+                    chunk.copy_from_slice(&texel);
+                }
+            }
+            MockCommand::CopyRegion {
+                dst,
+                src,
+                rows,
+                row_size,
+            } => {
+                if Arc::ptr_eq(&dst, &src) {
+                    let mut data = dst.lock();
+                    for (dst, src) in rows {
+                        data.copy_within(src..(src + row_size), dst);
+                    }
+                    return;
+                }
+
+                let (src, mut dst) = (src.lock(), dst.lock());
+                for (dst_offset, src_offset) in rows {
+                    dst[dst_offset..(dst_offset + row_size)]
+                        .copy_from_slice(&src[src_offset..(src_offset + row_size)]);
+                }
+            }
+            MockCommand::Fill {
+                dst,
+                ranges,
+                pattern,
+            } => {
+                let mut dst = dst.lock();
+                for range in ranges {
+                    for chunk in dst[range].chunks_exact_mut(pattern.len()) {
+                        chunk.copy_from_slice(&pattern);
+                    }
+                }
+            }
+            MockCommand::WriteTimestamp { dst, offset } => {
+                dst.lock()[offset..(offset + 8)].copy_from_slice(&clock.to_ne_bytes());
+            }
+            MockCommand::PushDebugGroup(_)
+            | MockCommand::PopDebugGroup
+            | MockCommand::Marker(_)
+            | MockCommand::Draw(_)
+            | MockCommand::DrawIndirect { .. }
+            | MockCommand::Dispatch(_)
+            | MockCommand::DispatchIndirect { .. } => {}
+        }
+    }
+}
+
+/// Fence shared between devices, opening it shares the value.
+#[derive(Clone, Debug, Default)]
+pub struct MockSharedFence {
+    value: Arc<AtomicU64>,
+}
+
+impl MockSharedFence {
+    pub fn value(&self) -> FenceValue {
+        self.value.load(Ordering::Relaxed)
+    }
+
+    fn signal(&self, value: FenceValue) {
+        self.value.store(value, Ordering::Relaxed);
+    }
+}
+
+/// Completed value of a mock queue, advanced whenever a flush runs a submission.
+#[derive(Debug, Default)]
+pub(super) struct MockTimeline {
+    completed: Mutex<FenceValue>,
+    advanced: Condvar,
+}
+
+impl MockTimeline {
+    fn advance(&self, value: FenceValue) {
+        *self.completed.lock() = value;
+        self.advanced.notify_all();
+    }
+}
+
+impl Timeline for MockTimeline {
+    fn completed(&self) -> FenceValue {
+        *self.completed.lock()
+    }
+
+    fn wait(&self, value: FenceValue, timeout: Duration) -> bool {
+        let mut completed = self.completed.lock();
+        let _ = self
+            .advanced
+            .wait_while_for(&mut completed, |c| *c < value, timeout);
+
+        *completed >= value
+    }
+}
+
+/// Work committed together, executed once the queues it waits for reach their sync points.
+#[derive(Debug)]
+struct MockSubmission {
+    sync_point: FenceValue,
+    waits: Vec<(CommandBufferType, FenceValue)>,
+    shared_waits: Vec<(MockSharedFence, FenceValue)>,
+    cmd_buffers: Vec<MockCommandBuffer>,
+}
+
+#[derive(Debug)]
+pub(super) struct MockCommandQueue {
+    ty: CommandBufferType,
+    /// Whether new buffers mirror their commands into a [`CommandStream`].
+    streams: bool,
+
+    in_record: Mutex<Vec<MockCommandBuffer>>,
+    pending: Mutex<Vec<MockCommandBuffer>>,
+    /// GPU waits applied to the next commit.
+    waits: Mutex<Vec<(CommandBufferType, FenceValue)>>,
+    shared_waits: Mutex<Vec<(MockSharedFence, FenceValue)>>,
+    submissions: Mutex<VecDeque<MockSubmission>>,
+    /// Shared fences set once this queue reaches a sync point.
+    signals: Mutex<Vec<(FenceValue, MockSharedFence, FenceValue)>>,
+
+    value: AtomicU64,
+    timeline: Arc<MockTimeline>,
+    completion: Arc<GpuTimeline>,
+    clock: Mutex<u64>,
+}
+
+impl MockCommandQueue {
+    pub(super) fn new(ty: CommandBufferType, debug: DebugFlags) -> Self {
+        let timeline = Arc::new(MockTimeline::default());
+
+        Self {
+            ty,
+            streams: debug.contains(DebugFlags::CommandStream),
+            in_record: Default::default(),
+            pending: Default::default(),
+            waits: Default::default(),
+            shared_waits: Default::default(),
+            submissions: Default::default(),
+            signals: Default::default(),
+            value: Default::default(),
+            completion: GpuTimeline::new(timeline.clone()),
+            timeline,
+            clock: Default::default(),
+        }
+    }
+
+    pub(super) fn create_command_buffer(&self, handles: Arc<HandleStorage>) -> MockCommandBuffer {
+        if let Some(buffer) = self.in_record.lock().pop() {
+            return buffer;
+        };
+
+        self.allocate_command_buffer(handles)
+    }
+
+    /// Always returns an empty buffer, ignoring stashed ones.
+    pub(super) fn allocate_command_buffer(&self, handles: Arc<HandleStorage>) -> MockCommandBuffer {
+        MockCommandBuffer {
+            ty: self.ty,
+            commands: Default::default(),
+            scopes: Default::default(),
+            stream: (self.streams || handles.capture.lock().is_some()).then(Default::default),
+            validation: handles
+                .validator
+                .as_ref()
+                .map(|v| Mutex::new(CommandValidator::new(Arc::clone(v), self.ty))),
+            handles,
+        }
+    }
+
+    pub(super) fn stash_cmd_buffer(&self, cmd_buffer: MockCommandBuffer) {
+        self.in_record.lock().push(cmd_buffer);
+    }
+
+    pub(super) fn push_cmd_buffer(&self, cmd_buffer: MockCommandBuffer) {
+        if let Some(validation) = &cmd_buffer.validation {
+            validation.lock().finish();
+        }
+
+        let scopes = cmd_buffer.scopes.lock().take();
+        if !scopes.is_empty() {
+            cmd_buffer.handles.profiler.lock().submit(scopes);
+        }
+
+        self.pending.lock().push(cmd_buffer);
+    }
+
+    /// Queues the pending buffers behind the waits added since the last commit.
+    /// Nothing runs until [`MockRenderContext::flush`] reaches the returned sync point.
+    pub(super) fn commit(&self) -> FenceValue {
+        let sync_point = self.value.fetch_add(1, Ordering::Relaxed) + 1;
+
+        self.submissions.lock().push_back(MockSubmission {
+            sync_point,
+            waits: self.waits.lock().drain(..).collect(),
+            shared_waits: self.shared_waits.lock().drain(..).collect(),
+            cmd_buffers: self.pending.lock().drain(..).collect(),
+        });
+
+        sync_point
+    }
+
+    pub(super) fn wait(&self, ty: CommandBufferType, sync_point: FenceValue) {
+        self.waits.lock().push((ty, sync_point));
+    }
+
+    pub(super) fn wait_shared(&self, fence: &MockSharedFence, value: FenceValue) {
+        self.shared_waits.lock().push((fence.clone(), value));
+    }
+
+    /// Sets `fence` to `value` once everything committed so far has run.
+    pub(super) fn signal_shared(&self, fence: &MockSharedFence, value: FenceValue) {
+        let sync_point = self.committed();
+        if self.completed() >= sync_point {
+            fence.signal(value);
+        } else {
+            self.signals.lock().push((sync_point, fence.clone(), value));
+        }
+    }
+
+    pub(super) fn committed(&self) -> FenceValue {
+        self.value.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn completed(&self) -> FenceValue {
+        self.timeline.completed()
+    }
+
+    fn execute(&self, submission: MockSubmission) {
+        let mut clock = self.clock.lock();
+
+        for buffer in submission.cmd_buffers {
+            for command in buffer.commands.into_inner() {
+                if command.advances_clock() {
+                    *clock += 1;
+                }
+
+                command.execute(*clock);
+            }
+        }
+
+        self.timeline.advance(submission.sync_point);
+
+        self.signals.lock().retain(|(sync_point, fence, value)| {
+            if *sync_point > submission.sync_point {
+                return true;
+            }
+
+            fence.signal(*value);
+            false
+        });
+    }
+}
+
+impl MockRenderContext {
+    fn queue(&self, ty: CommandBufferType) -> &MockCommandQueue {
+        match ty {
+            CommandBufferType::Graphics => &self.gfx_queue,
+            CommandBufferType::Compute => &self.compute_queue,
+            CommandBufferType::Transfer => &self.transfer_queue,
+        }
+    }
+
+    /// Runs the work committed to `ty` up to `sync_point`, after the work of
+    /// every queue it waits for.
+    ///
+    /// The graphics queue is flushed on every commit, compute and transfer queues
+    /// only when something waits for them, so a missing wait shows up as stale data.
+    pub(super) fn flush(&self, ty: CommandBufferType, sync_point: FenceValue) {
+        let queue = self.queue(ty);
+        debug_assert!(
+            sync_point <= queue.committed(),
+            "waiting for a sync point that was never committed"
+        );
+
+        while queue.completed() < sync_point {
+            let Some((waits, signaled)) = queue.submissions.lock().front().map(|s| {
+                let signaled = s
+                    .shared_waits
+                    .iter()
+                    .all(|(fence, value)| fence.value() >= *value);
+
+                (s.waits.clone(), signaled)
+            }) else {
+                return;
+            };
+
+            // Other devices run on their own, work waiting on them stays queued
+            // until a later flush finds the fence signaled.
+            if !signaled {
+                return;
+            }
+
+            // Waits only name committed sync points, so this can not cycle back to `queue`.
+            for (other, value) in waits {
+                self.flush(other, value);
+            }
+
+            let submission = queue.submissions.lock().pop_front().expect("unreachable");
+            queue.execute(submission);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct MockCommandBuffer {
+    pub(super) handles: Arc<HandleStorage>,
+    pub(super) ty: CommandBufferType,
+    pub(super) commands: Mutex<Vec<MockCommand>>,
+    pub(super) scopes: Mutex<ScopeStack>,
+    pub(super) stream: Option<Mutex<CommandStream>>,
+    /// Set with [`DebugFlags::CpuValidation`], checks handle based work before it is recorded.
+    pub(super) validation: Option<Mutex<CommandValidator>>,
+}
+
+impl MockCommandBuffer {
+    /// Every command recorded so far, with debug groups and markers in place.
+    pub fn log(&self) -> Vec<MockLogEntry> {
+        self.commands.lock().iter().map(|c| c.log_entry()).collect()
+    }
+
+    fn push_debug(&self, command: MockCommand) {
+        self.record(|| match &command {
+            MockCommand::PushDebugGroup(name) => StreamCommand::PushDebugGroup(name.clone()),
+            MockCommand::Marker(name) => StreamCommand::Marker(name.clone()),
+            _ => StreamCommand::PopDebugGroup,
+        });
+        self.commands.lock().push(command);
+    }
+
+    /// Mirrors a command into the stream, built only if the buffer records one.
+    fn record(&self, command: impl FnOnce() -> StreamCommand) {
+        if let Some(stream) = &self.stream {
+            stream.lock().push(command());
+        }
+    }
+
+    fn validate(&self, command: impl FnOnce() -> StreamCommand) {
+        if let Some(validation) = &self.validation {
+            validation.lock().validate(&command());
+        }
+    }
+
+    fn begin_encoder(&self, encoder: &'static str) {
+        if let Some(validation) = &self.validation {
+            validation.lock().begin_encoder(encoder);
+        }
+    }
+
+    /// Draws recorded so far, in submission order. Indirect draws are expanded
+    /// from the current contents of their argument and count buffers.
+    pub fn draws(&self) -> Vec<MockDraw> {
+        self.commands
+            .lock()
+            .iter()
+            .flat_map(|c| match c {
+                MockCommand::Draw(draw) => vec![draw.clone()],
+                MockCommand::DrawIndirect {
+                    args,
+                    offset,
+                    count,
+                    draw,
+                } => {
+                    let (args, count) = (args.lock(), count.get());
+
+                    if draw.indexed {
+                        indirect::read_args::<DrawIndexedIndirectArgs>(&args, *offset, count)
+                            .into_iter()
+                            .map(|a| MockDraw {
+                                vertices: a.first_index..(a.first_index + a.index_count),
+                                instances: a.first_instance..(a.first_instance + a.instance_count),
+                                base_vertex: a.base_vertex,
+                                ..draw.clone()
+                            })
+                            .collect()
+                    } else {
+                        indirect::read_args::<DrawIndirectArgs>(&args, *offset, count)
+                            .into_iter()
+                            .map(|a| MockDraw {
+                                vertices: a.first_vertex..(a.first_vertex + a.vertex_count),
+                                instances: a.first_instance..(a.first_instance + a.instance_count),
+                                ..draw.clone()
+                            })
+                            .collect()
+                    }
+                }
+                _ => vec![],
+            })
+            .collect()
+    }
+
+    /// Dispatches recorded so far. Indirect group counts are read from the
+    /// argument buffer at the time of the call.
+    pub fn dispatches(&self) -> Vec<MockDispatch> {
+        self.commands
+            .lock()
+            .iter()
+            .filter_map(|c| match c {
+                MockCommand::Dispatch(dispatch) => Some(dispatch.clone()),
+                MockCommand::DispatchIndirect {
+                    args,
+                    offset,
+                    dispatch,
+                } => {
+                    let args =
+                        indirect::read_args::<DispatchIndirectArgs>(&args.lock(), *offset, 1);
+
+                    Some(MockDispatch {
+                        groups: [args[0].x, args[0].y, args[0].z],
+                        ..dispatch.clone()
+                    })
+                }
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl CommandDevice for MockRenderContext {
+    type CommandBuffer = MockCommandBuffer;
+    type SharedFence = MockSharedFence;
+    type Bundle = MockBundle;
+    type BundleEncoder = MockBundleEncoder;
+
+    fn tag(&self) -> DeviceTag {
+        self.tag
+    }
+
+    fn create_command_buffer(&self, ty: CommandBufferType) -> Self::CommandBuffer {
+        match ty {
+            CommandBufferType::Graphics => self
+                .gfx_queue
+                .create_command_buffer(Arc::clone(&self.handles)),
+            CommandBufferType::Compute => self
+                .compute_queue
+                .create_command_buffer(Arc::clone(&self.handles)),
+            CommandBufferType::Transfer => self
+                .transfer_queue
+                .create_command_buffer(Arc::clone(&self.handles)),
+        }
+    }
+
+    fn allocate_command_buffer(&self, ty: CommandBufferType) -> Self::CommandBuffer {
+        self.queue(ty)
+            .allocate_command_buffer(Arc::clone(&self.handles))
+    }
+
+    fn stash_cmd_buffer(&self, cmd: Self::CommandBuffer) {
+        match cmd.ty {
+            CommandBufferType::Graphics => self.gfx_queue.stash_cmd_buffer(cmd),
+            CommandBufferType::Compute => self.compute_queue.stash_cmd_buffer(cmd),
+            CommandBufferType::Transfer => self.transfer_queue.stash_cmd_buffer(cmd),
+        }
+    }
+
+    fn push_cmd_buffer(&self, cmd: Self::CommandBuffer) {
+        if let Some(capture) = self.handles.capture.lock().as_mut() {
+            match &cmd.stream {
+                Some(stream) => capture.submit(cmd.ty, stream.lock().clone()),
+                None => warn!("Command buffer recorded before the capture began is not captured"),
+            }
+        }
+
+        match cmd.ty {
+            CommandBufferType::Graphics => self.gfx_queue.push_cmd_buffer(cmd),
+            CommandBufferType::Compute => self.compute_queue.push_cmd_buffer(cmd),
+            CommandBufferType::Transfer => self.transfer_queue.push_cmd_buffer(cmd),
+        }
+    }
+
+    fn commit(&self, ty: CommandBufferType) -> SyncPoint {
+        let value = match ty {
+            CommandBufferType::Graphics => {
+                self.resolve_profiler();
+                let value = self.gfx_queue.commit();
+                self.flush(CommandBufferType::Graphics, value);
+
+                self.handles.residency.lock().advance(value);
+                self.trim_residency(0);
+                self.collect_profiler(value);
+                self.end_capture_frame();
+
+                if self.debug.contains(DebugFlags::MemoryReport) {
+                    self.memory_report().log();
+                }
+
+                value
+            }
+            CommandBufferType::Compute => self.compute_queue.commit(),
+            CommandBufferType::Transfer => self.transfer_queue.commit(),
+        };
+
+        SyncPoint::new(self.tag, ty, value)
+    }
+
+    fn wait_cpu(&self, time: SyncPoint) {
+        self.flush(time.ty(), time.value_on(self.tag));
+    }
+
+    fn future(&self, time: SyncPoint) -> GpuFuture {
+        let queue = self.queue(time.ty());
+        debug_assert!(
+            time.value_on(self.tag) <= queue.committed(),
+            "waiting for a sync point that was never committed"
+        );
+
+        queue.completion.future(time)
+    }
+
+    fn wait_gpu(&self, waiting: CommandBufferType, time: SyncPoint) {
+        let value = time.value_on(self.tag);
+        debug_assert!(
+            value <= self.queue(time.ty()).committed(),
+            "waiting for a sync point that was never committed"
+        );
+
+        if waiting != time.ty() {
+            self.queue(waiting).wait(time.ty(), value);
+        }
+    }
+
+    fn bundle_encoder(&self, desc: BundleDesc) -> Self::BundleEncoder {
+        MockBundleEncoder {
+            handles: Arc::clone(&self.handles),
+            state: Mutex::new(MockRenderState {
+                viewport: Viewport::new(0.0, 0.0),
+                scissor: Scissor::new(0, 0),
+                stencil_reference: 0,
+                blend_constants: [0.0; 4],
+                has_pipeline: false,
+                vertex_buffers: vec![],
+                index_buffer: false,
+                color_formats: desc.color_formats.clone(),
+                depth_format: desc.depth_format,
+            }),
+            desc,
+            commands: Default::default(),
+            references: Default::default(),
+        }
+    }
+
+    fn finish_bundle(&self, encoder: Self::BundleEncoder) -> Self::Bundle {
+        let validity = self
+            .handles
+            .bundles
+            .lock()
+            .register(encoder.references.into_inner());
+
+        MockBundle {
+            desc: encoder.desc,
+            commands: encoder.commands.into_inner(),
+            validity,
+        }
+    }
+
+    fn create_shared_fence(&self) -> Self::SharedFence {
+        MockSharedFence::default()
+    }
+
+    fn open_shared_fence(&self, fence: &Self::SharedFence, _owner: &Self) -> Self::SharedFence {
+        fence.clone()
+    }
+
+    fn signal_shared(&self, ty: CommandBufferType, fence: &Self::SharedFence, value: FenceValue) {
+        let queue = self.queue(ty);
+        queue.signal_shared(fence, value);
+
+        // Another device is waiting for this, so the signaling work has to run.
+        self.flush(ty, queue.committed());
+    }
+
+    fn wait_shared(&self, ty: CommandBufferType, fence: &Self::SharedFence, value: FenceValue) {
+        self.queue(ty).wait_shared(fence, value);
+    }
+}
+
+impl CommandBuffer for MockCommandBuffer {
+    type Buffer = MockBuffer;
+    type Texture = MockTexture;
+    type QuerySet = MockQuerySet;
+
+    type RenderEncoder<'a> = MockRenderEncoder<'a>;
+    type ComputeEncoder<'a> = MockComputeEncoder<'a>;
+    type TransferEncoder<'a> = MockTransferEncoder<'a>;
+
+    fn render_encoder(&mut self, pass: &RenderPassDesc) -> Self::RenderEncoder<'_> {
+        let mut extent = None;
+        let (mut color_formats, mut depth_format) = (vec![], None);
+
+        let begin = || StreamCommand::BeginRenderPass {
+            colors: pass
+                .colors
+                .iter()
+                .map(|a| (StreamRef::Texture(a.texture), a.load))
+                .collect(),
+            depth: pass
+                .depth_stencil
+                .as_ref()
+                .map(|a| (StreamRef::Texture(a.texture), a.depth_load)),
+            views: pass
+                .buffer_views
+                .iter()
+                .map(|&v| StreamRef::BufferView(v))
+                .collect(),
+        };
+        self.validate(begin);
+        self.use_pass_buffer_views(&pass.buffer_views);
+        self.record(begin);
+
+        {
+            let guard = self.handles.textures.lock();
+            let mut commands = self.commands.lock();
+
+            let colors = pass
+                .colors
+                .iter()
+                .map(|a| (a.texture, ResourceState::RenderTarget, a.load));
+            let depth = pass.depth_stencil.iter().map(|a| {
+                let load = match a.depth_load {
+                    LoadOp::Clear(depth) => LoadOp::Clear([depth, 0.0, 0.0, 0.0]),
+                    LoadOp::Load => LoadOp::Load,
+                    LoadOp::DontCare => LoadOp::DontCare,
+                };
+
+                (a.texture, ResourceState::DepthWrite, load)
+            });
+
+            for (handle, state, load) in colors.chain(depth) {
+                let Some(texture) = guard.get(handle) else {
+                    cold_path();
+                    continue;
+                };
+
+                self.handles.touch_texture(handle, texture);
+                let before = texture.state.lock().uniform_state();
+                self.acquire(texture.stream_ref(), &texture.owner, before, state);
+                texture.state.lock().transition(None, state);
+                extent.get_or_insert((texture.desc.width, texture.desc.height));
+
+                match state {
+                    ResourceState::DepthWrite => depth_format = Some(texture.desc.format),
+                    _ => color_formats.push(texture.desc.format),
+                }
+
+                if let LoadOp::Clear(color) = load {
+                    match mips::encode_texel(texture.desc.format, color) {
+                        Some(texel) => commands.push(MockCommand::Clear {
+                            dst: Arc::clone(&texture.data),
+                            texel,
+                        }),
+                        None => error!(
+                            "Clear is not supported for format {:?}",
+                            texture.desc.format
+                        ),
+                    }
+                }
+            }
+        }
+
+        let (width, height) = extent.unwrap_or_default();
+
+        MockRenderEncoder {
+            cmd_buffer: self,
+            state: Mutex::new(MockRenderState {
+                viewport: Viewport::new(width as f32, height as f32),
+                scissor: Scissor::new(width, height),
+                stencil_reference: 0,
+                blend_constants: [0.0; 4],
+                has_pipeline: false,
+                vertex_buffers: vec![],
+                index_buffer: false,
+                color_formats,
+                depth_format,
+            }),
+        }
+    }
+
+    fn compute_encoder(&mut self) -> Self::ComputeEncoder<'_> {
+        debug_assert_ne!(
+            self.ty,
+            CommandBufferType::Transfer,
+            "compute can not be recorded on a transfer command buffer"
+        );
+
+        self.begin_encoder("compute encoder");
+
+        MockComputeEncoder {
+            cmd_buffer: self,
+            state: Default::default(),
+        }
+    }
+
+    fn transfer_encoder(&mut self) -> Self::TransferEncoder<'_> {
+        self.begin_encoder("transfer encoder");
+
+        MockTransferEncoder { cmd_buffer: self }
+    }
+
+    fn set_barriers(
+        &self,
+        buffers: &[BufferBarrier<'_, Self::Buffer>],
+        textures: &[TextureBarrier<'_, Self::Texture>],
+    ) {
+        for barrier in buffers {
+            self.validate(|| StreamCommand::Barrier {
+                resource: barrier.buffer.stream_ref(),
+                range: None,
+                state: barrier.state,
+            });
+        }
+
+        for barrier in textures {
+            self.validate(|| StreamCommand::Barrier {
+                resource: barrier.texture.stream_ref(),
+                range: barrier.range.clone(),
+                state: barrier.state,
+            });
+        }
+
+        self.transition(buffers, textures);
+    }
+
+    /// Transitions the backend makes for validated work skip the validation layer.
+    fn use_resources(&self, usages: &ResourceUsages<'_, Self::Buffer, Self::Texture>) {
+        if !usages.is_empty() {
+            self.transition(&usages.buffers, &usages.textures);
+        }
+    }
+
+    fn generate_mips(&mut self, handle: RenderHandle<Texture>) {
+        debug_assert_ne!(
+            self.ty,
+            CommandBufferType::Transfer,
+            "mips can not be generated on a transfer command buffer"
+        );
+
+        self.validate(|| StreamCommand::GenerateMips(StreamRef::Texture(handle)));
+        let guard = self.handles.textures.lock();
+
+        let Some(texture) = guard.get(handle) else {
+            cold_path();
+            return;
+        };
+        self.handles.touch_texture(handle, texture);
+        let before = texture.state.lock().uniform_state();
+        self.acquire(
+            texture.stream_ref(),
+            &texture.owner,
+            before,
+            ResourceState::Sampled,
+        );
+
+        self.record(|| StreamCommand::GenerateMips(StreamRef::Texture(handle)));
+        self.commands.lock().push(MockCommand::GenerateMips {
+            desc: texture.desc.clone(),
+            data: Arc::clone(&texture.data),
+        });
+        texture
+            .state
+            .lock()
+            .transition(None, ResourceState::Sampled);
+    }
+
+    fn release_buffer(&self, handle: RenderHandle<Buffer>, to: CommandBufferType) {
+        self.validate(|| StreamCommand::Release {
+            resource: StreamRef::Buffer(handle),
+            queue: to,
+        });
+
+        let guard = self.handles.buffers.lock();
+        let Some(buffer) = guard.get(handle) else {
+            cold_path();
+            return;
+        };
+
+        if !ownership::supports(to, buffer.state()) {
+            self.transition(
+                &[BufferBarrier {
+                    buffer,
+                    state: ResourceState::Common,
+                }],
+                &[],
+            );
+        }
+        self.release(buffer.stream_ref(), &buffer.owner, to);
+    }
+
+    fn release_texture(&self, handle: RenderHandle<Texture>, to: CommandBufferType) {
+        self.validate(|| StreamCommand::Release {
+            resource: StreamRef::Texture(handle),
+            queue: to,
+        });
+
+        let guard = self.handles.textures.lock();
+        let Some(texture) = guard.get(handle) else {
+            cold_path();
+            return;
+        };
+
+        let state = texture.state.lock().uniform_state();
+        if !state.is_some_and(|state| ownership::supports(to, state)) {
+            self.transition(&[], &[TextureBarrier::new(texture, ResourceState::Common)]);
+        }
+        self.release(texture.stream_ref(), &texture.owner, to);
+    }
+
+    fn write_timestamp(&self, set: &Self::QuerySet, index: u32) -> Result<(), QueryError> {
+        query::validate_query(&set.desc, QueryType::Timestamp, index, self.ty)?;
+        self.push_timestamp(set, index);
+
+        Ok(())
+    }
+
+    fn begin_query(&self, set: &Self::QuerySet, index: u32) -> Result<(), QueryError> {
+        query::validate_query(&set.desc, QueryType::PipelineStatistics, index, self.ty)
+    }
+
+    fn end_query(&self, set: &Self::QuerySet, index: u32) -> Result<(), QueryError> {
+        query::validate_query(&set.desc, QueryType::PipelineStatistics, index, self.ty)
+    }
+
+    fn resolve_queries(
+        &self,
+        set: &Self::QuerySet,
+        queries: Range<u32>,
+        dst: &Self::Buffer,
+        offset: usize,
+    ) -> Result<(), QueryError> {
+        query::validate_resolve(&set.desc, &queries, &dst.desc, offset, self.ty)?;
+        *dst.state.lock() = ResourceState::CopyDst;
+
+        let size = set.desc.ty.result_size();
+        self.commands.lock().push(MockCommand::CopyRegion {
+            dst: Arc::clone(&dst.data),
+            src: Arc::clone(&set.data),
+            rows: vec![(offset, queries.start as usize * size)],
+            row_size: queries.len() * size,
+        });
+
+        Ok(())
+    }
+
+    fn begin_scope(&self, name: &'static str) {
+        let begin = self.profiler_timestamp();
+        self.scopes.lock().push(name, begin);
+    }
+
+    fn end_scope(&self) {
+        let end = self.profiler_timestamp();
+        self.scopes.lock().pop(end);
+    }
+
+    fn command_stream(&self) -> Option<CommandStream> {
+        self.stream.as_ref().map(|stream| stream.lock().clone())
+    }
+}
+
+impl MockCommandBuffer {
+    /// Transitions the buffer behind a bound view for the view type, views
+    /// created without a handle are left as they are.
+    fn use_buffer_view(&self, view: &MockBufferView) {
+        let Some(handle) = view.buffer else {
+            return;
+        };
+        let guard = self.handles.buffers.lock();
+        let Some(buffer) = guard.get(handle) else {
+            cold_path();
+            return;
+        };
+
+        self.use_resources(
+            ResourceUsages::default().use_buffer(buffer, view.desc().ty.resource_state()),
+        );
+    }
+
+    /// Transitions the buffers behind the views of a render pass before it starts.
+    fn use_pass_buffer_views(&self, handles: &[RenderHandle<BufferView>]) {
+        let views = self.handles.buffer_views.lock();
+        let buffers = self.handles.buffers.lock();
+        let barriers = handles
+            .iter()
+            .filter_map(|&handle| {
+                let Some(view) = views.get(handle) else {
+                    cold_path();
+                    return None;
+                };
+
+                Some(BufferBarrier {
+                    buffer: buffers.get(view.buffer?)?,
+                    state: view.desc().ty.resource_state(),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        self.transition(&barriers, &[]);
+    }
+
+    /// Render passes record no barriers, a view bound inside one must have been
+    /// listed in [`RenderPassDesc::buffer_views`].
+    fn check_pass_buffer_view(&self, view: &MockBufferView) {
+        let Some(handle) = view.buffer else {
+            return;
+        };
+        let guard = self.handles.buffers.lock();
+        let Some(buffer) = guard.get(handle) else {
+            cold_path();
+            return;
+        };
+
+        let state = view.desc().ty.resource_state();
+        if buffer.state() != state {
+            error!(
+                "Buffer view {} is not listed in its render pass, the buffer is in {:?} instead of {:?}",
+                view.stream_ref(),
+                buffer.state(),
+                state
+            );
+        }
+    }
+
+    fn transition(
+        &self,
+        buffers: &[BufferBarrier<'_, MockBuffer>],
+        textures: &[TextureBarrier<'_, MockTexture>],
+    ) {
+        for barrier in buffers {
+            let before = std::mem::replace(&mut *barrier.buffer.state.lock(), barrier.state);
+            self.acquire(
+                barrier.buffer.stream_ref(),
+                &barrier.buffer.owner,
+                Some(before),
+                barrier.state,
+            );
+
+            if before != barrier.state {
+                self.record(|| StreamCommand::Barrier {
+                    resource: barrier.buffer.stream_ref(),
+                    range: None,
+                    state: barrier.state,
+                });
+            }
+        }
+
+        for barrier in textures {
+            let before = barrier.texture.state.lock().uniform_state();
+            self.acquire(
+                barrier.texture.stream_ref(),
+                &barrier.texture.owner,
+                before,
+                barrier.state,
+            );
+
+            let transitions = barrier
+                .texture
+                .state
+                .lock()
+                .transition(barrier.range.clone(), barrier.state);
+
+            if !transitions.is_empty() {
+                self.record(|| StreamCommand::Barrier {
+                    resource: barrier.texture.stream_ref(),
+                    range: barrier.range.clone(),
+                    state: barrier.state,
+                });
+            }
+        }
+    }
+
+    /// Takes a resource used in `after` for the queue of this buffer, `before`
+    /// being its state before the use.
+    fn acquire(
+        &self,
+        resource: StreamRef,
+        owner: &Mutex<QueueOwnership>,
+        before: Option<ResourceState>,
+        after: ResourceState,
+    ) {
+        if let Err(error) = owner.lock().acquire(self.ty, before) {
+            self.ownership_error(resource, error);
+        }
+
+        if !ownership::supports(self.ty, after) {
+            self.ownership_error(
+                resource,
+                OwnershipError::UnsupportedState {
+                    state: after,
+                    queue: self.ty,
+                },
+            );
+        }
+    }
+
+    fn release(&self, resource: StreamRef, owner: &Mutex<QueueOwnership>, to: CommandBufferType) {
+        if let Err(error) = owner.lock().release(self.ty, to) {
+            self.ownership_error(resource, error);
+        }
+
+        self.record(|| StreamCommand::Release {
+            resource,
+            queue: to,
+        });
+    }
+
+    /// Handoffs are tracked on every device, the validation layer only adds the
+    /// name of the resource.
+    fn ownership_error(&self, resource: StreamRef, error: OwnershipError) {
+        match &self.handles.validator {
+            Some(validator) => validator.report_ownership(resource, error),
+            None => error!("Invalid queue handoff of {}: {}", resource, error),
+        }
+    }
+
+    fn push_timestamp(&self, set: &MockQuerySet, index: u32) {
+        self.commands.lock().push(MockCommand::WriteTimestamp {
+            dst: Arc::clone(&set.data),
+            offset: index as usize * QueryType::Timestamp.result_size(),
+        });
+    }
+
+    /// Only graphics buffers are timed, their clock is the one read back by the profiler.
+    fn profiler_timestamp(&self) -> Option<u32> {
+        if self.ty != CommandBufferType::Graphics {
+            return None;
+        }
+
+        let mut profiler = self.handles.profiler.lock();
+        let (set, index) = profiler.allocate()?;
+        self.push_timestamp(set, index);
+
+        Some(index)
+    }
+}
+
+#[derive(Debug)]
+struct MockRenderState {
+    viewport: Viewport,
+    scissor: Scissor,
+    stencil_reference: u32,
+    blend_constants: [f32; 4],
+    has_pipeline: bool,
+    vertex_buffers: Vec<u32>,
+    index_buffer: bool,
+    color_formats: Vec<Format>,
+    depth_format: Option<Format>,
+}
+
+impl MockRenderState {
+    fn draw(
+        &self,
+        vertices: Range<u32>,
+        instances: Range<u32>,
+        base_vertex: i32,
+        indexed: bool,
+        indirect: bool,
+    ) -> MockDraw {
+        debug_assert!(self.has_pipeline, "draw without a pipeline");
+        debug_assert!(
+            !indexed || self.index_buffer,
+            "indexed draw without an index buffer"
+        );
+
+        MockDraw {
+            vertices,
+            instances,
+            base_vertex,
+            indexed,
+            indirect,
+            viewport: self.viewport,
+            scissor: self.scissor,
+            stencil_reference: self.stencil_reference,
+            blend_constants: self.blend_constants,
+            vertex_buffers: self.vertex_buffers.clone(),
+        }
+    }
+
+    /// Validates an indirect draw and builds its command, along with the states
+    /// its argument buffers have to be in.
+    fn indirect_draw<'b, T: Pod>(
+        &self,
+        args: &'b MockBuffer,
+        offset: usize,
+        count: (Option<(&'b MockBuffer, usize)>, u32),
+        indexed: bool,
+    ) -> Result<(MockCommand, ResourceUsages<'b, MockBuffer, MockTexture>), IndirectError> {
+        let (count_buffer, max) = count;
+        indirect::validate::<T>(&args.desc, offset, max)?;
+
+        let mut usages = ResourceUsages::default();
+        usages.use_buffer(args, ResourceState::Indirect);
+
+        let count = match count_buffer {
+            Some((buffer, count_offset)) => {
+                indirect::validate::<u32>(&buffer.desc, count_offset, 1)?;
+                usages.use_buffer(buffer, ResourceState::Indirect);
+
+                MockDrawCount::Buffer {
+                    data: Arc::clone(&buffer.data),
+                    offset: count_offset,
+                    max,
+                }
+            }
+            None => MockDrawCount::Fixed(max),
+        };
+
+        let command = MockCommand::DrawIndirect {
+            args: Arc::clone(&args.data),
+            offset,
+            count,
+            draw: self.draw(0..0, 0..0, 0, indexed, true),
+        };
+
+        Ok((command, usages))
+    }
+}
+
+pub struct MockRenderEncoder<'a> {
+    cmd_buffer: &'a mut MockCommandBuffer,
+    state: Mutex<MockRenderState>,
+}
+
+impl<'a> MockRenderEncoder<'a> {
+    fn draw_state(
+        &self,
+        vertices: Range<u32>,
+        instances: Range<u32>,
+        base_vertex: i32,
+        indexed: bool,
+        indirect: bool,
+    ) -> MockDraw {
+        self.state
+            .lock()
+            .draw(vertices, instances, base_vertex, indexed, indirect)
+    }
+
+    fn push_indirect<T: Pod>(
+        &self,
+        args: &MockBuffer,
+        offset: usize,
+        count: (Option<(&MockBuffer, usize)>, u32),
+        indexed: bool,
+    ) -> Result<(), IndirectError> {
+        let (command, usages) = self
+            .state
+            .lock()
+            .indirect_draw::<T>(args, offset, count, indexed)?;
+
+        self.cmd_buffer.use_resources(&usages);
+        self.cmd_buffer.record(|| StreamCommand::DrawIndirect {
+            args: args.stream_ref(),
+            offset,
+            count: count
+                .0
+                .map(|(buffer, offset)| (buffer.stream_ref(), offset)),
+            max_draws: count.1,
+            indexed,
+        });
+        self.cmd_buffer.commands.lock().push(command);
+
+        Ok(())
+    }
+}
+
+impl<'a> RenderEncoder for MockRenderEncoder<'a> {
+    type Buffer = MockBuffer;
+    type BufferView = MockBufferView;
+    type Pipeline = MockPipeline<RenderPipeline, RenderPipelineDesc>;
+    type Bundle = MockBundle;
+
+    fn set_viewport(&self, viewport: Viewport) {
+        self.cmd_buffer
+            .record(|| StreamCommand::SetViewport(viewport));
+        self.state.lock().viewport = viewport;
+    }
+
+    fn set_scissor(&self, scissor: Scissor) {
+        self.cmd_buffer
+            .record(|| StreamCommand::SetScissor(scissor));
+        self.state.lock().scissor = scissor;
+    }
+
+    fn set_stencil_reference(&self, reference: u32) {
+        self.cmd_buffer
+            .record(|| StreamCommand::SetStencilReference(reference));
+        self.state.lock().stencil_reference = reference;
+    }
+
+    fn set_blend_constants(&self, constants: [f32; 4]) {
+        self.cmd_buffer
+            .record(|| StreamCommand::SetBlendConstants(constants));
+        self.state.lock().blend_constants = constants;
+    }
+
+    fn set_pipeline(&self, pipeline: &Self::Pipeline) {
+        self.cmd_buffer
+            .record(|| StreamCommand::SetPipeline(pipeline.stream_ref()));
+        self.state.lock().has_pipeline = true;
+    }
+
+    fn set_vertex_buffer(&self, slot: u32, view: &Self::BufferView) {
+        debug_assert_eq!(view.desc().ty, BufferViewType::Vertex);
+        self.cmd_buffer.check_pass_buffer_view(view);
+        self.cmd_buffer.record(|| StreamCommand::SetVertexBuffer {
+            slot,
+            view: view.stream_ref(),
+        });
+
+        let mut state = self.state.lock();
+        state.vertex_buffers.retain(|s| *s != slot);
+        state.vertex_buffers.push(slot);
+    }
+
+    fn set_index_buffer(&self, view: &Self::BufferView) {
+        debug_assert_eq!(view.desc().ty, BufferViewType::Index);
+        self.cmd_buffer.check_pass_buffer_view(view);
+        self.cmd_buffer
+            .record(|| StreamCommand::SetIndexBuffer(view.stream_ref()));
+        self.state.lock().index_buffer = true;
+    }
+
+    fn draw_instanced(&self, vertices: Range<u32>, instances: Range<u32>) {
+        let draw = self.draw_state(vertices, instances, 0, false, false);
+        self.cmd_buffer.record(|| StreamCommand::Draw {
+            vertices: draw.vertices.clone(),
+            instances: draw.instances.clone(),
+            base_vertex: None,
+        });
+        self.cmd_buffer
+            .commands
+            .lock()
+            .push(MockCommand::Draw(draw));
+    }
+
+    fn draw_indexed_instanced(&self, indices: Range<u32>, base_vertex: i32, instances: Range<u32>) {
+        let draw = self.draw_state(indices, instances, base_vertex, true, false);
+        self.cmd_buffer.record(|| StreamCommand::Draw {
+            vertices: draw.vertices.clone(),
+            instances: draw.instances.clone(),
+            base_vertex: Some(base_vertex),
+        });
+        self.cmd_buffer
+            .commands
+            .lock()
+            .push(MockCommand::Draw(draw));
+    }
+
+    fn draw_indirect(
+        &self,
+        args: &Self::Buffer,
+        offset: usize,
+        draw_count: u32,
+    ) -> Result<(), IndirectError> {
+        self.push_indirect::<DrawIndirectArgs>(args, offset, (None, draw_count), false)
+    }
+
+    fn draw_indexed_indirect(
+        &self,
+        args: &Self::Buffer,
+        offset: usize,
+        draw_count: u32,
+    ) -> Result<(), IndirectError> {
+        self.push_indirect::<DrawIndexedIndirectArgs>(args, offset, (None, draw_count), true)
+    }
+
+    fn draw_indirect_count(
+        &self,
+        args: &Self::Buffer,
+        offset: usize,
+        count: &Self::Buffer,
+        count_offset: usize,
+        max_draws: u32,
+    ) -> Result<(), IndirectError> {
+        self.push_indirect::<DrawIndirectArgs>(
+            args,
+            offset,
+            (Some((count, count_offset)), max_draws),
+            false,
+        )
+    }
+
+    fn draw_indexed_indirect_count(
+        &self,
+        args: &Self::Buffer,
+        offset: usize,
+        count: &Self::Buffer,
+        count_offset: usize,
+        max_draws: u32,
+    ) -> Result<(), IndirectError> {
+        self.push_indirect::<DrawIndexedIndirectArgs>(
+            args,
+            offset,
+            (Some((count, count_offset)), max_draws),
+            true,
+        )
+    }
+
+    fn execute_bundle(&self, bundle: &Self::Bundle) -> Result<(), BundleError> {
+        if !bundle.validity.is_valid() {
+            return Err(BundleError::Invalidated);
+        }
+
+        let state = self.state.lock();
+        bundle::validate_pass(&bundle.desc, &state.color_formats, state.depth_format)?;
+
+        self.cmd_buffer
+            .record(|| StreamCommand::ExecuteBundle(bundle.desc.name.clone()));
+        self.cmd_buffer.commands.lock().extend(
+            bundle
+                .commands
+                .iter()
+                .map(|command| command.replay(state.viewport, state.scissor)),
+        );
+
+        Ok(())
+    }
+}
+
+impl<'a> Drop for MockRenderEncoder<'a> {
+    fn drop(&mut self) {
+        self.cmd_buffer.validate(|| StreamCommand::EndRenderPass);
+        self.cmd_buffer.record(|| StreamCommand::EndRenderPass);
+    }
+}
+
+impl<'a> DebugEncoder for MockRenderEncoder<'a> {
+    fn push_debug_group(&self, name: &str) {
+        self.cmd_buffer
+            .push_debug(MockCommand::PushDebugGroup(name.to_owned()));
+    }
+
+    fn pop_debug_group(&self) {
+        self.cmd_buffer.push_debug(MockCommand::PopDebugGroup);
+    }
+
+    fn insert_marker(&self, name: &str) {
+        self.cmd_buffer
+            .push_debug(MockCommand::Marker(name.to_owned()));
+    }
+}
+
+impl<'a> DynRenderEncoder for MockRenderEncoder<'a> {
+    fn set_pipeline(&self, pipeline: RenderHandle<RenderPipeline>) {
+        self.cmd_buffer
+            .validate(|| StreamCommand::SetPipeline(StreamRef::RenderPipeline(pipeline)));
+        let guard = self.cmd_buffer.handles.render_pipelines.lock();
+
+        let Some(pipeline) = guard.get(pipeline) else {
+            cold_path();
+            return;
+        };
+
+        <Self as RenderEncoder>::set_pipeline(self, pipeline);
+    }
+
+    fn set_vertex_buffer(&self, slot: u32, view: RenderHandle<BufferView>) {
+        self.cmd_buffer.validate(|| StreamCommand::SetVertexBuffer {
+            slot,
+            view: StreamRef::BufferView(view),
+        });
+        let guard = self.cmd_buffer.handles.buffer_views.lock();
+
+        let Some(view) = guard.get(view) else {
+            cold_path();
+            return;
+        };
+
+        <Self as RenderEncoder>::set_vertex_buffer(self, slot, view);
+    }
+
+    fn set_index_buffer(&self, view: RenderHandle<BufferView>) {
+        self.cmd_buffer
+            .validate(|| StreamCommand::SetIndexBuffer(StreamRef::BufferView(view)));
+        let guard = self.cmd_buffer.handles.buffer_views.lock();
+
+        let Some(view) = guard.get(view) else {
+            cold_path();
+            return;
+        };
+
+        <Self as RenderEncoder>::set_index_buffer(self, view);
+    }
+
+    fn draw_indirect(
+        &self,
+        args: RenderHandle<Buffer>,
+        offset: usize,
+        draw_count: u32,
+    ) -> Result<(), IndirectError> {
+        self.cmd_buffer.validate(|| StreamCommand::DrawIndirect {
+            args: StreamRef::Buffer(args),
+            offset,
+            count: None,
+            max_draws: draw_count,
+            indexed: false,
+        });
+        let guard = self.cmd_buffer.handles.buffers.lock();
+
+        let Some(args) = guard.get(args) else {
+            cold_path();
+            return Err(IndirectError::DeadHandle);
+        };
+
+        <Self as RenderEncoder>::draw_indirect(self, args, offset, draw_count)
+    }
+
+    fn draw_indexed_indirect(
+        &self,
+        args: RenderHandle<Buffer>,
+        offset: usize,
+        draw_count: u32,
+    ) -> Result<(), IndirectError> {
+        self.cmd_buffer.validate(|| StreamCommand::DrawIndirect {
+            args: StreamRef::Buffer(args),
+            offset,
+            count: None,
+            max_draws: draw_count,
+            indexed: true,
+        });
+        let guard = self.cmd_buffer.handles.buffers.lock();
+
+        let Some(args) = guard.get(args) else {
+            cold_path();
+            return Err(IndirectError::DeadHandle);
+        };
+
+        <Self as RenderEncoder>::draw_indexed_indirect(self, args, offset, draw_count)
+    }
+
+    fn draw_indirect_count(
+        &self,
+        args: RenderHandle<Buffer>,
+        offset: usize,
+        count: RenderHandle<Buffer>,
+        count_offset: usize,
+        max_draws: u32,
+    ) -> Result<(), IndirectError> {
+        self.cmd_buffer.validate(|| StreamCommand::DrawIndirect {
+            args: StreamRef::Buffer(args),
+            offset,
+            count: Some((StreamRef::Buffer(count), count_offset)),
+            max_draws,
+            indexed: false,
+        });
+        let guard = self.cmd_buffer.handles.buffers.lock();
+
+        let (Some(args), Some(count)) = (guard.get(args), guard.get(count)) else {
+            cold_path();
+            return Err(IndirectError::DeadHandle);
+        };
+
+        <Self as RenderEncoder>::draw_indirect_count(
+            self,
+            args,
+            offset,
+            count,
+            count_offset,
+            max_draws,
+        )
+    }
+
+    fn draw_indexed_indirect_count(
+        &self,
+        args: RenderHandle<Buffer>,
+        offset: usize,
+        count: RenderHandle<Buffer>,
+        count_offset: usize,
+        max_draws: u32,
+    ) -> Result<(), IndirectError> {
+        self.cmd_buffer.validate(|| StreamCommand::DrawIndirect {
+            args: StreamRef::Buffer(args),
+            offset,
+            count: Some((StreamRef::Buffer(count), count_offset)),
+            max_draws,
+            indexed: true,
+        });
+        let guard = self.cmd_buffer.handles.buffers.lock();
+
+        let (Some(args), Some(count)) = (guard.get(args), guard.get(count)) else {
+            cold_path();
+            return Err(IndirectError::DeadHandle);
+        };
+
+        <Self as RenderEncoder>::draw_indexed_indirect_count(
+            self,
+            args,
+            offset,
+            count,
+            count_offset,
+            max_draws,
+        )
+    }
+}
+
+/// Draws recorded once, replayed into any pass with matching attachment formats.
+#[derive(Debug)]
+pub struct MockBundle {
+    desc: BundleDesc,
+    commands: Vec<MockCommand>,
+    validity: BundleValidity,
+}
+
+impl MockBundle {
+    /// Commands replayed on every execution.
+    pub fn log(&self) -> Vec<MockLogEntry> {
+        self.commands.iter().map(|c| c.log_entry()).collect()
+    }
+}
+
+pub struct MockBundleEncoder {
+    handles: Arc<HandleStorage>,
+    desc: BundleDesc,
+    commands: Mutex<Vec<MockCommand>>,
+    state: Mutex<MockRenderState>,
+    references: Mutex<BundleReferences>,
+}
+
+impl MockBundleEncoder {
+    fn push(&self, command: MockCommand) {
+        self.commands.lock().push(command);
+    }
+
+    fn push_indirect<T: Pod>(
+        &self,
+        args: &MockBuffer,
+        offset: usize,
+        count: (Option<(&MockBuffer, usize)>, u32),
+        indexed: bool,
+    ) -> Result<(), IndirectError> {
+        // Bundles can not transition resources, the pass has to.
+        let (command, _) = self
+            .state
+            .lock()
+            .indirect_draw::<T>(args, offset, count, indexed)?;
+        self.push(command);
+
+        Ok(())
+    }
+}
+
+impl RenderEncoder for MockBundleEncoder {
+    type Buffer = MockBuffer;
+    type BufferView = MockBufferView;
+    type Pipeline = MockPipeline<RenderPipeline, RenderPipelineDesc>;
+    type Bundle = MockBundle;
+
+    fn set_viewport(&self, _viewport: Viewport) {
+        warn!("Ignoring a viewport set in a bundle, bundles inherit it from the pass");
+    }
+
+    fn set_scissor(&self, _scissor: Scissor) {
+        warn!("Ignoring a scissor set in a bundle, bundles inherit it from the pass");
+    }
+
+    fn set_stencil_reference(&self, reference: u32) {
+        self.state.lock().stencil_reference = reference;
+    }
+
+    fn set_blend_constants(&self, constants: [f32; 4]) {
+        self.state.lock().blend_constants = constants;
+    }
+
+    fn set_pipeline(&self, _pipeline: &Self::Pipeline) {
+        self.state.lock().has_pipeline = true;
+    }
+
+    fn set_vertex_buffer(&self, slot: u32, view: &Self::BufferView) {
+        debug_assert_eq!(view.desc().ty, BufferViewType::Vertex);
+        let mut state = self.state.lock();
+        state.vertex_buffers.retain(|s| *s != slot);
+        state.vertex_buffers.push(slot);
+    }
+
+    fn set_index_buffer(&self, view: &Self::BufferView) {
+        debug_assert_eq!(view.desc().ty, BufferViewType::Index);
+        self.state.lock().index_buffer = true;
+    }
+
+    fn draw_instanced(&self, vertices: Range<u32>, instances: Range<u32>) {
+        let draw = self.state.lock().draw(vertices, instances, 0, false, false);
+        self.push(MockCommand::Draw(draw));
+    }
+
+    fn draw_indexed_instanced(&self, indices: Range<u32>, base_vertex: i32, instances: Range<u32>) {
+        let draw = self
+            .state
+            .lock()
+            .draw(indices, instances, base_vertex, true, false);
+        self.push(MockCommand::Draw(draw));
+    }
+
+    fn draw_indirect(
+        &self,
+        args: &Self::Buffer,
+        offset: usize,
+        draw_count: u32,
+    ) -> Result<(), IndirectError> {
+        self.push_indirect::<DrawIndirectArgs>(args, offset, (None, draw_count), false)
+    }
+
+    fn draw_indexed_indirect(
+        &self,
+        args: &Self::Buffer,
+        offset: usize,
+        draw_count: u32,
+    ) -> Result<(), IndirectError> {
+        self.push_indirect::<DrawIndexedIndirectArgs>(args, offset, (None, draw_count), true)
+    }
+
+    fn draw_indirect_count(
+        &self,
+        args: &Self::Buffer,
+        offset: usize,
+        count: &Self::Buffer,
+        count_offset: usize,
+        max_draws: u32,
+    ) -> Result<(), IndirectError> {
+        self.push_indirect::<DrawIndirectArgs>(
+            args,
+            offset,
+            (Some((count, count_offset)), max_draws),
+            false,
+        )
+    }
+
+    fn draw_indexed_indirect_count(
+        &self,
+        args: &Self::Buffer,
+        offset: usize,
+        count: &Self::Buffer,
+        count_offset: usize,
+        max_draws: u32,
+    ) -> Result<(), IndirectError> {
+        self.push_indirect::<DrawIndexedIndirectArgs>(
+            args,
+            offset,
+            (Some((count, count_offset)), max_draws),
+            true,
+        )
+    }
+
+    fn execute_bundle(&self, _bundle: &Self::Bundle) -> Result<(), BundleError> {
+        Err(BundleError::Nested)
+    }
+}
+
+impl DebugEncoder for MockBundleEncoder {
+    fn push_debug_group(&self, name: &str) {
+        self.push(MockCommand::PushDebugGroup(name.to_owned()));
+    }
+
+    fn pop_debug_group(&self) {
+        self.push(MockCommand::PopDebugGroup);
+    }
+
+    fn insert_marker(&self, name: &str) {
+        self.push(MockCommand::Marker(name.to_owned()));
+    }
+}
+
+impl DynRenderEncoder for MockBundleEncoder {
+    fn set_pipeline(&self, handle: RenderHandle<RenderPipeline>) {
+        let guard = self.handles.render_pipelines.lock();
+
+        let Some(pipeline) = guard.get(handle) else {
+            cold_path();
+            return;
+        };
+
+        self.references.lock().use_render_pipeline(handle);
+        <Self as RenderEncoder>::set_pipeline(self, pipeline);
+    }
+
+    fn set_vertex_buffer(&self, slot: u32, handle: RenderHandle<BufferView>) {
+        let guard = self.handles.buffer_views.lock();
+
+        let Some(view) = guard.get(handle) else {
+            cold_path();
+            return;
+        };
+
+        let mut references = self.references.lock();
+        references.use_buffer_view(handle);
+        if let Some(buffer) = view.buffer {
+            references.use_buffer(buffer);
+        }
+        drop(references);
+        <Self as RenderEncoder>::set_vertex_buffer(self, slot, view);
+    }
+
+    fn set_index_buffer(&self, handle: RenderHandle<BufferView>) {
+        let guard = self.handles.buffer_views.lock();
+
+        let Some(view) = guard.get(handle) else {
+            cold_path();
+            return;
+        };
+
+        let mut references = self.references.lock();
+        references.use_buffer_view(handle);
+        if let Some(buffer) = view.buffer {
+            references.use_buffer(buffer);
+        }
+        drop(references);
+        <Self as RenderEncoder>::set_index_buffer(self, view);
+    }
+
+    fn draw_indirect(
+        &self,
+        args_handle: RenderHandle<Buffer>,
+        offset: usize,
+        draw_count: u32,
+    ) -> Result<(), IndirectError> {
+        let guard = self.handles.buffers.lock();
+
+        let Some(args) = guard.get(args_handle) else {
+            cold_path();
+            return Err(IndirectError::DeadHandle);
+        };
+
+        self.references.lock().use_buffer(args_handle);
+        <Self as RenderEncoder>::draw_indirect(self, args, offset, draw_count)
+    }
+
+    fn draw_indexed_indirect(
+        &self,
+        args_handle: RenderHandle<Buffer>,
+        offset: usize,
+        draw_count: u32,
+    ) -> Result<(), IndirectError> {
+        let guard = self.handles.buffers.lock();
+
+        let Some(args) = guard.get(args_handle) else {
+            cold_path();
+            return Err(IndirectError::DeadHandle);
+        };
+
+        self.references.lock().use_buffer(args_handle);
+        <Self as RenderEncoder>::draw_indexed_indirect(self, args, offset, draw_count)
+    }
+
+    fn draw_indirect_count(
+        &self,
+        args_handle: RenderHandle<Buffer>,
+        offset: usize,
+        count_handle: RenderHandle<Buffer>,
+        count_offset: usize,
+        max_draws: u32,
+    ) -> Result<(), IndirectError> {
+        let guard = self.handles.buffers.lock();
+
+        let (Some(args), Some(count)) = (guard.get(args_handle), guard.get(count_handle)) else {
+            cold_path();
+            return Err(IndirectError::DeadHandle);
+        };
+
+        let mut references = self.references.lock();
+        references.use_buffer(args_handle);
+        references.use_buffer(count_handle);
+        drop(references);
+
+        <Self as RenderEncoder>::draw_indirect_count(
+            self,
+            args,
+            offset,
+            count,
+            count_offset,
+            max_draws,
+        )
+    }
+
+    fn draw_indexed_indirect_count(
+        &self,
+        args_handle: RenderHandle<Buffer>,
+        offset: usize,
+        count_handle: RenderHandle<Buffer>,
+        count_offset: usize,
+        max_draws: u32,
+    ) -> Result<(), IndirectError> {
+        let guard = self.handles.buffers.lock();
+
+        let (Some(args), Some(count)) = (guard.get(args_handle), guard.get(count_handle)) else {
+            cold_path();
+            return Err(IndirectError::DeadHandle);
+        };
+
+        let mut references = self.references.lock();
+        references.use_buffer(args_handle);
+        references.use_buffer(count_handle);
+        drop(references);
+
+        <Self as RenderEncoder>::draw_indexed_indirect_count(
+            self,
+            args,
+            offset,
+            count,
+            count_offset,
+            max_draws,
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+struct MockComputeState {
+    has_pipeline: bool,
+    views: Vec<u32>,
+    constants: Vec<(u32, Vec<u32>)>,
+}
+
+pub struct MockComputeEncoder<'a> {
+    cmd_buffer: &'a mut MockCommandBuffer,
+    state: Mutex<MockComputeState>,
+}
+
+impl<'a> MockComputeEncoder<'a> {
+    fn dispatch_state(&self, groups: [u32; 3], indirect: bool) -> MockDispatch {
+        let state = self.state.lock();
+        debug_assert!(state.has_pipeline, "dispatch without a pipeline");
+
+        MockDispatch {
+            groups,
+            indirect,
+            views: state.views.clone(),
+            constants: state.constants.clone(),
+        }
+    }
+}
+
+impl<'a> ComputeEncoder for MockComputeEncoder<'a> {
+    type Buffer = MockBuffer;
+    type BufferView = MockBufferView;
+    type Pipeline = MockPipeline<ComputePipeline, ComputePipelineDesc>;
+
+    fn set_pipeline(&self, pipeline: &Self::Pipeline) {
+        self.cmd_buffer
+            .record(|| StreamCommand::SetPipeline(pipeline.stream_ref()));
+
+        let mut state = self.state.lock();
+        state.has_pipeline = true;
+        state.views.clear();
+        state.constants.clear();
+    }
+
+    fn set_buffer_view(&self, slot: u32, view: &Self::BufferView) {
+        debug_assert!(matches!(
+            view.desc().ty,
+            BufferViewType::ShaderResource | BufferViewType::Storage
+        ));
+        self.cmd_buffer.use_buffer_view(view);
+        self.cmd_buffer.record(|| StreamCommand::SetBufferView {
+            slot,
+            view: view.stream_ref(),
+        });
+
+        let mut state = self.state.lock();
+        state.views.retain(|s| *s != slot);
+        state.views.push(slot);
+    }
+
+    fn set_constants(&self, slot: u32, constants: &[u32]) {
+        self.cmd_buffer.record(|| StreamCommand::SetConstants {
+            slot,
+            constants: constants.to_vec(),
+        });
+
+        let mut state = self.state.lock();
+        state.constants.retain(|(s, _)| *s != slot);
+        state.constants.push((slot, constants.to_vec()));
+    }
+
+    fn dispatch(&self, x: u32, y: u32, z: u32) {
+        let dispatch = self.dispatch_state([x, y, z], false);
+        self.cmd_buffer
+            .record(|| StreamCommand::Dispatch([x, y, z]));
+        self.cmd_buffer
+            .commands
+            .lock()
+            .push(MockCommand::Dispatch(dispatch));
+    }
+
+    fn dispatch_indirect(&self, buffer: &Self::Buffer, offset: usize) -> Result<(), IndirectError> {
+        indirect::validate::<DispatchIndirectArgs>(&buffer.desc, offset, 1)?;
+
+        self.cmd_buffer
+            .use_resources(ResourceUsages::default().use_buffer(buffer, ResourceState::Indirect));
+
+        let dispatch = self.dispatch_state([0; 3], true);
+        self.cmd_buffer.record(|| StreamCommand::DispatchIndirect {
+            args: buffer.stream_ref(),
+            offset,
+        });
+        self.cmd_buffer
+            .commands
+            .lock()
+            .push(MockCommand::DispatchIndirect {
+                args: Arc::clone(&buffer.data),
+                offset,
+                dispatch,
+            });
+
+        Ok(())
+    }
+}
+
+impl<'a> DebugEncoder for MockComputeEncoder<'a> {
+    fn push_debug_group(&self, name: &str) {
+        self.cmd_buffer
+            .push_debug(MockCommand::PushDebugGroup(name.to_owned()));
+    }
+
+    fn pop_debug_group(&self) {
+        self.cmd_buffer.push_debug(MockCommand::PopDebugGroup);
+    }
+
+    fn insert_marker(&self, name: &str) {
+        self.cmd_buffer
+            .push_debug(MockCommand::Marker(name.to_owned()));
+    }
+}
+
+impl<'a> DynComputeEncoder for MockComputeEncoder<'a> {
+    fn set_pipeline(&self, pipeline: RenderHandle<ComputePipeline>) {
+        self.cmd_buffer
+            .validate(|| StreamCommand::SetPipeline(StreamRef::ComputePipeline(pipeline)));
+        let guard = self.cmd_buffer.handles.compute_pipelines.lock();
+
+        let Some(pipeline) = guard.get(pipeline) else {
+            cold_path();
+            return;
+        };
+
+        <Self as ComputeEncoder>::set_pipeline(self, pipeline);
+    }
+
+    fn set_buffer_view(&self, slot: u32, view: RenderHandle<BufferView>) {
+        self.cmd_buffer.validate(|| StreamCommand::SetBufferView {
+            slot,
+            view: StreamRef::BufferView(view),
+        });
+        let guard = self.cmd_buffer.handles.buffer_views.lock();
+
+        let Some(view) = guard.get(view) else {
+            cold_path();
+            return;
+        };
+
+        <Self as ComputeEncoder>::set_buffer_view(self, slot, view);
+    }
+
+    fn dispatch_indirect(
+        &self,
+        buffer: RenderHandle<Buffer>,
+        offset: usize,
+    ) -> Result<(), IndirectError> {
+        self.cmd_buffer
+            .validate(|| StreamCommand::DispatchIndirect {
+                args: StreamRef::Buffer(buffer),
+                offset,
+            });
+        let guard = self.cmd_buffer.handles.buffers.lock();
+
+        let Some(buffer) = guard.get(buffer) else {
+            cold_path();
+            return Err(IndirectError::DeadHandle);
+        };
+
+        <Self as ComputeEncoder>::dispatch_indirect(self, buffer, offset)
+    }
+}
+
+pub struct MockTransferEncoder<'a> {
+    cmd_buffer: &'a mut MockCommandBuffer,
+}
+
+impl<'a> MockTransferEncoder<'a> {
+    fn push(&self, command: MockCommand) {
+        self.cmd_buffer.commands.lock().push(command);
+    }
+}
+
+/// Memory layout of the subresource `region` points into.
+fn subresource_layout(desc: &TextureDesc, region: &TextureRegion) -> BufferTextureLayout {
+    let layout =
+        &desc.subresource_layouts()[desc.subresource_index(region.mip, region.layer) as usize];
+
+    BufferTextureLayout {
+        offset: layout.offset,
+        row_pitch: layout.row_pitch,
+        slice_pitch: layout.slice_pitch,
+    }
+}
+
+fn region_range(region: &TextureRegion) -> SubresourceRange {
+    SubresourceRange::mip(region.mip, region.layer..(region.layer + 1))
+}
+
+impl<'a> TransferEncoder for MockTransferEncoder<'a> {
+    type Buffer = MockBuffer;
+    type Texture = MockTexture;
+
+    fn copy_buffer_to_buffer(
+        &self,
+        dst: &Self::Buffer,
+        src: &Self::Buffer,
+    ) -> Result<(), TransferError> {
+        transfer::validate_buffer_range(&dst.desc, &(0..src.desc.size))?;
+
+        self.cmd_buffer.use_resources(
+            ResourceUsages::default()
+                .use_buffer(dst, ResourceState::CopyDst)
+                .use_buffer(src, ResourceState::CopySrc),
+        );
+
+        self.cmd_buffer.record(|| StreamCommand::CopyBuffer {
+            dst: dst.stream_ref(),
+            src: src.stream_ref(),
+        });
+        self.push(MockCommand::CopyBuffer {
+            dst: Arc::clone(&dst.data),
+            src: Arc::clone(&src.data),
+        });
+
+        Ok(())
+    }
+
+    fn copy_texture_to_texture(
+        &self,
+        dst: &Self::Texture,
+        src: &Self::Texture,
+    ) -> Result<(), TransferError> {
+        transfer::validate_texture_copy(&dst.desc, &src.desc)?;
+
+        self.cmd_buffer.use_resources(
+            ResourceUsages::default()
+                .use_texture(dst, ResourceState::CopyDst)
+                .use_texture(src, ResourceState::CopySrc),
+        );
+
+        self.cmd_buffer.record(|| StreamCommand::CopyTexture {
+            dst: dst.stream_ref(),
+            src: src.stream_ref(),
+        });
+        self.push(MockCommand::CopyTexture {
+            dst: Arc::clone(&dst.data),
+            src: Arc::clone(&src.data),
+        });
+
+        Ok(())
+    }
+
+    fn upload_to_texture(
+        &self,
+        dst: &Self::Texture,
+        src: &Self::Buffer,
+        data: &[u8],
+    ) -> Result<(), TransferError> {
+        if data.len() < dst.desc.packed_size() {
+            return Err(TransferError::OutOfBounds {
+                end: dst.desc.packed_size(),
+                size: data.len(),
+            });
+        }
+
+        self.cmd_buffer
+            .use_resources(ResourceUsages::default().use_texture(dst, ResourceState::CopyDst));
+
+        self.cmd_buffer.record(|| StreamCommand::UploadTexture {
+            dst: dst.stream_ref(),
+            src: src.stream_ref(),
+            data: data.to_vec(),
+        });
+        self.push(MockCommand::UploadTexture {
+            dst: Arc::clone(&dst.data),
+            data: data.to_vec(),
+        });
+
+        Ok(())
+    }
+
+    fn copy_buffer_region(
+        &self,
+        dst: &Self::Buffer,
+        dst_offset: usize,
+        src: &Self::Buffer,
+        src_offset: usize,
+        size: usize,
+    ) -> Result<(), TransferError> {
+        transfer::validate_buffer_range(&dst.desc, &(dst_offset..(dst_offset + size)))?;
+        transfer::validate_buffer_range(&src.desc, &(src_offset..(src_offset + size)))?;
+
+        self.cmd_buffer.use_resources(
+            ResourceUsages::default()
+                .use_buffer(dst, ResourceState::CopyDst)
+                .use_buffer(src, ResourceState::CopySrc),
+        );
+
+        self.cmd_buffer.record(|| StreamCommand::CopyBufferRegion {
+            dst: dst.stream_ref(),
+            dst_offset,
+            src: src.stream_ref(),
+            src_offset,
+            size,
+        });
+        self.push(MockCommand::CopyRegion {
+            dst: Arc::clone(&dst.data),
+            src: Arc::clone(&src.data),
+            rows: vec![(dst_offset, src_offset)],
+            row_size: size,
+        });
+
+        Ok(())
+    }
+
+    fn copy_buffer_to_texture(
+        &self,
+        dst: &Self::Texture,
+        region: TextureRegion,
+        src: &Self::Buffer,
+        layout: BufferTextureLayout,
+    ) -> Result<(), TransferError> {
+        region.validate(&dst.desc)?;
+        layout.validate(&src.desc, dst.desc.format, region.extent)?;
+
+        self.cmd_buffer.use_resources(
+            ResourceUsages::default()
+                .use_subresources(dst, ResourceState::CopyDst, region_range(&region))
+                .use_buffer(src, ResourceState::CopySrc),
+        );
+
+        let format = dst.desc.format;
+        let (dst_rows, row_size) = transfer::region_rows(
+            format,
+            region.origin,
+            region.extent,
+            subresource_layout(&dst.desc, &region),
+        );
+        let (src_rows, _) = transfer::region_rows(format, [0; 3], region.extent, layout);
+
+        self.cmd_buffer
+            .record(|| StreamCommand::CopyBufferToTexture {
+                dst: dst.stream_ref(),
+                region,
+                src: src.stream_ref(),
+                layout,
+            });
+        self.push(MockCommand::CopyRegion {
+            dst: Arc::clone(&dst.data),
+            src: Arc::clone(&src.data),
+            rows: dst_rows.into_iter().zip(src_rows).collect(),
+            row_size,
+        });
+
+        Ok(())
+    }
+
+    fn copy_texture_to_buffer(
+        &self,
+        dst: &Self::Buffer,
+        layout: BufferTextureLayout,
+        src: &Self::Texture,
+        region: TextureRegion,
+    ) -> Result<(), TransferError> {
+        region.validate(&src.desc)?;
+        layout.validate(&dst.desc, src.desc.format, region.extent)?;
+
+        self.cmd_buffer.use_resources(
+            ResourceUsages::default()
+                .use_buffer(dst, ResourceState::CopyDst)
+                .use_subresources(src, ResourceState::CopySrc, region_range(&region)),
+        );
+
+        let format = src.desc.format;
+        let (dst_rows, row_size) = transfer::region_rows(format, [0; 3], region.extent, layout);
+        let (src_rows, _) = transfer::region_rows(
+            format,
+            region.origin,
+            region.extent,
+            subresource_layout(&src.desc, &region),
+        );
+
+        self.cmd_buffer
+            .record(|| StreamCommand::CopyTextureToBuffer {
+                dst: dst.stream_ref(),
+                layout,
+                src: src.stream_ref(),
+                region,
+            });
+        self.push(MockCommand::CopyRegion {
+            dst: Arc::clone(&dst.data),
+            src: Arc::clone(&src.data),
+            rows: dst_rows.into_iter().zip(src_rows).collect(),
+            row_size,
+        });
+
+        Ok(())
+    }
+
+    fn copy_texture_region(
+        &self,
+        dst: &Self::Texture,
+        dst_region: TextureRegion,
+        src: &Self::Texture,
+        src_region: TextureRegion,
+    ) -> Result<(), TransferError> {
+        if dst.desc.format != src.desc.format {
+            return Err(TransferError::FormatMismatch);
+        }
+
+        if dst_region.extent != src_region.extent {
+            return Err(TransferError::InvalidRegion);
+        }
+
+        dst_region.validate(&dst.desc)?;
+        src_region.validate(&src.desc)?;
+
+        self.cmd_buffer.use_resources(
+            ResourceUsages::default()
+                .use_subresources(dst, ResourceState::CopyDst, region_range(&dst_region))
+                .use_subresources(src, ResourceState::CopySrc, region_range(&src_region)),
+        );
+
+        let format = dst.desc.format;
+        let (dst_rows, row_size) = transfer::region_rows(
+            format,
+            dst_region.origin,
+            dst_region.extent,
+            subresource_layout(&dst.desc, &dst_region),
+        );
+        let (src_rows, _) = transfer::region_rows(
+            format,
+            src_region.origin,
+            src_region.extent,
+            subresource_layout(&src.desc, &src_region),
+        );
+
+        self.cmd_buffer.record(|| StreamCommand::CopyTextureRegion {
+            dst: dst.stream_ref(),
+            dst_region,
+            src: src.stream_ref(),
+            src_region,
+        });
+        self.push(MockCommand::CopyRegion {
+            dst: Arc::clone(&dst.data),
+            src: Arc::clone(&src.data),
+            rows: dst_rows.into_iter().zip(src_rows).collect(),
+            row_size,
+        });
+
+        Ok(())
+    }
+
+    fn fill_buffer(
+        &self,
+        dst: &Self::Buffer,
+        range: Range<usize>,
+        value: u32,
+    ) -> Result<(), TransferError> {
+        transfer::validate_fill(&dst.desc, &range, self.cmd_buffer.ty)?;
+
+        self.cmd_buffer
+            .use_resources(ResourceUsages::default().use_buffer(dst, ResourceState::StorageWrite));
+
+        self.cmd_buffer.record(|| StreamCommand::FillBuffer {
+            dst: dst.stream_ref(),
+            range: range.clone(),
+            value,
+        });
+        self.push(MockCommand::Fill {
+            dst: Arc::clone(&dst.data),
+            ranges: vec![range],
+            pattern: value.to_ne_bytes().to_vec(),
+        });
+
+        Ok(())
+    }
+
+    fn clear_texture(
+        &self,
+        dst: &Self::Texture,
+        range: SubresourceRange,
+        value: ClearValue,
+    ) -> Result<(), TransferError> {
+        let state = transfer::clear_state(&dst.desc, &value, self.cmd_buffer.ty)?;
+
+        if range.mips.end > dst.desc.mip_levels || range.layers.end > dst.desc.array_layers() {
+            return Err(TransferError::InvalidRegion);
+        }
+
+        let color = match value {
+            ClearValue::Color(color) => color,
+            ClearValue::DepthStencil { depth, .. } => [depth, 0.0, 0.0, 0.0],
+        };
+        let Some(texel) = mips::encode_texel(dst.desc.format, color) else {
+            return Err(TransferError::Unsupported(
+                "clears are not supported for this format",
+            ));
+        };
+
+        self.cmd_buffer
+            .use_resources(ResourceUsages::default().use_subresources(dst, state, range.clone()));
+
+        let ranges = dst
+            .desc
+            .subresource_layouts()
+            .into_iter()
+            .filter(|l| range.mips.contains(&l.mip) && range.layers.contains(&l.layer))
+            .map(|l| l.offset..(l.offset + l.size))
+            .collect();
+
+        self.cmd_buffer.record(|| StreamCommand::ClearTexture {
+            dst: dst.stream_ref(),
+            range: range.clone(),
+            value,
+        });
+        self.push(MockCommand::Fill {
+            dst: Arc::clone(&dst.data),
+            ranges,
+            pattern: texel,
+        });
+
+        Ok(())
+    }
+}
+
+impl<'a> DebugEncoder for MockTransferEncoder<'a> {
+    fn push_debug_group(&self, name: &str) {
+        self.cmd_buffer
+            .push_debug(MockCommand::PushDebugGroup(name.to_owned()));
+    }
+
+    fn pop_debug_group(&self) {
+        self.cmd_buffer.push_debug(MockCommand::PopDebugGroup);
+    }
+
+    fn insert_marker(&self, name: &str) {
+        self.cmd_buffer
+            .push_debug(MockCommand::Marker(name.to_owned()));
+    }
+}
+
+impl<'a> DynTransferEncoder for MockTransferEncoder<'a> {
+    fn copy_buffer_to_buffer(
+        &self,
+        dst: RenderHandle<Buffer>,
+        src: RenderHandle<Buffer>,
+    ) -> Result<(), TransferError> {
+        self.cmd_buffer.validate(|| StreamCommand::CopyBuffer {
+            dst: StreamRef::Buffer(dst),
+            src: StreamRef::Buffer(src),
+        });
+        let guard = self.cmd_buffer.handles.buffers.lock();
+
+        let Some(dst) = guard.get(dst) else {
+            cold_path();
+            return Err(TransferError::DeadHandle);
+        };
+
+        let Some(src) = guard.get(src) else {
+            cold_path();
+            return Err(TransferError::DeadHandle);
+        };
+
+        <Self as TransferEncoder>::copy_buffer_to_buffer(self, dst, src)
+    }
+
+    fn copy_texture_to_texture(
+        &self,
+        dst_handle: RenderHandle<Texture>,
+        src_handle: RenderHandle<Texture>,
+    ) -> Result<(), TransferError> {
+        self.cmd_buffer.validate(|| StreamCommand::CopyTexture {
+            dst: StreamRef::Texture(dst_handle),
+            src: StreamRef::Texture(src_handle),
+        });
+        let guard = self.cmd_buffer.handles.textures.lock();
+
+        let Some(dst) = guard.get(dst_handle) else {
+            cold_path();
+            return Err(TransferError::DeadHandle);
+        };
+
+        let Some(src) = guard.get(src_handle) else {
+            cold_path();
+            return Err(TransferError::DeadHandle);
+        };
+
+        self.cmd_buffer.handles.touch_texture(dst_handle, dst);
+        self.cmd_buffer.handles.touch_texture(src_handle, src);
+
+        <Self as TransferEncoder>::copy_texture_to_texture(self, dst, src)
+    }
+
+    fn upload_to_texture(
+        &self,
+        dst_handle: RenderHandle<Texture>,
+        src: RenderHandle<Buffer>,
+        data: &[u8],
+    ) -> Result<(), TransferError> {
+        self.cmd_buffer.validate(|| StreamCommand::UploadTexture {
+            dst: StreamRef::Texture(dst_handle),
+            src: StreamRef::Buffer(src),
+            data: data.to_vec(),
+        });
+        let bguard = self.cmd_buffer.handles.buffers.lock();
+        let tguard = self.cmd_buffer.handles.textures.lock();
+
+        let Some(dst) = tguard.get(dst_handle) else {
+            cold_path();
+            return Err(TransferError::DeadHandle);
+        };
+
+        let Some(src) = bguard.get(src) else {
+            cold_path();
+            return Err(TransferError::DeadHandle);
+        };
+
+        self.cmd_buffer.handles.touch_texture(dst_handle, dst);
+
+        <Self as TransferEncoder>::upload_to_texture(self, dst, src, data)
+    }
+
+    fn copy_buffer_region(
+        &self,
+        dst: RenderHandle<Buffer>,
+        dst_offset: usize,
+        src: RenderHandle<Buffer>,
+        src_offset: usize,
+        size: usize,
+    ) -> Result<(), TransferError> {
+        self.cmd_buffer
+            .validate(|| StreamCommand::CopyBufferRegion {
+                dst: StreamRef::Buffer(dst),
+                dst_offset,
+                src: StreamRef::Buffer(src),
+                src_offset,
+                size,
+            });
+        let guard = self.cmd_buffer.handles.buffers.lock();
+
+        let (Some(dst), Some(src)) = (guard.get(dst), guard.get(src)) else {
+            cold_path();
+            return Err(TransferError::DeadHandle);
+        };
+
+        <Self as TransferEncoder>::copy_buffer_region(self, dst, dst_offset, src, src_offset, size)
+    }
+
+    fn copy_buffer_to_texture(
+        &self,
+        dst_handle: RenderHandle<Texture>,
+        region: TextureRegion,
+        src: RenderHandle<Buffer>,
+        layout: BufferTextureLayout,
+    ) -> Result<(), TransferError> {
+        self.cmd_buffer
+            .validate(|| StreamCommand::CopyBufferToTexture {
+                dst: StreamRef::Texture(dst_handle),
+                region,
+                src: StreamRef::Buffer(src),
+                layout,
+            });
+        let bguard = self.cmd_buffer.handles.buffers.lock();
+        let tguard = self.cmd_buffer.handles.textures.lock();
+
+        let (Some(dst), Some(src)) = (tguard.get(dst_handle), bguard.get(src)) else {
+            cold_path();
+            return Err(TransferError::DeadHandle);
+        };
+
+        self.cmd_buffer.handles.touch_texture(dst_handle, dst);
+
+        <Self as TransferEncoder>::copy_buffer_to_texture(self, dst, region, src, layout)
+    }
+
+    fn copy_texture_to_buffer(
+        &self,
+        dst: RenderHandle<Buffer>,
+        layout: BufferTextureLayout,
+        src_handle: RenderHandle<Texture>,
+        region: TextureRegion,
+    ) -> Result<(), TransferError> {
+        self.cmd_buffer
+            .validate(|| StreamCommand::CopyTextureToBuffer {
+                dst: StreamRef::Buffer(dst),
+                layout,
+                src: StreamRef::Texture(src_handle),
+                region,
+            });
+        let bguard = self.cmd_buffer.handles.buffers.lock();
+        let tguard = self.cmd_buffer.handles.textures.lock();
+
+        let (Some(dst), Some(src)) = (bguard.get(dst), tguard.get(src_handle)) else {
+            cold_path();
+            return Err(TransferError::DeadHandle);
+        };
+
+        self.cmd_buffer.handles.touch_texture(src_handle, src);
+
+        <Self as TransferEncoder>::copy_texture_to_buffer(self, dst, layout, src, region)
+    }
+
+    fn copy_texture_region(
+        &self,
+        dst_handle: RenderHandle<Texture>,
+        dst_region: TextureRegion,
+        src_handle: RenderHandle<Texture>,
+        src_region: TextureRegion,
+    ) -> Result<(), TransferError> {
+        self.cmd_buffer
+            .validate(|| StreamCommand::CopyTextureRegion {
+                dst: StreamRef::Texture(dst_handle),
+                dst_region,
+                src: StreamRef::Texture(src_handle),
+                src_region,
+            });
+        let guard = self.cmd_buffer.handles.textures.lock();
+
+        let (Some(dst), Some(src)) = (guard.get(dst_handle), guard.get(src_handle)) else {
+            cold_path();
+            return Err(TransferError::DeadHandle);
+        };
+
+        self.cmd_buffer.handles.touch_texture(dst_handle, dst);
+        self.cmd_buffer.handles.touch_texture(src_handle, src);
+
+        <Self as TransferEncoder>::copy_texture_region(self, dst, dst_region, src, src_region)
+    }
+
+    fn fill_buffer(
+        &self,
+        dst: RenderHandle<Buffer>,
+        range: Range<usize>,
+        value: u32,
+    ) -> Result<(), TransferError> {
+        self.cmd_buffer.validate(|| StreamCommand::FillBuffer {
+            dst: StreamRef::Buffer(dst),
+            range: range.clone(),
+            value,
+        });
+        let guard = self.cmd_buffer.handles.buffers.lock();
+
+        let Some(dst) = guard.get(dst) else {
+            cold_path();
+            return Err(TransferError::DeadHandle);
+        };
+
+        <Self as TransferEncoder>::fill_buffer(self, dst, range, value)
+    }
+
+    fn clear_texture(
+        &self,
+        dst_handle: RenderHandle<Texture>,
+        range: SubresourceRange,
+        value: ClearValue,
+    ) -> Result<(), TransferError> {
+        self.cmd_buffer.validate(|| StreamCommand::ClearTexture {
+            dst: StreamRef::Texture(dst_handle),
+            range: range.clone(),
+            value,
+        });
+        let guard = self.cmd_buffer.handles.textures.lock();
+
+        let Some(dst) = guard.get(dst_handle) else {
+            cold_path();
+            return Err(TransferError::DeadHandle);
+        };
+
+        self.cmd_buffer.handles.touch_texture(dst_handle, dst);
+
+        <Self as TransferEncoder>::clear_texture(self, dst, range, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::graphics::{
+        context::RenderContext,
+        core::{
+            backend::{Api, RenderDeviceGroup},
+            bundle::{BundleDesc, BundleError},
+            capture::Capture,
+            commands::{
+                BufferBarrier, ColorAttachment, CommandBuffer, CommandBufferType, CommandDevice,
+                ComputeEncoder, DebugEncoder, DynComputeEncoder, DynRenderEncoder, RenderEncoder,
+                RenderPassDesc, Scissor, SyncPointError, TransferEncoder, Viewport,
+            },
+            future::WaitTimeout,
+            handle::{RenderHandle, RenderHandleAllocator},
+            indirect::{DrawIndexedIndirectArgs, IndirectError},
+            memory::MemoryLocation,
+            ownership::OwnershipError,
+            query::{QueryError, QuerySetDesc, QueryType},
+            residency::{Residency, ResidencyPriority},
+            resource::{
+                Buffer, BufferDesc, BufferUsages, BufferView, BufferViewDesc, ResourceDevice,
+                SubresourceRange, Texture, TextureDesc, TextureType, TextureUsages,
+            },
+            shader::{
+                ComputePipeline, ComputePipelineDesc, EntryPoint, PipelineBinding, PipelineLayout,
+                PrimitiveTopology, RenderPipeline, RenderPipelineDesc, ShaderEntry, ShaderModule,
+                ShaderModuleDesc, ShaderSource, ShaderStage, VertexAttribute, VertexSemantic,
+            },
+            stream::StreamCommand,
+            transfer::{BufferTextureLayout, ClearValue, TextureRegion, TransferError},
+            types::{Format, ResourceState},
+            validation::ValidationError,
+        },
+        mock::{
+            backend::MockBackend,
+            commands::{MockCommandBuffer, MockLogEntry},
+            context::MockRenderContext,
+            resources::MockPipeline,
+        },
+        DebugFlags,
+    };
+
+    #[test]
+    fn generate_mips_fills_every_cube_face() {
+        let device = Arc::new(MockBackend::new(DebugFlags::empty()).create_device(0));
+        let handle = RenderHandleAllocator::<Texture>::new().allocate();
+
+        let desc = TextureDesc {
+            name: None,
+            ty: TextureType::Cube,
+            width: 2,
+            height: 2,
+            depth: 6,
+            mip_levels: 2,
+            format: Format::Rgba8UnormSrgb,
+            usage: TextureUsages::Resource | TextureUsages::Storage,
+        };
+
+        let mut data = vec![0u8; desc.packed_size()];
+        for layout in desc.subresource_layouts().iter().filter(|l| l.mip == 0) {
+            data[layout.offset..(layout.offset + layout.size)]
+                .copy_from_slice(&[0, 0, 0, 255, 255, 255, 255, 255].repeat(2));
+        }
+
+        device.bind_texture(handle, desc.clone(), Some(&data));
+
+        let mut cmd = device.create_command_buffer(CommandBufferType::Graphics);
+        cmd.generate_mips(handle);
+        device.push_cmd_buffer(cmd);
+        device.wait_cpu(device.commit(CommandBufferType::Graphics));
+
+        let data = device.handles.textures.lock().get(handle).unwrap().read();
+        for layout in desc.subresource_layouts().iter().filter(|l| l.mip == 1) {
+            assert_eq!(
+                &data[layout.offset..(layout.offset + layout.size)],
+                &[188, 188, 188, 255]
+            );
+        }
+    }
+
+    #[test]
+    fn copies_transition_their_resources() {
+        let device = MockBackend::new(DebugFlags::empty()).create_device(0);
+        let desc = BufferDesc {
+            name: None,
+            size: 16,
+            stride: 0,
+            usage: BufferUsages::Storage,
+        };
+
+        let dst = device.create_buffer::<u8>(desc.clone(), None);
+        let src = device.create_buffer(desc, Some(&[7u8; 16]));
+
+        let mut cmd = device.create_command_buffer(CommandBufferType::Transfer);
+        cmd.transfer_encoder()
+            .copy_buffer_to_buffer(&dst, &src)
+            .unwrap();
+
+        assert_eq!(dst.state(), ResourceState::CopyDst);
+        assert_eq!(src.state(), ResourceState::CopySrc);
+
+        device.push_cmd_buffer(cmd);
+        device.wait_cpu(device.commit(CommandBufferType::Transfer));
+
+        assert_eq!(dst.read(), vec![7; 16]);
+    }
+
+    #[test]
+    fn region_copies_move_rows_between_buffers_and_textures() {
+        let device = MockBackend::new(DebugFlags::empty()).create_device(0);
+        let buffer = |size| BufferDesc {
+            name: None,
+            size,
+            stride: 0,
+            usage: BufferUsages::Copy,
+        };
+
+        let texels = (0..16u8).collect::<Vec<_>>();
+        let src = device.create_buffer(buffer(20), Some(&[[0; 4].as_slice(), &texels].concat()));
+        let readback = device.create_buffer::<u8>(buffer(24), None);
+        let texture = device.create_texture::<u8>(
+            TextureDesc {
+                name: None,
+                ty: TextureType::D2,
+                width: 4,
+                height: 4,
+                depth: 1,
+                mip_levels: 1,
+                format: Format::Rgba8Unorm,
+                usage: TextureUsages::Copy,
+            },
+            None,
+        );
+
+        let region = TextureRegion {
+            mip: 0,
+            layer: 0,
+            origin: [1, 2, 0],
+            extent: [2, 2, 1],
+        };
+
+        let mut cmd = device.create_command_buffer(CommandBufferType::Transfer);
+        {
+            let encoder = cmd.transfer_encoder();
+            encoder
+                .copy_buffer_to_texture(
+                    &texture,
+                    region,
+                    &src,
+                    BufferTextureLayout::packed(4, Format::Rgba8Unorm, region.extent),
+                )
+                .unwrap();
+
+            let padded = BufferTextureLayout {
+                offset: 0,
+                row_pitch: 12,
+                slice_pitch: 24,
+            };
+            encoder
+                .copy_texture_to_buffer(&readback, padded, &texture, region)
+                .unwrap();
+            encoder
+                .copy_buffer_region(&readback, 20, &src, 4, 4)
+                .unwrap();
+
+            assert_eq!(
+                encoder.copy_buffer_region(&readback, 22, &src, 0, 4),
+                Err(TransferError::OutOfBounds { end: 26, size: 24 })
+            );
+            assert_eq!(
+                encoder.copy_texture_to_buffer(
+                    &readback,
+                    padded,
+                    &texture,
+                    TextureRegion {
+                        origin: [3, 2, 0],
+                        ..region
+                    }
+                ),
+                Err(TransferError::InvalidRegion)
+            );
+        }
+
+        device.push_cmd_buffer(cmd);
+        device.wait_cpu(device.commit(CommandBufferType::Transfer));
+
+        let mut texture_data = vec![0; 64];
+        texture_data[36..44].copy_from_slice(&texels[..8]);
+        texture_data[52..60].copy_from_slice(&texels[8..]);
+        assert_eq!(texture.read(), texture_data);
+
+        let readback = readback.read();
+        assert_eq!(readback[..8], texels[..8]);
+        assert_eq!(readback[12..20], texels[8..]);
+        assert_eq!(readback[20..], texels[..4]);
+    }
+
+    #[test]
+    fn clears_and_fills_check_usage_and_queue() {
+        let device = MockBackend::new(DebugFlags::empty()).create_device(0);
+
+        let buffer = device.create_buffer::<u8>(
+            BufferDesc {
+                name: None,
+                size: 16,
+                stride: 0,
+                usage: BufferUsages::Storage,
+            },
+            None,
+        );
+        let depth_desc = TextureDesc {
+            name: None,
+            ty: TextureType::D2,
+            width: 2,
+            height: 2,
+            depth: 2,
+            mip_levels: 1,
+            format: Format::R32,
+            usage: TextureUsages::DepthTarget,
+        };
+        let depth = device.create_texture::<u8>(depth_desc.clone(), None);
+        let sampled = device.create_texture::<u8>(
+            TextureDesc {
+                usage: TextureUsages::Resource,
+                ..depth_desc
+            },
+            None,
+        );
+
+        let mut cmd = device.create_command_buffer(CommandBufferType::Transfer);
+        assert!(matches!(
+            cmd.transfer_encoder().fill_buffer(&buffer, 0..16, 1),
+            Err(TransferError::Unsupported(_))
+        ));
+        device.stash_cmd_buffer(cmd);
+
+        let mut cmd = device.create_command_buffer(CommandBufferType::Graphics);
+        {
+            let encoder = cmd.transfer_encoder();
+            encoder.fill_buffer(&buffer, 4..12, 0x01020304).unwrap();
+            encoder.clear_buffer(&buffer, 8..12).unwrap();
+            assert_eq!(
+                encoder.fill_buffer(&buffer, 2..6, 0),
+                Err(TransferError::Misaligned {
+                    value: 2,
+                    alignment: 4
+                })
+            );
+            assert_eq!(
+                crate::graphics::core::commands::DynTransferEncoder::fill_buffer(
+                    &encoder,
+                    RenderHandleAllocator::<Buffer>::new().allocate(),
+                    0..4,
+                    0
+                ),
+                Err(TransferError::DeadHandle)
+            );
+
+            let depth_clear = ClearValue::DepthStencil {
+                depth: 0.5,
+                stencil: 0,
+            };
+            encoder
+                .clear_texture(&depth, SubresourceRange::mip(0, 1..2), depth_clear)
+                .unwrap();
+            assert!(matches!(
+                encoder.clear_texture(&sampled, SubresourceRange::mip(0, 0..1), depth_clear),
+                Err(TransferError::Unsupported(_))
+            ));
+        }
+
+        assert_eq!(depth.state(0, 0), ResourceState::Common);
+        assert_eq!(depth.state(0, 1), ResourceState::DepthWrite);
+
+        device.push_cmd_buffer(cmd);
+        device.commit(CommandBufferType::Graphics);
+
+        let fill = 0x01020304u32.to_ne_bytes();
+        assert_eq!(buffer.read(), [[0; 4], fill, [0; 4], [0; 4]].concat());
+        assert_eq!(
+            depth.read(),
+            [vec![0; 16], 0.5f32.to_ne_bytes().repeat(4)].concat()
+        );
+    }
+
+    #[test]
+    fn over_budget_textures_are_downgraded_and_restored_on_use() {
+        let device = Arc::new(
+            MockBackend::new(DebugFlags::empty())
+                .with_memory_budget(400)
+                .create_device(0),
+        );
+        let mut handles = RenderHandleAllocator::<Texture>::new();
+        let texture = |size, mip_levels| TextureDesc {
+            name: None,
+            ty: TextureType::D2,
+            width: size,
+            height: size,
+            depth: 1,
+            mip_levels,
+            format: Format::Rgba8Unorm,
+            usage: TextureUsages::Resource,
+        };
+        let device_bytes = || {
+            device
+                .memory_report()
+                .location(MemoryLocation::Device)
+                .bytes
+        };
+
+        let background = handles.allocate();
+        device.bind_texture(background, texture(8, 2), None);
+        device.set_texture_priority(background, ResidencyPriority::Low);
+
+        let ui = handles.allocate();
+        device.bind_texture(ui, texture(4, 1), None);
+        device.commit(CommandBufferType::Graphics);
+
+        let overlay = handles.allocate();
+        device.bind_texture(overlay, texture(4, 1), None);
+
+        let residency = |handle| device.handles.residency.lock().residency(handle);
+        assert_eq!(
+            residency(background),
+            Some(Residency::Downgraded { first_mip: 1 })
+        );
+        assert_eq!(residency(ui), Some(Residency::Resident));
+        assert_eq!(device_bytes(), 192);
+
+        let staging = RenderHandleAllocator::<Buffer>::new().allocate();
+        device.bind_buffer(
+            staging,
+            BufferDesc {
+                name: None,
+                size: 256,
+                stride: 0,
+                usage: BufferUsages::Copy,
+            },
+            None,
+        );
+
+        let mut cmd = device.create_command_buffer(CommandBufferType::Graphics);
+        crate::graphics::core::commands::DynTransferEncoder::upload_to_texture(
+            &cmd.transfer_encoder(),
+            background,
+            staging,
+            &[0; 320],
+        )
+        .unwrap();
+
+        assert_eq!(residency(background), Some(Residency::Resident));
+        assert_eq!(device_bytes(), 448);
+
+        device.push_cmd_buffer(cmd);
+        device.commit(CommandBufferType::Graphics);
+    }
+
+    #[test]
+    fn render_pass_clears_and_records_draws() {
+        let device = Arc::new(MockBackend::new(DebugFlags::empty()).create_device(0));
+
+        let target = RenderHandleAllocator::<Texture>::new().allocate();
+        device.bind_texture(
+            target,
+            TextureDesc {
+                name: None,
+                ty: TextureType::D2,
+                width: 2,
+                height: 2,
+                depth: 1,
+                mip_levels: 1,
+                format: Format::Rgba8Unorm,
+                usage: TextureUsages::RenderTarget,
+            },
+            None,
+        );
+
+        let vertices = RenderHandleAllocator::<Buffer>::new().allocate();
+        device.bind_buffer(
+            vertices,
+            BufferDesc {
+                name: None,
+                size: 36,
+                stride: 12,
+                usage: BufferUsages::Vertex,
+            },
+            None,
+        );
+        let view = RenderHandleAllocator::<BufferView>::new().allocate();
+        device
+            .bind_buffer_view(view, vertices, BufferViewDesc::vertex(12))
+            .unwrap();
+
+        let pipeline = render_pipeline(&device);
+
+        let mut cmd = device.create_command_buffer(CommandBufferType::Graphics);
+        {
+            let encoder = cmd.render_encoder(&RenderPassDesc {
+                colors: vec![ColorAttachment::clear(target, [1.0, 0.0, 0.0, 1.0])],
+                depth_stencil: None,
+                buffer_views: vec![view],
+            });
+
+            DynRenderEncoder::set_pipeline(&encoder, pipeline);
+            DynRenderEncoder::set_vertex_buffer(&encoder, 0, view);
+            encoder.set_stencil_reference(3);
+            encoder.draw(0..3);
+        }
+
+        assert_eq!(cmd.draws().len(), 1);
+        let draw = &cmd.draws()[0];
+        assert_eq!(draw.vertices, 0..3);
+        assert_eq!(draw.stencil_reference, 3);
+        assert_eq!(draw.viewport, Viewport::new(2.0, 2.0));
+        assert_eq!(draw.scissor, Scissor::new(2, 2));
+        assert_eq!(draw.vertex_buffers, vec![0]);
+
+        device.push_cmd_buffer(cmd);
+        device.commit(CommandBufferType::Graphics);
+
+        let guard = device.handles.textures.lock();
+        let texture = guard.get(target).unwrap();
+        assert_eq!(texture.state(0, 0), ResourceState::RenderTarget);
+        assert_eq!(texture.read(), [255, 0, 0, 255].repeat(4));
+    }
+
+    #[test]
+    fn compute_records_direct_and_indirect_dispatches_on_graphics_buffers() {
+        let device = Arc::new(MockBackend::new(DebugFlags::empty()).create_device(0));
+
+        let mut buffers = RenderHandleAllocator::<Buffer>::new();
+        let args = buffers.allocate();
+        device.bind_buffer(
+            args,
+            BufferDesc {
+                name: None,
+                size: 16,
+                stride: 0,
+                usage: BufferUsages::Indirect,
+            },
+            Some(bytemuck::cast_slice(&[0u32, 4, 2, 1])),
+        );
+
+        let module = RenderHandleAllocator::<ShaderModule>::new().allocate();
+        device
+            .bind_shader_module(
+                module,
+                ShaderModuleDesc {
+                    name: None,
+                    source: ShaderSource::Hlsl("".into()),
+                    entry_points: vec![EntryPoint {
+                        name: "main".into(),
+                        stage: ShaderStage::Compute,
+                    }],
+                },
+            )
+            .unwrap();
+
+        let pipeline = RenderHandleAllocator::<ComputePipeline>::new().allocate();
+        device
+            .bind_compute_pipeline(
+                pipeline,
+                ComputePipelineDesc {
+                    name: None,
+                    layout: PipelineLayout {
+                        bindings: vec![PipelineBinding::Constants {
+                            register: 0,
+                            space: 0,
+                            count: 1,
+                        }],
+                    },
+                    shader: ShaderEntry {
+                        module,
+                        name: "main".into(),
+                    },
+                },
+            )
+            .unwrap();
+
+        let mut cmd = device.create_command_buffer(CommandBufferType::Graphics);
+        {
+            let encoder = cmd.compute_encoder();
+            DynComputeEncoder::set_pipeline(&encoder, pipeline);
+            encoder.set_constants(0, &[7]);
+            encoder.dispatch(8, 8, 1);
+            DynComputeEncoder::dispatch_indirect(&encoder, args, 4).unwrap();
+            assert_eq!(
+                DynComputeEncoder::dispatch_indirect(&encoder, buffers.allocate(), 0),
+                Err(IndirectError::DeadHandle)
+            );
+        }
+
+        let dispatches = cmd.dispatches();
+        assert_eq!(dispatches[0].groups, [8, 8, 1]);
+        assert_eq!(dispatches[0].constants, vec![(0, vec![7])]);
+        assert_eq!(dispatches[1].groups, [4, 2, 1]);
+        assert!(dispatches[1].indirect);
+
+        assert_eq!(
+            device.handles.buffers.lock().get(args).unwrap().state(),
+            ResourceState::Indirect
+        );
+    }
+
+    #[test]
+    fn indirect_count_draws_read_arguments_at_execution() {
+        let device = Arc::new(MockBackend::new(DebugFlags::empty()).create_device(0));
+        let mut allocator = RenderHandleAllocator::<Buffer>::new();
+
+        let args = (0..3)
+            .map(|i| DrawIndexedIndirectArgs {
+                index_count: 6,
+                instance_count: 1,
+                first_index: 6 * i,
+                base_vertex: 0,
+                first_instance: i,
+            })
+            .collect::<Vec<_>>();
+
+        let (args_buffer, count_buffer, indices) = (
+            allocator.allocate(),
+            allocator.allocate(),
+            allocator.allocate(),
+        );
+        device.bind_buffer(
+            args_buffer,
+            BufferDesc {
+                name: None,
+                size: 60,
+                stride: 20,
+                usage: BufferUsages::Indirect,
+            },
+            Some(bytemuck::cast_slice(&args)),
+        );
+        device.bind_buffer(
+            count_buffer,
+            BufferDesc {
+                name: None,
+                size: 4,
+                stride: 4,
+                usage: BufferUsages::Indirect | BufferUsages::Storage,
+            },
+            None,
+        );
+        device.bind_buffer(
+            indices,
+            BufferDesc {
+                name: None,
+                size: 72,
+                stride: 4,
+                usage: BufferUsages::Index,
+            },
+            None,
+        );
+        let view = RenderHandleAllocator::<BufferView>::new().allocate();
+        device
+            .bind_buffer_view(view, indices, BufferViewDesc::index(Format::R32Uint))
+            .unwrap();
+
+        let pipeline = render_pipeline(&device);
+
+        let mut cmd = device.create_command_buffer(CommandBufferType::Graphics);
+        {
+            let encoder = cmd.render_encoder(&RenderPassDesc {
+                buffer_views: vec![view],
+                ..Default::default()
+            });
+            DynRenderEncoder::set_pipeline(&encoder, pipeline);
+            DynRenderEncoder::set_index_buffer(&encoder, view);
+
+            assert_eq!(
+                DynRenderEncoder::draw_indexed_indirect(&encoder, indices, 0, 1),
+                Err(IndirectError::MissingUsage)
+            );
+            assert_eq!(
+                DynRenderEncoder::draw_indexed_indirect(&encoder, allocator.allocate(), 0, 1),
+                Err(IndirectError::DeadHandle)
+            );
+            assert_eq!(
+                DynRenderEncoder::draw_indexed_indirect_count(
+                    &encoder,
+                    args_buffer,
+                    0,
+                    allocator.allocate(),
+                    0,
+                    3,
+                ),
+                Err(IndirectError::DeadHandle)
+            );
+            DynRenderEncoder::draw_indexed_indirect_count(
+                &encoder,
+                args_buffer,
+                0,
+                count_buffer,
+                0,
+                3,
+            )
+            .unwrap();
+        }
+
+        // Culling pass writing the visible count after the draw was recorded.
+        device
+            .handles
+            .buffers
+            .lock()
+            .get(count_buffer)
+            .unwrap()
+            .data
+            .lock()
+            .copy_from_slice(&2u32.to_ne_bytes());
+
+        let draws = cmd.draws();
+        assert_eq!(draws.len(), 2);
+        assert!(draws.iter().all(|d| d.indexed && d.indirect));
+        assert_eq!(draws[1].vertices, 6..12);
+        assert_eq!(draws[1].instances, 1..2);
+
+        let guard = device.handles.buffers.lock();
+        assert_eq!(
+            guard.get(count_buffer).unwrap().state(),
+            ResourceState::Indirect
+        );
+    }
+
+    #[test]
+    fn gpu_scopes_time_nested_work_and_queries_resolve_to_buffers() {
+        let device = Arc::new(MockBackend::new(DebugFlags::GpuTimings).create_device(0));
+
+        let target = RenderHandleAllocator::<Texture>::new().allocate();
+        device.bind_texture(
+            target,
+            TextureDesc {
+                name: None,
+                ty: TextureType::D2,
+                width: 2,
+                height: 2,
+                depth: 1,
+                mip_levels: 1,
+                format: Format::Rgba8Unorm,
+                usage: TextureUsages::RenderTarget,
+            },
+            None,
+        );
+        let buffer = |size, usage| {
+            device.create_buffer::<u8>(
+                BufferDesc {
+                    name: None,
+                    size,
+                    stride: 0,
+                    usage,
+                },
+                None,
+            )
+        };
+        let storage = buffer(16, BufferUsages::Storage);
+        let resolved = buffer(16, BufferUsages::QueryResolve);
+        let queries = device.create_query_set(QuerySetDesc {
+            name: None,
+            ty: QueryType::Timestamp,
+            count: 2,
+        });
+
+        let mut cmd = device.create_command_buffer(CommandBufferType::Graphics);
+        {
+            crate::gpu_scope!("frame", cmd);
+            cmd.write_timestamp(&queries, 0).unwrap();
+            cmd.transfer_encoder()
+                .fill_buffer(&storage, 0..16, 7)
+                .unwrap();
+
+            {
+                crate::gpu_scope!("draws", cmd);
+                let encoder = cmd.render_encoder(&RenderPassDesc {
+                    colors: vec![ColorAttachment::load(target)],
+                    depth_stencil: None,
+                    buffer_views: vec![],
+                });
+
+                RenderEncoder::set_pipeline(&encoder, &MockPipeline::default());
+                for _ in 0..3 {
+                    encoder.draw(0..3);
+                }
+            }
+
+            cmd.write_timestamp(&queries, 1).unwrap();
+            assert_eq!(
+                cmd.begin_query(&queries, 0),
+                Err(QueryError::WrongType {
+                    expected: QueryType::PipelineStatistics,
+                    found: QueryType::Timestamp
+                })
+            );
+            assert_eq!(
+                cmd.resolve_queries(&queries, 0..2, &resolved, 4),
+                Err(QueryError::Misaligned { offset: 4 })
+            );
+            cmd.resolve_queries(&queries, 0..2, &resolved, 0).unwrap();
+        }
+
+        device.push_cmd_buffer(cmd);
+        device.commit(CommandBufferType::Graphics);
+
+        assert_eq!(
+            resolved.read(),
+            [0u64.to_ne_bytes(), 4u64.to_ne_bytes()].concat()
+        );
+
+        let timings = device.gpu_timings();
+        let timings = timings
+            .iter()
+            .map(|t| (t.name, t.depth, (t.milliseconds * 1000.0).round() as u32))
+            .collect::<Vec<_>>();
+        assert_eq!(timings, [("draws", 1, 3), ("frame", 0, 5)]);
+    }
+
+    #[test]
+    fn debug_groups_and_markers_are_logged_in_place() {
+        let device = Arc::new(MockBackend::new(DebugFlags::Pix).create_device(0));
+        let storage = device.create_buffer::<u8>(
+            BufferDesc {
+                name: None,
+                size: 16,
+                stride: 0,
+                usage: BufferUsages::Storage,
+            },
+            None,
+        );
+
+        let mut cmd = device.create_command_buffer(CommandBufferType::Graphics);
+        {
+            let encoder = cmd.transfer_encoder();
+            let group = encoder.debug_group("upload");
+            group.insert_marker("zero");
+            group.clear_buffer(&storage, 0..16).unwrap();
+        }
+        {
+            let encoder = cmd.compute_encoder();
+            encoder.push_debug_group("culling");
+            ComputeEncoder::set_pipeline(&encoder, &MockPipeline::default());
+            encoder.dispatch(1, 1, 1);
+            encoder.pop_debug_group();
+        }
+
+        assert_eq!(
+            cmd.log(),
+            [
+                MockLogEntry::PushDebugGroup("upload".into()),
+                MockLogEntry::Marker("zero".into()),
+                MockLogEntry::Command("fill"),
+                MockLogEntry::PopDebugGroup,
+                MockLogEntry::PushDebugGroup("culling".into()),
+                MockLogEntry::Command("dispatch"),
+                MockLogEntry::PopDebugGroup,
+            ]
+        );
+    }
+
+    #[test]
+    fn async_queues_only_run_once_waited_for() {
+        let device = Arc::new(MockBackend::new(DebugFlags::empty()).create_device(0));
+        let buffer = |usage, data: Option<&[u8]>| {
+            device.create_buffer(
+                BufferDesc {
+                    name: None,
+                    size: 4,
+                    stride: 0,
+                    usage,
+                },
+                data,
+            )
+        };
+        let upload = buffer(BufferUsages::Copy, Some(&[1, 2, 3, 4]));
+        let shared = buffer(BufferUsages::Storage, None);
+        let (stale, fresh) = (
+            buffer(BufferUsages::Storage, None),
+            buffer(BufferUsages::Storage, None),
+        );
+
+        let mut cmd = device.create_command_buffer(CommandBufferType::Transfer);
+        cmd.transfer_encoder()
+            .copy_buffer_region(&shared, 0, &upload, 0, 4)
+            .unwrap();
+        device.push_cmd_buffer(cmd);
+        let uploaded = device.commit(CommandBufferType::Transfer);
+
+        let copy_shared = |dst| {
+            let mut cmd = device.create_command_buffer(CommandBufferType::Graphics);
+            cmd.transfer_encoder()
+                .copy_buffer_region(dst, 0, &shared, 0, 4)
+                .unwrap();
+            device.push_cmd_buffer(cmd);
+        };
+
+        copy_shared(&stale);
+        device.commit(CommandBufferType::Graphics);
+        assert_eq!(device.transfer_queue.completed(), 0);
+
+        copy_shared(&fresh);
+        device.commit_with_waits(CommandBufferType::Graphics, &[uploaded]);
+        assert_eq!(device.transfer_queue.completed(), uploaded.value());
+
+        assert_eq!(stale.read(), [0; 4]);
+        assert_eq!(fresh.read(), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn shared_fences_order_work_across_devices() {
+        let backend = MockBackend::new(DebugFlags::empty());
+        let group = RenderDeviceGroup::new(
+            Arc::new(backend.create_device(0)),
+            vec![Arc::new(backend.create_device(1))],
+        );
+        let (primary, secondary) = (group.device(0), group.device(1));
+
+        let desc = |usage| TextureDesc {
+            name: None,
+            ty: TextureType::D2,
+            width: 1,
+            height: 1,
+            depth: 1,
+            mip_levels: 1,
+            format: Format::R32,
+            usage,
+        };
+        let source = primary.create_texture(desc(TextureUsages::empty()), Some(&[7u32]));
+        let shared = primary.create_texture::<u8>(desc(TextureUsages::Shared), None);
+        let opened = secondary.open_texture(&shared, primary);
+        let (stale, fresh) = (
+            secondary.create_texture::<u8>(desc(TextureUsages::empty()), None),
+            secondary.create_texture::<u8>(desc(TextureUsages::empty()), None),
+        );
+
+        let mut cmd = primary.create_command_buffer(CommandBufferType::Compute);
+        cmd.transfer_encoder()
+            .copy_texture_to_texture(&shared, &source)
+            .unwrap();
+        primary.push_cmd_buffer(cmd);
+        primary.commit(CommandBufferType::Compute);
+
+        let copy_opened = |dst| {
+            let mut cmd = secondary.create_command_buffer(CommandBufferType::Graphics);
+            cmd.transfer_encoder()
+                .copy_texture_to_texture(dst, &opened)
+                .unwrap();
+            secondary.push_cmd_buffer(cmd);
+            secondary.commit(CommandBufferType::Graphics);
+        };
+
+        copy_opened(&stale);
+
+        let rendered = group.signal_shared(0, CommandBufferType::Compute);
+        group.wait_shared(1, CommandBufferType::Graphics, rendered);
+        copy_opened(&fresh);
+
+        assert_eq!((rendered.device(), rendered.value()), (primary.tag(), 1));
+        assert_eq!(stale.read(), [0; 4]);
+        assert_eq!(fresh.read(), 7u32.to_ne_bytes());
+
+        // Every device queue has a timeline of its own.
+        let copied = group.signal_shared(1, CommandBufferType::Graphics);
+        assert_eq!((copied.device(), copied.value()), (secondary.tag(), 1));
+        group.wait_shared(0, CommandBufferType::Graphics, copied);
+        assert_eq!(
+            group.signal_shared(0, CommandBufferType::Compute).value(),
+            2
+        );
+    }
+
+    #[test]
+    fn parallel_slots_submit_in_slot_order() {
+        let device = MockBackend::new(DebugFlags::empty()).create_device(0);
+        let buffer = |size| {
+            device.create_buffer::<u8>(
+                BufferDesc {
+                    name: None,
+                    size,
+                    stride: 0,
+                    usage: BufferUsages::Storage,
+                },
+                None,
+            )
+        };
+        let counter = buffer(4);
+        let order = buffer(32);
+
+        // Every slot records the counter before bumping it, so the recorded
+        // values only count up if the slots ran in order.
+        let slots = device.reserve_slots(CommandBufferType::Graphics, 8);
+        std::thread::scope(|scope| {
+            for index in (0..slots.len()).rev() {
+                let (device, slots, counter, order) = (&device, &slots, &counter, &order);
+                scope.spawn(move || {
+                    let mut cmd = device.allocate_command_buffer(CommandBufferType::Graphics);
+                    {
+                        let encoder = cmd.transfer_encoder();
+                        encoder
+                            .copy_buffer_region(order, index * 4, counter, 0, 4)
+                            .unwrap();
+                        encoder
+                            .fill_buffer(counter, 0..4, index as u32 + 1)
+                            .unwrap();
+                    }
+
+                    slots.fill(index, cmd);
+                });
+            }
+        });
+        device.commit_slots(slots);
+
+        let expected = (0..8u32).collect::<Vec<_>>();
+        assert_eq!(order.read(), bytemuck::cast_slice::<u32, u8>(&expected));
+        assert_eq!(counter.read(), 8u32.to_ne_bytes());
+    }
+
+    #[test]
+    fn bundles_replay_in_compatible_passes_until_unbound() {
+        let device = Arc::new(MockBackend::new(DebugFlags::empty()).create_device(0));
+
+        let mut textures = RenderHandleAllocator::<Texture>::new();
+        let (target, other) = (textures.allocate(), textures.allocate());
+        for (handle, format) in [(target, Format::Rgba8Unorm), (other, Format::R32)] {
+            device.bind_texture(
+                handle,
+                TextureDesc {
+                    name: None,
+                    ty: TextureType::D2,
+                    width: 4,
+                    height: 4,
+                    depth: 1,
+                    mip_levels: 1,
+                    format,
+                    usage: TextureUsages::RenderTarget,
+                },
+                None,
+            );
+        }
+
+        let vertices = RenderHandleAllocator::<Buffer>::new().allocate();
+        device.bind_buffer(
+            vertices,
+            BufferDesc {
+                name: None,
+                size: 36,
+                stride: 12,
+                usage: BufferUsages::Vertex,
+            },
+            None,
+        );
+        let mut views = RenderHandleAllocator::<BufferView>::new();
+        let view = views.allocate();
+        device
+            .bind_buffer_view(view, vertices, BufferViewDesc::vertex(12))
+            .unwrap();
+        let pipeline = render_pipeline(&device);
+
+        let bundle_desc = BundleDesc {
+            name: None,
+            color_formats: vec![Format::Rgba8Unorm],
+            depth_format: None,
+        };
+        let shared = views.allocate();
+        device
+            .bind_buffer_view(shared, vertices, BufferViewDesc::vertex(12))
+            .unwrap();
+        let encoder = device.bundle_encoder(bundle_desc.clone());
+        DynRenderEncoder::set_vertex_buffer(&encoder, 0, shared);
+        let through_buffer = device.finish_bundle(encoder);
+
+        let encoder = device.bundle_encoder(bundle_desc);
+        DynRenderEncoder::set_pipeline(&encoder, pipeline);
+        DynRenderEncoder::set_vertex_buffer(&encoder, 0, view);
+        encoder.insert_marker("static");
+        // Ignored, the draws keep the viewport of the pass.
+        encoder.set_viewport(Viewport::new(1.0, 1.0));
+        encoder.draw(0..3);
+        encoder.draw(3..6);
+        let bundle = device.finish_bundle(encoder);
+
+        assert_eq!(
+            bundle.log(),
+            [
+                MockLogEntry::Marker("static".into()),
+                MockLogEntry::Command("draw"),
+                MockLogEntry::Command("draw")
+            ]
+        );
+
+        let pass = |target| RenderPassDesc {
+            colors: vec![ColorAttachment::clear(target, [0.0; 4])],
+            depth_stencil: None,
+            buffer_views: vec![view],
+        };
+        let mut cmd = device.create_command_buffer(CommandBufferType::Graphics);
+        {
+            let encoder = cmd.render_encoder(&pass(target));
+            encoder.set_viewport(Viewport::new(2.0, 2.0));
+            encoder.execute_bundle(&bundle).unwrap();
+            encoder.execute_bundle(&bundle).unwrap();
+        }
+        {
+            let encoder = cmd.render_encoder(&pass(other));
+            assert_eq!(
+                encoder.execute_bundle(&bundle),
+                Err(BundleError::IncompatiblePass)
+            );
+        }
+
+        let draws = cmd.draws();
+        assert_eq!(draws.len(), 4);
+        assert_eq!(draws[3].vertices, 3..6);
+        assert_eq!(draws[3].viewport, Viewport::new(2.0, 2.0));
+        assert_eq!(draws[3].scissor, Scissor::new(4, 4));
+        assert_eq!(draws[3].vertex_buffers, vec![0]);
+
+        device.unbind_buffer_view(view);
+        let encoder = cmd.render_encoder(&pass(target));
+        assert_eq!(
+            encoder.execute_bundle(&bundle),
+            Err(BundleError::Invalidated)
+        );
+        assert_eq!(encoder.execute_bundle(&through_buffer), Ok(()));
+
+        // The buffer behind a view is referenced as well.
+        device.unbind_buffer(vertices);
+        assert_eq!(
+            encoder.execute_bundle(&through_buffer),
+            Err(BundleError::Invalidated)
+        );
+    }
+
+    #[test]
+    fn command_streams_dump_recorded_work_as_text() {
+        let device = Arc::new(MockBackend::new(DebugFlags::CommandStream).create_device(0));
+
+        let target = RenderHandleAllocator::<Texture>::new().allocate();
+        device.bind_texture(
+            target,
+            TextureDesc {
+                name: None,
+                ty: TextureType::D2,
+                width: 2,
+                height: 2,
+                depth: 1,
+                mip_levels: 1,
+                format: Format::Rgba8Unorm,
+                usage: TextureUsages::RenderTarget,
+            },
+            None,
+        );
+
+        let mut buffers = RenderHandleAllocator::<Buffer>::new();
+        let (staging, vertices) = (buffers.allocate(), buffers.allocate());
+        for (handle, usage) in [
+            (staging, BufferUsages::Copy),
+            (vertices, BufferUsages::Vertex),
+        ] {
+            let desc = BufferDesc {
+                name: None,
+                size: 36,
+                stride: 12,
+                usage,
+            };
+            device.bind_buffer(handle, desc, None);
+        }
+        let view = RenderHandleAllocator::<BufferView>::new().allocate();
+        device
+            .bind_buffer_view(view, vertices, BufferViewDesc::vertex(12))
+            .unwrap();
+        let pipeline = render_pipeline(&device);
+
+        let mut cmd = device.create_command_buffer(CommandBufferType::Graphics);
+        crate::graphics::core::commands::DynTransferEncoder::copy_buffer_to_buffer(
+            &cmd.transfer_encoder(),
+            vertices,
+            staging,
+        )
+        .unwrap();
+        {
+            let encoder = cmd.render_encoder(&RenderPassDesc {
+                colors: vec![ColorAttachment::clear(target, [0.0, 0.0, 0.0, 1.0])],
+                depth_stencil: None,
+                buffer_views: vec![view],
+            });
+            encoder.push_debug_group("opaque");
+            DynRenderEncoder::set_pipeline(&encoder, pipeline);
+            DynRenderEncoder::set_vertex_buffer(&encoder, 0, view);
+            encoder.draw(0..3);
+            encoder.pop_debug_group();
+        }
+
+        assert_eq!(
+            cmd.command_stream().unwrap().to_string(),
+            "barrier buffer#1 -> CopyDst\n\
+             barrier buffer#0 -> CopySrc\n\
+             copy_buffer buffer#1 <- buffer#0\n\
+             barrier buffer#1 -> VertexBuffer\n\
+             begin_render_pass texture#0 Clear([0.0, 0.0, 0.0, 1.0]) view buffer_view#0\n\
+             \x20 push_debug_group \"opaque\"\n\
+             \x20   set_pipeline render_pipeline#0\n\
+             \x20   set_vertex_buffer 0 buffer_view#0\n\
+             \x20   draw vertices 0..3 instances 0..1\n\
+             \x20 pop_debug_group\n\
+             end_render_pass\n"
+        );
+
+        let device = MockBackend::new(DebugFlags::empty()).create_device(0);
+        let cmd = device.create_command_buffer(CommandBufferType::Graphics);
+        assert_eq!(cmd.command_stream(), None);
+    }
+
+    #[test]
+    fn captured_frames_replay_on_another_device() {
+        let device = Arc::new(MockBackend::new(DebugFlags::empty()).create_device(0));
+
+        let mut buffers = RenderHandleAllocator::<Buffer>::new();
+        let (src, dst) = (buffers.allocate(), buffers.allocate());
+        let desc = BufferDesc {
+            name: None,
+            size: 16,
+            stride: 0,
+            usage: BufferUsages::Storage | BufferUsages::Copy,
+        };
+        device.bind_buffer(src, desc.clone(), Some(&[7; 16]));
+        device.bind_buffer(dst, desc, None);
+
+        device.begin_capture(1);
+        let mut cmd = device.create_command_buffer(CommandBufferType::Graphics);
+        {
+            let encoder = cmd.transfer_encoder();
+            crate::graphics::core::commands::DynTransferEncoder::fill_buffer(
+                &encoder,
+                dst,
+                0..16,
+                0x01010101,
+            )
+            .unwrap();
+            crate::graphics::core::commands::DynTransferEncoder::copy_buffer_region(
+                &encoder, dst, 8, src, 0, 8,
+            )
+            .unwrap();
+        }
+        device.push_cmd_buffer(cmd);
+        assert!(device.take_capture().is_none());
+        device.commit(CommandBufferType::Graphics);
+
+        let mut file = vec![];
+        device.take_capture().unwrap().write_to(&mut file).unwrap();
+        let capture = Capture::read_from(file.as_slice()).unwrap();
+        assert_eq!(capture.buffers.len(), 2);
+
+        let replayed = Arc::new(MockBackend::new(DebugFlags::empty()).create_device(0));
+        capture.replay(&replayed);
+        assert_eq!(replayed.read_buffer(dst), device.read_buffer(dst));
+
+        let (fill, _) = capture
+            .commands()
+            .find(|(_, c)| matches!(c, StreamCommand::FillBuffer { .. }))
+            .unwrap();
+        let stepped = Arc::new(MockBackend::new(DebugFlags::empty()).create_device(0));
+        capture.replay_until(&stepped, Some(fill));
+        assert_eq!(stepped.read_buffer(dst), Some(vec![1; 16]));
+        assert_eq!(stepped.read_buffer(src), Some(vec![7; 16]));
+    }
+
+    #[test]
+    fn gpu_futures_resolve_when_the_queue_is_flushed() {
+        struct Unpark(std::thread::Thread);
+
+        impl std::task::Wake for Unpark {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let device = MockBackend::new(DebugFlags::empty()).create_device(0);
+        let cmd = device.create_command_buffer(CommandBufferType::Transfer);
+        device.push_cmd_buffer(cmd);
+        let mut future = device.commit_future(CommandBufferType::Transfer);
+        let uploaded = future.sync_point();
+
+        assert!(!future.is_complete());
+        assert_eq!(
+            future.wait_timeout(Duration::from_millis(1)),
+            Err(WaitTimeout {
+                sync_point: future.sync_point(),
+                completed: 0
+            })
+        );
+
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                std::thread::sleep(Duration::from_millis(20));
+                device.wait_cpu(uploaded);
+            });
+
+            let waker = Arc::new(Unpark(std::thread::current())).into();
+            let mut cx = std::task::Context::from_waker(&waker);
+            while std::pin::Pin::new(&mut future).poll(&mut cx).is_pending() {
+                std::thread::park();
+            }
+        });
+
+        assert!(future.is_complete());
+        assert_eq!(future.wait_timeout(Duration::ZERO), Ok(()));
+    }
+
+    #[test]
+    fn sync_points_only_work_on_their_own_timeline() {
+        let first = MockBackend::new(DebugFlags::empty()).create_device(0);
+        let second = MockBackend::new(DebugFlags::empty()).create_device(0);
+
+        let rendered = first.commit(CommandBufferType::Graphics);
+        let uploaded = first.commit(CommandBufferType::Transfer);
+        assert!(rendered < first.commit(CommandBufferType::Graphics));
+
+        let panics =
+            |f: &dyn Fn()| std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).is_err();
+        assert!(panics(&|| second.wait_cpu(rendered)));
+        assert!(panics(
+            &|| second.wait_gpu(CommandBufferType::Compute, uploaded)
+        ));
+        assert_eq!(rendered.partial_cmp(&uploaded), None);
+        assert_eq!(
+            rendered.try_cmp(uploaded),
+            Err(SyncPointError::DifferentTimelines)
+        );
+    }
+
+    #[test]
+    fn validation_reports_misuse_with_handle_names() {
+        let device = Arc::new(MockBackend::new(DebugFlags::CpuValidation).create_device(0));
+
+        let mut buffers = RenderHandleAllocator::<Buffer>::new();
+        let (vertices, staging) = (buffers.allocate(), buffers.allocate());
+        for (handle, name, usage) in [
+            (vertices, "vertices", BufferUsages::Vertex),
+            (staging, "staging", BufferUsages::Copy),
+        ] {
+            let desc = BufferDesc {
+                name: Some(name.into()),
+                size: 64,
+                stride: 16,
+                usage,
+            };
+            device.bind_buffer(handle, desc, None);
+        }
+
+        let albedo = RenderHandleAllocator::<Texture>::new().allocate();
+        device.bind_texture(
+            albedo,
+            TextureDesc {
+                name: Some("albedo".into()),
+                ty: TextureType::D2,
+                width: 4,
+                height: 4,
+                depth: 1,
+                mip_levels: 1,
+                format: Format::Rgba8Unorm,
+                usage: TextureUsages::Resource,
+            },
+            None,
+        );
+
+        let mut cmd = device.create_command_buffer(CommandBufferType::Graphics);
+        {
+            let guard = device.handles.buffers.lock();
+            cmd.set_buffer_barriers(&[BufferBarrier {
+                buffer: guard.get(vertices).unwrap(),
+                state: ResourceState::RenderTarget,
+            }]);
+        }
+        {
+            let encoder = cmd.transfer_encoder();
+            assert!(
+                crate::graphics::core::commands::DynTransferEncoder::copy_buffer_region(
+                    &encoder, vertices, 60, staging, 0, 16
+                )
+                .is_err()
+            );
+            assert!(
+                crate::graphics::core::commands::DynTransferEncoder::fill_buffer(
+                    &encoder,
+                    vertices,
+                    0..16,
+                    0
+                )
+                .is_err()
+            );
+        }
+
+        device.unbind_buffer(staging);
+        assert_eq!(
+            crate::graphics::core::commands::DynTransferEncoder::copy_buffer_to_buffer(
+                &cmd.transfer_encoder(),
+                vertices,
+                staging,
+            ),
+            Err(TransferError::DeadHandle)
+        );
+
+        {
+            let encoder = cmd.render_encoder(&RenderPassDesc {
+                colors: vec![ColorAttachment::load(albedo)],
+                depth_stencil: None,
+                buffer_views: vec![],
+            });
+            assert_eq!(
+                DynRenderEncoder::draw_indirect(&encoder, vertices, 0, 1),
+                Err(IndirectError::MissingUsage)
+            );
+        }
+
+        // Leaking an encoder leaves its pass open for whatever is recorded next.
+        std::mem::forget(cmd.render_encoder(&RenderPassDesc {
+            colors: vec![],
+            depth_stencil: None,
+            buffer_views: vec![],
+        }));
+        let _ = cmd.transfer_encoder();
+        device.push_cmd_buffer(cmd);
+
+        let vertices = "buffer#0 \"vertices\"".to_owned();
+        assert_eq!(
+            device.validation_errors(),
+            [
+                ValidationError::StateMismatch {
+                    resource: vertices.clone(),
+                    state: ResourceState::RenderTarget,
+                },
+                ValidationError::Transfer {
+                    resource: vertices.clone(),
+                    error: TransferError::OutOfBounds { end: 76, size: 64 },
+                },
+                ValidationError::Transfer {
+                    resource: vertices.clone(),
+                    error: TransferError::Unsupported("fills need a storage buffer"),
+                },
+                ValidationError::DeadHandle("buffer#1 \"staging\"".to_owned()),
+                ValidationError::MissingTextureUsage {
+                    texture: "texture#0 \"albedo\"".to_owned(),
+                    usage: TextureUsages::RenderTarget,
+                },
+                ValidationError::MissingBufferUsage {
+                    buffer: vertices,
+                    usage: BufferUsages::Indirect,
+                },
+                ValidationError::EncoderInterleaved("transfer encoder"),
+                ValidationError::UnclosedRenderPass,
+            ]
+        );
+
+        let device = MockBackend::new(DebugFlags::empty()).create_device(0);
+        assert!(device.validation_errors().is_empty());
+    }
+
+    #[test]
+    fn queue_handoffs_need_a_release_unless_common() {
+        let device = Arc::new(
+            MockBackend::new(DebugFlags::CpuValidation | DebugFlags::CommandStream)
+                .create_device(0),
+        );
+
+        let mut buffers = RenderHandleAllocator::<Buffer>::new();
+        let (particles, staging) = (buffers.allocate(), buffers.allocate());
+        for (handle, name, usage) in [
+            (particles, "particles", BufferUsages::Storage),
+            (staging, "staging", BufferUsages::Copy),
+        ] {
+            let desc = BufferDesc {
+                name: Some(name.into()),
+                size: 64,
+                stride: 16,
+                usage,
+            };
+            device.bind_buffer(handle, desc, None);
+        }
+
+        let fill = |cmd: &mut MockCommandBuffer| {
+            crate::graphics::core::commands::DynTransferEncoder::fill_buffer(
+                &cmd.transfer_encoder(),
+                particles,
+                0..64,
+                7,
+            )
+            .unwrap();
+        };
+
+        let mut simulate = device.create_command_buffer(CommandBufferType::Compute);
+        fill(&mut simulate);
+        // Transfer queues can not read storage buffers, the release moves it to Common.
+        simulate.release_buffer(particles, CommandBufferType::Transfer);
+        assert!(simulate
+            .command_stream()
+            .unwrap()
+            .to_string()
+            .ends_with("barrier buffer#0 -> Common\nrelease buffer#0 -> Transfer\n"));
+
+        let mut readback = device.create_command_buffer(CommandBufferType::Transfer);
+        crate::graphics::core::commands::DynTransferEncoder::copy_buffer_region(
+            &readback.transfer_encoder(),
+            staging,
+            0,
+            particles,
+            0,
+            64,
+        )
+        .unwrap();
+        assert!(device.validation_errors().is_empty());
+
+        fill(&mut device.create_command_buffer(CommandBufferType::Compute));
+        device
+            .create_command_buffer(CommandBufferType::Graphics)
+            .release_buffer(particles, CommandBufferType::Compute);
+        let guard = device.handles.buffers.lock();
+        readback.set_buffer_barriers(&[BufferBarrier {
+            buffer: guard.get(particles).unwrap(),
+            state: ResourceState::StorageRead,
+        }]);
+
+        let particles = "buffer#0 \"particles\"".to_owned();
+        let ownership = |error| ValidationError::Ownership {
+            resource: particles.clone(),
+            error,
+        };
+        assert_eq!(
+            device.validation_errors(),
+            [
+                ownership(OwnershipError::NotReleased {
+                    owner: CommandBufferType::Transfer,
+                    queue: CommandBufferType::Compute,
+                }),
+                ownership(OwnershipError::NotOwner {
+                    owner: CommandBufferType::Compute,
+                    queue: CommandBufferType::Graphics,
+                }),
+                ownership(OwnershipError::ReleasedElsewhere {
+                    to: CommandBufferType::Compute,
+                    queue: CommandBufferType::Transfer,
+                }),
+                ownership(OwnershipError::UnsupportedState {
+                    state: ResourceState::StorageRead,
+                    queue: CommandBufferType::Transfer,
+                }),
+            ]
+        );
+    }
+
+    /// Binds a pipeline drawing into a single `Rgba8Unorm` target.
+    fn render_pipeline(device: &MockRenderContext) -> RenderHandle<RenderPipeline> {
+        let module = RenderHandleAllocator::<ShaderModule>::new().allocate();
+        device
+            .bind_shader_module(
+                module,
+                ShaderModuleDesc {
+                    name: None,
+                    source: ShaderSource::Hlsl("".into()),
+                    entry_points: vec![
+                        EntryPoint {
+                            name: "vs_main".into(),
+                            stage: ShaderStage::Vertex,
+                        },
+                        EntryPoint {
+                            name: "ps_main".into(),
+                            stage: ShaderStage::Pixel,
+                        },
+                    ],
+                },
+            )
+            .unwrap();
+
+        let pipeline = RenderHandleAllocator::<RenderPipeline>::new().allocate();
+        device
+            .bind_render_pipeline(
+                pipeline,
+                RenderPipelineDesc {
+                    name: None,
+                    layout: PipelineLayout::default(),
+                    vertex: ShaderEntry {
+                        module,
+                        name: "vs_main".into(),
+                    },
+                    pixel: Some(ShaderEntry {
+                        module,
+                        name: "ps_main".into(),
+                    }),
+                    vertex_attributes: vec![VertexAttribute {
+                        semantic: VertexSemantic::Position,
+                        index: 0,
+                        format: Format::Rgb32,
+                        slot: 0,
+                        offset: 0,
+                    }],
+                    topology: PrimitiveTopology::TriangleList,
+                    color_formats: vec![Format::Rgba8Unorm],
+                    depth_format: None,
+                },
+            )
+            .unwrap();
+
+        pipeline
+    }
+}
@@ -0,0 +1,528 @@
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use tracing::{debug, info};
+
+use crate::graphics::{
+    context::RenderContext,
+    core::{
+        backend::RenderDeviceInfo,
+        bundle::BundleTracker,
+        capture::{Capture, CaptureRecorder},
+        commands::{CommandBuffer, CommandBufferType, DeviceTag, FenceValue},
+        handle::{RenderHandle, SparseArray},
+        memory::{MemoryCounters, MemoryLocation},
+        profiler::{self, GpuProfiler, GpuTiming},
+        residency::{ResidencyPriority, ResidencySet},
+        resource::{
+            Buffer, BufferDesc, BufferView, BufferViewDesc, BufferViewError, ResourceDevice,
+            Sampler, SamplerDesc, Texture, TextureDesc, TextureViewDesc,
+        },
+        shader::{
+            ComputePipeline, ComputePipelineDesc, PipelineError, RenderPipeline,
+            RenderPipelineDesc, ShaderEntry, ShaderError, ShaderModule, ShaderModuleDesc,
+            ShaderStage,
+        },
+        stream::StreamRef,
+        validation::{ValidationError, Validator},
+    },
+    DebugFlags,
+};
+
+use super::{
+    commands::{MockCommandQueue, MOCK_TIMESTAMP_PERIOD},
+    resources::{
+        MockBuffer, MockBufferView, MockPipeline, MockQuerySet, MockShaderModule, MockTexture,
+    },
+};
+
+#[derive(Debug)]
+pub struct MockRenderContext {
+    pub(super) gfx_queue: MockCommandQueue,
+    pub(super) compute_queue: MockCommandQueue,
+    pub(super) transfer_queue: MockCommandQueue,
+
+    pub(super) desc: RenderDeviceInfo,
+    pub(super) tag: DeviceTag,
+    pub(super) debug: DebugFlags,
+
+    pub(super) memory: Arc<MemoryCounters>,
+    pub(super) memory_budget: Option<u64>,
+
+    pub(super) handles: Arc<HandleStorage>,
+    pub(super) finished_capture: Mutex<Option<Capture>>,
+}
+
+impl MockRenderContext {
+    pub(super) fn new(
+        desc: RenderDeviceInfo,
+        debug: DebugFlags,
+        memory_budget: Option<u64>,
+    ) -> Self {
+        info!(
+            "Creating mock device with adapter {} and id {}",
+            desc.name, desc.id
+        );
+
+        let context = Self {
+            gfx_queue: MockCommandQueue::new(CommandBufferType::Graphics, debug),
+            compute_queue: MockCommandQueue::new(CommandBufferType::Compute, debug),
+            transfer_queue: MockCommandQueue::new(CommandBufferType::Transfer, debug),
+            desc,
+            tag: DeviceTag::unique(),
+            debug,
+            memory: Arc::new(MemoryCounters::default()),
+            memory_budget,
+            handles: Arc::new(HandleStorage {
+                buffers: Mutex::new(SparseArray::new(128)),
+                buffer_views: Mutex::new(SparseArray::new(128)),
+                textures: Mutex::new(SparseArray::new(128)),
+                samplers: Mutex::new(SparseArray::new(128)),
+                shader_modules: Mutex::new(SparseArray::new(128)),
+                compute_pipelines: Mutex::new(SparseArray::new(128)),
+                render_pipelines: Mutex::new(SparseArray::new(128)),
+                residency: Mutex::new(ResidencySet::new(true)),
+                profiler: Mutex::new(GpuProfiler::new(MOCK_TIMESTAMP_PERIOD)),
+                bundles: Default::default(),
+                capture: Default::default(),
+                validator: debug
+                    .contains(DebugFlags::CpuValidation)
+                    .then(Validator::new),
+            }),
+            finished_capture: Default::default(),
+        };
+
+        context
+            .handles
+            .profiler
+            .lock()
+            .begin_frame(|| context.profiler_frame());
+
+        context
+    }
+
+    fn profiler_frame(&self) -> (MockQuerySet, MockBuffer) {
+        (
+            self.create_query_set(profiler::query_set_desc()),
+            self.create_buffer::<u8>(profiler::readback_desc(), None),
+        )
+    }
+
+    /// Records the resolve of this frame's profiler timestamps behind the
+    /// pending graphics work.
+    pub(super) fn resolve_profiler(&self) {
+        let profiler = self.handles.profiler.lock();
+        let Some((queries, range, readback)) = profiler.pending() else {
+            return;
+        };
+
+        let cmd_buffer = self
+            .gfx_queue
+            .allocate_command_buffer(Arc::clone(&self.handles));
+        cmd_buffer
+            .resolve_queries(queries, range, readback, 0)
+            .expect("failed to resolve profiler queries");
+        drop(profiler);
+
+        self.gfx_queue.push_cmd_buffer(cmd_buffer);
+    }
+
+    /// Ends the profiler frame submitted at `sync_point` and reads back every finished one.
+    pub(super) fn collect_profiler(&self, sync_point: FenceValue) {
+        let mut profiler = self.handles.profiler.lock();
+        profiler.end_frame(sync_point);
+
+        let changed = profiler.collect(self.gfx_queue.completed(), |readback| {
+            readback
+                .data
+                .lock()
+                .chunks_exact(8)
+                .map(bytemuck::pod_read_unaligned)
+                .collect()
+        });
+        if changed && self.debug.contains(DebugFlags::GpuTimings) {
+            profiler.log();
+        }
+
+        profiler.begin_frame(|| self.profiler_frame());
+    }
+
+    /// Contents of a bound buffer, to inspect the results of a replayed capture.
+    pub fn read_buffer(&self, handle: RenderHandle<Buffer>) -> Option<Vec<u8>> {
+        self.handles.buffers.lock().get(handle).map(|b| b.read())
+    }
+
+    pub fn read_texture(&self, handle: RenderHandle<Texture>) -> Option<Vec<u8>> {
+        self.handles.textures.lock().get(handle).map(|t| t.read())
+    }
+
+    /// Errors the validation layer reported since the last call, empty without
+    /// [`DebugFlags::CpuValidation`].
+    pub fn validation_errors(&self) -> Vec<ValidationError> {
+        self.handles
+            .validator
+            .as_ref()
+            .map(|v| v.take_errors())
+            .unwrap_or_default()
+    }
+
+    /// Ends the captured frame, called on every graphics commit.
+    pub(super) fn end_capture_frame(&self) {
+        let mut capture = self.handles.capture.lock();
+        let Some(recorder) = capture.as_mut() else {
+            return;
+        };
+
+        if let Some(finished) = recorder.end_frame() {
+            info!("Captured {} frames", finished.frames.len());
+            *self.finished_capture.lock() = Some(finished);
+            *capture = None;
+        }
+    }
+
+    /// Gives up textures until `incoming` more bytes fit in the fake budget.
+    pub(super) fn trim_residency(&self, incoming: u64) {
+        let Some(budget) = self.memory_budget else {
+            return;
+        };
+
+        let usage = self
+            .memory
+            .report(self.desc.id, None)
+            .location(MemoryLocation::Device)
+            .bytes
+            + incoming;
+
+        let changes = self
+            .handles
+            .residency
+            .lock()
+            .trim(usage, budget, self.gfx_queue.completed());
+
+        let textures = self.handles.textures.lock();
+        for change in changes {
+            debug!("Residency change {:?}", change);
+
+            if let Some(texture) = textures.get(change.key) {
+                texture.set_resident_size(change.size);
+            }
+        }
+    }
+}
+
+impl RenderContext for MockRenderContext {
+    fn bind_buffer(
+        self: &Arc<Self>,
+        handle: RenderHandle<Buffer>,
+        desc: BufferDesc,
+        init_data: Option<&[u8]>,
+    ) {
+        let mut buffer = self.create_buffer(desc, init_data);
+        buffer.handle = Some(handle);
+        if let Some(capture) = self.handles.capture.lock().as_mut() {
+            capture.buffer(handle, buffer.desc.clone(), buffer.read());
+        }
+        if let Some(validator) = &self.handles.validator {
+            validator.bind_buffer(handle, &buffer.desc);
+        }
+        self.handles.buffers.lock().set(handle, buffer);
+    }
+
+    fn unbind_buffer(&self, handle: RenderHandle<Buffer>) {
+        self.handles
+            .bundles
+            .lock()
+            .invalidate(|r| r.buffers.contains(&handle));
+        self.handles.unbind(StreamRef::Buffer(handle));
+        self.handles.buffers.lock().remove(handle);
+    }
+
+    fn bind_buffer_view(
+        &self,
+        handle: RenderHandle<BufferView>,
+        buffer: RenderHandle<Buffer>,
+        desc: BufferViewDesc,
+    ) -> Result<(), BufferViewError> {
+        let mut view = {
+            let guard = self.handles.buffers.lock();
+            let buffer = guard.get(buffer).expect("Wrong handle");
+            self.create_buffer_view(buffer, desc)?
+        };
+        view.handle = Some(handle);
+        view.buffer = Some(buffer);
+        if let Some(capture) = self.handles.capture.lock().as_mut() {
+            capture.buffer_view(handle, buffer, desc);
+        }
+        if let Some(validator) = &self.handles.validator {
+            validator.bind_buffer_view(handle, buffer, desc);
+        }
+        self.handles.buffer_views.lock().set(handle, view);
+
+        Ok(())
+    }
+
+    fn unbind_buffer_view(&self, handle: RenderHandle<BufferView>) {
+        self.handles
+            .bundles
+            .lock()
+            .invalidate(|r| r.buffer_views.contains(&handle));
+        self.handles.unbind(StreamRef::BufferView(handle));
+        self.handles.buffer_views.lock().remove(handle);
+    }
+
+    fn bind_texture(
+        self: &Arc<Self>,
+        handle: RenderHandle<Texture>,
+        desc: TextureDesc,
+        init_data: Option<&[u8]>,
+    ) {
+        self.trim_residency(desc.packed_size() as u64);
+
+        self.handles
+            .residency
+            .lock()
+            .insert_texture(handle, ResidencyPriority::default(), &desc);
+
+        let mut texture = self.create_texture(desc, init_data);
+        texture.handle = Some(handle);
+        if let Some(capture) = self.handles.capture.lock().as_mut() {
+            capture.texture(handle, texture.desc.clone(), texture.read());
+        }
+        if let Some(validator) = &self.handles.validator {
+            validator.bind_texture(handle, &texture.desc);
+        }
+        self.handles.textures.lock().set(handle, texture);
+    }
+
+    fn unbind_texture(&self, handle: RenderHandle<Texture>) {
+        self.handles.residency.lock().remove(handle);
+        self.handles.unbind(StreamRef::Texture(handle));
+        self.handles.textures.lock().remove(handle);
+    }
+
+    fn set_texture_priority(&self, handle: RenderHandle<Texture>, priority: ResidencyPriority) {
+        self.handles.residency.lock().set_priority(handle, priority);
+    }
+
+    fn bind_texture_view(
+        &self,
+        handle: RenderHandle<Texture>,
+        texture: RenderHandle<Texture>,
+        desc: TextureViewDesc,
+    ) {
+        let mut view = {
+            let guard = self.handles.textures.lock();
+            let texture = guard.get(texture).expect("Wrong handle");
+            self.create_texture_view(texture, desc)
+        };
+        view.handle = Some(handle);
+        if let Some(validator) = &self.handles.validator {
+            validator.bind_texture(handle, &view.desc);
+        }
+        self.handles.textures.lock().set(handle, view);
+    }
+
+    fn open_texture_handle(&self, handle: RenderHandle<Texture>, other: &Self) {
+        let mut texture = {
+            let guard = other.handles.textures.lock();
+            let texture = guard.get(handle).expect("Wrong handle");
+            self.open_texture(texture, other)
+        };
+        texture.handle = Some(handle);
+        if let Some(validator) = &self.handles.validator {
+            validator.bind_texture(handle, &texture.desc);
+        }
+        self.handles.textures.lock().set(handle, texture);
+    }
+
+    fn bind_sampler(&self, handle: RenderHandle<Sampler>, desc: SamplerDesc) {
+        self.handles.samplers.lock().set(handle, desc);
+    }
+
+    fn unbind_sampler(&self, handle: RenderHandle<Sampler>) {
+        self.handles.samplers.lock().remove(handle);
+    }
+
+    fn bind_shader_module(
+        &self,
+        handle: RenderHandle<ShaderModule>,
+        desc: ShaderModuleDesc,
+    ) -> Result<(), ShaderError> {
+        desc.validate(self.desc.shader_languages)?;
+        if let Some(capture) = self.handles.capture.lock().as_mut() {
+            capture.shader_module(handle, desc.clone());
+        }
+        self.handles
+            .shader_modules
+            .lock()
+            .set(handle, MockShaderModule { handle, desc });
+
+        Ok(())
+    }
+
+    fn unbind_shader_module(&self, handle: RenderHandle<ShaderModule>) {
+        self.handles.shader_modules.lock().remove(handle);
+    }
+
+    fn bind_compute_pipeline(
+        &self,
+        handle: RenderHandle<ComputePipeline>,
+        desc: ComputePipelineDesc,
+    ) -> Result<(), PipelineError> {
+        self.handles
+            .check_entry(&desc.shader, ShaderStage::Compute)?;
+
+        if let Some(capture) = self.handles.capture.lock().as_mut() {
+            capture.compute_pipeline(handle, desc.clone());
+        }
+        if let Some(validator) = &self.handles.validator {
+            validator.bind_pipeline(StreamRef::ComputePipeline(handle));
+        }
+        self.handles.compute_pipelines.lock().set(
+            handle,
+            MockPipeline {
+                handle: Some(handle),
+                desc: Some(desc),
+            },
+        );
+
+        Ok(())
+    }
+
+    fn unbind_compute_pipeline(&self, handle: RenderHandle<ComputePipeline>) {
+        self.handles.unbind(StreamRef::ComputePipeline(handle));
+        self.handles.compute_pipelines.lock().remove(handle);
+    }
+
+    fn bind_render_pipeline(
+        &self,
+        handle: RenderHandle<RenderPipeline>,
+        desc: RenderPipelineDesc,
+    ) -> Result<(), PipelineError> {
+        desc.validate()?;
+        self.handles
+            .check_entry(&desc.vertex, ShaderStage::Vertex)?;
+        if let Some(pixel) = &desc.pixel {
+            self.handles.check_entry(pixel, ShaderStage::Pixel)?;
+        }
+
+        if let Some(capture) = self.handles.capture.lock().as_mut() {
+            capture.render_pipeline(handle, desc.clone());
+        }
+        if let Some(validator) = &self.handles.validator {
+            validator.bind_pipeline(StreamRef::RenderPipeline(handle));
+        }
+        self.handles.render_pipelines.lock().set(
+            handle,
+            MockPipeline {
+                handle: Some(handle),
+                desc: Some(desc),
+            },
+        );
+
+        Ok(())
+    }
+
+    fn unbind_render_pipeline(&self, handle: RenderHandle<RenderPipeline>) {
+        self.handles
+            .bundles
+            .lock()
+            .invalidate(|r| r.render_pipelines.contains(&handle));
+        self.handles.unbind(StreamRef::RenderPipeline(handle));
+        self.handles.render_pipelines.lock().remove(handle);
+    }
+
+    fn gpu_timings(&self) -> Vec<GpuTiming> {
+        self.handles.profiler.lock().timings().to_vec()
+    }
+
+    fn begin_capture(&self, frames: u32) {
+        let mut recorder = CaptureRecorder::new(frames);
+
+        for buffer in self.handles.buffers.lock().values() {
+            if let Some(handle) = buffer.handle {
+                recorder.buffer(handle, buffer.desc.clone(), buffer.read());
+            }
+        }
+
+        for view in self.handles.buffer_views.lock().values() {
+            if let (Some(handle), Some(buffer)) = (view.handle, view.buffer) {
+                recorder.buffer_view(handle, buffer, view.desc);
+            }
+        }
+
+        for texture in self.handles.textures.lock().values() {
+            if let Some(handle) = texture.handle {
+                recorder.texture(handle, texture.desc.clone(), texture.read());
+            }
+        }
+
+        for module in self.handles.shader_modules.lock().values() {
+            recorder.shader_module(module.handle, module.desc.clone());
+        }
+
+        for pipeline in self.handles.render_pipelines.lock().values() {
+            if let (Some(handle), Some(desc)) = (pipeline.handle, &pipeline.desc) {
+                recorder.render_pipeline(handle, desc.clone());
+            }
+        }
+
+        for pipeline in self.handles.compute_pipelines.lock().values() {
+            if let (Some(handle), Some(desc)) = (pipeline.handle, &pipeline.desc) {
+                recorder.compute_pipeline(handle, desc.clone());
+            }
+        }
+
+        info!("Capturing the next {} frames", frames);
+        *self.handles.capture.lock() = Some(recorder);
+    }
+
+    fn take_capture(&self) -> Option<Capture> {
+        self.finished_capture.lock().take()
+    }
+}
+
+#[derive(Debug)]
+pub(super) struct HandleStorage {
+    pub(super) buffers: Mutex<SparseArray<Buffer, MockBuffer>>,
+    pub(super) buffer_views: Mutex<SparseArray<BufferView, MockBufferView>>,
+    pub(super) textures: Mutex<SparseArray<Texture, MockTexture>>,
+    /// Samplers are plain descriptions, the mock never samples.
+    pub(super) samplers: Mutex<SparseArray<Sampler, SamplerDesc>>,
+    pub(super) shader_modules: Mutex<SparseArray<ShaderModule, MockShaderModule>>,
+    pub(super) compute_pipelines:
+        Mutex<SparseArray<ComputePipeline, MockPipeline<ComputePipeline, ComputePipelineDesc>>>,
+    pub(super) render_pipelines:
+        Mutex<SparseArray<RenderPipeline, MockPipeline<RenderPipeline, RenderPipelineDesc>>>,
+    pub(super) residency: Mutex<ResidencySet<RenderHandle<Texture>>>,
+    pub(super) profiler: Mutex<GpuProfiler<MockQuerySet, MockBuffer>>,
+    pub(super) bundles: Mutex<BundleTracker>,
+    /// Set while a capture runs, new command buffers then record a stream.
+    pub(super) capture: Mutex<Option<CaptureRecorder>>,
+    /// Set with [`DebugFlags::CpuValidation`].
+    pub(super) validator: Option<Arc<Validator>>,
+}
+
+impl HandleStorage {
+    fn unbind(&self, resource: StreamRef) {
+        if let Some(validator) = &self.validator {
+            validator.unbind(resource);
+        }
+    }
+
+    fn check_entry(&self, entry: &ShaderEntry, stage: ShaderStage) -> Result<(), PipelineError> {
+        let guard = self.shader_modules.lock();
+        let Some(module) = guard.get(entry.module) else {
+            return Err(PipelineError::MissingModule);
+        };
+
+        entry.find(&module.desc.entry_points, stage).map(|_| ())
+    }
+
+    /// Records a use of `texture` and pages it back in if it was given up.
+    pub(super) fn touch_texture(&self, handle: RenderHandle<Texture>, texture: &MockTexture) {
+        if let Some(change) = self.residency.lock().touch(handle) {
+            texture.set_resident_size(change.size);
+        }
+    }
+}
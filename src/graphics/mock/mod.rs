@@ -1 +1,4 @@
-
+pub mod backend;
+pub mod commands;
+pub mod context;
+pub mod resources;
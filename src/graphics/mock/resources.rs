@@ -0,0 +1,492 @@
+use std::{ops::Range, sync::Arc};
+
+use bytemuck::Pod;
+use parking_lot::Mutex;
+
+use crate::graphics::core::{
+    handle::RenderHandle,
+    memory::{MemoryAllocation, MemoryBudget, MemoryCategory, MemoryLocation, MemoryReport},
+    ownership::QueueOwnership,
+    query::QuerySetDesc,
+    resource::{
+        Buffer, BufferDesc, BufferView, BufferViewDesc, BufferViewError, ResourceDevice,
+        SamplerDesc, Texture, TextureDesc, TextureUsages, TextureViewDesc,
+    },
+    shader::{ComputePipeline, RenderPipeline, ShaderModule, ShaderModuleDesc},
+    stream::StreamRef,
+    tracker::StateTracker,
+    types::ResourceState,
+};
+
+use super::context::MockRenderContext;
+
+impl ResourceDevice for MockRenderContext {
+    type Buffer = MockBuffer;
+    type BufferView = MockBufferView;
+    type Texture = MockTexture;
+    type Sampler = ();
+    type QuerySet = MockQuerySet;
+
+    fn create_buffer<T: Pod>(&self, desc: BufferDesc, init_data: Option<&[T]>) -> Self::Buffer {
+        let buffer = MockBuffer::new(self, desc);
+
+        if let Some(data) = init_data {
+            let data = bytemuck::cast_slice::<_, u8>(data);
+            buffer.data.lock()[..data.len()].copy_from_slice(data);
+        }
+
+        buffer
+    }
+
+    fn destroy_buffer(&self, _buffer: Self::Buffer) {}
+
+    fn create_buffer_view(
+        &self,
+        buffer: &Self::Buffer,
+        desc: BufferViewDesc,
+    ) -> Result<Self::BufferView, BufferViewError> {
+        let count = desc.validate(&buffer.desc)?;
+        let element_size = desc.element_size(&buffer.desc);
+        let offset = desc.first_element * element_size;
+
+        Ok(MockBufferView {
+            handle: None,
+            buffer: None,
+            desc,
+            range: offset..(offset + count * element_size),
+            data: Arc::clone(&buffer.data),
+        })
+    }
+
+    fn destroy_buffer_view(&self, _view: Self::BufferView) {}
+
+    fn create_texture<T: Pod>(&self, desc: TextureDesc, init_data: Option<&[T]>) -> Self::Texture {
+        let texture = MockTexture::new(self, desc);
+
+        if let Some(data) = init_data {
+            let data = bytemuck::cast_slice::<_, u8>(data);
+            texture.data.lock()[..data.len()].copy_from_slice(data);
+        }
+
+        texture
+    }
+
+    fn destroy_texture(&self, _texture: Self::Texture) {}
+
+    fn open_texture(&self, texture: &Self::Texture, _other: &Self) -> Self::Texture {
+        if !texture.desc.usage.contains(TextureUsages::Shared) {
+            panic!("Texture is local, can not open handle");
+        }
+
+        MockTexture::with_data(texture.desc.clone(), Arc::clone(&texture.data), None)
+    }
+
+    /// Views cover the whole texture and share its memory.
+    fn create_texture_view(
+        &self,
+        texture: &Self::Texture,
+        _desc: TextureViewDesc,
+    ) -> Self::Texture {
+        MockTexture::with_data(texture.desc.clone(), Arc::clone(&texture.data), None)
+    }
+
+    fn create_sampler(&self, _desc: SamplerDesc) -> Self::Sampler {}
+
+    fn destroy_sampler(&self, _sampler: Self::Sampler) {}
+
+    fn create_query_set(&self, desc: QuerySetDesc) -> Self::QuerySet {
+        MockQuerySet {
+            data: Arc::new(Mutex::new(vec![
+                0;
+                desc.count as usize * desc.ty.result_size()
+            ])),
+            desc,
+        }
+    }
+
+    fn destroy_query_set(&self, _set: Self::QuerySet) {}
+
+    fn memory_report(&self) -> MemoryReport {
+        let mut report = self.memory.report(self.desc.id, None);
+        report.budget = self.memory_budget.map(|budget| MemoryBudget {
+            budget,
+            usage: report.location(MemoryLocation::Device).bytes,
+        });
+
+        report
+    }
+}
+
+#[derive(Debug)]
+pub struct MockBuffer {
+    /// Set once bound, names the buffer in command streams.
+    pub(super) handle: Option<RenderHandle<Buffer>>,
+    pub(super) desc: BufferDesc,
+    pub(super) data: Arc<Mutex<Vec<u8>>>,
+    pub(super) state: Mutex<ResourceState>,
+    pub(super) owner: Mutex<QueueOwnership>,
+    _allocation: MemoryAllocation,
+}
+
+impl MockBuffer {
+    fn new(device: &MockRenderContext, desc: BufferDesc) -> Self {
+        Self {
+            handle: None,
+            data: Arc::new(Mutex::new(vec![0; desc.size])),
+            state: Mutex::new(ResourceState::Common),
+            owner: Default::default(),
+            _allocation: device.memory.allocate(
+                MemoryLocation::for_buffer(desc.usage),
+                MemoryCategory::Buffer,
+                desc.size as u64,
+            ),
+            desc,
+        }
+    }
+
+    pub fn state(&self) -> ResourceState {
+        *self.state.lock()
+    }
+
+    pub fn read(&self) -> Vec<u8> {
+        self.data.lock().clone()
+    }
+
+    pub(super) fn stream_ref(&self) -> StreamRef {
+        self.handle.map_or(StreamRef::Unbound, StreamRef::Buffer)
+    }
+}
+
+#[derive(Debug)]
+pub struct MockBufferView {
+    pub(super) handle: Option<RenderHandle<BufferView>>,
+    pub(super) buffer: Option<RenderHandle<Buffer>>,
+    pub(super) desc: BufferViewDesc,
+    pub(super) range: Range<usize>,
+    pub(super) data: Arc<Mutex<Vec<u8>>>,
+}
+
+impl MockBufferView {
+    pub fn desc(&self) -> &BufferViewDesc {
+        &self.desc
+    }
+
+    pub fn read(&self) -> Vec<u8> {
+        self.data.lock()[self.range.clone()].to_vec()
+    }
+
+    pub(super) fn stream_ref(&self) -> StreamRef {
+        self.handle
+            .map_or(StreamRef::Unbound, StreamRef::BufferView)
+    }
+}
+
+/// CPU copy of a texture with all subresources tightly packed in subresource order.
+#[derive(Debug)]
+pub struct MockTexture {
+    pub(super) handle: Option<RenderHandle<Texture>>,
+    pub(super) desc: TextureDesc,
+    pub(super) data: Arc<Mutex<Vec<u8>>>,
+    pub(super) state: Mutex<StateTracker<ResourceState>>,
+    pub(super) owner: Mutex<QueueOwnership>,
+    allocation: Mutex<Option<MemoryAllocation>>,
+}
+
+impl MockTexture {
+    fn new(device: &MockRenderContext, desc: TextureDesc) -> Self {
+        let size = desc.packed_size();
+        let allocation =
+            device
+                .memory
+                .allocate(MemoryLocation::Device, MemoryCategory::Texture, size as u64);
+
+        Self::with_data(desc, Arc::new(Mutex::new(vec![0; size])), Some(allocation))
+    }
+
+    /// Opened textures share the memory of the original and are not accounted again.
+    fn with_data(
+        desc: TextureDesc,
+        data: Arc<Mutex<Vec<u8>>>,
+        allocation: Option<MemoryAllocation>,
+    ) -> Self {
+        Self {
+            handle: None,
+            allocation: Mutex::new(allocation),
+            state: Mutex::new(StateTracker::new(
+                desc.mip_levels,
+                desc.array_layers(),
+                ResourceState::Common,
+            )),
+            owner: Default::default(),
+            data,
+            desc,
+        }
+    }
+
+    pub fn state(&self, mip: u32, layer: u32) -> ResourceState {
+        self.state.lock().state(mip, layer)
+    }
+
+    pub fn read(&self) -> Vec<u8> {
+        self.data.lock().clone()
+    }
+
+    /// Contents stay on the CPU, only the accounted device memory follows residency.
+    pub(super) fn set_resident_size(&self, size: u64) {
+        if let Some(allocation) = self.allocation.lock().as_mut() {
+            allocation.resize(size);
+        }
+    }
+
+    pub(super) fn stream_ref(&self) -> StreamRef {
+        self.handle.map_or(StreamRef::Unbound, StreamRef::Texture)
+    }
+}
+
+/// The mock backend runs no shaders, a pipeline only keeps its handle and
+/// description for captures.
+#[derive(Debug)]
+pub struct MockPipeline<T, D> {
+    pub(super) handle: Option<RenderHandle<T>>,
+    pub(super) desc: Option<D>,
+}
+
+impl<T, D> Default for MockPipeline<T, D> {
+    fn default() -> Self {
+        Self {
+            handle: None,
+            desc: None,
+        }
+    }
+}
+
+impl<D> MockPipeline<RenderPipeline, D> {
+    pub(super) fn stream_ref(&self) -> StreamRef {
+        self.handle
+            .map_or(StreamRef::Unbound, StreamRef::RenderPipeline)
+    }
+}
+
+impl<D> MockPipeline<ComputePipeline, D> {
+    pub(super) fn stream_ref(&self) -> StreamRef {
+        self.handle
+            .map_or(StreamRef::Unbound, StreamRef::ComputePipeline)
+    }
+}
+
+/// The mock backend runs no shaders, binding a module only checks that the device
+/// accepts its source. The description is kept to check pipeline entry points.
+#[derive(Debug)]
+pub struct MockShaderModule {
+    pub(super) handle: RenderHandle<ShaderModule>,
+    pub(super) desc: ShaderModuleDesc,
+}
+
+/// Query results in resolve layout. Pipeline statistics stay zero since the
+/// mock backend runs no shaders.
+#[derive(Debug)]
+pub struct MockQuerySet {
+    pub(super) desc: QuerySetDesc,
+    pub(super) data: Arc<Mutex<Vec<u8>>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::graphics::{
+        context::RenderContext,
+        core::{
+            backend::Api,
+            handle::RenderHandleAllocator,
+            memory::{MemoryCategory, MemoryLocation, MemoryStats},
+            resource::{
+                BufferDesc, BufferUsages, ResourceDevice, Sampler, SamplerDesc, Texture,
+                TextureDesc, TextureType, TextureUsages, TextureViewDesc,
+            },
+            shader::{
+                EntryPoint, PipelineError, PipelineLayout, PrimitiveTopology, RenderPipeline,
+                RenderPipelineDesc, ShaderEntry, ShaderError, ShaderLanguage, ShaderLanguages,
+                ShaderModule, ShaderModuleDesc, ShaderSource, ShaderStage,
+            },
+            types::Format,
+        },
+        mock::backend::MockBackend,
+        DebugFlags,
+    };
+
+    #[test]
+    fn memory_report_uses_fake_budget() {
+        let device = MockBackend::new(DebugFlags::empty())
+            .with_memory_budget(1024)
+            .create_device(0);
+        // The profiler keeps a readback buffer for every frame in flight.
+        let baseline = device.memory_report().total();
+
+        let buffer = device.create_buffer::<u8>(
+            BufferDesc {
+                name: None,
+                size: 256,
+                stride: 0,
+                usage: BufferUsages::Storage,
+            },
+            None,
+        );
+
+        let report = device.memory_report();
+        assert_eq!(
+            report.get(MemoryLocation::Device, MemoryCategory::Buffer),
+            MemoryStats {
+                count: 1,
+                bytes: 256
+            }
+        );
+        assert_eq!(
+            report.budget.map(|b| (b.budget, b.usage)),
+            Some((1024, 256))
+        );
+
+        device.destroy_buffer(buffer);
+        assert_eq!(device.memory_report().total(), baseline);
+    }
+
+    #[test]
+    fn texture_views_share_memory_and_samplers_keep_their_desc() {
+        let device = Arc::new(MockBackend::new(DebugFlags::empty()).create_device(0));
+        let mut textures = RenderHandleAllocator::<Texture>::new();
+        let (texture, view) = (textures.allocate(), textures.allocate());
+
+        device.bind_texture(
+            texture,
+            TextureDesc {
+                name: None,
+                ty: TextureType::D2,
+                width: 1,
+                height: 1,
+                depth: 1,
+                mip_levels: 1,
+                format: Format::Rgba8Unorm,
+                usage: TextureUsages::Resource,
+            },
+            Some(&[1, 2, 3, 4]),
+        );
+        device.bind_texture_view(view, texture, TextureViewDesc {});
+        assert_eq!(device.read_texture(view), Some(vec![1, 2, 3, 4]));
+
+        device.unbind_texture(view);
+        assert_eq!(device.read_texture(texture), Some(vec![1, 2, 3, 4]));
+
+        let sampler = RenderHandleAllocator::<Sampler>::new().allocate();
+        device.bind_sampler(sampler, SamplerDesc {});
+        assert!(device.handles.samplers.lock().get(sampler).is_some());
+        device.unbind_sampler(sampler);
+        assert!(device.handles.samplers.lock().get(sampler).is_none());
+    }
+
+    #[test]
+    fn shader_modules_need_a_supported_language() {
+        let languages = ShaderLanguages::Hlsl | ShaderLanguages::Dxil;
+        let device = Arc::new(
+            MockBackend::new(DebugFlags::empty())
+                .with_shader_languages(languages)
+                .create_device(0),
+        );
+        let mut handles = RenderHandleAllocator::<ShaderModule>::new();
+        let desc = |source| ShaderModuleDesc {
+            name: None,
+            source,
+            entry_points: vec![EntryPoint {
+                name: "main".into(),
+                stage: ShaderStage::Compute,
+            }],
+        };
+
+        let hlsl = handles.allocate();
+        device
+            .bind_shader_module(
+                hlsl,
+                desc(ShaderSource::Hlsl("[numthreads(1, 1, 1)]".into())),
+            )
+            .unwrap();
+        assert!(device.handles.shader_modules.lock().get(hlsl).is_some());
+
+        let wgsl = handles.allocate();
+        let err = device
+            .bind_shader_module(wgsl, desc(ShaderSource::Wgsl("@compute".into())))
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ShaderError::UnsupportedLanguage {
+                language: ShaderLanguage::Wgsl,
+                supported: languages
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "Wgsl shaders are not supported by this backend, it accepts ShaderLanguages(Hlsl | Dxil)"
+        );
+        assert!(device.handles.shader_modules.lock().get(wgsl).is_none());
+
+        device.unbind_shader_module(hlsl);
+        assert!(device.handles.shader_modules.lock().get(hlsl).is_none());
+    }
+
+    #[test]
+    fn render_pipelines_need_bound_entry_points_of_their_stage() {
+        let device = MockBackend::new(DebugFlags::empty()).create_device(0);
+        let module = RenderHandleAllocator::<ShaderModule>::new().allocate();
+        device
+            .bind_shader_module(
+                module,
+                ShaderModuleDesc {
+                    name: None,
+                    source: ShaderSource::Hlsl("".into()),
+                    entry_points: vec![EntryPoint {
+                        name: "main".into(),
+                        stage: ShaderStage::Compute,
+                    }],
+                },
+            )
+            .unwrap();
+
+        let desc = |name: &'static str| RenderPipelineDesc {
+            name: None,
+            layout: PipelineLayout::default(),
+            vertex: ShaderEntry {
+                module,
+                name: name.into(),
+            },
+            pixel: None,
+            vertex_attributes: vec![],
+            topology: PrimitiveTopology::TriangleList,
+            color_formats: vec![Format::Rgba8Unorm],
+            depth_format: None,
+        };
+        let pipeline = RenderHandleAllocator::<RenderPipeline>::new().allocate();
+
+        assert_eq!(
+            device.bind_render_pipeline(pipeline, desc("vs_main")),
+            Err(PipelineError::MissingEntryPoint("vs_main".to_string()))
+        );
+        assert_eq!(
+            device.bind_render_pipeline(pipeline, desc("main")),
+            Err(PipelineError::WrongStage {
+                entry: "main".to_string(),
+                expected: ShaderStage::Vertex,
+                found: ShaderStage::Compute
+            })
+        );
+        assert!(device
+            .handles
+            .render_pipelines
+            .lock()
+            .get(pipeline)
+            .is_none());
+
+        device.unbind_shader_module(module);
+        assert_eq!(
+            device.bind_render_pipeline(pipeline, desc("main")),
+            Err(PipelineError::MissingModule)
+        );
+    }
+}
@@ -1,12 +1,16 @@
 use core::{
     handle::{RenderHandle, RenderHandleAllocator},
-    resource::{Buffer, Texture},
+    resource::{Buffer, BufferView, Texture},
+    shader::ShaderModule,
 };
 use std::sync::Arc;
 
+#[cfg(target_os = "windows")]
 use dx12::backend::DxBackend;
+use mock::backend::MockBackend;
 use parking_lot::Mutex;
 
+pub mod asset;
 pub mod context;
 pub mod core;
 
@@ -21,10 +25,16 @@ pub struct RenderSystem {
 
     #[cfg(target_os = "windows")]
     dx_api: Option<Arc<DxBackend>>,
+    mock_api: Option<Arc<MockBackend>>,
 }
 
 impl RenderSystem {
     pub fn new(backend_settings: &[RenderBackendSettings]) -> Self {
+        let mock_api = backend_settings
+            .iter()
+            .find(|b| b.api == RenderBackend::Mock)
+            .map(|settings| Arc::new(MockBackend::new(settings.debug)));
+
         cfg_if::cfg_if! {
             if #[cfg(target_os = "windows")] {
                 let dx_api = backend_settings
@@ -35,6 +45,12 @@ impl RenderSystem {
                 Self {
                     handles: HandleStorage::new(),
                     dx_api,
+                    mock_api,
+                }
+            } else {
+                Self {
+                    handles: HandleStorage::new(),
+                    mock_api,
                 }
             }
         }
@@ -45,6 +61,10 @@ impl RenderSystem {
         self.dx_api.clone()
     }
 
+    pub fn mock_api(&self) -> Option<Arc<MockBackend>> {
+        self.mock_api.clone()
+    }
+
     #[inline]
     pub fn create_buffer_handle(&self) -> RenderHandle<Buffer> {
         self.handles.create_buffer_handle()
@@ -55,6 +75,16 @@ impl RenderSystem {
         self.handles.free_buffer_handle(handle)
     }
 
+    #[inline]
+    pub fn create_buffer_view_handle(&self) -> RenderHandle<BufferView> {
+        self.handles.create_buffer_view_handle()
+    }
+
+    #[inline]
+    pub fn free_buffer_view_handle(&self, handle: RenderHandle<BufferView>) {
+        self.handles.free_buffer_view_handle(handle)
+    }
+
     #[inline]
     pub fn create_texture_handle(&self) -> RenderHandle<Texture> {
         self.handles.create_texture_handle()
@@ -64,19 +94,33 @@ impl RenderSystem {
     pub fn free_texture_handle(&self, handle: RenderHandle<Texture>) {
         self.handles.free_texture_handle(handle)
     }
+
+    #[inline]
+    pub fn create_shader_module_handle(&self) -> RenderHandle<ShaderModule> {
+        self.handles.create_shader_module_handle()
+    }
+
+    #[inline]
+    pub fn free_shader_module_handle(&self, handle: RenderHandle<ShaderModule>) {
+        self.handles.free_shader_module_handle(handle)
+    }
 }
 
 #[derive(Debug)]
 pub struct HandleStorage {
     buffers: Mutex<RenderHandleAllocator<Buffer>>,
+    buffer_views: Mutex<RenderHandleAllocator<BufferView>>,
     textures: Mutex<RenderHandleAllocator<Texture>>,
+    shader_modules: Mutex<RenderHandleAllocator<ShaderModule>>,
 }
 
 impl HandleStorage {
     fn new() -> Self {
         Self {
             buffers: Mutex::new(RenderHandleAllocator::new()),
+            buffer_views: Mutex::new(RenderHandleAllocator::new()),
             textures: Mutex::new(RenderHandleAllocator::new()),
+            shader_modules: Mutex::new(RenderHandleAllocator::new()),
         }
     }
 
@@ -90,6 +134,16 @@ impl HandleStorage {
         self.buffers.lock().free(handle);
     }
 
+    #[inline]
+    pub fn create_buffer_view_handle(&self) -> RenderHandle<BufferView> {
+        self.buffer_views.lock().allocate()
+    }
+
+    #[inline]
+    pub fn free_buffer_view_handle(&self, handle: RenderHandle<BufferView>) {
+        self.buffer_views.lock().free(handle);
+    }
+
     #[inline]
     pub fn create_texture_handle(&self) -> RenderHandle<Texture> {
         self.textures.lock().allocate()
@@ -99,6 +153,16 @@ impl HandleStorage {
     pub fn free_texture_handle(&self, handle: RenderHandle<Texture>) {
         self.textures.lock().free(handle);
     }
+
+    #[inline]
+    pub fn create_shader_module_handle(&self) -> RenderHandle<ShaderModule> {
+        self.shader_modules.lock().allocate()
+    }
+
+    #[inline]
+    pub fn free_shader_module_handle(&self, handle: RenderHandle<ShaderModule>) {
+        self.shader_modules.lock().free(handle);
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -117,9 +181,19 @@ pub enum RenderBackend {
 bitflags::bitflags! {
     #[derive(Clone, Copy, Debug, PartialEq, Eq)]
     pub struct DebugFlags: u32 {
+        /// Checks work recorded through handles on the CPU and reports misuse through
+        /// `tracing`, see `core::validation`. DX12 does not check barriers recorded
+        /// on resource objects.
         const CpuValidation = 0x1;
         const GpuValidation = 0x2;
         const RenderDoc = 0x4;
         const Pix = 0x8;
+        /// Logs a memory report after every graphics submission.
+        const MemoryReport = 0x10;
+        /// Logs the GPU profiling scopes of every frame once they are read back.
+        const GpuTimings = 0x20;
+        /// Mirrors recorded commands into a backend-neutral stream, read back with
+        /// `CommandBuffer::command_stream`.
+        const CommandStream = 0x40;
     }
 }
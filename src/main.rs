@@ -11,6 +11,7 @@ use graphics::{
     context::RenderContext,
     core::{
         backend::{Api, RenderDeviceGroup},
+        commands::CommandBufferType,
         resource::{BufferDesc, BufferUsages, TextureDesc, TextureType, TextureUsages},
         types::Format,
     },
@@ -153,6 +154,10 @@ fn main() {
     );
     devices.secondaries[0].open_texture_handle(handle, &devices.primary);
 
+    // The secondary reads the texture only after the primary's rendering into it.
+    let rendered = devices.signal_shared(0, CommandBufferType::Graphics);
+    devices.wait_shared(1, CommandBufferType::Graphics, rendered);
+
     let data = [0u8, 1, 2, 3, 4, 5, 6, 7];
     let handle = render_system.create_buffer_handle();
     devices.call(|d| {